@@ -0,0 +1,50 @@
+use cblas::{Layout, Transpose};
+use crate::{multiply_rows, Scalar};
+
+//the cblas interface is taken from the system: Accelerate on macOS, OpenBLAS everywhere else.
+#[cfg(target_os = "macos")]
+#[link(name = "Accelerate", kind = "framework")]
+extern "C" {}
+
+#[cfg(not(target_os = "macos"))]
+#[link(name = "openblas")]
+extern "C" {}
+
+//products with fewer multiplications than this keep the pure rust loops,
+//the call into BLAS would cost more than it saves.
+const BLAS_THRESHOLD: usize = 1 << 18;
+
+//cblas takes its dimensions as i32, multiply checks they fit before calling gemm.
+fn dimension(size: usize) -> i32 {
+    i32::try_from(size).expect("a BLAS dimension must fit in an i32.")
+}
+
+//the general matrix multiplication of BLAS for one element type.
+pub(crate) trait Gemm: Scalar {
+    //c = a * b for row major a of m by k and b of k by n.
+    fn gemm(m: usize, n: usize, k: usize, a: &[Self], b: &[Self], c: &mut [Self]);
+}
+
+impl Gemm for f32 {
+    fn gemm(m: usize, n: usize, k: usize, a: &[f32], b: &[f32], c: &mut [f32]) {
+        //safe, the slices have the sizes the dimensions promise.
+        let (m, n, k) = (dimension(m), dimension(n), dimension(k));
+        unsafe { cblas::sgemm(Layout::RowMajor, Transpose::None, Transpose::None, m, n, k, 1.0, a, k, b, n, 0.0, c, n) }
+    }
+}
+
+impl Gemm for f64 {
+    fn gemm(m: usize, n: usize, k: usize, a: &[f64], b: &[f64], c: &mut [f64]) {
+        let (m, n, k) = (dimension(m), dimension(n), dimension(k));
+        unsafe { cblas::dgemm(Layout::RowMajor, Transpose::None, Transpose::None, m, n, k, 1.0, a, k, b, n, 0.0, c, n) }
+    }
+}
+
+pub(crate) fn multiply<T: Gemm>(lhs: &[T], rhs: &[T], result: &mut [T], m: usize, k: usize, n: usize) {
+    //dimensions beyond i32 can not be passed to cblas and keep the rust loops too.
+    let fits = [m, k, n].iter().all(|&size| i32::try_from(size).is_ok());
+    if !fits || m.saturating_mul(k).saturating_mul(n) < BLAS_THRESHOLD {
+        return multiply_rows(lhs, rhs, result, m, k, n);
+    }
+    T::gemm(m, n, k, lhs, rhs, result);
+}
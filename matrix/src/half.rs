@@ -0,0 +1,172 @@
+use std::fmt;
+use std::iter::zip;
+use serde::{Deserialize, Serialize};
+use crate::{ColumnVector, Matrix};
+#[cfg(feature = "simd")]
+use crate::simd::{add_scaled_half, dot_half};
+#[cfg(not(feature = "simd"))]
+use self::scalar::{add_scaled as add_scaled_half, dot as dot_half};
+
+//an IEEE 754 half precision float, only meant for storage. arithmetic converts to f32.
+//the largest finite value is 65504, the smallest positive one 2^-24. transparent so the
+//vectorized loops can load the bits of a slice of halves.
+#[derive(PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+#[repr(transparent)]
+pub struct F16(pub u16);
+
+impl F16 {
+    //rounds to the nearest half, ties to even. values too large become infinity.
+    pub fn from_f32(value: f32) -> F16 {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xff) as i32;
+        let mantissa = bits & 0x7f_ffff;
+        if exponent == 0xff {
+            //infinity, or a quiet nan.
+            return F16(sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 });
+        }
+        let half_exponent = exponent - 127 + 15;
+        if half_exponent >= 0x1f {
+            return F16(sign | 0x7c00);
+        }
+        let round = |value: u32, dropped_bits: u32| -> u32 {
+            let kept = value >> dropped_bits;
+            let remainder = value & ((1 << dropped_bits) - 1);
+            let halfway = 1 << (dropped_bits - 1);
+            kept + (remainder > halfway || (remainder == halfway && kept & 1 == 1)) as u32
+        };
+        if half_exponent <= 0 {
+            //subnormal, the implicit leading one becomes part of the mantissa.
+            if half_exponent < -10 {
+                return F16(sign);
+            }
+            return F16(sign | round(mantissa | 0x80_0000, (14 - half_exponent) as u32) as u16);
+        }
+        //a carry out of the mantissa correctly rounds up into the exponent, or to infinity.
+        F16(sign | round(((half_exponent as u32) << 23) | mantissa, 13) as u16)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        let sign = ((self.0 & 0x8000) as u32) << 16;
+        let exponent = ((self.0 >> 10) & 0x1f) as u32;
+        let mantissa = (self.0 & 0x3ff) as u32;
+        match exponent {
+            0 => {
+                let magnitude = mantissa as f32 / (1 << 24) as f32;
+                if sign == 0 { magnitude } else { -magnitude }
+            }
+            0x1f => f32::from_bits(sign | 0x7f80_0000 | mantissa << 13),
+            _ => f32::from_bits(sign | (exponent + 127 - 15) << 23 | mantissa << 13),
+        }
+    }
+}
+
+impl fmt::Debug for F16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_f32().fmt(f)
+    }
+}
+
+impl ColumnVector<F16> {
+    //the generic constructors need a Scalar, which F16 is not.
+    pub fn from_single(vector: &ColumnVector) -> ColumnVector<F16> {
+        ColumnVector { data: vector.data.iter().map(|&elem| F16::from_f32(elem)).collect() }
+    }
+
+    pub fn to_single(&self) -> ColumnVector {
+        ColumnVector::from_vec(self.data.iter().map(|elem| elem.to_f32()).collect())
+    }
+}
+
+impl Matrix<F16> {
+    pub fn from_single(matrix: &Matrix) -> Matrix<F16> {
+        Matrix::from_row_major(matrix.height(), matrix.width(), matrix.data.iter().map(|&elem| F16::from_f32(elem)).collect())
+    }
+
+    pub fn to_single(&self) -> Matrix {
+        Matrix::from_row_major(self.height(), self.width(), self.data.iter().map(|elem| elem.to_f32()).collect())
+    }
+
+    //self * vector with the products summed up in f32, so long rows do not lose
+    //precision to the 11 bit mantissa of every partial sum.
+    pub fn mul_accumulating(&self, vector: &ColumnVector<F16>) -> ColumnVector {
+        ColumnVector::from_vec(self.rows().map(|row| dot_half(row, &vector.data)).collect())
+    }
+
+    //self^T * vector for an f32 vector, e.g. propagating an error backwards through half
+    //precision weights. the rows are added up in f32, scaled by the elements of the vector.
+    pub fn transposed_mul_accumulating(&self, vector: &ColumnVector) -> ColumnVector {
+        if self.height() != vector.data.len() {
+            panic!("the height of the matrix must match the length of the vector.");
+        }
+        let mut result = vec![0.0; self.width()];
+        for (&elem, row) in zip(&vector.data, self.rows()) {
+            add_scaled_half(&mut result, elem, row);
+        }
+        ColumnVector::from_vec(result)
+    }
+}
+
+//the loops of the products with halves, every half converted to f32 before it is multiplied.
+pub(crate) mod scalar {
+    use std::iter::zip;
+    use super::F16;
+
+    pub(crate) fn dot(lhs: &[F16], rhs: &[F16]) -> f32 {
+        let mut sum = 0.0;
+        for (lhs_elem, rhs_elem) in zip(lhs, rhs) {
+            sum += lhs_elem.to_f32() * rhs_elem.to_f32();
+        }
+        sum
+    }
+
+    pub(crate) fn add_scaled(result: &mut [f32], factor: f32, elements: &[F16]) {
+        for (result_elem, elem) in zip(result, elements) {
+            *result_elem += factor * elem.to_f32();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{ColumnVector, Matrix, F16};
+
+    #[test]
+    fn conversion() {
+        for value in [0.0, -0.0, 1.0, -2.5, 0.099975586, 65504.0, 6.1035156e-5, 5.9604645e-8, f32::INFINITY, f32::NEG_INFINITY] {
+            assert_eq!(F16::from_f32(value).to_f32().to_bits(), value.to_bits(), "{}", value);
+        }
+        assert_eq!(F16::from_f32(1.0).0, 0x3c00);
+        assert_eq!(F16::from_f32(-2.0).0, 0xc000);
+        assert!(F16::from_f32(f32::NAN).to_f32().is_nan());
+        //between 1 and the next half 1 + 2^-10, ties go to the even mantissa.
+        assert_eq!(F16::from_f32(1.0 + 2.0_f32.powi(-11)).to_f32(), 1.0);
+        assert_eq!(F16::from_f32(1.0 + 3.0 * 2.0_f32.powi(-11)).to_f32(), 1.0 + 2.0 * 2.0_f32.powi(-10));
+        assert_eq!(F16::from_f32(1.0 + 1.1 * 2.0_f32.powi(-11)).to_f32(), 1.0 + 2.0_f32.powi(-10));
+        //rounding up the largest mantissa carries into the exponent.
+        assert_eq!(F16::from_f32(2047.9).to_f32(), 2048.0);
+        assert_eq!(F16::from_f32(65520.0).to_f32(), f32::INFINITY);
+        //subnormals and underflow.
+        assert_eq!(F16::from_f32(3.0 * 2.0_f32.powi(-24)).to_f32(), 3.0 * 2.0_f32.powi(-24));
+        assert_eq!(F16::from_f32(2.0_f32.powi(-26)).to_f32(), 0.0);
+    }
+
+    #[test]
+    fn multiplication_accumulates_in_single_precision() {
+        //2048 + 1 is not a half, every partial sum in half precision would stay at 2048.
+        let row = vec![1.0; 2049];
+        let matrix = Matrix::<F16>::from_single(&Matrix::from_vec(vec![row.clone()]));
+        let vector = ColumnVector::<F16>::from_single(&ColumnVector::from_vec(row));
+        assert_eq!(matrix.mul_accumulating(&vector).data, vec![2049.0]);
+        assert_eq!(matrix.to_single(), Matrix::from_vec(vec![vec![1.0; 2049]]));
+    }
+
+    #[test]
+    fn transposed_multiplication() {
+        let single = Matrix::from_vec(vec![vec![1.0, -2.0, 0.5], vec![0.25, 3.0, -1.0]]);
+        let vector = ColumnVector::from_vec(vec![2.0, -4.0]);
+        let result = Matrix::<F16>::from_single(&single).transposed_mul_accumulating(&vector);
+        assert_eq!(result.data, vec![1.0, -16.0, 5.0]);
+    }
+}
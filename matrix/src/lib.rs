@@ -2,46 +2,195 @@ extern crate core;
 
 use std::fmt::{Debug, Formatter};
 use std::iter::{zip};
-use std::ops::{Add, Sub, Mul, Neg, AddAssign};
+use std::ops::{Add, Sub, Mul, Neg, AddAssign, Index, IndexMut};
 use std::clone::Clone;
 use std::{fmt, vec};
-use rand_distr::{Distribution, Normal, NormalError};
-use rand::thread_rng;
+use std::iter::Sum;
+use num_traits::{Float, FromPrimitive};
+use rand_distr::{Distribution, StandardNormal};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+mod half;
+mod view;
+#[cfg(feature = "simd")]
+mod simd;
+#[cfg(feature = "blas")]
+mod blas;
+
+//products with fewer multiplications than this stay on the calling thread,
+//handing out the rows would take longer than computing them.
+#[cfg(feature = "parallel")]
+const PARALLEL_THRESHOLD: usize = 1 << 15;
+
+//calls compute for every chunk of result_length results and the matching chunk of row_length
+//elements of rows, e.g. an element of a matrix vector product and a row of the matrix. with
+//the parallel feature and work multiplications in total the chunks are spread over the threads
+//of rayon. every chunk is computed the same way either way, so the results do not depend on
+//the amount of threads.
+fn for_each_row<R: Send, S: Sync>(results: &mut [R], result_length: usize, rows: &[S], row_length: usize, #[cfg_attr(not(feature = "parallel"), allow(unused_variables))] work: usize, compute: impl Fn(&mut [R], &[S]) + Send + Sync) {
+    #[cfg(feature = "parallel")]
+    if work >= PARALLEL_THRESHOLD {
+        use rayon::prelude::*;
+        results.par_chunks_mut(result_length).zip(rows.par_chunks(row_length)).for_each(|(result, row)| compute(result, row));
+        return;
+    }
+    zip(results.chunks_mut(result_length), rows.chunks(row_length)).for_each(|(result, row)| compute(result, row));
+}
+
+//the tiles of multiply_rows, in elements. a block of result rows is computed together so the
+//TILE_DEPTH x TILE_WIDTH tile of rhs it works on, 128 KiB in f32, stays in the l2 cache for
+//all of them, while the TILE_WIDTH elements of a result row being summed stay in l1.
+const ROW_BLOCK: usize = 32;
+const TILE_DEPTH: usize = 128;
+const TILE_WIDTH: usize = 256;
+
+//result = lhs * rhs for a row major m x k lhs and k x n rhs, with the pure rust loops.
+//rows of rhs are added up scaled by the elements of the lhs row, which walks
+//every row in memory order instead of striding down the columns of rhs. large products go
+//tile by tile, see TILE_WIDTH. every element still sums its products in order of the rows of
+//rhs, so tiling does not change the results.
+fn multiply_rows<T: Scalar>(lhs: &[T], rhs: &[T], result: &mut [T], m: usize, k: usize, n: usize) {
+    result.iter_mut().for_each(|elem| *elem = T::zero());
+    if m * k * n == 0 {
+        return;
+    }
+    for_each_row(result, ROW_BLOCK * n, lhs, ROW_BLOCK * k, m * k * n, |result_block, lhs_block| {
+        for depth_start in (0..k).step_by(TILE_DEPTH) {
+            let depth = depth_start..k.min(depth_start + TILE_DEPTH);
+            for column_start in (0..n).step_by(TILE_WIDTH) {
+                let columns = column_start..n.min(column_start + TILE_WIDTH);
+                for (result_row, lhs_row) in zip(result_block.chunks_mut(n), lhs_block.chunks(k)) {
+                    for (&lhs_row_elem, rhs_row) in zip(&lhs_row[depth.clone()], rhs[depth.start * n..depth.end * n].chunks(n)) {
+                        T::add_scaled(&mut result_row[columns.clone()], lhs_row_elem, &rhs_row[columns.clone()]);
+                    }
+                }
+            }
+        }
+    });
+}
+
+pub use half::F16;
+pub use view::MatrixView;
+
+//the element type of matrices and vectors. f32 is the default everywhere,
+//f64 is there for numerical work like gradient checking that needs the precision.
+pub trait Scalar: Float + FromPrimitive + AddAssign + Sum + Default + Debug + fmt::Display + Send + Sync + 'static {
+    fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> Self;
+
+    //the sum of the products of lhs and rhs, the inner loop of matrix vector products.
+    fn dot(lhs: &[Self], rhs: &[Self]) -> Self {
+        let mut sum = Self::zero();
+        for (&lhs_elem, &rhs_elem) in zip(lhs, rhs) {
+            sum += lhs_elem * rhs_elem;
+        }
+        sum
+    }
+
+    //result = lhs * rhs for a row major m x k lhs and k x n rhs. the blas feature replaces it.
+    fn multiply(lhs: &[Self], rhs: &[Self], result: &mut [Self], m: usize, k: usize, n: usize) {
+        multiply_rows(lhs, rhs, result, m, k, n)
+    }
+
+    //result += factor * elements, the inner loop of matrix products.
+    fn add_scaled(result: &mut [Self], factor: Self, elements: &[Self]) {
+        for (result_elem, &elem) in zip(result, elements) {
+            *result_elem += factor * elem;
+        }
+    }
+}
+
+impl Scalar for f32 {
+    fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f32 {
+        StandardNormal.sample(rng)
+    }
+
+    #[cfg(feature = "simd")]
+    fn dot(lhs: &[f32], rhs: &[f32]) -> f32 {
+        simd::dot(lhs, rhs)
+    }
+
+    #[cfg(feature = "simd")]
+    fn add_scaled(result: &mut [f32], factor: f32, elements: &[f32]) {
+        simd::add_scaled(result, factor, elements)
+    }
+
+    #[cfg(feature = "blas")]
+    fn multiply(lhs: &[f32], rhs: &[f32], result: &mut [f32], m: usize, k: usize, n: usize) {
+        blas::multiply(lhs, rhs, result, m, k, n)
+    }
+}
+
+impl Scalar for f64 {
+    fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+        StandardNormal.sample(rng)
+    }
+
+    #[cfg(feature = "blas")]
+    fn multiply(lhs: &[f64], rhs: &[f64], result: &mut [f64], m: usize, k: usize, n: usize) {
+        blas::multiply(lhs, rhs, result, m, k, n)
+    }
+}
 
 //should be used for faster operations with a matrix.
 //This exists to allow for matrix multiplication with a vector to happen across
 //contiguous data.
-#[derive(Clone)]
-pub struct ColumnVector {
-    pub data: Vec<f32>,
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ColumnVector<T = f32> {
+    pub data: Vec<T>,
 }
 
 
-pub struct Matrix {
-    pub data: Vec<Vec<f32>>,
+//the elements are stored row after row in one allocation, element (row, column) is at
+//data[row * width + column]. index with matrix[(row, column)] or go through row and rows.
+#[derive(Clone)]
+pub struct Matrix<T = f32> {
+    data: Vec<T>,
+    height: usize,
+    width: usize,
 }
 
 
-impl ColumnVector {
-    pub fn _apply(&self, f: fn(f32) ->f32, result: &mut ColumnVector){
+impl<T: Scalar> ColumnVector<T> {
+    pub fn _apply(&self, f: fn(T) ->T, result: &mut ColumnVector<T>){
         zip(self.data.iter(),result.data.iter_mut()).for_each(|(x, y)|{
             *y = f(*x);
         });
     }
 
-    pub fn apply(&self, f: fn(f32) -> f32) -> ColumnVector {
-        let mut result = ColumnVector::new_with_elements(self.data.len(), 0.0);
+    pub fn apply(&self, f: fn(T) -> T) -> ColumnVector<T> {
+        let mut result = ColumnVector::new_with_elements(self.data.len(), T::zero());
         self._apply(f, &mut result);
         result
     }
 
-    pub fn from_vec(input: Vec<f32>) -> Self {
+    //like apply, for closures that capture something, e.g. the slope of a leaky relu.
+    pub fn map(&self, f: impl Fn(T) -> T) -> ColumnVector<T> {
+        ColumnVector::from_vec(self.data.iter().map(|&elem| f(elem)).collect())
+    }
+
+    pub fn map_inplace(&mut self, mut f: impl FnMut(T) -> T) {
+        self.data.iter_mut().for_each(|elem| *elem = f(*elem));
+    }
+
+    //the elements filled into a matrix row after row without copying them, e.g. a 784
+    //element image into 28 x 28.
+    pub fn into_matrix(self, height: usize, width: usize) -> Matrix<T> {
+        Matrix::from_row_major(height, width, self.data)
+    }
+
+    pub fn from_vec(input: Vec<T>) -> Self {
         ColumnVector {
             data: input
         }
     }
 
-    pub fn new_with_elements(size: usize, element: f32) -> Self {
+    //the same vector with another element type, e.g. f64 for numerical analysis.
+    pub fn cast<U: Scalar>(&self) -> ColumnVector<U> {
+        ColumnVector::from_vec(self.data.iter().map(|&elem| U::from(elem).unwrap()).collect())
+    }
+
+    pub fn new_with_elements(size: usize, element: T) -> Self {
         let mut result = Vec::with_capacity(size);
         for _ in 0..size {
             result.push(element);
@@ -51,441 +200,670 @@ impl ColumnVector {
 
 
     pub fn new_with_random_number(size: usize) -> Self {
-        let normal = Normal::new(0.0, 1.0).unwrap();
-        let mut rng = thread_rng();
+        ColumnVector::new_with_random_number_from_rng(size, &mut thread_rng())
+    }
+
+    pub fn new_with_random_number_from_rng<R: Rng + ?Sized>(size: usize, rng: &mut R) -> Self {
         let mut data = vec::Vec::with_capacity(size);
         (0..size).for_each(|_|{
-            data.push(normal.sample(&mut rng))
+            data.push(T::standard_normal(rng))
         });
         ColumnVector::from_vec(data)
     }
 
-    pub fn total(&self) -> f32 {
-        let mut result = 0.0;
+    pub fn total(&self) -> T {
+        let mut result = T::zero();
         for elem in &self.data {
-            result += elem;
+            result += *elem;
         }
         result
     }
 
-    pub fn average(&self) -> f32 {
-        self.total() / (self.data.len() as f32)
+    pub fn average(&self) -> T {
+        self.total() / T::from_usize(self.data.len()).unwrap()
     }
 
-    pub fn magnitude_squared(&self) -> f32 {
-        let mut acc = 0.0;
+    pub fn magnitude_squared(&self) -> T {
+        let mut acc = T::zero();
         for elem in &self.data {
             acc += elem.powi(2)
         }
         acc
     }
 
-    pub fn _mul_matrix<'a>(&self, matrix: &Matrix, result: &'a mut ColumnVector) -> &'a ColumnVector {
-        for (result_elem, matrix_row) in zip(&mut result.data.iter_mut(), &matrix.data) {
-            *result_elem = 0.0;
-            for (elem_vec, matrix_row_elem) in zip(&self.data, matrix_row) {
-                *result_elem += elem_vec * matrix_row_elem;
-            }
+    pub fn dot(&self, other: &ColumnVector<T>) -> T {
+        if self.data.len() != other.data.len() {
+            panic!("the dot product requires both vectors to be the same size.");
+        }
+        T::dot(&self.data, &other.data)
+    }
+
+    //the cosine of the angle between the vectors, from -1 to 1. zero when either vector is zero,
+    //as there is no angle then. both are scaled to a largest element of 1 first, so squaring very
+    //large or very small elements neither overflows nor underflows.
+    pub fn cosine_similarity(&self, other: &ColumnVector<T>) -> T {
+        let largest = |vector: &ColumnVector<T>| vector.data.iter().fold(T::zero(), |max, x| max.max(x.abs()));
+        let (self_largest, other_largest) = (largest(self), largest(other));
+        if self_largest == T::zero() || other_largest == T::zero() {
+            return T::zero();
+        }
+        let (lhs, rhs) = (self.map(|x| x / self_largest), other.map(|x| x / other_largest));
+        lhs.dot(&rhs) / (lhs.magnitude_squared().sqrt() * rhs.magnitude_squared().sqrt())
+    }
+
+    pub fn _mul_matrix<'a>(&self, matrix: &Matrix<T>, result: &'a mut ColumnVector<T>) -> &'a ColumnVector<T> {
+        if matrix.width == 0 {
+            result.data.iter_mut().for_each(|elem| *elem = T::zero());
+            return result;
+        }
+        let work = matrix.data.len();
+        for_each_row(&mut result.data, 1, &matrix.data, matrix.width, work, |result_elem, matrix_row| {
+            result_elem[0] = T::dot(matrix_row, &self.data);
+        });
+        result
+    }
+
+    //result = matrix^T * self without transposing the matrix, e.g. propagating an error
+    //backwards through a layer. the rows of the matrix are added up scaled by the elements.
+    pub fn _mul_transposed_matrix<'a>(&self, matrix: &Matrix<T>, result: &'a mut ColumnVector<T>) -> &'a ColumnVector<T> {
+        if matrix.height != self.data.len() {
+            panic!("the height of the matrix must match the length of the vector.");
+        }
+        result.data.iter_mut().for_each(|elem| *elem = T::zero());
+        for (&elem, matrix_row) in zip(&self.data, matrix.rows()) {
+            T::add_scaled(&mut result.data, elem, matrix_row);
+        }
+        result
+    }
+
+    //self * other^T, the matrix of every product of an element of self and one of other. the
+    //gradient of a weight matrix is the outer product of the delta and the input of the layer.
+    pub fn outer(&self, other: &ColumnVector<T>) -> Matrix<T> {
+        let mut result = Matrix::zeros(self.data.len(), other.data.len());
+        self._add_outer(other, &mut result);
+        result
+    }
+
+    //result += self * other^T, e.g. to sum the weight gradients of a batch without a matrix
+    //for every sample.
+    pub fn _add_outer<'a>(&self, other: &ColumnVector<T>, result: &'a mut Matrix<T>) -> &'a Matrix<T> {
+        if result.height != self.data.len() || result.width != other.data.len() {
+            panic!("the result of an outer product must have a row for every element of self and a column for every element of other.");
+        }
+        for (&elem, result_row) in zip(&self.data, result.rows_mut()) {
+            T::add_scaled(result_row, elem, &other.data);
         }
         result
     }
 
-    pub fn _add<'a>(&self, rhs: &ColumnVector, result: &'a mut ColumnVector) -> &'a ColumnVector {
+    pub fn _add<'a>(&self, rhs: &ColumnVector<T>, result: &'a mut ColumnVector<T>) -> &'a ColumnVector<T> {
         if self.data.len() != rhs.data.len() {
             panic!("addition requires both vectors to be the same size.");
         }
         for ((lhs_elem, rhs_elem), result_elem) in zip(zip(&self.data, &rhs.data), result.data.iter_mut()) {
-            *result_elem = lhs_elem + rhs_elem;
+            *result_elem = *lhs_elem + *rhs_elem;
         }
         result
     }
 
-    pub fn _neg<'a>(&self, result: &'a mut ColumnVector) -> &'a ColumnVector {
+    pub fn _neg<'a>(&self, result: &'a mut ColumnVector<T>) -> &'a ColumnVector<T> {
         for (elem, result_elem) in zip(&self.data, &mut result.data) {
-            *result_elem = *elem;
+            *result_elem = -*elem;
         }
         result
     }
 
-    pub fn _sub<'a>(&self, rhs: &ColumnVector, result: &'a mut ColumnVector) -> &'a ColumnVector {
+    pub fn _sub<'a>(&self, rhs: &ColumnVector<T>, result: &'a mut ColumnVector<T>) -> &'a ColumnVector<T> {
         if self.data.len() != rhs.data.len() {
             panic!("subtraction requires both vectors to be the same size.");
         }
         for ((lhs_elem, rhs_elem), result_elem) in zip(zip(&self.data, &rhs.data), result.data.iter_mut()) {
-            *result_elem = lhs_elem - rhs_elem;
+            *result_elem = *lhs_elem - *rhs_elem;
         }
         result
     }
 
-    pub fn _hadamard_product<'a>(self, rhs: &ColumnVector, result: &'a mut ColumnVector) -> &'a ColumnVector {
+    //the element wise product, e.g. a gradient times the derivative of an activation.
+    pub fn hadamard(&self, rhs: &ColumnVector<T>) -> ColumnVector<T> {
+        let mut result = ColumnVector::new_with_elements(self.data.len(), T::zero());
+        self._hadamard(rhs, &mut result);
+        result
+    }
+
+    pub fn _hadamard<'a>(&self, rhs: &ColumnVector<T>, result: &'a mut ColumnVector<T>) -> &'a ColumnVector<T> {
         if self.data.len() != rhs.data.len() {
-            panic!("the hadarmard product requires both vectors to be the same size.");
+            panic!("the hadamard product requires both vectors to be the same size.");
         }
         for ((elem_lhs, elem_rhs), elem_result) in zip(zip(&self.data, &rhs.data), result.data.iter_mut()) {
-            *elem_result = elem_lhs * elem_rhs;
+            *elem_result = *elem_lhs * *elem_rhs;
         }
         result
     }
 }
 
-impl Matrix {
-    pub fn from_vec(input: Vec<Vec<f32>>) -> Self {
-        Matrix {
-            data: input
+//the parts that need no arithmetic, shared with storage only types like F16.
+impl<T> Matrix<T> {
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    //a matrix from its elements, row after row.
+    pub fn from_row_major(height: usize, width: usize, data: Vec<T>) -> Matrix<T> {
+        if data.len() != height * width {
+            panic!("a {} x {} matrix needs {} elements, got {}.", height, width, height * width, data.len());
         }
+        Matrix { data, height, width }
     }
 
-    pub fn transpose(self) -> Self{
-        let mut data = Vec::with_capacity(self.data[0].len());
-        (0..self.data[0].len()).for_each(|_|{
-            data.push(Vec::with_capacity(self.data.len()));
-        });
-        zip((0..self.data.len()), (0..self.data[0].len())).for_each(|(row, col)|{
-            data[col][row] = self.data[row][col];
-        });
-        Matrix::from_vec(data)
+    //every element, row after row.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
     }
 
-    pub fn identity(size: usize) -> Matrix {
-        let mut result = Vec::with_capacity(size);
-        for row_index in 0..size {
-            result.push(Vec::with_capacity(size));
-            for col_index in 0..size {
-                result.last_mut().unwrap().push(if col_index == row_index {
-                    1.0
-                } else {
-                    0.0
-                });
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    pub fn row(&self, index: usize) -> &[T] {
+        &self.data[index * self.width..(index + 1) * self.width]
+    }
+
+    pub fn row_mut(&mut self, index: usize) -> &mut [T] {
+        &mut self.data[index * self.width..(index + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item=&[T]> + '_ {
+        (0..self.height).map(|index| self.row(index))
+    }
+
+    //every row, unless the matrix has a width of 0.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item=&mut [T]> + '_ {
+        self.data.chunks_mut(self.width.max(1))
+    }
+
+    //the same elements read row after row into another shape, e.g. a 784 x 1 image into 28 x 28.
+    //moves the elements instead of copying them, panics when the amount does not match.
+    pub fn reshape(self, height: usize, width: usize) -> Matrix<T> {
+        Matrix::from_row_major(height, width, self.data)
+    }
+
+    //every element row after row, e.g. an image back into the input of a network.
+    pub fn into_column_vector(self) -> ColumnVector<T> {
+        ColumnVector { data: self.data }
+    }
+}
+
+impl<T: Copy> Matrix<T> {
+    //panics unless every row has the same length.
+    pub fn from_vec(input: Vec<Vec<T>>) -> Self {
+        let width = input.first().map_or(0, Vec::len);
+        if input.iter().any(|row| row.len() != width) {
+            panic!("every row of a matrix needs the same length.");
+        }
+        Matrix::from_row_major(input.len(), width, input.concat())
+    }
+
+    //the matrices side by side, e.g. two batches of samples as one. panics unless they all
+    //have the same height.
+    pub fn concat_horizontal(matrices: &[&Matrix<T>]) -> Matrix<T> {
+        let height = matrices.first().map_or(0, |matrix| matrix.height);
+        if matrices.iter().any(|matrix| matrix.height != height) {
+            panic!("matrices concatenated horizontally need the same height.");
+        }
+        let width = matrices.iter().map(|matrix| matrix.width).sum();
+        let mut data = Vec::with_capacity(height * width);
+        for row in 0..height {
+            for matrix in matrices {
+                data.extend_from_slice(matrix.row(row));
             }
         }
-        Matrix::from_vec(result)
+        Matrix::from_row_major(height, width, data)
     }
 
-    pub fn zeros(height: usize, width: usize) -> Self {
-        Matrix::new_with_elements(height, width, 0.0)
+    //the matrices on top of each other. panics unless they all have the same width.
+    pub fn concat_vertical(matrices: &[&Matrix<T>]) -> Matrix<T> {
+        let width = matrices.first().map_or(0, |matrix| matrix.width);
+        if matrices.iter().any(|matrix| matrix.width != width) {
+            panic!("matrices concatenated vertically need the same width.");
+        }
+        let height = matrices.iter().map(|matrix| matrix.height).sum();
+        Matrix::from_row_major(height, width, matrices.iter().flat_map(|matrix| matrix.data.iter().copied()).collect())
     }
+}
 
-    pub fn new_with_elements(height: usize, width: usize, element: f32) -> Self {
-        let mut result = Vec::with_capacity(height);
-        for _ in 0..height {
-            result.push(Vec::with_capacity(width));
-            for _ in 0..width {
-                let row = result.last_mut().unwrap();
-                row.push(element);
-            }
+impl<T> Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+    fn index(&self, (row, column): (usize, usize)) -> &T {
+        if column >= self.width {
+            panic!("column {} is out of a matrix of width {}.", column, self.width);
         }
-        Matrix::from_vec(result)
+        &self.data[row * self.width + column]
     }
+}
 
-    pub fn new_with_number_generator(height: usize, width: usize, element_gen: fn(usize) -> f32) -> Matrix {
-        let mut result = Vec::with_capacity(height);
-        for index in 0..height {
-            result.push(Vec::with_capacity(width));
-            for _ in 0..width {
-                let row = result.last_mut().unwrap();
-                row.push(element_gen(index));
-            }
+impl<T> IndexMut<(usize, usize)> for Matrix<T> {
+    fn index_mut(&mut self, (row, column): (usize, usize)) -> &mut T {
+        if column >= self.width {
+            panic!("column {} is out of a matrix of width {}.", column, self.width);
+        }
+        &mut self.data[row * self.width + column]
+    }
+}
+
+//stored as a list of rows, like before the storage became flat.
+impl<T: Serialize> Serialize for Matrix<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.rows())
+    }
+}
+
+impl<'de, T: Deserialize<'de> + Copy> Deserialize<'de> for Matrix<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<Vec<T>>::deserialize(deserializer)?;
+        let width = rows.first().map_or(0, Vec::len);
+        if rows.iter().any(|row| row.len() != width) {
+            return Err(serde::de::Error::custom("every row of a matrix needs the same length."));
+        }
+        Ok(Matrix::from_vec(rows))
+    }
+}
+
+impl<T: Scalar> Matrix<T> {
+    pub fn cast<U: Scalar>(&self) -> Matrix<U> {
+        Matrix::from_row_major(self.height, self.width, self.data.iter().map(|&elem| U::from(elem).unwrap()).collect())
+    }
+
+    //f applied to every element.
+    pub fn map(&self, f: impl Fn(T) -> T) -> Matrix<T> {
+        Matrix::from_row_major(self.height, self.width, self.data.iter().map(|&elem| f(elem)).collect())
+    }
+
+    pub fn map_inplace(&mut self, mut f: impl FnMut(T) -> T) {
+        self.data.iter_mut().for_each(|elem| *elem = f(*elem));
+    }
+
+    pub fn transpose(self) -> Self {
+        let mut data = Vec::with_capacity(self.data.len());
+        for col in 0..self.width {
+            data.extend((0..self.height).map(|row| self[(row, col)]));
+        }
+        Matrix::from_row_major(self.width, self.height, data)
+    }
+
+    //self^T * rhs, computed without transposing self.
+    pub fn transposed_mul(&self, rhs: &Matrix<T>) -> Matrix<T> {
+        let mut result = Matrix::zeros(self.width, rhs.width);
+        self._transposed_mul(rhs, &mut result);
+        result
+    }
+
+    //self * rhs^T, computed without transposing rhs.
+    pub fn mul_transposed(&self, rhs: &Matrix<T>) -> Matrix<T> {
+        let mut result = Matrix::zeros(self.height, rhs.height);
+        self._mul_transposed(rhs, &mut result);
+        result
+    }
+
+    //the element wise product of two matrices of the same shape.
+    pub fn hadamard(&self, rhs: &Matrix<T>) -> Matrix<T> {
+        let mut result = Matrix::zeros(self.height, self.width);
+        self._hadamard(rhs, &mut result);
+        result
+    }
+
+    pub fn identity(size: usize) -> Matrix<T> {
+        let mut result = Matrix::zeros(size, size);
+        for index in 0..size {
+            result[(index, index)] = T::one();
         }
-        Matrix::from_vec(result)
+        result
+    }
+
+    pub fn zeros(height: usize, width: usize) -> Self {
+        Matrix::new_with_elements(height, width, T::zero())
+    }
+
+    pub fn new_with_elements(height: usize, width: usize, element: T) -> Self {
+        Matrix::from_row_major(height, width, vec![element; height * width])
+    }
+
+    pub fn new_with_number_generator(height: usize, width: usize, element_gen: fn(usize) -> T) -> Matrix<T> {
+        Matrix::from_row_major(height, width, (0..height * width).map(|index| element_gen(index / width)).collect())
     }
 
     pub fn new_with_random_number(height: usize, width: usize) -> Self {
-        let normal = Normal::new(0.0, 1.0).unwrap();
-        let mut rng = thread_rng();
-        let mut rows = Vec::with_capacity(height);
-        [..height].iter_mut().for_each(|_|{
-            let mut row = Vec::with_capacity(width);
-            [..width].iter().for_each(|_|{
-                row.push(normal.sample(&mut rng));
-            });
-            rows.push(row);
-        });
-        Matrix::from_vec(rows)
+        Matrix::new_with_random_number_from_rng(height, width, &mut thread_rng())
+    }
+
+    pub fn new_with_random_number_from_rng<R: Rng + ?Sized>(height: usize, width: usize, rng: &mut R) -> Self {
+        Matrix::from_row_major(height, width, (0..height * width).map(|_| T::standard_normal(rng)).collect())
+    }
+
+    pub fn is_same_shape(&self, other: &Matrix<T>) -> bool {
+        self.height == other.height && self.width == other.width
+    }
+
+    //the width of self has to match the height of other.
+    pub fn is_multipliable(&self, other: &Matrix<T>) -> bool {
+        self.width == other.height
     }
 
-    pub fn is_same_shape(&self, other: &Matrix) -> bool {
-        !((self.data.len() != other.data.len()) ||
-            (self.data.len() > 0 && (self.data[0].len() != other.data[0].len())))
+    //a matrix with the given vectors as its columns, e.g. a batch of samples.
+    pub fn from_columns(columns: &[ColumnVector<T>]) -> Matrix<T> {
+        let height = columns.first().map_or(0, |column| column.data.len());
+        if let Some(column) = columns.iter().find(|column| column.data.len() != height) {
+            panic!("every column must have {} elements, got one with {}.", height, column.data.len());
+        }
+        let data = (0..height).flat_map(|row| columns.iter().map(move |column| column.data[row])).collect();
+        Matrix::from_row_major(height, columns.len(), data)
+    }
+
+    pub fn column(&self, index: usize) -> ColumnVector<T> {
+        ColumnVector::from_vec(self.rows().map(|row| row[index]).collect())
     }
 
-    pub fn is_multipliable(&self, other: &Matrix) -> bool {
-        ((other.data.len() > 0) && (self.data.len() == other.data[0].len())) &&
-            ((self.data.len() > 0) && (self.data[0].len() == other.data.len())) ||
-            (self.data.len() == 0 && other.data.len() == 0)
+    pub fn columns(&self) -> impl Iterator<Item=ColumnVector<T>> + '_ {
+        (0..self.width).map(|index| self.column(index))
     }
 
-    pub fn _add<'a>(&self, rhs: &Matrix, result: &'a mut Matrix) -> &'a Matrix {
-        if self.is_same_shape(rhs) {
+    pub fn _add<'a>(&self, rhs: &Matrix<T>, result: &'a mut Matrix<T>) -> &'a Matrix<T> {
+        if !self.is_same_shape(rhs) {
             panic!("For addition both matrices must be the same size")
         } else {
-            for ((row1, row2), result_row) in zip(zip(self.data.iter(), rhs.data.iter()), &mut result.data) {
-                for ((elem1, elem2), result_elem) in zip(zip(row1, row2), result_row) {
-                    *result_elem = elem1 + elem2;
-                }
+            for ((elem1, elem2), result_elem) in zip(zip(&self.data, &rhs.data), &mut result.data) {
+                *result_elem = *elem1 + *elem2;
             }
             result
         }
     }
 
-    pub fn _sub<'a>(&self, rhs: &Matrix, result: &'a mut Matrix) -> &'a Matrix {
-        if self.is_same_shape(rhs) {
+    pub fn _sub<'a>(&self, rhs: &Matrix<T>, result: &'a mut Matrix<T>) -> &'a Matrix<T> {
+        if !self.is_same_shape(rhs) {
             panic!("For subtraction both matrices must be the same size")
         } else {
-            for ((row1, row2), result_row) in zip(zip(self.data.iter(), rhs.data.iter()), &mut result.data) {
-                for ((elem1, elem2), result_elem) in zip(zip(row1, row2), result_row) {
-                    *result_elem = elem1 - elem2;
-                }
+            for ((elem1, elem2), result_elem) in zip(zip(&self.data, &rhs.data), &mut result.data) {
+                *result_elem = *elem1 - *elem2;
             }
             result
         }
     }
 
-    fn _mul_num<'a>(&self, rhs: f32, result: &'a mut Matrix) -> &'a Matrix {
-        for (row_original, result_row) in zip(&self.data, result.data.iter_mut()) {
-            for (original_elem, result_elem) in zip(row_original, result_row.iter_mut()) {
-                *result_elem = original_elem * rhs;
-            }
+    pub fn _hadamard<'a>(&self, rhs: &Matrix<T>, result: &'a mut Matrix<T>) -> &'a Matrix<T> {
+        if !self.is_same_shape(rhs) {
+            panic!("the hadamard product requires both matrices to be the same size");
+        }
+        for ((elem1, elem2), result_elem) in zip(zip(&self.data, &rhs.data), &mut result.data) {
+            *result_elem = *elem1 * *elem2;
         }
         result
     }
 
-    fn _add_num<'a>(&self, rhs: f32, result: &'a mut Matrix) -> &'a Matrix {
-        for (row_original, result_row) in zip(&self.data, result.data.iter_mut()) {
-            for (original_elem, result_elem) in zip(row_original, result_row.iter_mut()) {
-                *result_elem = original_elem + rhs;
-            }
+    fn _mul_num<'a>(&self, rhs: T, result: &'a mut Matrix<T>) -> &'a Matrix<T> {
+        for (original_elem, result_elem) in zip(&self.data, &mut result.data) {
+            *result_elem = *original_elem * rhs;
         }
         result
     }
 
-    fn _sub_num<'a>(&self, rhs: f32, result: &'a mut Matrix) -> &'a Matrix {
-        for (row_original, result_row) in zip(&self.data, result.data.iter_mut()) {
-            for (original_elem, result_elem) in zip(row_original, result_row.iter_mut()) {
-                *result_elem = original_elem - rhs;
-            }
+    fn _add_num<'a>(&self, rhs: T, result: &'a mut Matrix<T>) -> &'a Matrix<T> {
+        for (original_elem, result_elem) in zip(&self.data, &mut result.data) {
+            *result_elem = *original_elem + rhs;
         }
         result
     }
 
-    fn _neg<'a>(&self, result: &'a mut Matrix) -> &'a Matrix {
-        for (row_original, result_row) in zip(&self.data, result.data.iter_mut()) {
-            for (&original_elem, result_elem) in zip(row_original, result_row.iter_mut()) {
-                *result_elem = -original_elem;
-            }
+    fn _sub_num<'a>(&self, rhs: T, result: &'a mut Matrix<T>) -> &'a Matrix<T> {
+        for (original_elem, result_elem) in zip(&self.data, &mut result.data) {
+            *result_elem = *original_elem - rhs;
         }
         result
     }
 
+    fn _neg<'a>(&self, result: &'a mut Matrix<T>) -> &'a Matrix<T> {
+        for (&original_elem, result_elem) in zip(&self.data, &mut result.data) {
+            *result_elem = -original_elem;
+        }
+        result
+    }
 
-    fn _mul<'a>(&self, rhs: &Matrix, result: &'a mut Matrix) -> &'a Matrix {
+    pub fn _mul<'a>(&self, rhs: &Matrix<T>, result: &'a mut Matrix<T>) -> &'a Matrix<T> {
+        if result.height != self.height || result.width != rhs.width {
+            panic!("the result of a matrix multiplication must be {} x {}.", self.height, rhs.width);
+        }
         if self.is_multipliable(rhs) {
-            for (lhs_row_index, lhs_row) in self.data.iter().enumerate() {
-                for rhs_col_index in 0..rhs.data[0].len() {
-                    let mut acc = 0.0;
-                    for (lhs_row_elem, rhs_row) in zip(lhs_row, &rhs.data) {
-                        let rhs_col_elem = rhs_row[rhs_col_index];
-                        acc += rhs_col_elem * lhs_row_elem;
-                    }
-                    result.data[lhs_row_index][rhs_col_index] = acc;
-                }
-            }
+            T::multiply(&self.data, &rhs.data, &mut result.data, self.height, self.width, rhs.width);
             result
         } else {
             panic!("left hand side matrix must have same amount of rows as right hand side cols in matrix multiplication");
         }
     }
+
+    //row p of rhs is added to result row i scaled by self[(p, i)], so like multiply_rows every
+    //element sums its products in order and the result matches transposing first.
+    pub fn _transposed_mul<'a>(&self, rhs: &Matrix<T>, result: &'a mut Matrix<T>) -> &'a Matrix<T> {
+        if self.height != rhs.height {
+            panic!("both matrices must have the same amount of rows in a transposed multiplication");
+        }
+        result.data.iter_mut().for_each(|elem| *elem = T::zero());
+        for (lhs_row, rhs_row) in zip(self.rows(), rhs.rows()) {
+            for (&lhs_elem, result_row) in zip(lhs_row, result.rows_mut()) {
+                T::add_scaled(result_row, lhs_elem, rhs_row);
+            }
+        }
+        result
+    }
+
+    //every element is the dot product of a row of self and a row of rhs, both contiguous.
+    pub fn _mul_transposed<'a>(&self, rhs: &Matrix<T>, result: &'a mut Matrix<T>) -> &'a Matrix<T> {
+        if self.width != rhs.width {
+            panic!("both matrices must have the same amount of columns in a multiplication with a transpose");
+        }
+        if self.width == 0 || rhs.height == 0 {
+            result.data.iter_mut().for_each(|elem| *elem = T::zero());
+            return result;
+        }
+        let work = self.height * self.width * rhs.height;
+        for_each_row(&mut result.data, rhs.height, &self.data, self.width, work, |result_row, lhs_row| {
+            for (result_elem, rhs_row) in zip(result_row, rhs.rows()) {
+                *result_elem = T::dot(lhs_row, rhs_row);
+            }
+        });
+        result
+    }
 }
 
-impl fmt::Debug for Matrix {
+impl<T: Scalar> fmt::Debug for Matrix<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for row in &self.data{
+        for row in self.rows() {
             row.fmt(f).unwrap();
-            write!(f, "\n").unwrap();
+            writeln!(f).unwrap();
         }
-        write!(f, "\n")
+        writeln!(f)
     }
 }
 
-impl fmt::Debug for ColumnVector {
+impl<T: Scalar> fmt::Debug for ColumnVector<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         self.data.fmt(f).unwrap();
-        write!(f, "\n")
+        writeln!(f)
     }
 }
 
-impl fmt::Display for ColumnVector {
+impl<T: Scalar> fmt::Display for ColumnVector<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.data.fmt(f).unwrap();
-        write!(f, "\n")
+        writeln!(f)
     }
 }
 
-impl fmt::Display for Matrix {
+impl<T: Scalar> fmt::Display for Matrix<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for row in &self.data{
+        for row in self.rows() {
             row.fmt(f).unwrap();
-            write!(f, "\n").unwrap();
+            writeln!(f).unwrap();
         }
-        write!(f, "\n")
+        writeln!(f)
     }
 }
 
-impl Add<&Matrix> for &Matrix {
-    type Output = Matrix;
-    fn add(self, rhs: &Matrix) -> Matrix {
-        let mut result = Matrix::new_with_elements(self.data.len(), self.data[0].len(), 0.0);
+impl<T: Scalar> Add<&Matrix<T>> for &Matrix<T> {
+    type Output = Matrix<T>;
+    fn add(self, rhs: &Matrix<T>) -> Matrix<T> {
+        let mut result = Matrix::new_with_elements(self.height, self.width, T::zero());
         self._add(rhs, &mut result);
         result
     }
 }
 
-impl Sub<&Matrix> for &Matrix {
-    type Output = Matrix;
-    fn sub(self, rhs: &Matrix) -> Self::Output {
-        let mut result = Matrix::new_with_elements(self.data.len(), self.data[0].len(), 0.0);
+impl<T: Scalar> Sub<&Matrix<T>> for &Matrix<T> {
+    type Output = Matrix<T>;
+    fn sub(self, rhs: &Matrix<T>) -> Self::Output {
+        let mut result = Matrix::new_with_elements(self.height, self.width, T::zero());
         self._sub(rhs, &mut result);
         result
     }
 }
 
-impl Sub<&ColumnVector> for &ColumnVector {
-    type Output = ColumnVector;
-    fn sub(self, rhs: &ColumnVector) -> Self::Output {
-        let mut result = ColumnVector::new_with_elements(rhs.data.len(), 0.0);
+impl<T: Scalar> Sub<&ColumnVector<T>> for &ColumnVector<T> {
+    type Output = ColumnVector<T>;
+    fn sub(self, rhs: &ColumnVector<T>) -> Self::Output {
+        let mut result = ColumnVector::new_with_elements(rhs.data.len(), T::zero());
         self._sub(rhs, &mut result);
         result
     }
 }
 
-impl Neg for &ColumnVector {
-    type Output = ColumnVector;
+impl<T: Scalar> Neg for &ColumnVector<T> {
+    type Output = ColumnVector<T>;
     fn neg(self) -> Self::Output {
-        let mut result = ColumnVector::new_with_elements(self.data.len(), 0.0);
+        let mut result = ColumnVector::new_with_elements(self.data.len(), T::zero());
         self._neg(&mut result);
         result
     }
 }
 
-impl Mul<&ColumnVector> for &Matrix {
-    type Output = ColumnVector;
+impl<T: Scalar> Mul<&ColumnVector<T>> for &Matrix<T> {
+    type Output = ColumnVector<T>;
 
-    fn mul(self, rhs: &ColumnVector) -> Self::Output {
-        let mut result = ColumnVector::new_with_elements(rhs.data.len(), 0.0);
+    fn mul(self, rhs: &ColumnVector<T>) -> Self::Output {
+        let mut result = ColumnVector::new_with_elements(rhs.data.len(), T::zero());
         rhs._mul_matrix(self, &mut result);
         result
     }
 }
 
-impl Mul<&Matrix> for &ColumnVector {
-    type Output = ColumnVector;
-    fn mul(self, rhs: &Matrix) -> Self::Output {
-        let mut result = ColumnVector::new_with_elements(self.data.len(), 0.0);
+impl<T: Scalar> Mul<&Matrix<T>> for &ColumnVector<T> {
+    type Output = ColumnVector<T>;
+    fn mul(self, rhs: &Matrix<T>) -> Self::Output {
+        let mut result = ColumnVector::new_with_elements(self.data.len(), T::zero());
         self._mul_matrix(rhs, &mut result);
         result
     }
 }
 
-impl Add<&ColumnVector> for &ColumnVector {
-    type Output = ColumnVector;
-    fn add(self, rhs: &ColumnVector) -> Self::Output {
-        let mut result = ColumnVector::new_with_elements(self.data.len(), 0.0);
+impl<T: Scalar> Add<&ColumnVector<T>> for &ColumnVector<T> {
+    type Output = ColumnVector<T>;
+    fn add(self, rhs: &ColumnVector<T>) -> Self::Output {
+        let mut result = ColumnVector::new_with_elements(self.data.len(), T::zero());
         self._add(rhs, &mut result);
         result
     }
 }
 
-impl Mul<f32> for &Matrix {
-    type Output = Matrix;
-    fn mul(self, rhs: f32) -> Self::Output {
-        let mut result = Matrix::new_with_elements(self.data.len(), self.data[0].len(), 0.0);
+impl<T: Scalar> Mul<T> for &Matrix<T> {
+    type Output = Matrix<T>;
+    fn mul(self, rhs: T) -> Self::Output {
+        let mut result = Matrix::new_with_elements(self.height, self.width, T::zero());
         self._mul_num(rhs, &mut result);
         result
     }
 }
 
-impl Mul<&Matrix> for f32 {
-    type Output = Matrix;
-    fn mul(self, rhs: &Matrix) -> Self::Output {
-        let mut result = Matrix::new_with_elements(rhs.data.len(), rhs.data[0].len(), 0.0);
-        rhs._mul_num(self, &mut result);
-        result
-    }
-}
-
-impl Add<f32> for &Matrix {
-    type Output = Matrix;
-    fn add(self, rhs: f32) -> Self::Output {
-        let mut result = Matrix::new_with_elements(self.data.len(), self.data[0].len(), 0.0);
+impl<T: Scalar> Add<T> for &Matrix<T> {
+    type Output = Matrix<T>;
+    fn add(self, rhs: T) -> Self::Output {
+        let mut result = Matrix::new_with_elements(self.height, self.width, T::zero());
         self._add_num(rhs, &mut result);
         result
     }
 }
 
-impl Add<&Matrix> for f32 {
-    type Output = Matrix;
-    fn add(self, rhs: &Matrix) -> Self::Output {
-        let mut result = Matrix::new_with_elements(rhs.data.len(), rhs.data[0].len(), 0.0);
-        rhs._add_num(self, &mut result);
-        result
-    }
-}
-
-impl Sub<f32> for &Matrix {
-    type Output = Matrix;
-    fn sub(self, rhs: f32) -> Self::Output {
-        let mut result = Matrix::new_with_elements(self.data.len(), self.data[0].len(), 0.0);
+impl<T: Scalar> Sub<T> for &Matrix<T> {
+    type Output = Matrix<T>;
+    fn sub(self, rhs: T) -> Self::Output {
+        let mut result = Matrix::new_with_elements(self.height, self.width, T::zero());
         self._sub_num(rhs, &mut result);
         result
     }
 }
 
-impl Sub<&Matrix> for f32 {
-    type Output = Matrix;
-    fn sub(self, rhs: &Matrix) -> Self::Output {
-        let mut result = Matrix::new_with_elements(rhs.data.len(), rhs.data[0].len(), 0.0);
-        rhs._sub_num(self, &mut result);
-        result
-    }
+//a scalar on the left hand side can not be generic because of the orphan rules.
+macro_rules! scalar_lhs_operations {
+    ($($scalar:ty),*) => {$(
+        impl Mul<&Matrix<$scalar>> for $scalar {
+            type Output = Matrix<$scalar>;
+            fn mul(self, rhs: &Matrix<$scalar>) -> Self::Output {
+                let mut result = Matrix::new_with_elements(rhs.height, rhs.width, 0.0);
+                rhs._mul_num(self, &mut result);
+                result
+            }
+        }
+
+        impl Add<&Matrix<$scalar>> for $scalar {
+            type Output = Matrix<$scalar>;
+            fn add(self, rhs: &Matrix<$scalar>) -> Self::Output {
+                let mut result = Matrix::new_with_elements(rhs.height, rhs.width, 0.0);
+                rhs._add_num(self, &mut result);
+                result
+            }
+        }
+
+        impl Sub<&Matrix<$scalar>> for $scalar {
+            type Output = Matrix<$scalar>;
+            fn sub(self, rhs: &Matrix<$scalar>) -> Self::Output {
+                let mut result = Matrix::new_with_elements(rhs.height, rhs.width, 0.0);
+                rhs._sub_num(self, &mut result);
+                result
+            }
+        }
+    )*};
 }
 
-impl Mul<&Matrix> for &Matrix {
-    type Output = Matrix;
-    fn mul(self, rhs: &Matrix) -> Self::Output {
-        let mut result = Matrix::new_with_elements(self.data.len(), rhs.data[0].len(), 0.0);
+scalar_lhs_operations!(f32, f64);
+
+impl<T: Scalar> Mul<&Matrix<T>> for &Matrix<T> {
+    type Output = Matrix<T>;
+    fn mul(self, rhs: &Matrix<T>) -> Self::Output {
+        let mut result = Matrix::new_with_elements(self.height, rhs.width, T::zero());
         self._mul(rhs, &mut result);
         result
     }
 }
 
-impl Neg for &Matrix {
-    type Output = Matrix;
+impl<T: Scalar> Neg for &Matrix<T> {
+    type Output = Matrix<T>;
     fn neg(self) -> Self::Output {
-        let mut result = Matrix::new_with_elements(self.data.len(), self.data[0].len(), 0.0);
+        let mut result = Matrix::new_with_elements(self.height, self.width, T::zero());
         self._neg(&mut result);
         result
     }
 }
 
-impl PartialEq for Matrix {
+impl<T: Scalar> PartialEq for Matrix<T> {
     fn eq(&self, other: &Self) -> bool {
-        if self.data.len() != other.data.len() || self.data[0].len() != other.data[0].len() {
-            false
-        } else {
-            for (row1, row2) in zip(&self.data, &other.data) {
-                for (elem1, elem2) in zip(row1, row2) {
-                    if elem1 == elem2 {
-                        continue;
-                    } else {
-                        return false;
-                    }
-                }
-            }
-            true
-        }
+        self.is_same_shape(other) && self.data == other.data
     }
 }
 
-impl PartialEq for ColumnVector {
+impl<T: Scalar> PartialEq for ColumnVector<T> {
     fn eq(&self, other: &Self) -> bool {
         if self.data.len() != other.data.len() {
             false
@@ -502,13 +880,13 @@ impl PartialEq for ColumnVector {
     }
 }
 
-impl AddAssign<&ColumnVector> for ColumnVector {
-    fn add_assign(&mut self, other: &ColumnVector) {
+impl<T: Scalar> AddAssign<&ColumnVector<T>> for ColumnVector<T> {
+    fn add_assign(&mut self, other: &ColumnVector<T>) {
         if self.data.len() != other.data.len() {
             panic!("column vectors must be equal in length.");
         }
         for (index, elem) in other.data.iter().enumerate() {
-            self.data[index] += elem.clone();
+            self.data[index] += *elem;
         }
     }
 }
@@ -516,7 +894,7 @@ impl AddAssign<&ColumnVector> for ColumnVector {
 
 #[cfg(test)]
 mod tests {
-    use super::Matrix;
+    use super::{ColumnVector, Matrix};
 
     #[test]
     fn equality() {
@@ -525,10 +903,33 @@ mod tests {
         let mat_x = Matrix::from_vec(x);
         let mut mat_y = Matrix::from_vec(y);
         assert_eq!(mat_y, mat_x);
-        mat_y.data[0][0] = 1.0;
+        mat_y[(0, 0)] = 1.0;
         assert_ne!(mat_y, mat_x);
     }
 
+    #[test]
+    fn flat_storage() {
+        let mut matrix = Matrix::from_vec(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        assert_eq!((matrix.height(), matrix.width()), (2, 3));
+        assert_eq!(matrix.as_slice(), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(matrix, Matrix::from_row_major(2, 3, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]));
+        assert_eq!(matrix[(1, 0)], 4.0);
+        matrix[(0, 2)] = 7.0;
+        matrix.row_mut(1)[1] = 8.0;
+        assert_eq!(matrix.rows().collect::<Vec<_>>(), vec![&[1.0, 2.0, 7.0][..], &[4.0, 8.0, 6.0][..]]);
+        assert_eq!(matrix.clone().transpose(), Matrix::from_vec(vec![vec![1.0, 4.0], vec![2.0, 8.0], vec![7.0, 6.0]]));
+        //a batch of no samples still knows its height.
+        let empty: Matrix = Matrix::from_vec(vec![Vec::new(); 4]);
+        assert_eq!((empty.height(), empty.width(), empty.rows().count()), (4, 0, 4));
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_past_the_width() {
+        let matrix = Matrix::from_vec(vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        let _ = matrix[(0, 2)];
+    }
+
     #[test]
     fn number_arithmetic() {
         let x = vec![vec![0.0, 0.0], vec![0.0, 0.0]];
@@ -536,12 +937,12 @@ mod tests {
         let mat_x = Matrix::from_vec(x);
         let mat_y = Matrix::from_vec(y);
         let mat_z = &mat_x + 1.0;
-        let mat_w = 1.0 as f32 + &mat_x;
-        let mat_p = &mat_w - 1.0 as f32;
-        let mat_u = &mat_p - 1.0 as f32;
+        let mat_w = 1.0_f32 + &mat_x;
+        let mat_p = &mat_w - 1.0_f32;
+        let mat_u = &mat_p - 1.0_f32;
         let mat_a = -&mat_z;
-        let mat_0 = 0.0 as f32 * &mat_w;
-        let mat_1 = &mat_w * 0.0 as f32;
+        let mat_0 = 0.0_f32 * &mat_w;
+        let mat_1 = &mat_w * 0.0_f32;
         assert_eq!(mat_w, mat_y);
         assert_eq!(mat_z, mat_w);
         assert_eq!(mat_p, mat_x);
@@ -550,6 +951,22 @@ mod tests {
         assert_eq!(mat_1, mat_x);
     }
 
+    #[test]
+    fn element_wise_arithmetic() {
+        let a = Matrix::from_vec(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        let b = Matrix::from_vec(vec![vec![6.0, 5.0, 4.0], vec![3.0, 2.0, 1.0]]);
+        assert_eq!(&a + &b, Matrix::new_with_elements(2, 3, 7.0));
+        assert_eq!(&a - &b, Matrix::from_vec(vec![vec![-5.0, -3.0, -1.0], vec![1.0, 3.0, 5.0]]));
+        let vector = ColumnVector::from_vec(vec![1.0, -2.0, 0.5]);
+        assert_eq!(-&vector, ColumnVector::from_vec(vec![-1.0, 2.0, -0.5]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn adding_different_shapes() {
+        let _ = &Matrix::<f32>::zeros(2, 3) + &Matrix::zeros(3, 2);
+    }
+
     #[test]
     fn test_multiplication() {
         let identity = Matrix::identity(4);
@@ -572,5 +989,180 @@ mod tests {
         let scalar = Matrix::from_vec(vec![vec![1.0]]);
         let prod_mat = &(&row_mat * &col_mat) * &scalar;
         assert_eq!(Matrix::from_vec(vec![vec![4.0]]), prod_mat);
+        let wide = Matrix::from_vec(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        let tall = Matrix::from_vec(vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]]);
+        assert_eq!(&wide * &tall, Matrix::from_vec(vec![vec![4.0, 5.0], vec![10.0, 11.0]]));
+        assert_eq!(&wide * &Matrix::from_columns(&[tall.column(1)]), Matrix::from_vec(vec![vec![5.0], vec![11.0]]));
+    }
+
+    #[test]
+    fn transposed_products() {
+        let lhs = Matrix::from_vec(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        let rhs = Matrix::from_vec(vec![vec![1.0, 0.0, -1.0], vec![2.0, 1.0, 0.0]]);
+        assert_eq!(lhs.transposed_mul(&rhs), &lhs.clone().transpose() * &rhs);
+        assert_eq!(lhs.mul_transposed(&rhs), &lhs * &rhs.clone().transpose());
+        assert_eq!(lhs.mul_transposed(&rhs), Matrix::from_vec(vec![vec![-2.0, 4.0], vec![-2.0, 13.0]]));
+        let vector = ColumnVector::from_vec(vec![1.0, -2.0]);
+        let expected = &lhs.clone().transpose() * &Matrix::from_columns(std::slice::from_ref(&vector));
+        assert_eq!(vector._mul_transposed_matrix(&lhs, &mut ColumnVector::new_with_elements(3, 0.0)).data, expected.data);
+    }
+
+    #[test]
+    fn hadamard_products() {
+        let lhs = Matrix::from_vec(vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        let rhs = Matrix::from_vec(vec![vec![0.5, -1.0], vec![2.0, 0.0]]);
+        assert_eq!(lhs.hadamard(&rhs), Matrix::from_vec(vec![vec![0.5, -2.0], vec![6.0, 0.0]]));
+        let vector = ColumnVector::from_vec(vec![1.0, 2.0, 3.0]);
+        let mut result = ColumnVector::new_with_elements(3, 0.0);
+        vector._hadamard(&ColumnVector::from_vec(vec![2.0, 0.5, -1.0]), &mut result);
+        assert_eq!(result, ColumnVector::from_vec(vec![2.0, 1.0, -3.0]));
+        assert_eq!(vector.hadamard(&vector), ColumnVector::from_vec(vec![1.0, 4.0, 9.0]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn hadamard_of_different_shapes() {
+        let square = Matrix::from_vec(vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        square.hadamard(&Matrix::from_vec(vec![vec![1.0, 2.0]]));
+    }
+
+    #[test]
+    fn outer_products() {
+        let lhs = ColumnVector::from_vec(vec![1.0, -2.0]);
+        let rhs = ColumnVector::from_vec(vec![3.0, 0.5, 1.0]);
+        let product = lhs.outer(&rhs);
+        assert_eq!(product, Matrix::from_vec(vec![vec![3.0, 0.5, 1.0], vec![-6.0, -1.0, -2.0]]));
+        assert_eq!(product, &Matrix::from_columns(std::slice::from_ref(&lhs)) * &Matrix::from_vec(vec![rhs.data.clone()]));
+        let mut sum = product.clone();
+        lhs._add_outer(&rhs, &mut sum);
+        assert_eq!(sum, &product * 2.0);
+    }
+
+    #[test]
+    fn element_wise_maps() {
+        let offset = 0.5;
+        let mut matrix: Matrix = Matrix::from_vec(vec![vec![1.0, -2.0], vec![3.0, 0.0]]);
+        assert_eq!(matrix.map(|x| x * 2.0 + offset), Matrix::from_vec(vec![vec![2.5, -3.5], vec![6.5, 0.5]]));
+        let mut calls = 0;
+        matrix.map_inplace(|x| {
+            calls += 1;
+            x.max(0.0)
+        });
+        assert_eq!((matrix, calls), (Matrix::from_vec(vec![vec![1.0, 0.0], vec![3.0, 0.0]]), 4));
+        let mut vector = ColumnVector::from_vec(vec![-1.0, 4.0]);
+        assert_eq!(vector.map(|x| x - offset), ColumnVector::from_vec(vec![-1.5, 3.5]));
+        vector.map_inplace(f32::abs);
+        assert_eq!(vector, ColumnVector::from_vec(vec![1.0, 4.0]));
+    }
+
+    #[test]
+    fn dot_products() {
+        let lhs: ColumnVector = ColumnVector::from_vec(vec![1.0, 2.0, 2.0]);
+        let rhs = ColumnVector::from_vec(vec![2.0, 0.0, -1.0]);
+        assert_eq!(lhs.dot(&rhs), 0.0);
+        assert_eq!(lhs.dot(&lhs), 9.0);
+        assert_eq!(lhs.cosine_similarity(&rhs), 0.0);
+        assert!((lhs.cosine_similarity(&lhs.map(|x| x * 3.0)) - 1.0).abs() < 1e-6);
+        assert!((lhs.cosine_similarity(&-&lhs) + 1.0).abs() < 1e-6);
+        assert_eq!(lhs.cosine_similarity(&ColumnVector::new_with_elements(3, 0.0)), 0.0);
+        let large: ColumnVector = ColumnVector::from_vec(vec![1e20, 1e20]);
+        assert!((large.cosine_similarity(&large) - 1.0).abs() < 1e-6);
+        let small: ColumnVector = ColumnVector::from_vec(vec![1e-20, 1e-20]);
+        assert!((small.cosine_similarity(&small) - 1.0).abs() < 1e-6);
+        assert!((large.cosine_similarity(&small) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn columns() {
+        let columns = vec![ColumnVector::from_vec(vec![1.0, 2.0]), ColumnVector::from_vec(vec![3.0, 4.0]), ColumnVector::from_vec(vec![5.0, 6.0])];
+        let matrix = Matrix::from_columns(&columns);
+        assert_eq!(matrix, Matrix::from_vec(vec![vec![1.0, 3.0, 5.0], vec![2.0, 4.0, 6.0]]));
+        assert_eq!(matrix.columns().collect::<Vec<_>>(), columns);
+    }
+
+    #[test]
+    #[should_panic]
+    fn columns_of_different_lengths() {
+        Matrix::from_columns(&[ColumnVector::from_vec(vec![1.0, 2.0]), ColumnVector::from_vec(vec![3.0])]);
+    }
+
+    #[test]
+    fn concatenation() {
+        let left = Matrix::from_vec(vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        let right = Matrix::from_columns(&[ColumnVector::from_vec(vec![5.0, 6.0])]);
+        assert_eq!(Matrix::concat_horizontal(&[&left, &right]), Matrix::from_vec(vec![vec![1.0, 2.0, 5.0], vec![3.0, 4.0, 6.0]]));
+        let bottom = Matrix::from_vec(vec![vec![7.0, 8.0]]);
+        assert_eq!(Matrix::concat_vertical(&[&left, &bottom]), Matrix::from_vec(vec![vec![1.0, 2.0], vec![3.0, 4.0], vec![7.0, 8.0]]));
+        //an empty batch adds no columns.
+        let empty = Matrix::from_vec(vec![Vec::new(); 2]);
+        assert_eq!(Matrix::concat_horizontal(&[&empty, &left, &empty]), left);
+    }
+
+    #[test]
+    #[should_panic]
+    fn concatenating_different_heights() {
+        let square = Matrix::from_vec(vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        Matrix::concat_horizontal(&[&square, &Matrix::from_vec(vec![vec![5.0]])]);
+    }
+
+    #[test]
+    fn reshaping() {
+        let image = ColumnVector::from_vec((0..784).map(|x| x as f32).collect());
+        let matrix = image.clone().into_matrix(28, 28);
+        assert_eq!((matrix.height(), matrix.width()), (28, 28));
+        assert_eq!(matrix[(1, 2)], 30.0);
+        assert_eq!(matrix.clone().reshape(784, 1).column(0), image);
+        assert_eq!(matrix.into_column_vector(), image);
+    }
+
+    #[test]
+    #[should_panic]
+    fn reshaping_into_another_size() {
+        ColumnVector::from_vec(vec![1.0, 2.0, 3.0]).into_matrix(2, 2);
+    }
+
+    #[test]
+    fn large_products() {
+        //large enough to be split over threads with the parallel feature.
+        let (height, width) = (70, 90);
+        let lhs = Matrix::from_vec((0..height).map(|row| (0..width).map(|col| ((row * width + col) % 13) as f32 - 6.0).collect()).collect());
+        let rhs = Matrix::from_vec((0..width).map(|row| (0..height).map(|col| ((row + 2 * col) % 7) as f32).collect()).collect());
+        let product = &lhs * &rhs;
+        for (row, col) in [(0, 0), (13, 42), (69, 69)] {
+            let expected: f32 = (0..width).map(|index| lhs[(row, index)] * rhs[(index, col)]).sum();
+            assert_eq!(product[(row, col)], expected);
+        }
+        let vector = rhs.column(5);
+        assert_eq!(vector._mul_matrix(&lhs, &mut ColumnVector::new_with_elements(height, 0.0)).data, product.column(5).data);
+    }
+
+    #[test]
+    fn tiled_products() {
+        //several tiles in every direction, none of them full at the edges.
+        let (height, depth, width) = (70, 300, 270);
+        let lhs: Matrix = Matrix::from_vec((0..height).map(|row| (0..depth).map(|col| ((row * 31 + col * 17) % 23) as f32 * 0.1 - 1.0).collect()).collect());
+        let rhs: Matrix = Matrix::from_vec((0..depth).map(|row| (0..width).map(|col| ((row * 7 + col * 11) % 19) as f32 * 0.2 - 1.5).collect()).collect());
+        let product = &lhs * &rhs;
+        //BLAS adds the products in another order, so its rounding differs.
+        let tolerance = if cfg!(feature = "blas") { 1e-3 } else { 0.0 };
+        for (row, col) in [(0, 0), (31, 255), (32, 256), (69, 269)] {
+            let mut expected = 0.0;
+            for index in 0..depth {
+                expected += lhs[(row, index)] * rhs[(index, col)];
+            }
+            assert!((product[(row, col)] - expected).abs() <= tolerance, "{} != {}", product[(row, col)], expected);
+        }
+    }
+
+    #[test]
+    fn double_precision() {
+        let small = 1e-9;
+        let mat_x: Matrix<f64> = &Matrix::identity(2) + small;
+        let vector = ColumnVector::from_vec(vec![1.0_f64, 1.0]);
+        //1 + 2e-9 rounds to 1 in single precision.
+        let product = &mat_x * &vector;
+        assert!(product.data.iter().all(|&elem| (elem - 1.0 - 2.0 * small).abs() < 1e-15));
+        assert_eq!(product.cast::<f32>().data, vec![1.0, 1.0]);
+        assert_eq!(vector.cast::<f32>().cast::<f64>(), vector);
     }
 }
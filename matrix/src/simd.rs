@@ -0,0 +1,219 @@
+//vectorized inner loops of f32 matrix products. on x86_64 AVX is used when the processor
+//has it, aarch64 always has NEON. every other target keeps the scalar loops.
+//products and sums are not fused and every lane sums its elements in order, so add_scaled
+//gives exactly the results of the scalar loop. dot sums in 8 (AVX) or 4 (NEON) lanes,
+//which only differs from the scalar loop in rounding. the loops over halves convert 8 of them
+//at a time with F16C, which comes with every processor that has AVX2 but is checked for anyway.
+
+use crate::F16;
+
+pub(crate) fn dot(lhs: &[f32], rhs: &[f32]) -> f32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx") {
+        //safe, the processor supports avx.
+        return unsafe { avx::dot(lhs, rhs) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        return unsafe { neon::dot(lhs, rhs) };
+    }
+    scalar::dot(lhs, rhs)
+}
+
+//result += factor * elements, element by element.
+pub(crate) fn add_scaled(result: &mut [f32], factor: f32, elements: &[f32]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx") {
+        return unsafe { avx::add_scaled(result, factor, elements) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        return unsafe { neon::add_scaled(result, factor, elements) };
+    }
+    scalar::add_scaled(result, factor, elements)
+}
+
+//dot for halves, summed in f32.
+pub(crate) fn dot_half(lhs: &[F16], rhs: &[F16]) -> f32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx") && is_x86_feature_detected!("f16c") {
+        return unsafe { avx::dot_half(lhs, rhs) };
+    }
+    crate::half::scalar::dot(lhs, rhs)
+}
+
+//add_scaled for halves added to f32 results.
+pub(crate) fn add_scaled_half(result: &mut [f32], factor: f32, elements: &[F16]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx") && is_x86_feature_detected!("f16c") {
+        return unsafe { avx::add_scaled_half(result, factor, elements) };
+    }
+    crate::half::scalar::add_scaled(result, factor, elements)
+}
+
+pub(crate) mod scalar {
+    use std::iter::zip;
+
+    pub(crate) fn dot(lhs: &[f32], rhs: &[f32]) -> f32 {
+        let mut sum = 0.0;
+        for (lhs_elem, rhs_elem) in zip(lhs, rhs) {
+            sum += lhs_elem * rhs_elem;
+        }
+        sum
+    }
+
+    pub(crate) fn add_scaled(result: &mut [f32], factor: f32, elements: &[f32]) {
+        for (result_elem, elem) in zip(result, elements) {
+            *result_elem += factor * elem;
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx {
+    use std::arch::x86_64::*;
+    use crate::F16;
+
+    const LANES: usize = 8;
+
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn dot(lhs: &[f32], rhs: &[f32]) -> f32 {
+        let length = lhs.len().min(rhs.len());
+        let chunks = length / LANES;
+        let mut sums = _mm256_setzero_ps();
+        for chunk in 0..chunks {
+            let (lhs_lanes, rhs_lanes) = unsafe {
+                (_mm256_loadu_ps(lhs.as_ptr().add(chunk * LANES)), _mm256_loadu_ps(rhs.as_ptr().add(chunk * LANES)))
+            };
+            sums = _mm256_add_ps(sums, _mm256_mul_ps(lhs_lanes, rhs_lanes));
+        }
+        let mut lanes = [0.0; LANES];
+        unsafe { _mm256_storeu_ps(lanes.as_mut_ptr(), sums) };
+        let sum: f32 = lanes.iter().sum();
+        sum + super::scalar::dot(&lhs[chunks * LANES..length], &rhs[chunks * LANES..length])
+    }
+
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn add_scaled(result: &mut [f32], factor: f32, elements: &[f32]) {
+        let length = result.len().min(elements.len());
+        let chunks = length / LANES;
+        let factors = _mm256_set1_ps(factor);
+        for chunk in 0..chunks {
+            unsafe {
+                let result_pointer = result.as_mut_ptr().add(chunk * LANES);
+                let products = _mm256_mul_ps(factors, _mm256_loadu_ps(elements.as_ptr().add(chunk * LANES)));
+                _mm256_storeu_ps(result_pointer, _mm256_add_ps(_mm256_loadu_ps(result_pointer), products));
+            }
+        }
+        super::scalar::add_scaled(&mut result[chunks * LANES..length], factor, &elements[chunks * LANES..length]);
+    }
+
+    //8 halves from elements[start..], F16 being a transparent u16.
+    #[target_feature(enable = "avx,f16c")]
+    unsafe fn load_halves(elements: &[F16], start: usize) -> __m256 {
+        unsafe { _mm256_cvtph_ps(_mm_loadu_si128(elements.as_ptr().add(start) as *const __m128i)) }
+    }
+
+    #[target_feature(enable = "avx,f16c")]
+    pub(super) unsafe fn dot_half(lhs: &[F16], rhs: &[F16]) -> f32 {
+        let length = lhs.len().min(rhs.len());
+        let chunks = length / LANES;
+        let mut sums = _mm256_setzero_ps();
+        for chunk in 0..chunks {
+            let (lhs_lanes, rhs_lanes) = unsafe { (load_halves(lhs, chunk * LANES), load_halves(rhs, chunk * LANES)) };
+            sums = _mm256_add_ps(sums, _mm256_mul_ps(lhs_lanes, rhs_lanes));
+        }
+        let mut lanes = [0.0; LANES];
+        unsafe { _mm256_storeu_ps(lanes.as_mut_ptr(), sums) };
+        let sum: f32 = lanes.iter().sum();
+        sum + crate::half::scalar::dot(&lhs[chunks * LANES..length], &rhs[chunks * LANES..length])
+    }
+
+    #[target_feature(enable = "avx,f16c")]
+    pub(super) unsafe fn add_scaled_half(result: &mut [f32], factor: f32, elements: &[F16]) {
+        let length = result.len().min(elements.len());
+        let chunks = length / LANES;
+        let factors = _mm256_set1_ps(factor);
+        for chunk in 0..chunks {
+            unsafe {
+                let result_pointer = result.as_mut_ptr().add(chunk * LANES);
+                let products = _mm256_mul_ps(factors, load_halves(elements, chunk * LANES));
+                _mm256_storeu_ps(result_pointer, _mm256_add_ps(_mm256_loadu_ps(result_pointer), products));
+            }
+        }
+        crate::half::scalar::add_scaled(&mut result[chunks * LANES..length], factor, &elements[chunks * LANES..length]);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    const LANES: usize = 4;
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn dot(lhs: &[f32], rhs: &[f32]) -> f32 {
+        let length = lhs.len().min(rhs.len());
+        let chunks = length / LANES;
+        let mut sums = vdupq_n_f32(0.0);
+        for chunk in 0..chunks {
+            let (lhs_lanes, rhs_lanes) = unsafe {
+                (vld1q_f32(lhs.as_ptr().add(chunk * LANES)), vld1q_f32(rhs.as_ptr().add(chunk * LANES)))
+            };
+            sums = vaddq_f32(sums, vmulq_f32(lhs_lanes, rhs_lanes));
+        }
+        vaddvq_f32(sums) + super::scalar::dot(&lhs[chunks * LANES..length], &rhs[chunks * LANES..length])
+    }
+
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn add_scaled(result: &mut [f32], factor: f32, elements: &[f32]) {
+        let length = result.len().min(elements.len());
+        let chunks = length / LANES;
+        let factors = vdupq_n_f32(factor);
+        for chunk in 0..chunks {
+            unsafe {
+                let result_pointer = result.as_mut_ptr().add(chunk * LANES);
+                let products = vmulq_f32(factors, vld1q_f32(elements.as_ptr().add(chunk * LANES)));
+                vst1q_f32(result_pointer, vaddq_f32(vld1q_f32(result_pointer), products));
+            }
+        }
+        super::scalar::add_scaled(&mut result[chunks * LANES..length], factor, &elements[chunks * LANES..length]);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::half;
+    use crate::F16;
+    use super::{add_scaled, add_scaled_half, dot, dot_half, scalar};
+
+    #[test]
+    fn vectorized_loops_match_the_scalar_ones() {
+        //lengths around the lane counts, with and without a scalar remainder.
+        for length in [0, 1, 3, 4, 7, 8, 9, 16, 31] {
+            let lhs: Vec<f32> = (0..length).map(|x| (x as f32 * 0.3).sin()).collect();
+            let rhs: Vec<f32> = (0..length).map(|x| (x as f32 * 0.7).cos()).collect();
+            assert!((dot(&lhs, &rhs) - scalar::dot(&lhs, &rhs)).abs() < 1e-5);
+            let mut result = rhs.clone();
+            let mut expected = rhs.clone();
+            add_scaled(&mut result, 1.5, &lhs);
+            scalar::add_scaled(&mut expected, 1.5, &lhs);
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn vectorized_half_loops_match_the_scalar_ones() {
+        for length in [0, 1, 7, 8, 9, 16, 31] {
+            let lhs: Vec<F16> = (0..length).map(|x| F16::from_f32((x as f32 * 0.3).sin())).collect();
+            let rhs: Vec<F16> = (0..length).map(|x| F16::from_f32((x as f32 * 0.7).cos())).collect();
+            assert!((dot_half(&lhs, &rhs) - half::scalar::dot(&lhs, &rhs)).abs() < 1e-5);
+            let mut result: Vec<f32> = (0..length).map(|x| x as f32 * 0.1).collect();
+            let mut expected = result.clone();
+            add_scaled_half(&mut result, 1.5, &lhs);
+            half::scalar::add_scaled(&mut expected, 1.5, &lhs);
+            assert_eq!(result, expected);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::ops::{Index, Mul, Range};
+use crate::{ColumnVector, Matrix, Scalar};
+
+//a borrowed rectangle of a matrix, e.g. some samples of a batch or one column. nothing is
+//copied until to_matrix. row r of the view starts stride elements after row r - 1, the stride
+//being the width of the matrix the view was taken from.
+pub struct MatrixView<'a, T = f32> {
+    data: &'a [T],
+    offset: usize,
+    height: usize,
+    width: usize,
+    stride: usize,
+}
+
+//derived Clone and Copy would require T: Copy, a view only copies the reference.
+impl<T> Clone for MatrixView<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for MatrixView<'_, T> {}
+
+impl<'a, T> MatrixView<'a, T> {
+    pub(crate) fn new(matrix: &'a Matrix<T>) -> MatrixView<'a, T> {
+        MatrixView { data: &matrix.data, offset: 0, height: matrix.height(), width: matrix.width(), stride: matrix.width() }
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn row(&self, index: usize) -> &'a [T] {
+        if index >= self.height {
+            panic!("row {} of a view with {} rows.", index, self.height);
+        }
+        let start = self.offset + index * self.stride;
+        &self.data[start..start + self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item=&'a [T]> + 'a {
+        let view = *self;
+        (0..self.height).map(move |index| view.row(index))
+    }
+
+    //the part of this view in the given rows and columns, counted from the corner of the view.
+    pub fn view(&self, rows: Range<usize>, columns: Range<usize>) -> MatrixView<'a, T> {
+        if rows.start > rows.end || rows.end > self.height || columns.start > columns.end || columns.end > self.width {
+            panic!("rows {:?} and columns {:?} are not within a {} x {} view.", rows, columns, self.height, self.width);
+        }
+        MatrixView {
+            data: self.data,
+            offset: self.offset + rows.start * self.stride + columns.start,
+            height: rows.len(),
+            width: columns.len(),
+            stride: self.stride,
+        }
+    }
+}
+
+impl<T: Copy> MatrixView<'_, T> {
+    pub fn to_matrix(&self) -> Matrix<T> {
+        Matrix::from_row_major(self.height, self.width, self.rows().flatten().copied().collect())
+    }
+}
+
+impl<T: Scalar> MatrixView<'_, T> {
+    pub fn column(&self, index: usize) -> ColumnVector<T> {
+        ColumnVector::from_vec(self.rows().map(|row| row[index]).collect())
+    }
+}
+
+impl<T> Index<(usize, usize)> for MatrixView<'_, T> {
+    type Output = T;
+    fn index(&self, (row, column): (usize, usize)) -> &T {
+        if column >= self.width {
+            panic!("column {} of a view with {} columns.", column, self.width);
+        }
+        &self.row(row)[column]
+    }
+}
+
+impl<T: Scalar> Mul<&ColumnVector<T>> for MatrixView<'_, T> {
+    type Output = ColumnVector<T>;
+    fn mul(self, rhs: &ColumnVector<T>) -> Self::Output {
+        if self.width != rhs.data.len() {
+            panic!("the width of the view must match the length of the vector.");
+        }
+        ColumnVector::from_vec(self.rows().map(|row| T::dot(row, &rhs.data)).collect())
+    }
+}
+
+impl<T> Matrix<T> {
+    //all of the matrix as a view.
+    pub fn view(&self) -> MatrixView<'_, T> {
+        MatrixView::new(self)
+    }
+
+    //the elements in the given rows and columns, without copying them.
+    pub fn sub_matrix(&self, rows: Range<usize>, columns: Range<usize>) -> MatrixView<'_, T> {
+        self.view().view(rows, columns)
+    }
+
+    //one column, e.g. a single sample of a batch.
+    pub fn column_view(&self, index: usize) -> MatrixView<'_, T> {
+        self.sub_matrix(0..self.height(), index..index + 1)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{ColumnVector, Matrix};
+
+    fn counting_matrix() -> Matrix {
+        Matrix::from_row_major(3, 4, (0..12).map(|x| x as f32).collect())
+    }
+
+    #[test]
+    fn sub_matrices() {
+        let matrix = counting_matrix();
+        let view = matrix.sub_matrix(1..3, 1..3);
+        assert_eq!((view.height(), view.width()), (2, 2));
+        assert_eq!(view.rows().collect::<Vec<_>>(), vec![&[5.0, 6.0][..], &[9.0, 10.0][..]]);
+        assert_eq!(view[(1, 0)], 9.0);
+        assert_eq!(view.to_matrix(), Matrix::from_vec(vec![vec![5.0, 6.0], vec![9.0, 10.0]]));
+        //views of views count from their own corner.
+        assert_eq!(view.view(1..2, 0..2).to_matrix(), Matrix::from_vec(vec![vec![9.0, 10.0]]));
+        assert_eq!(matrix.view().to_matrix(), matrix);
+        let empty = matrix.sub_matrix(3..3, 0..4);
+        assert_eq!((empty.height(), empty.rows().count()), (0, 0));
+    }
+
+    #[test]
+    fn columns() {
+        let matrix = counting_matrix();
+        let column = matrix.column_view(2);
+        assert_eq!((column.height(), column.width()), (3, 1));
+        assert_eq!(column.column(0), matrix.column(2));
+        let vector = ColumnVector::from_vec(vec![1.0, 0.0, -1.0]);
+        assert_eq!(matrix.sub_matrix(0..3, 1..4) * &vector, ColumnVector::from_vec(vec![-2.0, -2.0, -2.0]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_past_the_view() {
+        let matrix = counting_matrix();
+        let _ = matrix.sub_matrix(0..2, 0..2)[(0, 2)];
+    }
+}
@@ -0,0 +1,294 @@
+use std::cell::RefCell;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+use matrix::ColumnVector;
+use crate::Dataset;
+
+//random perturbations of row by row flattened width x height images, drawn anew for every call.
+//images with several channels are stored one channel after the other and every channel
+//is perturbed the same way. 0 disables the respective perturbation. the noise is in pixel
+//values, so the defaults of new expect pixels scaled to [0, 1], e.g. by Preprocessing::Scale.
+//the loaders return raw 0..255 values, new_for_raw_pixels suits those.
+pub struct Augmentation {
+    pub width: usize,
+    pub height: usize,
+    pub channels: usize,
+    //largest translation in pixels along each axis.
+    pub max_shift: f32,
+    //largest rotation around the image center in radians.
+    pub max_rotation: f32,
+    //standard deviation of the gaussian noise added to every pixel, in the units of the pixels.
+    pub noise_standard_deviation: f32,
+    //strength of the elastic distortion of Simard et al. in pixels, 34 in the paper.
+    pub elastic_alpha: f32,
+    //smoothness of the elastic displacement field, 4 in the paper. 0 leaves the field unsmoothed.
+    pub elastic_sigma: f32,
+    pub rng: RefCell<StdRng>,
+}
+
+//value at the fractional position x, y, interpolated between the four surrounding pixels.
+//positions outside the image read as 0, the background of MNIST.
+fn sample_bilinear(channel: &[f32], width: usize, height: usize, x: f32, y: f32) -> f32 {
+    let pixel = |column: i64, row: i64| -> f32 {
+        if column < 0 || row < 0 || column >= width as i64 || row >= height as i64 {
+            0.0
+        } else {
+            channel[row as usize * width + column as usize]
+        }
+    };
+    let (left, top) = (x.floor(), y.floor());
+    let (horizontal, vertical) = (x - left, y - top);
+    let (left, top) = (left as i64, top as i64);
+    (1.0 - vertical) * ((1.0 - horizontal) * pixel(left, top) + horizontal * pixel(left + 1, top))
+        + vertical * ((1.0 - horizontal) * pixel(left, top + 1) + horizontal * pixel(left + 1, top + 1))
+}
+
+//resamples every channel of the image, reading the output pixel at column, row
+//from the input position source(column, row).
+fn resample(image: &ColumnVector, width: usize, height: usize, source: impl Fn(usize, usize) -> (f32, f32)) -> ColumnVector {
+    let positions: Vec<(f32, f32)> = (0..width * height).map(|i| source(i % width, i / width)).collect();
+    ColumnVector::from_vec(image.data.chunks(width * height)
+        .flat_map(|channel| positions.iter().map(move |&(x, y)| sample_bilinear(channel, width, height, x, y)))
+        .collect())
+}
+
+//rotates the image by angle around its center, then translates it by shift_x, shift_y.
+pub fn transform(image: &ColumnVector, width: usize, height: usize, angle: f32, shift_x: f32, shift_y: f32) -> ColumnVector {
+    let (center_x, center_y) = ((width as f32 - 1.0) / 2.0, (height as f32 - 1.0) / 2.0);
+    let (sin, cos) = angle.sin_cos();
+    //every output pixel is read from where the inverse transformation maps it.
+    resample(image, width, height, |column, row| {
+        let x = column as f32 - shift_x - center_x;
+        let y = row as f32 - shift_y - center_y;
+        (cos * x + sin * y + center_x, -sin * x + cos * y + center_y)
+    })
+}
+
+//moves every pixel along its own displacement: the output at a pixel is read from
+//the input at the pixel plus its displacement.
+pub fn displace(image: &ColumnVector, width: usize, height: usize, displacement_x: &[f32], displacement_y: &[f32]) -> ColumnVector {
+    resample(image, width, height, |column, row| {
+        let i = row * width + column;
+        (column as f32 + displacement_x[i], row as f32 + displacement_y[i])
+    })
+}
+
+//separable gaussian blur of a width x height field, treating pixels outside as 0.
+//a sigma of 0 or less returns the field unchanged, the kernel would divide by zero.
+fn gaussian_blur(field: &[f32], width: usize, height: usize, sigma: f32) -> Vec<f32> {
+    if sigma <= 0.0 {
+        return field.to_vec();
+    }
+    let radius = (3.0 * sigma).ceil() as i64;
+    let kernel: Vec<f32> = (-radius..=radius).map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp()).collect();
+    let total: f32 = kernel.iter().sum();
+    let kernel: Vec<f32> = kernel.iter().map(|x| x / total).collect();
+    let blur = |field: &[f32], step: (i64, i64)| -> Vec<f32> {
+        (0..width * height).map(|i| {
+            let (column, row) = ((i % width) as i64, (i / width) as i64);
+            kernel.iter().enumerate().map(|(k, weight)| {
+                let offset = k as i64 - radius;
+                let (column, row) = (column + offset * step.0, row + offset * step.1);
+                if column < 0 || row < 0 || column >= width as i64 || row >= height as i64 {
+                    0.0
+                } else {
+                    weight * field[row as usize * width + column as usize]
+                }
+            }).sum()
+        }).collect()
+    };
+    blur(&blur(field, (1, 0)), (0, 1))
+}
+
+impl Augmentation {
+    //small perturbations suited to 28x28 MNIST digits with pixels in [0, 1].
+    pub fn new(width: usize, height: usize) -> Augmentation {
+        Augmentation::new_with_channels(width, height, 1)
+    }
+
+    pub fn new_with_channels(width: usize, height: usize, channels: usize) -> Augmentation {
+        Augmentation {
+            width,
+            height,
+            channels,
+            max_shift: 2.0,
+            max_rotation: 15.0_f32.to_radians(),
+            noise_standard_deviation: 0.05,
+            elastic_alpha: 0.0,
+            elastic_sigma: 4.0,
+            rng: RefCell::new(StdRng::from_entropy()),
+        }
+    }
+
+    //the defaults of new with the noise scaled to raw 0..255 pixels, as the loaders return them.
+    pub fn new_for_raw_pixels(width: usize, height: usize) -> Augmentation {
+        let mut augmentation = Augmentation::new(width, height);
+        augmentation.noise_standard_deviation *= 255.0;
+        augmentation
+    }
+
+    pub fn new_with_seed(width: usize, height: usize, seed: u64) -> Augmentation {
+        let augmentation = Augmentation::new(width, height);
+        augmentation.rng.replace(StdRng::seed_from_u64(seed));
+        augmentation
+    }
+
+    //the elastic distortion of Simard et al. with alpha 34 and sigma 4 on top of the defaults.
+    pub fn new_with_elastic_deformation(width: usize, height: usize) -> Augmentation {
+        let mut augmentation = Augmentation::new(width, height);
+        augmentation.elastic_alpha = 34.0;
+        augmentation
+    }
+
+    //uniform random displacements in [-1, 1], smoothed and scaled by alpha.
+    fn elastic_displacement(&self, rng: &mut StdRng) -> Vec<f32> {
+        let field: Vec<f32> = (0..self.width * self.height).map(|_| rng.gen_range(-1.0..=1.0)).collect();
+        gaussian_blur(&field, self.width, self.height, self.elastic_sigma).iter().map(|x| x * self.elastic_alpha).collect()
+    }
+
+    pub fn apply(&self, image: &ColumnVector) -> ColumnVector {
+        if image.data.len() != self.width * self.height * self.channels {
+            panic!("expected a {}x{} image with {} channels, got {} values.", self.width, self.height, self.channels, image.data.len());
+        }
+        let mut rng = self.rng.borrow_mut();
+        let mut distorted = None;
+        if self.elastic_alpha > 0.0 {
+            let displacement_x = self.elastic_displacement(&mut rng);
+            let displacement_y = self.elastic_displacement(&mut rng);
+            distorted = Some(displace(image, self.width, self.height, &displacement_x, &displacement_y));
+        }
+        let image = distorted.as_ref().unwrap_or(image);
+        let mut uniform = |max: f32| if max > 0.0 { rng.gen_range(-max..=max) } else { 0.0 };
+        let angle = uniform(self.max_rotation);
+        let shift_x = uniform(self.max_shift);
+        let shift_y = uniform(self.max_shift);
+        let mut result = transform(image, self.width, self.height, angle, shift_x, shift_y);
+        if self.noise_standard_deviation > 0.0 {
+            let normal = Normal::new(0.0, self.noise_standard_deviation).unwrap();
+            result.data.iter_mut().for_each(|x| *x += normal.sample(&mut *rng));
+        }
+        result
+    }
+}
+
+//a dataset whose inputs are augmented every time they are read, so every epoch of
+//training sees different variations of the same samples. labels are unchanged.
+pub struct Augmented<'a, D: Dataset + ?Sized> {
+    pub dataset: &'a D,
+    pub augmentation: Augmentation,
+}
+
+impl<D: Dataset + ?Sized> Dataset for Augmented<'_, D> {
+    fn len(&self) -> usize {
+        self.dataset.len()
+    }
+
+    fn get(&self, index: usize) -> (ColumnVector, usize) {
+        let (input, label) = self.dataset.get(index);
+        (self.augmentation.apply(&input), label)
+    }
+
+    fn label(&self, index: usize) -> usize {
+        self.dataset.label(index)
+    }
+
+    fn reseed(&self, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        self.dataset.reseed(rng.gen());
+        self.augmentation.rng.replace(rng);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::{ColumnVector, Matrix};
+    use crate::{displace, transform, Augmentation, Augmented, Dataset};
+
+    //a single lit pixel at column, row of a 5x5 image.
+    fn dot(column: usize, row: usize) -> ColumnVector {
+        let mut image = ColumnVector::new_with_elements(25, 0.0);
+        image.data[row * 5 + column] = 1.0;
+        image
+    }
+
+    #[test]
+    fn shifts_and_rotations() {
+        assert_eq!(transform(&dot(2, 2), 5, 5, 0.0, 1.0, -2.0), dot(3, 0));
+        assert_eq!(transform(&dot(1, 1), 5, 5, 0.0, 4.0, 0.0), ColumnVector::new_with_elements(25, 0.0));
+        //a quarter turn moves the top row to the right column.
+        let rotated = transform(&dot(2, 0), 5, 5, std::f32::consts::FRAC_PI_2, 0.0, 0.0);
+        let expected = dot(4, 2);
+        assert!(rotated.data.iter().zip(&expected.data).all(|(a, b)| (a - b).abs() < 1e-5));
+        let half_pixel = transform(&dot(2, 2), 5, 5, 0.0, 0.5, 0.0);
+        assert_eq!((half_pixel.data[12], half_pixel.data[13]), (0.5, 0.5));
+    }
+
+    #[test]
+    fn augmented_dataset() {
+        let dataset = vec![(dot(2, 2), 3)];
+        let augmented = Augmented { dataset: &dataset, augmentation: Augmentation::new_with_seed(5, 5, 1) };
+        let (first, label) = augmented.get(0);
+        let (second, _) = augmented.get(0);
+        assert_eq!(label, 3);
+        assert_ne!(first, second);
+        //brightness is preserved up to noise and pixels pushed off the image.
+        assert!((first.data.iter().sum::<f32>() - 1.0).abs() < 1.0);
+
+        let mut quiet = Augmentation::new_with_seed(5, 5, 1);
+        quiet.max_shift = 0.0;
+        quiet.max_rotation = 0.0;
+        quiet.noise_standard_deviation = 0.0;
+        assert_eq!(quiet.apply(&dot(1, 3)), dot(1, 3));
+
+        //the noise is as large relative to a raw 255 pixel as the default one is to a pixel of 1.
+        let raw = Augmentation::new_for_raw_pixels(5, 5);
+        raw.rng.replace(rand::SeedableRng::seed_from_u64(1));
+        let raw_image = ColumnVector::from_vec(dot(2, 2).data.iter().map(|x| x * 255.0).collect());
+        let scaled_back: Vec<f32> = raw.apply(&raw_image).data.iter().map(|x| x / 255.0).collect();
+        assert!(scaled_back.iter().zip(&Augmentation::new_with_seed(5, 5, 1).apply(&dot(2, 2)).data).all(|(a, b)| (a - b).abs() < 1e-4));
+    }
+
+    #[test]
+    fn elastic_deformation() {
+        //a constant displacement is a translation.
+        assert_eq!(displace(&dot(2, 2), 5, 5, &[-1.0; 25], &[0.0; 25]), dot(3, 2));
+
+        let mut image = Matrix::zeros(28, 28);
+        (8..20).for_each(|row| (12..16).for_each(|column| image[(row, column)] = 1.0));
+        let image = image.into_column_vector();
+        let mut augmentation = Augmentation::new_with_elastic_deformation(28, 28);
+        augmentation.rng.replace(rand::SeedableRng::seed_from_u64(3));
+        augmentation.max_shift = 0.0;
+        augmentation.max_rotation = 0.0;
+        augmentation.noise_standard_deviation = 0.0;
+        let deformed = augmentation.apply(&image);
+        assert_ne!(deformed, image);
+        //the displacements are smooth and small, so the stroke stays where it was.
+        let brightness: f32 = deformed.data.iter().sum();
+        let center_row = deformed.data.iter().enumerate().map(|(i, x)| (i / 28) as f32 * x).sum::<f32>() / brightness;
+        let center_column = deformed.data.iter().enumerate().map(|(i, x)| (i % 28) as f32 * x).sum::<f32>() / brightness;
+        assert!((center_row - 13.5).abs() < 3.0 && (center_column - 13.5).abs() < 3.0);
+        assert!(deformed.data.iter().all(|&x| (0.0..=1.0 + 1e-6).contains(&x)));
+
+        //without smoothing the displacements are used as drawn instead of turning into NaN.
+        augmentation.elastic_sigma = 0.0;
+        augmentation.elastic_alpha = 1.0;
+        assert!(augmentation.apply(&image).data.iter().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn channels_are_moved_together() {
+        let mut image = dot(1, 1);
+        image.data.extend(dot(3, 2).data.iter().map(|x| x * 0.5));
+        let mut augmentation = Augmentation::new_with_channels(5, 5, 2);
+        augmentation.noise_standard_deviation = 0.0;
+        augmentation.max_rotation = 0.0;
+        let shifted = transform(&image, 5, 5, 0.0, 1.0, 1.0);
+        assert_eq!(&shifted.data[..25], &dot(2, 2).data[..]);
+        assert_eq!(shifted.data[25..].iter().sum::<f32>(), 0.5);
+        assert_eq!(shifted.data[25 + 3 * 5 + 4], 0.5);
+        assert_eq!(augmentation.apply(&image).data.len(), 50);
+    }
+}
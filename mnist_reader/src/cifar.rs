@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind, Read};
+use std::path::Path;
+use matrix::ColumnVector;
+use crate::ImageDataset;
+
+pub const CIFAR10_SIZE: usize = 32;
+pub const CIFAR10_CHANNELS: usize = 3;
+
+//names of the ten CIFAR-10 classes, indexed by label.
+pub const CIFAR10_CLASSES: [&str; 10] = [
+    "airplane", "automobile", "bird", "cat", "deer", "dog", "frog", "horse", "ship", "truck",
+];
+
+//parses the CIFAR-10 binary format: records of a label byte followed by the 1024 red,
+//1024 green and 1024 blue values of a 32x32 image, each channel row by row.
+//pixels keep their raw 0..255 values, like the idx images.
+pub fn parse_cifar10<R: Read>(mut reader: R) -> io::Result<ImageDataset> {
+    let image_size = CIFAR10_SIZE * CIFAR10_SIZE * CIFAR10_CHANNELS;
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() % (image_size + 1) != 0 {
+        return Err(io::Error::new(ErrorKind::InvalidData, format!("{} bytes are not a whole amount of {} byte records.", bytes.len(), image_size + 1)));
+    }
+    let mut images = Vec::new();
+    let mut labels = Vec::new();
+    for record in bytes.chunks(image_size + 1) {
+        if record[0] as usize >= CIFAR10_CLASSES.len() {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("label {} is not one of the 10 classes.", record[0])));
+        }
+        labels.push(record[0]);
+        images.push(ColumnVector::from_vec(record[1..].iter().map(|&x| x as f32).collect()));
+    }
+    Ok(ImageDataset {
+        rows: CIFAR10_SIZE,
+        columns: CIFAR10_SIZE,
+        channels: CIFAR10_CHANNELS,
+        images,
+        labels,
+        classes: CIFAR10_CLASSES.len(),
+    })
+}
+
+//reads and concatenates batch files, e.g. data_batch_1.bin to data_batch_5.bin for
+//training or test_batch.bin for testing.
+pub fn read_cifar10<P: AsRef<Path>>(file_paths: &[P]) -> io::Result<ImageDataset> {
+    let mut dataset = parse_cifar10(io::empty())?;
+    for file_path in file_paths {
+        let batch = parse_cifar10(BufReader::new(File::open(file_path)?))?;
+        dataset.images.extend(batch.images);
+        dataset.labels.extend(batch.labels);
+    }
+    Ok(dataset)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_cifar10, read_cifar10, Dataset};
+
+    fn record(label: u8, value: u8) -> Vec<u8> {
+        let mut bytes = vec![label];
+        bytes.extend(vec![value; 1024]);
+        bytes.extend(vec![value / 2; 1024]);
+        bytes.extend(vec![0; 1024]);
+        bytes
+    }
+
+    #[test]
+    fn cifar10_records() {
+        let mut bytes = record(3, 200);
+        bytes.extend(record(9, 10));
+        let dataset = parse_cifar10(bytes.as_slice()).unwrap();
+        assert_eq!((dataset.len(), dataset.image_size(), dataset.classes), (2, 3072, 10));
+        let (image, label) = dataset.get(0);
+        assert_eq!(label, 3);
+        assert_eq!((image.data[1023], image.data[1024], image.data[2048]), (200.0, 100.0, 0.0));
+
+        assert!(parse_cifar10(&bytes[1..]).is_err());
+        assert!(parse_cifar10(record(10, 0).as_slice()).is_err());
+
+        let directory = std::env::temp_dir().join(format!("mnist_reader_cifar_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let paths = [directory.join("data_batch_1.bin"), directory.join("data_batch_2.bin")];
+        std::fs::write(&paths[0], &bytes).unwrap();
+        std::fs::write(&paths[1], record(1, 5)).unwrap();
+        let dataset = read_cifar10(&paths).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(dataset.labels, vec![3, 9, 1]);
+    }
+}
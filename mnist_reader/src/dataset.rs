@@ -0,0 +1,377 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use matrix::ColumnVector;
+
+//a source of labeled samples, e.g. MNIST, synthetic data or a user's own images.
+pub trait Dataset {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    //the input of the sample at index together with its class label.
+    fn get(&self, index: usize) -> (ColumnVector, usize);
+
+    //worth overriding when the input is expensive to produce, stratification only needs labels.
+    fn label(&self, index: usize) -> usize {
+        self.get(index).1
+    }
+
+    //datasets that draw random numbers, like Augmented, restart them from seed.
+    //the trainer calls it before every epoch in deterministic mode.
+    fn reseed(&self, _seed: u64) {}
+}
+
+impl Dataset for [(ColumnVector, usize)] {
+    fn len(&self) -> usize {
+        <[(ColumnVector, usize)]>::len(self)
+    }
+
+    fn get(&self, index: usize) -> (ColumnVector, usize) {
+        self[index].clone()
+    }
+
+    fn label(&self, index: usize) -> usize {
+        self[index].1
+    }
+}
+
+impl Dataset for Vec<(ColumnVector, usize)> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn get(&self, index: usize) -> (ColumnVector, usize) {
+        self[index].clone()
+    }
+
+    fn label(&self, index: usize) -> usize {
+        self[index].1
+    }
+}
+
+//the samples of dataset at indices, in that order.
+pub struct Subset<'a, D: Dataset + ?Sized> {
+    pub dataset: &'a D,
+    pub indices: Vec<usize>,
+}
+
+impl<D: Dataset + ?Sized> Dataset for Subset<'_, D> {
+    fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn get(&self, index: usize) -> (ColumnVector, usize) {
+        self.dataset.get(self.indices[index])
+    }
+
+    fn label(&self, index: usize) -> usize {
+        self.dataset.label(self.indices[index])
+    }
+
+    fn reseed(&self, seed: u64) {
+        self.dataset.reseed(seed);
+    }
+}
+
+//shuffled indices of every class, keyed by label.
+fn indices_by_class<D: Dataset + ?Sized, R: Rng>(dataset: &D, rng: &mut R) -> BTreeMap<usize, Vec<usize>> {
+    let mut classes: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for index in 0..dataset.len() {
+        classes.entry(dataset.label(index)).or_default().push(index);
+    }
+    classes.values_mut().for_each(|indices| indices.shuffle(rng));
+    classes
+}
+
+//a random order of all samples in which the classes are spread out evenly, so every
+//contiguous run of it has roughly the class distribution of the whole dataset.
+fn stratified_order<D: Dataset + ?Sized, R: Rng>(dataset: &D, rng: &mut R) -> Vec<usize> {
+    let mut keyed: Vec<(f32, usize)> = Vec::with_capacity(dataset.len());
+    for indices in indices_by_class(dataset, rng).values() {
+        let offset: f32 = rng.gen();
+        keyed.extend(indices.iter().enumerate().map(|(rank, &index)| ((rank as f32 + offset) / indices.len() as f32, index)));
+    }
+    keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+    keyed.into_iter().map(|(_, index)| index).collect()
+}
+
+//randomly splits dataset into disjoint training and validation subsets, with
+//training_ratio of the samples (rounded down) going to training. the same seed gives the same split.
+pub fn split<D: Dataset + ?Sized>(dataset: &D, training_ratio: f32, seed: u64) -> (Subset<'_, D>, Subset<'_, D>) {
+    if !(0.0..=1.0).contains(&training_ratio) {
+        panic!("training ratio must be in [0, 1].");
+    }
+    let mut indices: Vec<usize> = (0..dataset.len()).collect();
+    indices.shuffle(&mut StdRng::seed_from_u64(seed));
+    let validation_indices = indices.split_off((dataset.len() as f32 * training_ratio) as usize);
+    (Subset { dataset, indices }, Subset { dataset, indices: validation_indices })
+}
+
+//splits a shuffled dataset into k folds and returns, for every fold, the remaining samples
+//for training together with the fold itself for validation. the same seed gives the same folds.
+pub fn k_fold<D: Dataset + ?Sized>(dataset: &D, k: usize, seed: u64) -> Vec<(Subset<'_, D>, Subset<'_, D>)> {
+    if k < 2 || k > dataset.len() {
+        panic!("k must be between 2 and the size of the dataset, got {}.", k);
+    }
+    let mut indices: Vec<usize> = (0..dataset.len()).collect();
+    indices.shuffle(&mut StdRng::seed_from_u64(seed));
+    (0..k).map(|fold| {
+        //folds differ in size by at most one sample.
+        let start = fold * dataset.len() / k;
+        let end = (fold + 1) * dataset.len() / k;
+        let training_indices = indices[..start].iter().chain(&indices[end..]).cloned().collect();
+        (Subset { dataset, indices: training_indices }, Subset { dataset, indices: indices[start..end].to_vec() })
+    }).collect()
+}
+
+//like split, but every class is split with training_ratio on its own, so both subsets keep
+//the class distribution of the dataset. the classes are shuffled together afterwards, not left in blocks.
+pub fn stratified_split<D: Dataset + ?Sized>(dataset: &D, training_ratio: f32, seed: u64) -> (Subset<'_, D>, Subset<'_, D>) {
+    if !(0.0..=1.0).contains(&training_ratio) {
+        panic!("training ratio must be in [0, 1].");
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut training_indices = Vec::new();
+    let mut validation_indices = Vec::new();
+    for indices in indices_by_class(dataset, &mut rng).values() {
+        let training_amount = (indices.len() as f32 * training_ratio).round() as usize;
+        training_indices.extend_from_slice(&indices[..training_amount]);
+        validation_indices.extend_from_slice(&indices[training_amount..]);
+    }
+    training_indices.shuffle(&mut rng);
+    validation_indices.shuffle(&mut rng);
+    (Subset { dataset, indices: training_indices }, Subset { dataset, indices: validation_indices })
+}
+
+//like k_fold, but every fold gets its share of every class.
+pub fn stratified_k_fold<D: Dataset + ?Sized>(dataset: &D, k: usize, seed: u64) -> Vec<(Subset<'_, D>, Subset<'_, D>)> {
+    if k < 2 || k > dataset.len() {
+        panic!("k must be between 2 and the size of the dataset, got {}.", k);
+    }
+    let order = stratified_order(dataset, &mut StdRng::seed_from_u64(seed));
+    (0..k).map(|fold| {
+        let mut training_indices = Vec::new();
+        let mut validation_indices = Vec::new();
+        for (position, &index) in order.iter().enumerate() {
+            if position % k == fold {
+                validation_indices.push(index);
+            } else {
+                training_indices.push(index);
+            }
+        }
+        (Subset { dataset, indices: training_indices }, Subset { dataset, indices: validation_indices })
+    }).collect()
+}
+
+//hands out the samples of a dataset in batches of batch_size, the last one may be smaller.
+//with shuffle set every iteration, i.e. every epoch, visits the samples in a new random order.
+//stratify additionally spreads the classes over that order, so every batch has about the
+//class distribution of the dataset. it implies shuffle.
+pub struct DataLoader<'a, D: Dataset + ?Sized> {
+    pub dataset: &'a D,
+    pub batch_size: usize,
+    pub shuffle: bool,
+    pub stratify: bool,
+    //advanced by every shuffled iteration, so a seeded loader repeats its sequence of epochs.
+    pub rng: RefCell<StdRng>,
+}
+
+impl<'a, D: Dataset + ?Sized> DataLoader<'a, D> {
+    pub fn new(dataset: &'a D, batch_size: usize) -> DataLoader<'a, D> {
+        if batch_size == 0 {
+            panic!("batch size must be at least 1.");
+        }
+        DataLoader {
+            dataset,
+            batch_size,
+            shuffle: false,
+            stratify: false,
+            rng: RefCell::new(StdRng::from_entropy()),
+        }
+    }
+
+    pub fn new_shuffled(dataset: &'a D, batch_size: usize) -> DataLoader<'a, D> {
+        let mut loader = DataLoader::new(dataset, batch_size);
+        loader.shuffle = true;
+        loader
+    }
+
+    pub fn new_shuffled_with_seed(dataset: &'a D, batch_size: usize, seed: u64) -> DataLoader<'a, D> {
+        let loader = DataLoader::new_shuffled(dataset, batch_size);
+        loader.rng.replace(StdRng::seed_from_u64(seed));
+        loader
+    }
+
+    pub fn batch_amount(&self) -> usize {
+        self.dataset.len().div_ceil(self.batch_size)
+    }
+
+    pub fn iter(&self) -> Batches<'a, D> {
+        let order = if self.stratify {
+            stratified_order(self.dataset, &mut *self.rng.borrow_mut())
+        } else {
+            let mut order: Vec<usize> = (0..self.dataset.len()).collect();
+            if self.shuffle {
+                order.shuffle(&mut *self.rng.borrow_mut());
+            }
+            order
+        };
+        Batches {
+            dataset: self.dataset,
+            batch_size: self.batch_size,
+            order,
+            position: 0,
+        }
+    }
+}
+
+impl<'a, D: Dataset + ?Sized> IntoIterator for &DataLoader<'a, D> {
+    type Item = Vec<(ColumnVector, usize)>;
+    type IntoIter = Batches<'a, D>;
+
+    fn into_iter(self) -> Batches<'a, D> {
+        self.iter()
+    }
+}
+
+pub struct Batches<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    batch_size: usize,
+    order: Vec<usize>,
+    position: usize,
+}
+
+impl<D: Dataset + ?Sized> Iterator for Batches<'_, D> {
+    type Item = Vec<(ColumnVector, usize)>;
+
+    fn next(&mut self) -> Option<Vec<(ColumnVector, usize)>> {
+        if self.position >= self.order.len() {
+            return None;
+        }
+        let end = (self.position + self.batch_size).min(self.order.len());
+        let batch = self.order[self.position..end].iter().map(|&index| self.dataset.get(index)).collect();
+        self.position = end;
+        Some(batch)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::ColumnVector;
+    use crate::{k_fold, split, stratified_k_fold, stratified_split, DataLoader, Dataset};
+
+    #[test]
+    fn data_loader_batches() {
+        let dataset: Vec<(ColumnVector, usize)> = (0..7).map(|x| (ColumnVector::from_vec(vec![x as f32]), x % 3)).collect();
+        let loader = DataLoader::new(&dataset, 3);
+        assert_eq!(loader.batch_amount(), 3);
+        let batches: Vec<Vec<(ColumnVector, usize)>> = loader.iter().collect();
+        assert_eq!(batches.iter().map(|x| x.len()).collect::<Vec<usize>>(), vec![3, 3, 1]);
+        assert_eq!(batches[2][0], dataset.get(6));
+        //iterating again starts from the beginning.
+        assert_eq!((&loader).into_iter().count(), 3);
+        assert!(!dataset.is_empty());
+    }
+
+    #[test]
+    fn shuffled_data_loader() {
+        let dataset: Vec<(ColumnVector, usize)> = (0..50).map(|x| (ColumnVector::from_vec(vec![x as f32]), x)).collect();
+        let loader = DataLoader::new_shuffled(&dataset, 8);
+        let epoch = |loader: &DataLoader<Vec<(ColumnVector, usize)>>| -> Vec<usize> {
+            loader.iter().flat_map(|batch| batch.into_iter().map(|(_, label)| label)).collect()
+        };
+        let first = epoch(&loader);
+        let second = epoch(&loader);
+        assert_eq!(loader.iter().map(|x| x.len()).collect::<Vec<usize>>(), vec![8, 8, 8, 8, 8, 8, 2]);
+        //every sample exactly once per epoch, in a different order each time.
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..50).collect::<Vec<usize>>());
+        assert_ne!(first, second);
+        assert_ne!(first, sorted);
+    }
+
+    #[test]
+    fn seeded_data_loader_repeats_epochs() {
+        let dataset: Vec<(ColumnVector, usize)> = (0..20).map(|x| (ColumnVector::from_vec(vec![x as f32]), x)).collect();
+        let epochs = |loader: DataLoader<Vec<(ColumnVector, usize)>>| -> Vec<Vec<usize>> {
+            (0..3).map(|_| loader.iter().flatten().map(|(_, label)| label).collect()).collect()
+        };
+        let first = epochs(DataLoader::new_shuffled_with_seed(&dataset, 4, 7));
+        assert_eq!(first, epochs(DataLoader::new_shuffled_with_seed(&dataset, 4, 7)));
+        assert_ne!(first[0], first[1]);
+        assert_ne!(first, epochs(DataLoader::new_shuffled_with_seed(&dataset, 4, 8)));
+    }
+
+    #[test]
+    fn train_validation_split() {
+        let dataset: Vec<(ColumnVector, usize)> = (0..10).map(|x| (ColumnVector::from_vec(vec![x as f32]), x)).collect();
+        let (training, validation) = split(&dataset, 0.8, 1);
+        assert_eq!((training.len(), validation.len()), (8, 2));
+        let mut labels: Vec<usize> = DataLoader::new(&training, 3).iter()
+            .chain(DataLoader::new(&validation, 3).iter())
+            .flatten()
+            .map(|(_, label)| label)
+            .collect();
+        labels.sort();
+        assert_eq!(labels, (0..10).collect::<Vec<usize>>());
+        assert_eq!(split(&dataset, 0.8, 1).1.indices, validation.indices);
+        assert_eq!(split(&dataset, 0.0, 1).0.len(), 0);
+    }
+
+    #[test]
+    fn k_fold_partitions_the_dataset() {
+        let dataset: Vec<(ColumnVector, usize)> = (0..10).map(|x| (ColumnVector::from_vec(vec![x as f32]), x)).collect();
+        let folds = k_fold(&dataset, 3, 5);
+        assert_eq!(folds.iter().map(|(_, validation)| validation.len()).collect::<Vec<usize>>(), vec![3, 3, 4]);
+        let mut validation_indices: Vec<usize> = folds.iter().flat_map(|(_, validation)| validation.indices.clone()).collect();
+        validation_indices.sort();
+        assert_eq!(validation_indices, (0..10).collect::<Vec<usize>>());
+        for (training, validation) in &folds {
+            assert_eq!(training.len() + validation.len(), 10);
+            assert!(training.indices.iter().all(|x| !validation.indices.contains(x)));
+        }
+    }
+
+    //80 samples of class 0, 20 of class 1.
+    fn imbalanced_dataset() -> Vec<(ColumnVector, usize)> {
+        (0..100).map(|x| (ColumnVector::from_vec(vec![x as f32]), if x % 5 == 0 { 1 } else { 0 })).collect()
+    }
+
+    fn class_count<D: Dataset + ?Sized>(dataset: &D, label: usize) -> usize {
+        (0..dataset.len()).filter(|&index| dataset.label(index) == label).count()
+    }
+
+    #[test]
+    fn stratified_splits_keep_the_class_distribution() {
+        let dataset = imbalanced_dataset();
+        let (training, validation) = stratified_split(&dataset, 0.9, 3);
+        assert_eq!((class_count(&training, 0), class_count(&training, 1)), (72, 18));
+        assert_eq!((class_count(&validation, 0), class_count(&validation, 1)), (8, 2));
+        //the 18 samples of class 1 are not all at the end of the training subset.
+        assert!((72..90).any(|index| training.label(index) == 0));
+        for (training, validation) in stratified_k_fold(&dataset, 4, 3) {
+            assert_eq!(training.len() + validation.len(), 100);
+            assert_eq!((class_count(&validation, 0), class_count(&validation, 1)), (20, 5));
+        }
+    }
+
+    #[test]
+    fn stratified_batches() {
+        let dataset = imbalanced_dataset();
+        let mut loader = DataLoader::new_shuffled_with_seed(&dataset, 10, 1);
+        loader.stratify = true;
+        let batches: Vec<Vec<(ColumnVector, usize)>> = loader.iter().collect();
+        for batch in &batches {
+            let minority = batch.iter().filter(|(_, label)| *label == 1).count();
+            assert!((1..=3).contains(&minority), "{} of class 1 in a batch", minority);
+        }
+        assert_eq!(batches.iter().map(|x| x.len()).sum::<usize>(), 100);
+    }
+}
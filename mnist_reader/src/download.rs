@@ -0,0 +1,87 @@
+use std::fs;
+use std::io::{self, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use crate::{read_mnist, IdxDataset};
+
+pub const MNIST_MIRROR: &str = "https://ossci-datasets.s3.amazonaws.com/mnist/";
+
+//file names and md5 checksums of the four MNIST files.
+const TRAIN_IMAGES: (&str, &str) = ("train-images-idx3-ubyte.gz", "f68b3c2dcbeaaa9fbdd348bbdeb94873");
+const TRAIN_LABELS: (&str, &str) = ("train-labels-idx1-ubyte.gz", "d53e105ee54ea40749a09fcbcd1e9432");
+const TEST_IMAGES: (&str, &str) = ("t10k-images-idx3-ubyte.gz", "9fb629c4189551a2d022fa330f9573f3");
+const TEST_LABELS: (&str, &str) = ("t10k-labels-idx1-ubyte.gz", "ec29112dd5afa0611ce80d1b7f02629c");
+
+//the 60000 training and 10000 test samples of MNIST.
+#[derive(PartialEq, Debug)]
+pub struct Mnist {
+    pub train: IdxDataset,
+    pub test: IdxDataset,
+}
+
+fn md5_hex(bytes: &[u8]) -> String {
+    format!("{:x}", md5::compute(bytes))
+}
+
+//returns the path of file_name in cache_dir, downloading it from base_url first unless
+//a copy with the right checksum is already there.
+fn fetch_cached(cache_dir: &Path, base_url: &str, file_name: &str, checksum: &str) -> io::Result<PathBuf> {
+    let path = cache_dir.join(file_name);
+    if let Ok(bytes) = fs::read(&path) {
+        if md5_hex(&bytes) == checksum {
+            return Ok(path);
+        }
+    }
+
+    let response = ureq::get(&format!("{}{}", base_url, file_name)).call()
+        .map_err(|error| io::Error::other(format!("could not download {}: {}", file_name, error)))?;
+    let mut bytes = Vec::new();
+    response.into_reader().read_to_end(&mut bytes)?;
+    let actual = md5_hex(&bytes);
+    if actual != checksum {
+        return Err(io::Error::new(ErrorKind::InvalidData, format!("checksum of {} is {}, expected {}.", file_name, actual, checksum)));
+    }
+    fs::create_dir_all(cache_dir)?;
+    //written under a temporary name first so an interrupted run never leaves a partial file behind.
+    let partial_path = cache_dir.join(format!("{}.part", file_name));
+    fs::write(&partial_path, &bytes)?;
+    fs::rename(&partial_path, &path)?;
+    Ok(path)
+}
+
+//downloads MNIST into cache_dir on first use and loads it from there afterwards.
+pub fn download_mnist<P: AsRef<Path>>(cache_dir: P) -> io::Result<Mnist> {
+    download_mnist_from(cache_dir, MNIST_MIRROR)
+}
+
+pub fn download_mnist_from<P: AsRef<Path>>(cache_dir: P, base_url: &str) -> io::Result<Mnist> {
+    let cache_dir = cache_dir.as_ref();
+    let [train_images, train_labels, test_images, test_labels] = [TRAIN_IMAGES, TRAIN_LABELS, TEST_IMAGES, TEST_LABELS]
+        .map(|(file_name, checksum)| fetch_cached(cache_dir, base_url, file_name, checksum));
+    Ok(Mnist {
+        train: read_mnist(train_images?, train_labels?)?,
+        test: read_mnist(test_images?, test_labels?)?,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::download::{fetch_cached, md5_hex};
+
+    #[test]
+    fn cached_files_are_verified() {
+        let directory = std::env::temp_dir().join(format!("mnist_reader_cache_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let contents = b"cached idx contents";
+        fs::write(directory.join("file.gz"), contents).unwrap();
+        assert_eq!(md5_hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+
+        //a matching checksum never touches the (unreachable) mirror.
+        let path = fetch_cached(&directory, "http://127.0.0.1:9/", "file.gz", &md5_hex(contents)).unwrap();
+        assert_eq!(fs::read(path).unwrap(), contents);
+        //a corrupted cache entry is downloaded again, which fails here.
+        assert!(fetch_cached(&directory, "http://127.0.0.1:9/", "file.gz", &md5_hex(b"other")).is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
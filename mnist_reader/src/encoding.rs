@@ -0,0 +1,66 @@
+use matrix::ColumnVector;
+
+//target vector for a class label, 1 at the label and 0 everywhere else.
+pub fn one_hot(label: usize, classes: usize) -> ColumnVector {
+    if label >= classes {
+        panic!("label {} does not fit in {} classes.", label, classes);
+    }
+    let mut target = ColumnVector::new_with_elements(classes, 0.0);
+    target.data[label] = 1.0;
+    target
+}
+
+//the class with the largest output, the first one on ties. turns a one hot vector back into its label.
+pub fn argmax(output: &ColumnVector) -> usize {
+    if output.data.is_empty() {
+        panic!("cannot take the argmax of an empty vector.");
+    }
+    output.data.iter().enumerate().fold(0, |best, (index, value)| {
+        if *value > output.data[best] { index } else { best }
+    })
+}
+
+//indices of the k largest outputs, largest first and ties in index order like argmax.
+//only the k largest are sorted, the rest is just partitioned off.
+pub fn top_k(output: &ColumnVector, k: usize) -> Vec<usize> {
+    let k = k.min(output.data.len());
+    let order = |a: &usize, b: &usize| output.data[*b].total_cmp(&output.data[*a]).then(a.cmp(b));
+    let mut indices: Vec<usize> = (0..output.data.len()).collect();
+    if k < indices.len() {
+        indices.select_nth_unstable_by(k, order);
+        indices.truncate(k);
+    }
+    indices.sort_unstable_by(order);
+    indices
+}
+
+//every index ordered by its output, largest first.
+pub fn argsort_descending(output: &ColumnVector) -> Vec<usize> {
+    top_k(output, output.data.len())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::ColumnVector;
+    use crate::{argmax, argsort_descending, one_hot, top_k};
+
+    #[test]
+    fn one_hot_round_trip() {
+        assert_eq!(one_hot(3, 5).data, vec![0.0, 0.0, 0.0, 1.0, 0.0]);
+        for label in 0..10 {
+            assert_eq!(argmax(&one_hot(label, 10)), label);
+        }
+        assert_eq!(argmax(&ColumnVector::from_vec(vec![0.1, 0.7, 0.7, -1.0])), 1);
+    }
+
+    #[test]
+    fn largest_outputs() {
+        let output = ColumnVector::from_vec(vec![0.1, 0.7, 0.05, 0.7, -1.0, 0.3]);
+        assert_eq!(top_k(&output, 3), vec![1, 3, 5]);
+        assert_eq!(top_k(&output, 1), vec![argmax(&output)]);
+        assert_eq!(top_k(&output, 0), Vec::<usize>::new());
+        assert_eq!(argsort_descending(&output), vec![1, 3, 5, 0, 2, 4]);
+        assert_eq!(top_k(&output, 10), argsort_descending(&output));
+    }
+}
@@ -0,0 +1,276 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read};
+use std::path::Path;
+use flate2::bufread::GzDecoder;
+use matrix::ColumnVector;
+use crate::ImageDataset;
+
+//the third byte of the magic number is the element type, 0x08 is unsigned byte,
+//the fourth is the amount of dimensions.
+const IMAGES_MAGIC_NUMBER: u32 = 0x00000803;
+const LABELS_MAGIC_NUMBER: u32 = 0x00000801;
+const GZIP_MAGIC_NUMBER: [u8; 2] = [0x1f, 0x8b];
+
+//images of an idx3 file, each flattened row by row into a single column vector of raw pixel values.
+#[derive(PartialEq, Debug)]
+pub struct IdxImages {
+    pub rows: usize,
+    pub columns: usize,
+    pub images: Vec<ColumnVector>,
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_magic_number<R: Read>(reader: &mut R, expected: u32) -> io::Result<()> {
+    let magic_number = read_u32(reader)?;
+    if magic_number != expected {
+        return Err(invalid_data(format!("expected idx magic number {:#010x}, found {:#010x}.", expected, magic_number)));
+    }
+    Ok(())
+}
+
+//reads exactly the amount of values announced in the header and rejects trailing data.
+fn read_body<R: Read>(reader: &mut R, size: usize) -> io::Result<Vec<u8>> {
+    //read through take instead of allocating size up front, a corrupt header could announce
+    //far more bytes than the file holds.
+    let mut body = Vec::new();
+    reader.by_ref().take(size as u64).read_to_end(&mut body)?;
+    if body.len() != size {
+        return Err(invalid_data(format!("idx file is shorter than the {} bytes in its header.", size)));
+    }
+    if reader.read(&mut [0])? != 0 {
+        return Err(invalid_data("idx file is longer than its header says.".to_string()));
+    }
+    Ok(body)
+}
+
+pub fn parse_idx_images<R: Read>(mut reader: R) -> io::Result<IdxImages> {
+    read_magic_number(&mut reader, IMAGES_MAGIC_NUMBER)?;
+    let image_amount = read_u32(&mut reader)? as usize;
+    let rows = read_u32(&mut reader)? as usize;
+    let columns = read_u32(&mut reader)? as usize;
+    if rows == 0 || columns == 0 {
+        return Err(invalid_data(format!("idx images must not be empty, got {}x{}.", rows, columns)));
+    }
+    let size = image_amount.checked_mul(rows)
+        .and_then(|x| x.checked_mul(columns))
+        .ok_or_else(|| invalid_data(format!("{} images of {}x{} are too large.", image_amount, rows, columns)))?;
+    let body = read_body(&mut reader, size)?;
+    Ok(IdxImages {
+        rows,
+        columns,
+        images: body.chunks(rows * columns)
+            .map(|pixels| ColumnVector::from_vec(pixels.iter().map(|&x| x as f32).collect()))
+            .collect(),
+    })
+}
+
+pub fn parse_idx_labels<R: Read>(mut reader: R) -> io::Result<Vec<u8>> {
+    read_magic_number(&mut reader, LABELS_MAGIC_NUMBER)?;
+    let label_amount = read_u32(&mut reader)? as usize;
+    read_body(&mut reader, label_amount)
+}
+
+//gzip compressed input, like the .gz files MNIST is distributed as, is recognized by its
+//magic number and decompressed on the fly. anything else is passed through unchanged.
+pub fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC_NUMBER) {
+        Ok(Box::new(GzDecoder::new(reader)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+pub fn read_idx_images<P: AsRef<Path>>(file_path: P) -> io::Result<IdxImages> {
+    parse_idx_images(decompress(BufReader::new(File::open(file_path)?))?)
+}
+
+pub fn read_idx_labels<P: AsRef<Path>>(file_path: P) -> io::Result<Vec<u8>> {
+    parse_idx_labels(decompress(BufReader::new(File::open(file_path)?))?)
+}
+
+//images of an idx3 file paired with the labels of the matching idx1 file. idx images
+//have a single channel, the storage and the Dataset impl are those of ImageDataset.
+pub type IdxDataset = ImageDataset;
+
+//the splits of EMNIST, which differ in which characters they contain.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum EmnistSplit {
+    ByClass,
+    ByMerge,
+    Balanced,
+    Letters,
+    Digits,
+    Mnist,
+}
+
+impl EmnistSplit {
+    pub fn classes(&self) -> usize {
+        match self {
+            EmnistSplit::ByClass => 62,
+            EmnistSplit::ByMerge | EmnistSplit::Balanced => 47,
+            EmnistSplit::Letters => 26,
+            EmnistSplit::Digits | EmnistSplit::Mnist => 10,
+        }
+    }
+}
+
+//names of the ten Fashion-MNIST classes, indexed by label.
+pub const FASHION_MNIST_CLASSES: [&str; 10] = [
+    "t-shirt/top", "trouser", "pullover", "dress", "coat", "sandal", "shirt", "sneaker", "bag", "ankle boot",
+];
+
+//turns a rows x columns image into its columns x rows transpose.
+fn transpose_image(image: &ColumnVector, rows: usize, columns: usize) -> ColumnVector {
+    ColumnVector::from_vec((0..rows * columns).map(|i| image.data[(i % rows) * columns + i / rows]).collect())
+}
+
+//label_offset is subtracted from every label in the file.
+fn read_dataset<P: AsRef<Path>>(images_path: P, labels_path: P, classes: usize, label_offset: u8, transposed: bool) -> io::Result<IdxDataset> {
+    let images = read_idx_images(images_path)?;
+    let labels = read_idx_labels(labels_path)?;
+    if images.images.len() != labels.len() {
+        return Err(invalid_data(format!("{} images but {} labels.", images.images.len(), labels.len())));
+    }
+    let labels = labels.iter()
+        .map(|&label| match label.checked_sub(label_offset) {
+            Some(label) if (label as usize) < classes => Ok(label),
+            _ => Err(invalid_data(format!("label {} is not one of the {} classes.", label, classes))),
+        })
+        .collect::<io::Result<Vec<u8>>>()?;
+    let (rows, columns, images) = if transposed {
+        (images.columns, images.rows, images.images.iter().map(|image| transpose_image(image, images.rows, images.columns)).collect())
+    } else {
+        (images.rows, images.columns, images.images)
+    };
+    Ok(IdxDataset {
+        rows,
+        columns,
+        channels: 1,
+        images,
+        labels,
+        classes,
+    })
+}
+
+//reads an images file together with its labels file, e.g. train-images-idx3-ubyte and
+//train-labels-idx1-ubyte, and checks that both describe the same amount of samples.
+pub fn read_mnist<P: AsRef<Path>>(images_path: P, labels_path: P) -> io::Result<IdxDataset> {
+    read_dataset(images_path, labels_path, 10, 0, false)
+}
+
+//Fashion-MNIST uses the layout of MNIST with pictures of clothing, see FASHION_MNIST_CLASSES.
+pub fn read_fashion_mnist<P: AsRef<Path>>(images_path: P, labels_path: P) -> io::Result<IdxDataset> {
+    read_dataset(images_path, labels_path, 10, 0, false)
+}
+
+//EMNIST images are stored transposed and are flipped back into the orientation of MNIST here.
+//the letters split labels a to z as 1 to 26, which become 0 to 25.
+pub fn read_emnist<P: AsRef<Path>>(images_path: P, labels_path: P, split: EmnistSplit) -> io::Result<IdxDataset> {
+    let label_offset = if split == EmnistSplit::Letters { 1 } else { 0 };
+    read_dataset(images_path, labels_path, split.classes(), label_offset, true)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ErrorKind, Write};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use crate::{decompress, parse_idx_images, parse_idx_labels, read_emnist, read_fashion_mnist, read_mnist, Dataset, EmnistSplit};
+
+    fn images_file(image_amount: u32, rows: u32, columns: u32, pixels: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0, 0, 8, 3];
+        for value in [image_amount, rows, columns] {
+            bytes.extend(value.to_be_bytes());
+        }
+        bytes.extend(pixels);
+        bytes
+    }
+
+    #[test]
+    fn idx_images_and_labels() {
+        let pixels: Vec<u8> = (0..12).collect();
+        let images = parse_idx_images(images_file(2, 2, 3, &pixels).as_slice()).unwrap();
+        assert_eq!((images.rows, images.columns), (2, 3));
+        assert_eq!(images.images.len(), 2);
+        assert_eq!(images.images[1].data, vec![6.0, 7.0, 8.0, 9.0, 10.0, 11.0]);
+
+        let labels = parse_idx_labels([0, 0, 8, 1, 0, 0, 0, 3, 7, 2, 9].as_slice()).unwrap();
+        assert_eq!(labels, vec![7, 2, 9]);
+    }
+
+    #[test]
+    fn idx_validation() {
+        let pixels: Vec<u8> = (0..12).collect();
+        let mut wrong_magic = images_file(2, 2, 3, &pixels);
+        wrong_magic[3] = 1;
+        assert_eq!(parse_idx_images(wrong_magic.as_slice()).unwrap_err().kind(), ErrorKind::InvalidData);
+        //labels are not images.
+        assert!(parse_idx_images([0, 0, 8, 1, 0, 0, 0, 0].as_slice()).is_err());
+        assert!(parse_idx_images(images_file(3, 2, 3, &pixels).as_slice()).is_err());
+        assert!(parse_idx_images(images_file(1, 2, 3, &pixels).as_slice()).is_err());
+        assert!(parse_idx_images(images_file(0, 0, 3, &[]).as_slice()).is_err());
+        assert!(parse_idx_labels([0, 0, 8, 1, 0, 0, 0, 3, 7].as_slice()).is_err());
+        //sizes that overflow, or that the file does not hold, are rejected without allocating them.
+        assert_eq!(parse_idx_images(images_file(u32::MAX, u32::MAX, u32::MAX, &pixels).as_slice()).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(parse_idx_images(images_file(u32::MAX, 1024, 1024, &pixels).as_slice()).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn gzip_compressed_idx() {
+        let pixels: Vec<u8> = (0..12).collect();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&images_file(2, 2, 3, &pixels)).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let images = parse_idx_images(decompress(compressed.as_slice()).unwrap()).unwrap();
+        assert_eq!(images, parse_idx_images(images_file(2, 2, 3, &pixels).as_slice()).unwrap());
+
+        let directory = std::env::temp_dir().join(format!("mnist_reader_gzip_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let images_path = directory.join("images-idx3-ubyte.gz");
+        let labels_path = directory.join("labels-idx1-ubyte");
+        std::fs::write(&images_path, compressed).unwrap();
+        std::fs::write(&labels_path, [0, 0, 8, 1, 0, 0, 0, 2, 4, 5]).unwrap();
+        let dataset = read_mnist(&images_path, &labels_path).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(dataset.len(), 2);
+        assert_eq!(dataset.get(1), (images.images[1].clone(), 5));
+    }
+
+    #[test]
+    fn fashion_mnist_and_emnist() {
+        let directory = std::env::temp_dir().join(format!("mnist_reader_emnist_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let images_path = directory.join("images-idx3-ubyte");
+        let labels_path = directory.join("labels-idx1-ubyte");
+        let pixels: Vec<u8> = (0..12).collect();
+        std::fs::write(&images_path, images_file(2, 2, 3, &pixels)).unwrap();
+
+        std::fs::write(&labels_path, [0, 0, 8, 1, 0, 0, 0, 2, 9, 1]).unwrap();
+        let fashion = read_fashion_mnist(&images_path, &labels_path).unwrap();
+        assert_eq!((fashion.classes, fashion.label(0)), (10, 9));
+        assert_eq!(fashion.images[0].data, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+
+        let letters = read_emnist(&images_path, &labels_path, EmnistSplit::Letters).unwrap();
+        assert_eq!(letters.classes, 26);
+        assert_eq!(letters.labels, vec![8, 0]);
+        //the pixels were stored column by column.
+        assert_eq!((letters.rows, letters.columns), (3, 2));
+        assert_eq!(letters.images[0].data, vec![0.0, 3.0, 1.0, 4.0, 2.0, 5.0]);
+
+        std::fs::write(&labels_path, [0, 0, 8, 1, 0, 0, 0, 2, 0, 12]).unwrap();
+        assert!(read_emnist(&images_path, &labels_path, EmnistSplit::Letters).is_err());
+        assert!(read_mnist(&images_path, &labels_path).is_err());
+        assert_eq!(read_emnist(&images_path, &labels_path, EmnistSplit::Balanced).unwrap().labels, vec![0, 12]);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use image::imageops::{self, FilterType};
+use matrix::ColumnVector;
+use crate::ImageDataset;
+
+const IMAGE_EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+
+//a dataset read from a directory with one subdirectory per class.
+#[derive(PartialEq, Debug)]
+pub struct ImageFolder {
+    pub dataset: ImageDataset,
+    //subdirectory names, indexed by label.
+    pub class_names: Vec<String>,
+}
+
+fn sorted_entries(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    entries.sort();
+    Ok(entries)
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+//decodes an image, converts it to grayscale and resizes it to columns x rows.
+//pixels keep their raw 0..255 values, like the idx images.
+pub fn read_image<P: AsRef<Path>>(file_path: P, rows: usize, columns: usize) -> io::Result<ColumnVector> {
+    let file_path = file_path.as_ref();
+    let image = image::open(file_path)
+        .map_err(|error| io::Error::new(ErrorKind::InvalidData, format!("could not decode {}: {}", file_path.display(), error)))?
+        .into_luma8();
+    let resized = imageops::resize(&image, columns as u32, rows as u32, FilterType::Triangle);
+    Ok(ColumnVector::from_vec(resized.into_raw().iter().map(|&x| x as f32).collect()))
+}
+
+//reads every png and jpeg image in the class subdirectories of directory, e.g.
+//digits/0/a.png, digits/1/b.jpg. classes are labeled in the alphabetical order of their
+//names, other files are ignored.
+pub fn read_image_folder<P: AsRef<Path>>(directory: P, rows: usize, columns: usize) -> io::Result<ImageFolder> {
+    let mut class_names = Vec::new();
+    let mut images = Vec::new();
+    let mut labels = Vec::new();
+    for class_directory in sorted_entries(directory.as_ref())?.into_iter().filter(|path| path.is_dir()) {
+        let label = u8::try_from(class_names.len())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidData, "more than 256 classes.".to_string()))?;
+        for file_path in sorted_entries(&class_directory)?.into_iter().filter(|path| is_image(path)) {
+            images.push(read_image(&file_path, rows, columns)?);
+            labels.push(label);
+        }
+        class_names.push(class_directory.file_name().unwrap().to_string_lossy().into_owned());
+    }
+    Ok(ImageFolder {
+        dataset: ImageDataset {
+            rows,
+            columns,
+            channels: 1,
+            images,
+            labels,
+            classes: class_names.len(),
+        },
+        class_names,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+    use crate::{read_image_folder, Dataset};
+
+    #[test]
+    fn image_folder() {
+        let directory = std::env::temp_dir().join(format!("mnist_reader_folder_{}", std::process::id()));
+        fs::create_dir_all(directory.join("cat")).unwrap();
+        fs::create_dir_all(directory.join("ant")).unwrap();
+        GrayImage::from_pixel(8, 8, Luma([200])).save(directory.join("ant/first.png")).unwrap();
+        GrayImage::from_pixel(4, 6, Luma([50])).save(directory.join("ant/second.PNG")).unwrap();
+        RgbImage::from_pixel(16, 16, Rgb([255, 255, 255])).save(directory.join("cat/white.jpg")).unwrap();
+        fs::write(directory.join("cat/notes.txt"), "not an image").unwrap();
+        fs::write(directory.join("readme.txt"), "not a class").unwrap();
+
+        let folder = read_image_folder(&directory, 4, 4).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(folder.class_names, vec!["ant", "cat"]);
+        assert_eq!((folder.dataset.len(), folder.dataset.classes, folder.dataset.image_size()), (3, 2, 16));
+        assert_eq!(folder.dataset.get(0).0.data, vec![200.0; 16]);
+        assert_eq!(folder.dataset.get(1), (folder.dataset.images[1].clone(), 0));
+        assert_eq!(folder.dataset.images[1].data, vec![50.0; 16]);
+        let (white, label) = folder.dataset.get(2);
+        assert_eq!(label, 1);
+        assert!(white.data.iter().all(|&x| x > 250.0));
+    }
+}
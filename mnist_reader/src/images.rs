@@ -0,0 +1,36 @@
+use matrix::ColumnVector;
+use crate::Dataset;
+
+//labeled images with one or more channels, e.g. the red, green and blue of CIFAR-10.
+//every image is flattened one channel after the other, each channel row by row, which is
+//the layout Augmentation expects.
+#[derive(PartialEq, Debug)]
+pub struct ImageDataset {
+    pub rows: usize,
+    pub columns: usize,
+    pub channels: usize,
+    pub images: Vec<ColumnVector>,
+    pub labels: Vec<u8>,
+    pub classes: usize,
+}
+
+impl ImageDataset {
+    //values of a single image, the network input size.
+    pub fn image_size(&self) -> usize {
+        self.rows * self.columns * self.channels
+    }
+}
+
+impl Dataset for ImageDataset {
+    fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    fn get(&self, index: usize) -> (ColumnVector, usize) {
+        (self.images[index].clone(), self.labels[index] as usize)
+    }
+
+    fn label(&self, index: usize) -> usize {
+        self.labels[index] as usize
+    }
+}
@@ -1,41 +1,65 @@
-use std::{env, fmt};
+use std::fmt;
 use std::fs::File;
-use std::path::PathBuf;
 use csv::{DeserializeRecordsIter, Reader};
 use serde::{Deserialize, Serialize};
 
+mod augmentation;
+mod cifar;
+mod dataset;
+#[cfg(feature = "download")]
+mod download;
+mod encoding;
+mod idx;
+#[cfg(feature = "image-folder")]
+mod image_folder;
+mod images;
+mod preprocessing;
+
+pub use augmentation::{displace, transform, Augmentation, Augmented};
+pub use cifar::{parse_cifar10, read_cifar10, CIFAR10_CHANNELS, CIFAR10_CLASSES, CIFAR10_SIZE};
+pub use dataset::{k_fold, split, stratified_k_fold, stratified_split, Batches, DataLoader, Dataset, Subset};
+pub use encoding::{argmax, argsort_descending, one_hot, top_k};
+pub use idx::{decompress, parse_idx_images, parse_idx_labels, read_emnist, read_fashion_mnist, read_idx_images, read_idx_labels, read_mnist, EmnistSplit, IdxDataset, IdxImages, FASHION_MNIST_CLASSES};
+pub use images::ImageDataset;
+pub use preprocessing::Preprocessing;
+#[cfg(feature = "download")]
+pub use download::{download_mnist, download_mnist_from, Mnist, MNIST_MIRROR};
+#[cfg(feature = "image-folder")]
+pub use image_folder::{read_image, read_image_folder, ImageFolder};
+
 #[derive(Debug, Deserialize, Serialize)]
-struct TrainingDataElement {
-    actual_result: u8,
-    image: Vec<u8>,
+pub struct TrainingDataElement {
+    pub actual_result: u8,
+    pub image: Vec<u8>,
 }
 
 impl fmt::Display for TrainingDataElement {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "The image below is a {}\n", self.actual_result).unwrap();
+        writeln!(f, "The image below is a {}", self.actual_result).unwrap();
         for (index, elem) in self.image.iter().enumerate() {
             write!(f, "{:>3} ", elem).unwrap();
             write!(f, "{}", if (index + 1) % 28 == 0  { "\n" } else { "" }).unwrap();
         }
-        write!(f, "\n")
+        writeln!(f)
     }
 }
 
-type TrainingData = Vec<TrainingDataElement>;
+pub type TrainingData = Vec<TrainingDataElement>;
 
-fn read_csv_data(file_path: &str) -> Reader<File> {
+pub fn read_csv_data(file_path: &str) -> Reader<File> {
     let mut reader_builder = csv::ReaderBuilder::new();
-    reader_builder.has_headers(false).delimiter(',' as u8);
+    reader_builder.has_headers(false).delimiter(b',');
     let reader = reader_builder.from_path(std::path::Path::new(file_path)).unwrap();
     reader
 }
 
-fn deserialize_mnist_data(reader: &mut Reader<File>) -> DeserializeRecordsIter<File, TrainingDataElement> {
+pub fn deserialize_mnist_data(reader: &mut Reader<File>) -> DeserializeRecordsIter<'_, File, TrainingDataElement> {
     reader.deserialize()
 }
 
-fn get_current_working_dir() -> std::io::Result<PathBuf> {
-    env::current_dir()
+#[cfg(test)]
+fn get_current_working_dir() -> std::io::Result<std::path::PathBuf> {
+    std::env::current_dir()
 }
 
 
@@ -44,6 +68,7 @@ mod tests {
     use crate::{get_current_working_dir, deserialize_mnist_data, read_csv_data};
 
     #[test]
+    #[ignore = "requires the MNIST csv export in ../data"]
     fn csv_reading() {
         println!("{}", get_current_working_dir().unwrap().as_path().display());
         let mut reader = read_csv_data("../data/mnist_train.csv");
@@ -0,0 +1,79 @@
+use matrix::ColumnVector;
+use serde::{Deserialize, Serialize};
+use crate::Dataset;
+
+//transformation of raw inputs, e.g. 0 to 255 pixel values, before they reach the network.
+//statistics are computed once from the training set and then reused unchanged for
+//validation, test and inference inputs.
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Preprocessing {
+    //divides every value by max, mapping [0, max] onto [0, 1].
+    Scale { max: f32 },
+    Standardize { mean: f32, standard_deviation: f32 },
+}
+
+impl Preprocessing {
+    //8 bit pixels scaled to [0, 1].
+    pub fn unit_range() -> Preprocessing {
+        Preprocessing::Scale { max: 255.0 }
+    }
+
+    //zero mean and unit variance over all values of all samples of the dataset.
+    pub fn standardize<D: Dataset + ?Sized>(dataset: &D) -> Preprocessing {
+        let mut sum = 0.0_f64;
+        let mut squared_sum = 0.0_f64;
+        let mut count = 0_usize;
+        for index in 0..dataset.len() {
+            let (input, _) = dataset.get(index);
+            for &value in &input.data {
+                sum += value as f64;
+                squared_sum += (value as f64).powi(2);
+            }
+            count += input.data.len();
+        }
+        if count == 0 {
+            panic!("cannot compute statistics of an empty dataset.");
+        }
+        let mean = sum / count as f64;
+        let variance = (squared_sum / count as f64 - mean * mean).max(0.0);
+        Preprocessing::Standardize {
+            mean: mean as f32,
+            //a constant dataset is only shifted.
+            standard_deviation: if variance > 0.0 { variance.sqrt() as f32 } else { 1.0 },
+        }
+    }
+
+    pub fn apply_to_value(&self, value: f32) -> f32 {
+        match self {
+            Preprocessing::Scale { max } => value / max,
+            Preprocessing::Standardize { mean, standard_deviation } => (value - mean) / standard_deviation,
+        }
+    }
+
+    pub fn apply(&self, input: &ColumnVector) -> ColumnVector {
+        ColumnVector::from_vec(input.data.iter().map(|&x| self.apply_to_value(x)).collect())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::ColumnVector;
+    use crate::Preprocessing;
+
+    #[test]
+    fn scaling_and_standardization() {
+        let pixels = ColumnVector::from_vec(vec![0.0, 51.0, 255.0]);
+        assert_eq!(Preprocessing::unit_range().apply(&pixels).data, vec![0.0, 0.2, 1.0]);
+
+        let dataset = vec![
+            (ColumnVector::from_vec(vec![1.0, 3.0]), 0),
+            (ColumnVector::from_vec(vec![5.0, 7.0]), 1),
+        ];
+        let standardize = Preprocessing::standardize(&dataset);
+        assert_eq!(standardize, Preprocessing::Standardize { mean: 4.0, standard_deviation: 5.0_f32.sqrt() });
+        let standardized: Vec<f32> = dataset.iter().flat_map(|(input, _)| standardize.apply(input).data).collect();
+        assert!(standardized.iter().sum::<f32>().abs() < 1e-6);
+        assert!((standardized.iter().map(|x| x * x).sum::<f32>() / 4.0 - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,424 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use matrix::{ColumnVector, Scalar};
+use std::iter::zip;
+use serde::{Deserialize, Serialize};
+use crate::{constant, relu, relu_deriv, sigmoid, sigmoid_deriv, softmax};
+
+//a nonlinearity applied to the z values of a layer. derivative is taken with respect
+//to the z values, which is all backpropagation needs to chain through it. T is the precision
+//it computes in, f32 unless it is implemented for others too.
+pub trait Activation<T: Scalar = f32>: Debug + Send + Sync {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T>;
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T>;
+
+    //turns the gradient with respect to the activations into the gradient with respect to z.
+    //only activations whose outputs depend on more than one z value need to override this.
+    fn backward(&self, z: &ColumnVector<T>, gradient: &ColumnVector<T>) -> ColumnVector<T> {
+        gradient.hadamard(&self.derivative(z))
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Relu;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Sigmoid;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Identity;
+
+//slope is the gradient for negative inputs.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct LeakyRelu {
+    pub slope: f32,
+}
+
+//negative inputs saturate towards -alpha.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Elu {
+    pub alpha: f32,
+}
+
+//uses the tanh approximation.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Gelu;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Tanh;
+
+//z * sigmoid(z).
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Swish;
+
+//normalizes the whole vector into probabilities, meant for the output layer.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Softmax;
+
+const GELU_COEFFICIENT: f64 = 0.044715;
+
+fn gelu_inner<T: Scalar>(z: T) -> T {
+    constant::<T>(2.0 / std::f64::consts::PI).sqrt() * (z + constant::<T>(GELU_COEFFICIENT) * z.powi(3))
+}
+
+fn leaky_relu<T: Scalar>(slope: T, z: T) -> T {
+    if z < T::zero() { slope * z } else { z }
+}
+
+fn elu<T: Scalar>(alpha: T, z: T) -> T {
+    if z < T::zero() { alpha * (z.exp() - T::one()) } else { z }
+}
+
+fn gelu<T: Scalar>(z: T) -> T {
+    constant::<T>(0.5) * z * (T::one() + gelu_inner(z).tanh())
+}
+
+fn swish<T: Scalar>(z: T) -> T {
+    z * sigmoid(z)
+}
+
+impl<T: Scalar> Activation<T> for Relu {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        z.apply(relu)
+    }
+
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        z.apply(relu_deriv)
+    }
+}
+
+impl<T: Scalar> Activation<T> for Sigmoid {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        z.apply(sigmoid)
+    }
+
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        z.apply(sigmoid_deriv)
+    }
+}
+
+impl<T: Scalar> Activation<T> for Identity {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        z.clone()
+    }
+
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        ColumnVector::new_with_elements(z.data.len(), T::one())
+    }
+}
+
+impl<T: Scalar> Activation<T> for LeakyRelu {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        let slope = T::from_f32(self.slope).unwrap();
+        z.map(|x| leaky_relu(slope, x))
+    }
+
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        let slope = T::from_f32(self.slope).unwrap();
+        z.map(|x| if x < T::zero() { slope } else { T::one() })
+    }
+}
+
+impl<T: Scalar> Activation<T> for Elu {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        let alpha = T::from_f32(self.alpha).unwrap();
+        z.map(|x| elu(alpha, x))
+    }
+
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        let alpha = T::from_f32(self.alpha).unwrap();
+        z.map(|x| if x < T::zero() { alpha * x.exp() } else { T::one() })
+    }
+}
+
+impl<T: Scalar> Activation<T> for Gelu {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        z.apply(gelu)
+    }
+
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        z.apply(|x| {
+            let (one, half) = (T::one(), constant::<T>(0.5));
+            let tanh = gelu_inner(x).tanh();
+            let inner_deriv = constant::<T>(2.0 / std::f64::consts::PI).sqrt() * (one + constant::<T>(3.0 * GELU_COEFFICIENT) * x * x);
+            half * (one + tanh) + half * x * (one - tanh * tanh) * inner_deriv
+        })
+    }
+}
+
+impl<T: Scalar> Activation<T> for Tanh {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        z.apply(T::tanh)
+    }
+
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        z.apply(|x| T::one() - x.tanh().powi(2))
+    }
+}
+
+impl<T: Scalar> Activation<T> for Swish {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        z.apply(swish)
+    }
+
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        z.apply(|x| sigmoid(x) + x * sigmoid_deriv(x))
+    }
+}
+
+impl<T: Scalar> Activation<T> for Softmax {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        softmax(z)
+    }
+
+    //only the diagonal of the jacobian, backward uses the full one.
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        softmax(z).apply(|x| x * (T::one() - x))
+    }
+
+    fn backward(&self, z: &ColumnVector<T>, gradient: &ColumnVector<T>) -> ColumnVector<T> {
+        let probabilities = softmax(z);
+        let dot = gradient.dot(&probabilities);
+        ColumnVector::from_vec(zip(&gradient.data, &probabilities.data).map(|(&g, &p)| p * (g - dot)).collect())
+    }
+}
+
+//a single precision activation computing in another precision, by casting its input to f32 and
+//its output back, e.g. a custom activation of a DoublePrecisionNetwork.
+#[derive(Debug)]
+struct SinglePrecision<'a>(&'a dyn Activation);
+
+impl<T: Scalar> Activation<T> for SinglePrecision<'_> {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        self.0.apply(&z.cast()).cast()
+    }
+
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        self.0.derivative(&z.cast()).cast()
+    }
+
+    fn backward(&self, z: &ColumnVector<T>, gradient: &ColumnVector<T>) -> ColumnVector<T> {
+        self.0.backward(&z.cast(), &gradient.cast()).cast()
+    }
+}
+
+//the activation of a single layer of the network. Custom holds any other implementation
+//and fails to serialize, since there is no way to get it back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ActivationFunction {
+    Relu,
+    Sigmoid,
+    Identity,
+    LeakyRelu(f32),
+    Elu(f32),
+    Gelu,
+    Tanh,
+    Swish,
+    Softmax,
+    #[serde(skip)]
+    Custom(Arc<dyn Activation>),
+}
+
+impl ActivationFunction {
+    //custom activations only compute in single precision, other precisions go through casts.
+    fn inner<T: Scalar>(&self) -> Box<dyn Activation<T> + '_> {
+        match self {
+            ActivationFunction::Relu => Box::new(Relu),
+            ActivationFunction::Sigmoid => Box::new(Sigmoid),
+            ActivationFunction::Identity => Box::new(Identity),
+            ActivationFunction::LeakyRelu(slope) => Box::new(LeakyRelu { slope: *slope }),
+            ActivationFunction::Elu(alpha) => Box::new(Elu { alpha: *alpha }),
+            ActivationFunction::Gelu => Box::new(Gelu),
+            ActivationFunction::Tanh => Box::new(Tanh),
+            ActivationFunction::Swish => Box::new(Swish),
+            ActivationFunction::Softmax => Box::new(Softmax),
+            ActivationFunction::Custom(activation) => Box::new(SinglePrecision(activation.as_ref())),
+        }
+    }
+
+    //the activation of a single z value for activations that work element by element, so a
+    //whole batch can be activated in place. softmax and custom activations have none.
+    pub(crate) fn element_wise(&self) -> Option<Box<dyn Fn(f32) -> f32>> {
+        Some(match *self {
+            ActivationFunction::Relu => Box::new(relu),
+            ActivationFunction::Sigmoid => Box::new(sigmoid),
+            ActivationFunction::Identity => Box::new(|x| x),
+            ActivationFunction::LeakyRelu(slope) => Box::new(move |x| leaky_relu(slope, x)),
+            ActivationFunction::Elu(alpha) => Box::new(move |x| elu(alpha, x)),
+            ActivationFunction::Gelu => Box::new(gelu),
+            ActivationFunction::Tanh => Box::new(f32::tanh),
+            ActivationFunction::Swish => Box::new(swish),
+            ActivationFunction::Softmax | ActivationFunction::Custom(_) => return None,
+        })
+    }
+
+    //a name like relu or leaky_relu(0.1) to store the activation in model files.
+    //custom activations have none.
+    pub fn name(&self) -> Option<String> {
+        Some(match self {
+            ActivationFunction::Relu => "relu".to_string(),
+            ActivationFunction::Sigmoid => "sigmoid".to_string(),
+            ActivationFunction::Identity => "identity".to_string(),
+            ActivationFunction::LeakyRelu(slope) => format!("leaky_relu({})", slope),
+            ActivationFunction::Elu(alpha) => format!("elu({})", alpha),
+            ActivationFunction::Gelu => "gelu".to_string(),
+            ActivationFunction::Tanh => "tanh".to_string(),
+            ActivationFunction::Swish => "swish".to_string(),
+            ActivationFunction::Softmax => "softmax".to_string(),
+            ActivationFunction::Custom(_) => return None,
+        })
+    }
+
+    pub fn from_name(name: &str) -> Option<ActivationFunction> {
+        let parameter = |prefix: &str| name.strip_prefix(prefix)?.strip_suffix(')')?.parse().ok();
+        Some(match name {
+            "relu" => ActivationFunction::Relu,
+            "sigmoid" => ActivationFunction::Sigmoid,
+            "identity" => ActivationFunction::Identity,
+            "gelu" => ActivationFunction::Gelu,
+            "tanh" => ActivationFunction::Tanh,
+            "swish" => ActivationFunction::Swish,
+            "softmax" => ActivationFunction::Softmax,
+            _ if name.starts_with("leaky_relu(") => ActivationFunction::LeakyRelu(parameter("leaky_relu(")?),
+            _ if name.starts_with("elu(") => ActivationFunction::Elu(parameter("elu(")?),
+            _ => return None,
+        })
+    }
+}
+
+impl<T: Scalar, A: Activation<T> + ?Sized> Activation<T> for &A {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        (**self).apply(z)
+    }
+
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        (**self).derivative(z)
+    }
+
+    fn backward(&self, z: &ColumnVector<T>, gradient: &ColumnVector<T>) -> ColumnVector<T> {
+        (**self).backward(z, gradient)
+    }
+}
+
+impl<T: Scalar> Activation<T> for ActivationFunction {
+    fn apply(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        self.inner().apply(z)
+    }
+
+    fn derivative(&self, z: &ColumnVector<T>) -> ColumnVector<T> {
+        self.inner().derivative(z)
+    }
+
+    fn backward(&self, z: &ColumnVector<T>, gradient: &ColumnVector<T>) -> ColumnVector<T> {
+        self.inner().backward(z, gradient)
+    }
+}
+
+//custom activations are only equal when they are the same instance.
+impl PartialEq for ActivationFunction {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ActivationFunction::Custom(a), ActivationFunction::Custom(b)) => Arc::ptr_eq(a, b),
+            (ActivationFunction::LeakyRelu(a), ActivationFunction::LeakyRelu(b)) => a == b,
+            (ActivationFunction::Elu(a), ActivationFunction::Elu(b)) => a == b,
+            (ActivationFunction::Custom(_), _) | (_, ActivationFunction::Custom(_)) => false,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use matrix::{ColumnVector, Matrix};
+    use crate::{check_gradients, softmax, Activation, ActivationFunction, NeuralNetwork};
+
+    #[derive(Debug)]
+    struct Square;
+
+    impl Activation for Square {
+        fn apply(&self, z: &ColumnVector) -> ColumnVector {
+            z.apply(|x| x * x)
+        }
+
+        fn derivative(&self, z: &ColumnVector) -> ColumnVector {
+            z.apply(|x| 2.0 * x)
+        }
+    }
+
+    #[test]
+    fn activation_functions() {
+        let z = ColumnVector::from_vec(vec![-2.0, 0.0, 3.0]);
+        assert_eq!(ActivationFunction::Relu.apply(&z).data, vec![0.0, 0.0, 3.0]);
+        assert_eq!(ActivationFunction::Identity.apply(&z), z);
+        assert_eq!(ActivationFunction::Sigmoid.apply(&z).data[1], 0.5);
+        assert_eq!(ActivationFunction::Sigmoid.derivative(&z).data[1], 0.25);
+        assert_eq!(ActivationFunction::Relu.derivative(&z).data, vec![0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn derivatives_against_finite_differences() {
+        let z = ColumnVector::from_vec(vec![-2.0, -0.7, -0.1, 0.3, 1.1, 2.5]);
+        let epsilon = 1e-2_f32;
+        let z_plus = z.map(|x| x + epsilon);
+        let z_minus = z.map(|x| x - epsilon);
+        for activation in [
+            ActivationFunction::Sigmoid,
+            ActivationFunction::LeakyRelu(0.1),
+            ActivationFunction::Elu(1.0),
+            ActivationFunction::Gelu,
+            ActivationFunction::Tanh,
+            ActivationFunction::Swish,
+        ] {
+            let plus = activation.apply(&z_plus);
+            let minus = activation.apply(&z_minus);
+            for (index, analytical) in activation.derivative(&z).data.iter().enumerate() {
+                let numerical = (plus.data[index] - minus.data[index]) / (2.0 * epsilon);
+                assert!((numerical - analytical).abs() < 1e-3, "{:?}: {} != {}", activation, numerical, analytical);
+            }
+        }
+        assert_eq!(ActivationFunction::LeakyRelu(0.1).apply(&z).data[0], -0.2);
+        assert_ne!(ActivationFunction::Elu(1.0), ActivationFunction::Elu(0.5));
+    }
+
+    #[test]
+    fn softmax_is_stable_and_sums_to_one() {
+        let probabilities: ColumnVector = softmax(&ColumnVector::from_vec(vec![1000.0, 1001.0, 1002.0]));
+        assert!(probabilities.data.iter().all(|x| x.is_finite()));
+        assert!((probabilities.data.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        let shifted = softmax(&ColumnVector::from_vec(vec![0.0, 1.0, 2.0]));
+        for (a, b) in probabilities.data.iter().zip(&shifted.data) {
+            assert!((a - b).abs() < 1e-6);
+        }
+        assert!(probabilities.data[2] > probabilities.data[1] && probabilities.data[1] > probabilities.data[0]);
+    }
+
+    #[test]
+    fn softmax_output_layer_against_finite_differences() {
+        let weights = vec![
+            Matrix::from_vec(vec![vec![0.5, -0.2, 0.1], vec![0.3, 0.8, -0.4]]),
+            Matrix::from_vec(vec![vec![0.7, -0.6], vec![0.2, 0.9], vec![-0.5, 0.4]]),
+        ];
+        let mut network = NeuralNetwork::new_from_vecs(weights, None, None, None);
+        network.activation_functions = vec![ActivationFunction::Tanh, ActivationFunction::Softmax];
+        let input = ColumnVector::from_vec(vec![1.0, 0.5, 0.25]);
+        let desired = ColumnVector::from_vec(vec![0.0, 1.0, 0.0]);
+        let check = check_gradients(&mut network, &input, &desired);
+        let output_sum: f32 = network.activation_values.back().unwrap().data.iter().sum();
+        assert!((output_sum - 1.0).abs() < 1e-6);
+        assert!(check.max_absolute_error < 1e-3, "{:?}", check);
+    }
+
+    #[test]
+    fn custom_activation_in_backpropagation() {
+        let mut network = NeuralNetwork::new_with_activations(&[2, 1], vec![ActivationFunction::Custom(Arc::new(Square))], Some(0.5));
+        let input = ColumnVector::from_vec(vec![1.0, 1.0]);
+        let gradients = network.backpropagation(&input, &ColumnVector::from_vec(vec![0.0]));
+        //z = 1.5, output = 2.25, so d cost / d bias = 2.25 * 2 * 1.5.
+        assert_eq!(network.activation_values.back().unwrap().data, vec![2.25]);
+        assert_eq!(gradients.biases[0].data, vec![6.75]);
+        assert_eq!(network.activation_functions[0], network.activation_functions[0].clone());
+        assert_ne!(network.activation_functions[0], ActivationFunction::Custom(Arc::new(Square)));
+    }
+}
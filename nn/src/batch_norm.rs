@@ -0,0 +1,303 @@
+use std::iter::zip;
+use matrix::{ColumnVector, Matrix};
+use serde::{Deserialize, Serialize};
+use crate::{Activation, Gradients, LossAccumulator, NeuralNetwork, Normalization};
+
+//batch normalization of the z values of every hidden layer, before the nonlinearity.
+//while training a whole mini batch is normalized with its own mean and variance, which also
+//update the running statistics. Single sample passes and inference use the running statistics.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct BatchNorm {
+    pub epsilon: f32,
+    //weight of the old running statistics when a new batch is folded in.
+    pub momentum: f32,
+    pub gammas: Vec<ColumnVector>,
+    pub betas: Vec<ColumnVector>,
+    pub running_means: Vec<ColumnVector>,
+    pub running_variances: Vec<ColumnVector>,
+}
+
+impl BatchNorm {
+    pub fn new(hidden_layer_sizes: &[usize]) -> BatchNorm {
+        BatchNorm {
+            epsilon: 1e-5,
+            momentum: 0.9,
+            gammas: hidden_layer_sizes.iter().map(|&x| ColumnVector::new_with_elements(x, 1.0)).collect(),
+            betas: hidden_layer_sizes.iter().map(|&x| ColumnVector::new_with_elements(x, 0.0)).collect(),
+            running_means: hidden_layer_sizes.iter().map(|&x| ColumnVector::new_with_elements(x, 0.0)).collect(),
+            running_variances: hidden_layer_sizes.iter().map(|&x| ColumnVector::new_with_elements(x, 1.0)).collect(),
+        }
+    }
+
+    //one set of parameters for every hidden layer of the network.
+    pub fn for_network(network: &NeuralNetwork) -> BatchNorm {
+        BatchNorm::new(&network.hidden_layer_sizes())
+    }
+
+    //1 / sqrt(running variance + epsilon) of a layer.
+    pub fn running_inverse_std(&self, layer_index: usize) -> ColumnVector {
+        let epsilon = self.epsilon;
+        ColumnVector::from_vec(self.running_variances[layer_index].data.iter()
+            .map(|variance| 1.0 / (variance + epsilon).sqrt())
+            .collect())
+    }
+
+    //z values of a layer normalized with the running statistics, before gamma and beta are applied.
+    pub fn normalize_with_running_statistics(&self, layer_index: usize, z_values: &ColumnVector) -> ColumnVector {
+        let inverse_std = self.running_inverse_std(layer_index);
+        ColumnVector::from_vec(zip(zip(&z_values.data, &self.running_means[layer_index].data), &inverse_std.data)
+            .map(|((z, mean), inverse_std)| (z - mean) * inverse_std)
+            .collect())
+    }
+
+    pub fn scale_and_shift(&self, layer_index: usize, normalized: &ColumnVector) -> ColumnVector {
+        ColumnVector::from_vec(zip(zip(&normalized.data, &self.gammas[layer_index].data), &self.betas[layer_index].data)
+            .map(|((x, gamma), beta)| gamma * x + beta)
+            .collect())
+    }
+}
+
+//everything the batched backward pass needs from the batched forward pass.
+//indexed by layer, then by sample.
+struct BatchCache {
+    activations: Vec<Vec<ColumnVector>>,
+    pre_activations: Vec<Vec<ColumnVector>>,
+    normalized: Vec<Vec<ColumnVector>>,
+    inverse_stds: Vec<ColumnVector>,
+    dropout_masks: Vec<Vec<ColumnVector>>,
+}
+
+fn weighted_input(weights: &Matrix, bias: &ColumnVector, input: &ColumnVector) -> ColumnVector {
+    let mut z_values = ColumnVector::new_with_elements(weights.height(), 0.0);
+    input._mul_matrix(weights, &mut z_values);
+    z_values += bias;
+    z_values
+}
+
+impl NeuralNetwork {
+    fn batch_norm(&self) -> &BatchNorm {
+        match &self.normalization {
+            Some(Normalization::Batch(batch_norm)) => batch_norm,
+            _ => panic!("the network does not use batch normalization."),
+        }
+    }
+
+    fn batch_norm_mut(&mut self) -> &mut BatchNorm {
+        match &mut self.normalization {
+            Some(Normalization::Batch(batch_norm)) => batch_norm,
+            _ => panic!("the network does not use batch normalization."),
+        }
+    }
+
+    fn batch_norm_forward(&mut self, inputs: &[&ColumnVector]) -> BatchCache {
+        let layer_amount = self.weights.len();
+        let sample_amount = inputs.len() as f32;
+        let mut cache = BatchCache {
+            activations: vec![inputs.iter().map(|&x| match &self.preprocessing {
+                Some(preprocessing) => preprocessing.apply(x),
+                None => x.clone(),
+            }).collect()],
+            pre_activations: Vec::with_capacity(layer_amount),
+            normalized: Vec::with_capacity(layer_amount - 1),
+            inverse_stds: Vec::with_capacity(layer_amount - 1),
+            dropout_masks: Vec::with_capacity(layer_amount - 1),
+        };
+        for layer_index in 0..layer_amount {
+            let z_values: Vec<ColumnVector> = cache.activations[layer_index].iter()
+                .map(|input| weighted_input(&self.weights[layer_index], &self.biases[layer_index], input))
+                .collect();
+            if layer_index == layer_amount - 1 {
+                cache.activations.push(z_values.iter().map(|x| self.activation_functions[layer_index].apply(x)).collect());
+                cache.pre_activations.push(z_values);
+                break;
+            }
+
+            let batch_norm = self.batch_norm_mut();
+            let size = z_values[0].data.len();
+            let mut mean = ColumnVector::new_with_elements(size, 0.0);
+            z_values.iter().for_each(|z| mean += z);
+            mean.data.iter_mut().for_each(|x| *x /= sample_amount);
+            let mut variance = ColumnVector::new_with_elements(size, 0.0);
+            z_values.iter().for_each(|z| {
+                zip(variance.data.iter_mut(), zip(&z.data, &mean.data)).for_each(|(v, (z, m))| *v += (z - m).powi(2));
+            });
+            variance.data.iter_mut().for_each(|x| *x /= sample_amount);
+
+            let momentum = batch_norm.momentum;
+            zip(batch_norm.running_means[layer_index].data.iter_mut(), &mean.data)
+                .for_each(|(running, batch)| *running = momentum * *running + (1.0 - momentum) * batch);
+            zip(batch_norm.running_variances[layer_index].data.iter_mut(), &variance.data)
+                .for_each(|(running, batch)| *running = momentum * *running + (1.0 - momentum) * batch);
+
+            let epsilon = batch_norm.epsilon;
+            let inverse_std = variance.map(|v| 1.0 / (v + epsilon).sqrt());
+            let normalized: Vec<ColumnVector> = z_values.iter().map(|z| {
+                ColumnVector::from_vec(zip(zip(&z.data, &mean.data), &inverse_std.data)
+                    .map(|((z, m), s)| (z - m) * s)
+                    .collect())
+            }).collect();
+            let pre_activations: Vec<ColumnVector> = normalized.iter()
+                .map(|x| batch_norm.scale_and_shift(layer_index, x))
+                .collect();
+
+            let activation_function = &self.activation_functions[layer_index];
+            let mut activations: Vec<ColumnVector> = pre_activations.iter().map(|x| activation_function.apply(x)).collect();
+            let mut masks = Vec::new();
+            if let Some(dropout) = &mut self.dropout {
+                for activation in activations.iter_mut() {
+                    dropout.apply(layer_index, activation);
+                    masks.push(dropout.mask(layer_index).clone());
+                }
+            }
+            cache.activations.push(activations);
+            cache.pre_activations.push(pre_activations);
+            cache.normalized.push(normalized);
+            cache.inverse_stds.push(inverse_std);
+            cache.dropout_masks.push(masks);
+        }
+        cache
+    }
+
+    //summed gradients of a whole mini batch normalized with its own statistics.
+    //used by batch_gradients while training a network with batch normalization.
+    pub fn batch_norm_gradients(&mut self, batch: &[(ColumnVector, ColumnVector)]) -> Gradients {
+        self.batch_norm_gradients_and_loss(batch).0
+    }
+
+    pub(crate) fn batch_norm_gradients_and_loss(&mut self, batch: &[(ColumnVector, ColumnVector)]) -> (Gradients, LossAccumulator) {
+        let inputs: Vec<&ColumnVector> = batch.iter().map(|(input, _)| input).collect();
+        let cache = self.batch_norm_forward(&inputs);
+        let layer_amount = self.weights.len();
+        let sample_amount = batch.len() as f32;
+        let mut gradients = Gradients::zeros_like(self);
+        let mut loss = LossAccumulator::new();
+        for (output, (_, desired)) in zip(&cache.activations[layer_amount], batch) {
+            loss.add(&self.cost, output, desired);
+        }
+        let batch_norm = self.batch_norm();
+
+        let mut deltas: Vec<ColumnVector> = zip(&cache.activations[layer_amount], zip(&cache.pre_activations[layer_amount - 1], batch))
+            .map(|(output, (pre_activation, (_, desired)))| {
+                self.output_delta(pre_activation, output, desired)
+            }).collect();
+
+        for layer_index in (0..layer_amount).rev() {
+            for (delta, input) in zip(&deltas, &cache.activations[layer_index]) {
+                delta._add_outer(input, &mut gradients.weights[layer_index]);
+                gradients.biases[layer_index] += delta;
+            }
+            if layer_index == 0 {
+                break;
+            }
+
+            let hidden_index = layer_index - 1;
+            //gradient with respect to the scaled and shifted values of the hidden layer.
+            let scaled_deltas: Vec<ColumnVector> = deltas.iter().enumerate().map(|(sample, delta)| {
+                let mut propagated = ColumnVector::new_with_elements(self.weights[layer_index].width(), 0.0);
+                delta._mul_transposed_matrix(&self.weights[layer_index], &mut propagated);
+                if let Some(mask) = cache.dropout_masks[hidden_index].get(sample) {
+                    zip(propagated.data.iter_mut(), &mask.data).for_each(|(x, m)| *x *= m);
+                }
+                self.activation_functions[hidden_index].backward(&cache.pre_activations[hidden_index][sample], &propagated)
+            }).collect();
+
+            let gamma = &batch_norm.gammas[hidden_index];
+            let size = gamma.data.len();
+            let mut normalized_delta_sum = ColumnVector::new_with_elements(size, 0.0);
+            let mut normalized_delta_dot = ColumnVector::new_with_elements(size, 0.0);
+            for (scaled_delta, normalized) in zip(&scaled_deltas, &cache.normalized[hidden_index]) {
+                for index in 0..size {
+                    let normalized_delta = scaled_delta.data[index] * gamma.data[index];
+                    gradients.gammas[hidden_index].data[index] += scaled_delta.data[index] * normalized.data[index];
+                    gradients.betas[hidden_index].data[index] += scaled_delta.data[index];
+                    normalized_delta_sum.data[index] += normalized_delta;
+                    normalized_delta_dot.data[index] += normalized_delta * normalized.data[index];
+                }
+            }
+            let inverse_std = &cache.inverse_stds[hidden_index];
+            deltas = zip(&scaled_deltas, &cache.normalized[hidden_index]).map(|(scaled_delta, normalized)| {
+                ColumnVector::from_vec((0..size).map(|index| {
+                    let normalized_delta = scaled_delta.data[index] * gamma.data[index];
+                    inverse_std.data[index] / sample_amount * (sample_amount * normalized_delta
+                        - normalized_delta_sum.data[index]
+                        - normalized.data[index] * normalized_delta_dot.data[index])
+                }).collect())
+            }).collect();
+        }
+        (gradients, loss)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::{ColumnVector, Matrix};
+    use crate::gradient_check::check_parameter_gradients;
+    use crate::{check_gradients, squared_error, BatchNorm, GradientValues, Mode, NeuralNetwork, Normalization};
+
+    fn test_network() -> NeuralNetwork {
+        let weights = vec![
+            Matrix::from_vec(vec![vec![0.5, -0.2, 0.1], vec![0.3, 0.8, -0.4]]),
+            Matrix::from_vec(vec![vec![0.7, 0.6], vec![-0.2, 0.9]]),
+        ];
+        let mut network = NeuralNetwork::new_from_vecs(weights, None, None, None);
+        let mut batch_norm = BatchNorm::for_network(&network);
+        batch_norm.gammas[0] = ColumnVector::from_vec(vec![1.2, 0.8]);
+        batch_norm.betas[0] = ColumnVector::from_vec(vec![0.5, 0.4]);
+        network.normalization = Some(Normalization::Batch(batch_norm));
+        network
+    }
+
+    fn test_batch() -> Vec<(ColumnVector, ColumnVector)> {
+        vec![
+            (ColumnVector::from_vec(vec![1.0, 0.5, 0.25]), ColumnVector::from_vec(vec![0.0, 1.0])),
+            (ColumnVector::from_vec(vec![0.2, 0.1, 0.9]), ColumnVector::from_vec(vec![1.0, 0.0])),
+            (ColumnVector::from_vec(vec![0.4, 0.8, 0.3]), ColumnVector::from_vec(vec![1.0, 1.0])),
+        ]
+    }
+
+    fn batch_loss(network: &mut NeuralNetwork, batch: &[(ColumnVector, ColumnVector)]) -> f32 {
+        let inputs: Vec<&ColumnVector> = batch.iter().map(|(input, _)| input).collect();
+        let cache = network.batch_norm_forward(&inputs);
+        batch.iter().enumerate()
+            .map(|(index, (_, desired))| squared_error(&cache.activations[2][index], desired))
+            .sum()
+    }
+
+    #[test]
+    fn batch_norm_normalizes_hidden_layers() {
+        let mut network = test_network();
+        let batch = test_batch();
+        let inputs: Vec<&ColumnVector> = batch.iter().map(|(input, _)| input).collect();
+        let cache = network.batch_norm_forward(&inputs);
+        for index in 0..2 {
+            let values: Vec<f32> = cache.normalized[0].iter().map(|x| x.data[index]).collect();
+            let mean = values.iter().sum::<f32>() / 3.0;
+            let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / 3.0;
+            assert!(mean.abs() < 1e-5);
+            assert!((variance - 1.0).abs() < 1e-3);
+        }
+        let batch_norm = network.batch_norm();
+        assert_ne!(batch_norm.running_means[0], ColumnVector::new_with_elements(2, 0.0));
+    }
+
+    #[test]
+    fn batch_norm_gradients_against_finite_differences() {
+        let mut network = test_network();
+        network.mode = Mode::Training;
+        let batch = test_batch();
+        let gradients = network.batch_gradients(&batch);
+        let gradients: Vec<f32> = gradients.values().copied().collect();
+        let check = check_parameter_gradients(&mut network, &gradients, |network| batch_loss(network, &batch));
+        assert!(check.max_absolute_error < 1e-2, "{:?}", check);
+    }
+
+    #[test]
+    fn single_sample_backpropagation_uses_running_statistics() {
+        let mut network = test_network();
+        let batch = test_batch();
+        let (input, desired) = &batch[0];
+        let check = check_gradients(&mut network, input, desired);
+        assert!(check.max_absolute_error < 1e-2, "{:?}", check);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{Evaluation, NeuralNetwork};
+
+//what the trainer reports after every mini batch.
+#[derive(PartialEq, Debug, Clone)]
+pub struct BatchEnd {
+    pub epoch: usize,
+    pub batch_index: usize,
+    //batches per epoch, when the trainer knows it in advance.
+    pub batch_amount: Option<usize>,
+    pub samples: usize,
+    //mean cost of the batch in training mode, computed before the update.
+    pub loss: f32,
+    pub learning_rate: f32,
+}
+
+//what the trainer reports after every epoch.
+#[derive(PartialEq, Debug, Clone)]
+pub struct EpochEnd {
+    pub epoch: usize,
+    //mean cost of all batches of the epoch, each computed before its update.
+    pub training_loss: f32,
+    //only present when training with validation data.
+    pub validation: Option<Evaluation>,
+    //the learning rate of the last optimizer step.
+    pub learning_rate: f32,
+}
+
+//returned by Callback::on_epoch_end to decide whether training goes on.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+//hooks the trainer calls while training, in the order the callbacks were added.
+//every hook does nothing by default, so a callback only implements the ones it needs.
+//N is what is trained, a NeuralNetwork or a Model.
+pub trait Callback<N = NeuralNetwork> {
+    fn on_epoch_start(&mut self, _network: &N, _epoch: usize) {}
+
+    fn on_batch_end(&mut self, _network: &N, _batch: &BatchEnd) {}
+
+    //training stops after this epoch when any callback returns Control::Stop.
+    fn on_epoch_end(&mut self, _network: &mut N, _epoch: &EpochEnd) -> Control {
+        Control::Continue
+    }
+
+    fn on_train_end(&mut self, _network: &mut N) {}
+}
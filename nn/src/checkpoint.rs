@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use crate::{Callback, Control, EpochEnd, Model, ModelMetadata, NeuralNetwork};
+
+//the value of an epoch a callback watches for improvements.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Monitor {
+    //the validation loss, or the training loss when there is no validation data.
+    Loss,
+    TrainingLoss,
+    ValidationAccuracy,
+    //a validation metric by its name, see Metric::name.
+    Metric(String),
+}
+
+impl Monitor {
+    pub fn value(&self, epoch: &EpochEnd) -> Option<f32> {
+        match self {
+            Monitor::Loss => Some(epoch.validation.as_ref().map_or(epoch.training_loss, |validation| validation.loss)),
+            Monitor::TrainingLoss => Some(epoch.training_loss),
+            Monitor::ValidationAccuracy => epoch.validation.as_ref().map(|validation| validation.accuracy),
+            Monitor::Metric(name) => epoch.validation.as_ref()
+                .and_then(|validation| validation.metrics.iter().find(|(metric, _)| metric == name))
+                .map(|(_, value)| *value),
+        }
+    }
+
+    //losses improve by going down, accuracies and metrics by going up.
+    pub fn higher_is_better(&self) -> bool {
+        !matches!(self, Monitor::Loss | Monitor::TrainingLoss)
+    }
+
+    pub fn is_improvement(&self, value: f32, best: Option<f32>) -> bool {
+        best.is_none_or(|best| if self.higher_is_better() { value > best } else { value < best })
+    }
+}
+
+//which of the written checkpoints stay on disk.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Keep {
+    Best,
+    //the last n checkpoints, which are the n best since one is only written on improvement.
+    Last(usize),
+}
+
+//saves the network into directory every time the monitored value improves, as
+//checkpoint_<epoch>.nn with the epoch counted from 1, in the format of NeuralNetwork::save
+//with the epochs and validation accuracy as metadata. Older checkpoints are removed
+//according to keep. A missing monitored value is reported on stderr once and the epoch is
+//skipped. A checkpoint that can not be written is reported on stderr and training goes on,
+//its value does not become the best, so the next improvement on the last saved one is saved.
+pub struct ModelCheckpoint {
+    pub directory: PathBuf,
+    pub monitor: Monitor,
+    pub keep: Keep,
+    pub best_value: Option<f32>,
+    //the checkpoints on disk, oldest first.
+    pub saved: VecDeque<PathBuf>,
+    reported_missing: bool,
+}
+
+impl ModelCheckpoint {
+    pub fn new<P: Into<PathBuf>>(directory: P) -> ModelCheckpoint {
+        ModelCheckpoint::new_with_monitor(directory, Monitor::Loss, Keep::Best)
+    }
+
+    pub fn new_with_monitor<P: Into<PathBuf>>(directory: P, monitor: Monitor, keep: Keep) -> ModelCheckpoint {
+        if keep == Keep::Last(0) {
+            panic!("at least one checkpoint must be kept.");
+        }
+        ModelCheckpoint {
+            directory: directory.into(),
+            monitor,
+            keep,
+            best_value: None,
+            saved: VecDeque::new(),
+            reported_missing: false,
+        }
+    }
+
+    //the checkpoint of the best epoch so far.
+    pub fn best_path(&self) -> Option<&PathBuf> {
+        self.saved.back()
+    }
+
+    //the monitored value of epoch when it is better than the best so far.
+    fn improvement(&mut self, epoch: &EpochEnd) -> Option<f32> {
+        let Some(value) = self.monitor.value(epoch) else {
+            if !self.reported_missing {
+                eprintln!("the monitored value {:?} is not reported by this training, no checkpoints are saved.", self.monitor);
+                self.reported_missing = true;
+            }
+            return None;
+        };
+        self.monitor.is_improvement(value, self.best_value).then_some(value)
+    }
+
+    fn report(&mut self, saved: io::Result<()>, value: f32) {
+        match saved {
+            Ok(()) => self.best_value = Some(value),
+            Err(error) => eprintln!("could not save a checkpoint to {}: {}.", self.directory.display(), error),
+        }
+    }
+
+    fn save(&mut self, network: &NeuralNetwork, epoch: &EpochEnd) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        let path = self.directory.join(format!("checkpoint_{}.nn", epoch.epoch + 1));
+        let metadata = ModelMetadata {
+            epochs: epoch.epoch + 1,
+            accuracy: epoch.validation.as_ref().map(|validation| validation.accuracy),
+        };
+        network.save_with_metadata(&path, metadata)?;
+        self.saved.push_back(path);
+        let kept = match self.keep {
+            Keep::Best => 1,
+            Keep::Last(n) => n,
+        };
+        while self.saved.len() > kept {
+            let old = self.saved.pop_front().unwrap();
+            //a checkpoint removed by hand is not an error.
+            let _ = fs::remove_file(old);
+        }
+        Ok(())
+    }
+}
+
+impl Callback for ModelCheckpoint {
+    fn on_epoch_end(&mut self, network: &mut NeuralNetwork, epoch: &EpochEnd) -> Control {
+        if let Some(value) = self.improvement(epoch) {
+            let saved = self.save(network, epoch);
+            self.report(saved, value);
+        }
+        Control::Continue
+    }
+}
+
+//a model is saved as the network of Model::to_network. one that has none can not be saved,
+//which is reported like a checkpoint that can not be written.
+impl Callback<Model> for ModelCheckpoint {
+    fn on_epoch_end(&mut self, model: &mut Model, epoch: &EpochEnd) -> Control {
+        if let Some(value) = self.improvement(epoch) {
+            let saved = model.to_network()
+                .map_err(|message| io::Error::new(io::ErrorKind::InvalidInput, message))
+                .and_then(|network| self.save(&network, epoch));
+            self.report(saved, value);
+        }
+        Control::Continue
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use matrix::ColumnVector;
+    use mnist_reader::Preprocessing;
+    use crate::{ActivationFunction, Callback, Control, Cost, EpochEnd, Evaluation, Keep, LayerNorm, ModelCheckpoint, ModelMetadata, Monitor, NeuralNetwork, Normalization};
+
+    #[test]
+    fn checkpoints_on_improvement() {
+        let directory = std::env::temp_dir().join(format!("nn_checkpoints_{}", std::process::id()));
+        let mut checkpoint = ModelCheckpoint::new_with_monitor(&directory, Monitor::ValidationAccuracy, Keep::Last(2));
+        let mut network = NeuralNetwork::new_with_seed(&[3, 4, 2], vec![ActivationFunction::Tanh, ActivationFunction::Softmax], 5);
+        network.cost = Cost::CrossEntropy;
+        network.preprocessing = Some(Preprocessing::unit_range());
+        network.normalization = Some(Normalization::Layer(LayerNorm::for_network(&network)));
+        for (epoch, accuracy) in [0.5, 0.75, 0.5, 0.8, 0.9].into_iter().enumerate() {
+            network.biases[0] = ColumnVector::new_with_elements(4, epoch as f32);
+            let validation = Some(Evaluation { loss: 1.0, accuracy, metrics: Vec::new() });
+            checkpoint.on_epoch_end(&mut network, &EpochEnd { epoch, training_loss: 1.0, validation, learning_rate: 0.1 });
+        }
+        assert_eq!(checkpoint.best_value, Some(0.9));
+        let mut files: Vec<String> = fs::read_dir(&directory).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["checkpoint_4.nn", "checkpoint_5.nn"]);
+        let (best, metadata) = NeuralNetwork::load_with_metadata(checkpoint.best_path().unwrap()).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(metadata, ModelMetadata { epochs: 5, accuracy: Some(0.9) });
+        //the activations, normalization, preprocessing and cost come back along with the parameters.
+        assert_eq!((&best.activation_functions, &best.cost, &best.preprocessing), (&network.activation_functions, &network.cost, &network.preprocessing));
+        let input = ColumnVector::from_vec(vec![30.0, 120.0, 250.0]);
+        assert_eq!(best.infer(&input), network.infer(&input));
+    }
+
+    #[test]
+    fn write_errors_do_not_stop_training() {
+        //the checkpoint directory can not be created where a file is.
+        let path = std::env::temp_dir().join(format!("nn_checkpoints_file_{}", std::process::id()));
+        fs::write(&path, "not a directory").unwrap();
+        let mut checkpoint = ModelCheckpoint::new(&path);
+        let mut network = NeuralNetwork::new_with_activations(&[2, 2], vec![ActivationFunction::Relu], Some(0.5));
+        let epoch = EpochEnd { epoch: 0, training_loss: 0.5, validation: None, learning_rate: 0.1 };
+        assert_eq!(checkpoint.on_epoch_end(&mut network, &epoch), Control::Continue);
+        fs::remove_file(&path).unwrap();
+        assert_eq!((checkpoint.best_path(), checkpoint.best_value), (None, None));
+    }
+
+    #[test]
+    fn missing_monitored_values_are_skipped() {
+        let directory = std::env::temp_dir().join(format!("nn_checkpoints_missing_{}", std::process::id()));
+        let mut checkpoint = ModelCheckpoint::new_with_monitor(&directory, Monitor::ValidationAccuracy, Keep::Best);
+        let mut network = NeuralNetwork::new_with_activations(&[2, 2], vec![ActivationFunction::Relu], Some(0.5));
+        for epoch in 0..2 {
+            let end = EpochEnd { epoch, training_loss: 0.5, validation: None, learning_rate: 0.1 };
+            assert_eq!(checkpoint.on_epoch_end(&mut network, &end), Control::Continue);
+        }
+        assert_eq!((checkpoint.best_path(), checkpoint.best_value), (None, None));
+        assert!(!directory.exists());
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+use crate::ConfusionMatrix;
+
+//precision, recall and f1 score of a class, or an average of them.
+//support is the amount of samples the values are based on.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ClassMetrics {
+    pub precision: f32,
+    pub recall: f32,
+    pub f1: f32,
+    pub support: usize,
+}
+
+//per class metrics together with their averages. the macro average weighs every class the
+//same, the micro average every sample, which makes it the accuracy for single label predictions.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ClassificationReport {
+    pub classes: Vec<ClassMetrics>,
+    pub macro_average: ClassMetrics,
+    pub micro_average: ClassMetrics,
+}
+
+//0 when nothing was counted, like a precision without a single prediction of the class.
+fn ratio(numerator: usize, denominator: usize) -> f32 {
+    if denominator == 0 { 0.0 } else { numerator as f32 / denominator as f32 }
+}
+
+fn f1(precision: f32, recall: f32) -> f32 {
+    if precision + recall == 0.0 { 0.0 } else { 2.0 * precision * recall / (precision + recall) }
+}
+
+impl ConfusionMatrix {
+    //fraction of the samples predicted as the class that actually are of it.
+    pub fn precision(&self, class: usize) -> f32 {
+        ratio(self.count(class, class), self.predicted_count(class))
+    }
+
+    //fraction of the samples of the class that were predicted as it.
+    pub fn recall(&self, class: usize) -> f32 {
+        ratio(self.count(class, class), self.actual_count(class))
+    }
+
+    pub fn f1(&self, class: usize) -> f32 {
+        f1(self.precision(class), self.recall(class))
+    }
+
+    pub fn report(&self) -> ClassificationReport {
+        let classes: Vec<ClassMetrics> = (0..self.classes())
+            .map(|class| ClassMetrics {
+                precision: self.precision(class),
+                recall: self.recall(class),
+                f1: self.f1(class),
+                support: self.actual_count(class),
+            })
+            .collect();
+        let mean = |value: fn(&ClassMetrics) -> f32| classes.iter().map(value).sum::<f32>() / classes.len().max(1) as f32;
+        let macro_average = ClassMetrics {
+            precision: mean(|metrics| metrics.precision),
+            recall: mean(|metrics| metrics.recall),
+            f1: mean(|metrics| metrics.f1),
+            support: self.total(),
+        };
+        //every misclassified sample is a false positive of one class and a false negative of
+        //another, so micro precision and recall are the same.
+        let accuracy = self.accuracy();
+        let micro_average = ClassMetrics {
+            precision: accuracy,
+            recall: accuracy,
+            f1: accuracy,
+            support: self.total(),
+        };
+        ClassificationReport {
+            classes,
+            macro_average,
+            micro_average,
+        }
+    }
+}
+
+impl fmt::Display for ClassificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let row = |f: &mut fmt::Formatter<'_>, name: &str, metrics: &ClassMetrics| {
+            writeln!(f, "{:>12} {:>9.4} {:>9.4} {:>9.4} {:>9}", name, metrics.precision, metrics.recall, metrics.f1, metrics.support)
+        };
+        writeln!(f, "{:>12} {:>9} {:>9} {:>9} {:>9}", "class", "precision", "recall", "f1", "support")?;
+        for (class, metrics) in self.classes.iter().enumerate() {
+            row(f, &class.to_string(), metrics)?;
+        }
+        row(f, "macro avg", &self.macro_average)?;
+        row(f, "micro avg", &self.micro_average)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{ClassMetrics, ConfusionMatrix};
+
+    #[test]
+    fn precision_recall_and_f1() {
+        let confusion_matrix = ConfusionMatrix { counts: vec![vec![3, 1, 0], vec![2, 4, 0], vec![0, 0, 0]] };
+        let report = confusion_matrix.report();
+        assert_eq!(report.classes[0].precision, 0.6);
+        assert_eq!(report.classes[0].recall, 0.75);
+        assert!((report.classes[0].f1 - 2.0 / 3.0).abs() < 1e-6);
+        assert_eq!((report.classes[1].precision, report.classes[1].recall, report.classes[1].support), (0.8, 4.0 / 6.0, 6));
+        //a class that never occurs and is never predicted scores 0.
+        assert_eq!(report.classes[2], ClassMetrics { precision: 0.0, recall: 0.0, f1: 0.0, support: 0 });
+        assert!((report.macro_average.precision - 1.4 / 3.0).abs() < 1e-6);
+        assert_eq!(report.micro_average.f1, 0.7);
+        assert_eq!(report.to_string().lines().count(), 6);
+        assert!(report.to_string().contains("   macro avg    0.4667"));
+    }
+}
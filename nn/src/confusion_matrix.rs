@@ -0,0 +1,131 @@
+use std::fmt;
+use mnist_reader::Dataset;
+use crate::{NeuralNetwork, Prediction};
+
+//counts of how often samples of every class were predicted as every class.
+//rows are the actual labels, columns the predicted classes.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ConfusionMatrix {
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl ConfusionMatrix {
+    pub fn new(classes: usize) -> ConfusionMatrix {
+        ConfusionMatrix {
+            counts: vec![vec![0; classes]; classes],
+        }
+    }
+
+    pub fn from_predictions(predictions: &[Prediction], labels: &[usize], classes: usize) -> ConfusionMatrix {
+        if predictions.len() != labels.len() {
+            panic!("expected a label for each of the {} predictions, got {}.", predictions.len(), labels.len());
+        }
+        let mut confusion_matrix = ConfusionMatrix::new(classes);
+        for (prediction, &label) in predictions.iter().zip(labels) {
+            confusion_matrix.add(label, prediction.class);
+        }
+        confusion_matrix
+    }
+
+    pub fn add(&mut self, actual: usize, predicted: usize) {
+        if actual >= self.classes() || predicted >= self.classes() {
+            panic!("classes must be smaller than {}.", self.classes());
+        }
+        self.counts[actual][predicted] += 1;
+    }
+
+    pub fn classes(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn count(&self, actual: usize, predicted: usize) -> usize {
+        self.counts[actual][predicted]
+    }
+
+    //samples of the class, however they were predicted.
+    pub fn actual_count(&self, class: usize) -> usize {
+        self.counts[class].iter().sum()
+    }
+
+    //samples predicted as the class, whatever they actually were.
+    pub fn predicted_count(&self, class: usize) -> usize {
+        self.counts.iter().map(|row| row[class]).sum()
+    }
+
+    pub fn correct(&self) -> usize {
+        (0..self.classes()).map(|class| self.counts[class][class]).sum()
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().flatten().sum()
+    }
+
+    pub fn accuracy(&self) -> f32 {
+        match self.total() {
+            0 => 0.0,
+            total => self.correct() as f32 / total as f32,
+        }
+    }
+}
+
+//a table with a row per actual class and a column per predicted class.
+impl fmt::Display for ConfusionMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.counts.iter().flatten().max().map_or(1, |max| max.to_string().len())
+            .max(self.classes().to_string().len())
+            .max(6);
+        write!(f, "{:>width$}", "actual", width = width)?;
+        for class in 0..self.classes() {
+            write!(f, " {:>width$}", class, width = width)?;
+        }
+        writeln!(f)?;
+        for (class, row) in self.counts.iter().enumerate() {
+            write!(f, "{:>width$}", class, width = width)?;
+            for count in row {
+                write!(f, " {:>width$}", count, width = width)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl NeuralNetwork {
+    //the confusion matrix of the network's predictions over a labeled dataset, in inference mode.
+    pub fn confusion_matrix<D: Dataset + ?Sized>(&self, dataset: &D) -> ConfusionMatrix {
+        let mut confusion_matrix = ConfusionMatrix::new(self.biases.last().unwrap().data.len());
+        for index in 0..dataset.len() {
+            let (input, label) = dataset.get(index);
+            confusion_matrix.add(label, self.predict(&input).class);
+        }
+        confusion_matrix
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::{ColumnVector, Matrix};
+    use crate::{ActivationFunction, ConfusionMatrix, NeuralNetwork};
+
+    #[test]
+    fn confusion_matrix() {
+        let weights = vec![Matrix::from_vec(vec![vec![1.0, -1.0], vec![-1.0, 1.0]])];
+        let mut network = NeuralNetwork::new_from_vecs(weights, None, None, None);
+        network.activation_functions = vec![ActivationFunction::Softmax];
+        let dataset = vec![
+            (ColumnVector::from_vec(vec![1.0, 0.0]), 0),
+            (ColumnVector::from_vec(vec![0.0, 1.0]), 1),
+            (ColumnVector::from_vec(vec![0.0, 2.0]), 0),
+            (ColumnVector::from_vec(vec![3.0, 0.0]), 0),
+        ];
+        let confusion_matrix = network.confusion_matrix(&dataset);
+        assert_eq!(confusion_matrix.counts, vec![vec![2, 1], vec![0, 1]]);
+        assert_eq!((confusion_matrix.actual_count(0), confusion_matrix.predicted_count(1)), (3, 2));
+        assert_eq!(confusion_matrix.accuracy(), network.evaluate(&dataset).accuracy);
+        let inputs: Vec<ColumnVector> = dataset.iter().map(|(input, _)| input.clone()).collect();
+        let labels: Vec<usize> = dataset.iter().map(|(_, label)| *label).collect();
+        assert_eq!(ConfusionMatrix::from_predictions(&network.predict_batch(&inputs), &labels, 2), confusion_matrix);
+        assert_eq!(confusion_matrix.to_string(), "actual      0      1\n     0      2      1\n     1      0      1\n");
+    }
+}
@@ -0,0 +1,186 @@
+use std::iter::zip;
+use matrix::{ColumnVector, Matrix};
+use rand::Rng;
+use crate::{Initialization, Layer, Param, Shape};
+
+//a 2d convolution over feature maps. inputs and outputs are flattened channel by channel,
+//every channel row by row, so an mnist image is a single channel of 28 by 28.
+//every row of kernels holds one output channel: its kernel for every input channel, row by row.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Conv2d {
+    pub input_channels: usize,
+    pub input_height: usize,
+    pub input_width: usize,
+    pub output_channels: usize,
+    pub kernel_size: usize,
+    pub stride: usize,
+    //zeros added around every side of the input.
+    pub padding: usize,
+    pub kernels: Matrix,
+    pub biases: ColumnVector,
+    pub kernel_gradients: Matrix,
+    pub bias_gradients: ColumnVector,
+    input: ColumnVector,
+}
+
+impl Conv2d {
+    //input_shape is (channels, height, width).
+    pub fn random<R: Rng + ?Sized>(input_shape: (usize, usize, usize), output_channels: usize, kernel_size: usize, stride: usize, padding: usize, initialization: Initialization, rng: &mut R) -> Conv2d {
+        let (input_channels, input_height, input_width) = input_shape;
+        if stride == 0 {
+            panic!("the stride of a convolution must be at least 1.");
+        }
+        if kernel_size == 0 || kernel_size > input_height + 2 * padding || kernel_size > input_width + 2 * padding {
+            panic!("a kernel of size {} does not fit an input of {} by {} with padding {}.", kernel_size, input_height, input_width, padding);
+        }
+        let (kernels, biases) = initialization.generate(output_channels, input_channels * kernel_size * kernel_size, rng);
+        Conv2d {
+            input_channels,
+            input_height,
+            input_width,
+            output_channels,
+            kernel_size,
+            stride,
+            padding,
+            kernel_gradients: Matrix::zeros(output_channels, input_channels * kernel_size * kernel_size),
+            bias_gradients: ColumnVector::new_with_elements(output_channels, 0.0),
+            input: ColumnVector::new_with_elements(input_channels * input_height * input_width, 0.0),
+            kernels,
+            biases,
+        }
+    }
+
+    //(channels, height, width) of the output.
+    pub fn output_dimensions(&self) -> (usize, usize, usize) {
+        let size = |input: usize| (input + 2 * self.padding - self.kernel_size) / self.stride + 1;
+        (self.output_channels, size(self.input_height), size(self.input_width))
+    }
+
+    //calls visit with the output index, the kernel index and the input index of every
+    //product that makes up the convolution, skipping the ones that fall into the padding.
+    fn for_each_product(&self, mut visit: impl FnMut(usize, usize, usize)) {
+        let (_, output_height, output_width) = self.output_dimensions();
+        let kernel_area = self.kernel_size * self.kernel_size;
+        for output_channel in 0..self.output_channels {
+            for y in 0..output_height {
+                for x in 0..output_width {
+                    let output_index = (output_channel * output_height + y) * output_width + x;
+                    for input_channel in 0..self.input_channels {
+                        for kernel_y in 0..self.kernel_size {
+                            let input_y = (y * self.stride + kernel_y).wrapping_sub(self.padding);
+                            if input_y >= self.input_height {
+                                continue;
+                            }
+                            for kernel_x in 0..self.kernel_size {
+                                let input_x = (x * self.stride + kernel_x).wrapping_sub(self.padding);
+                                if input_x >= self.input_width {
+                                    continue;
+                                }
+                                let kernel_index = input_channel * kernel_area + kernel_y * self.kernel_size + kernel_x;
+                                let input_index = (input_channel * self.input_height + input_y) * self.input_width + input_x;
+                                visit(output_index, kernel_index, input_index);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Layer for Conv2d {
+    fn forward(&mut self, input: &ColumnVector) -> ColumnVector {
+        if input.data.len() != self.input_channels * self.input_height * self.input_width {
+            panic!("expected {} inputs, got {}.", self.input_channels * self.input_height * self.input_width, input.data.len());
+        }
+        self.input = input.clone();
+        let (channels, height, width) = self.output_dimensions();
+        let mut output = ColumnVector::from_vec(self.biases.data.iter()
+            .flat_map(|&bias| std::iter::repeat_n(bias, height * width))
+            .collect());
+        let area = height * width;
+        self.for_each_product(|output_index, kernel_index, input_index| {
+            output.data[output_index] += self.kernels[(output_index / area, kernel_index)] * input.data[input_index];
+        });
+        debug_assert_eq!(output.data.len(), channels * area);
+        output
+    }
+
+    fn backward(&mut self, gradient: &ColumnVector) -> ColumnVector {
+        let (_, height, width) = self.output_dimensions();
+        let area = height * width;
+        let mut propagated = ColumnVector::new_with_elements(self.input.data.len(), 0.0);
+        let mut kernel_gradients = std::mem::replace(&mut self.kernel_gradients, Matrix::zeros(0, 0));
+        self.for_each_product(|output_index, kernel_index, input_index| {
+            let channel = output_index / area;
+            kernel_gradients[(channel, kernel_index)] += gradient.data[output_index] * self.input.data[input_index];
+            propagated.data[input_index] += gradient.data[output_index] * self.kernels[(channel, kernel_index)];
+        });
+        self.kernel_gradients = kernel_gradients;
+        for (bias_gradient, channel_gradient) in zip(&mut self.bias_gradients.data, gradient.data.chunks(area)) {
+            *bias_gradient += channel_gradient.iter().sum::<f32>();
+        }
+        propagated
+    }
+
+    fn output_shape(&self, input_shape: Shape) -> Result<Shape, String> {
+        let expected = Shape::image(self.input_channels, self.input_height, self.input_width);
+        if input_shape != expected {
+            return Err(format!("a convolution of {} can not follow {}.", expected, input_shape));
+        }
+        let (channels, height, width) = self.output_dimensions();
+        Ok(Shape::image(channels, height, width))
+    }
+
+    //every kernel row by row and then every bias.
+    fn params(&mut self) -> Vec<Param<'_>> {
+        let kernels = zip(self.kernels.as_mut_slice(), self.kernel_gradients.as_mut_slice())
+            .map(|(value, gradient)| Param { value, gradient, regularized: true });
+        let biases = zip(&mut self.biases.data, &mut self.bias_gradients.data)
+            .map(|(value, gradient)| Param { value, gradient, regularized: false });
+        kernels.chain(biases).collect()
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.output_channels * self.input_channels * self.kernel_size * self.kernel_size + self.output_channels
+    }
+
+    fn fork(&mut self) -> Option<Box<dyn Layer>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::{ColumnVector, Matrix};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::{check_layer_gradients, Conv2d, Initialization, Layer};
+
+    #[test]
+    fn forward_with_stride_and_padding() {
+        let mut conv = Conv2d::random((1, 3, 3), 1, 2, 1, 0, Initialization::default(), &mut StdRng::seed_from_u64(0));
+        conv.kernels = Matrix::from_vec(vec![vec![1.0, 0.0, 0.0, -1.0]]);
+        conv.biases = ColumnVector::from_vec(vec![0.5]);
+        let input = ColumnVector::from_vec((1..=9).map(|x| x as f32).collect());
+        //every output is the top left minus the bottom right element of its window.
+        assert_eq!(conv.forward(&input).data, vec![-3.5, -3.5, -3.5, -3.5]);
+
+        conv.stride = 2;
+        conv.padding = 1;
+        assert_eq!(conv.output_dimensions(), (1, 2, 2));
+        //windows start at -1 and 1, everything outside of the input is 0.
+        assert_eq!(conv.forward(&input).data, vec![-0.5, -2.5, -6.5, -3.5]);
+    }
+
+    #[test]
+    fn gradients_match_finite_differences() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut conv = Conv2d::random((2, 4, 5), 3, 3, 2, 1, Initialization::StandardNormal, &mut rng);
+        let input = ColumnVector::from_vec((0..40).map(|x| (x as f32 * 0.37).sin()).collect());
+        assert_eq!(conv.forward(&input).data.len(), 3 * 2 * 3);
+        let check = check_layer_gradients(&mut conv, &input);
+        assert!(check.max_relative_error < 1e-2, "{:?}", check);
+    }
+}
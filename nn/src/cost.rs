@@ -0,0 +1,298 @@
+use std::fmt::Debug;
+use std::iter::zip;
+use std::sync::Arc;
+use matrix::{ColumnVector, Scalar};
+use serde::{Deserialize, Serialize};
+use crate::{constant, cost_deriv, cross_entropy, squared_error, ActivationFunction, CROSS_ENTROPY_EPSILON};
+
+//the cost of a single sample. gradient is taken with respect to the output activations. T is
+//the precision it computes in, like for Activation.
+pub trait Loss<T: Scalar = f32>: Debug + Send + Sync {
+    fn value(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> T;
+    fn gradient(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> ColumnVector<T>;
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SquaredError;
+
+//expects the outputs to be probabilities, usually from a softmax output layer.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct CrossEntropy;
+
+impl<T: Scalar> Loss<T> for SquaredError {
+    fn value(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> T {
+        squared_error(output_activations, desired_output)
+    }
+
+    fn gradient(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> ColumnVector<T> {
+        cost_deriv(output_activations, desired_output)
+    }
+}
+
+impl<T: Scalar> Loss<T> for CrossEntropy {
+    fn value(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> T {
+        cross_entropy(output_activations, desired_output)
+    }
+
+    fn gradient(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> ColumnVector<T> {
+        ColumnVector::from_vec(zip(&output_activations.data, &desired_output.data)
+            .map(|(&output, &desired)| -desired / output.max(constant(CROSS_ENTROPY_EPSILON)))
+            .collect())
+    }
+}
+
+//averaged over the output elements. the gradient at a perfect match is taken to be 0.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct MeanAbsoluteError;
+
+//quadratic for errors up to delta and linear beyond, averaged over the output elements.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Huber {
+    pub delta: f32,
+}
+
+impl<T: Scalar> Loss<T> for MeanAbsoluteError {
+    fn value(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> T {
+        zip(&output_activations.data, &desired_output.data)
+            .map(|(&output, &desired)| (output - desired).abs())
+            .sum::<T>() / T::from_usize(output_activations.data.len()).unwrap()
+    }
+
+    fn gradient(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> ColumnVector<T> {
+        let size = T::from_usize(output_activations.data.len()).unwrap();
+        ColumnVector::from_vec(zip(&output_activations.data, &desired_output.data)
+            .map(|(&output, &desired)| {
+                let error = output - desired;
+                if error == T::zero() { T::zero() } else { error.signum() / size }
+            })
+            .collect())
+    }
+}
+
+impl<T: Scalar> Loss<T> for Huber {
+    fn value(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> T {
+        let (delta, half) = (T::from_f32(self.delta).unwrap(), constant::<T>(0.5));
+        zip(&output_activations.data, &desired_output.data)
+            .map(|(&output, &desired)| {
+                let error = (output - desired).abs();
+                if error <= delta {
+                    half * error * error
+                } else {
+                    delta * (error - half * delta)
+                }
+            })
+            .sum::<T>() / T::from_usize(output_activations.data.len()).unwrap()
+    }
+
+    fn gradient(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> ColumnVector<T> {
+        let (delta, size) = (T::from_f32(self.delta).unwrap(), T::from_usize(output_activations.data.len()).unwrap());
+        ColumnVector::from_vec(zip(&output_activations.data, &desired_output.data)
+            .map(|(&output, &desired)| (output - desired).clamp(-delta, delta) / size)
+            .collect())
+    }
+}
+
+//running total of a loss over the samples seen so far, so the mean loss of a data set
+//can be computed batch by batch without keeping every output around.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct LossAccumulator {
+    pub total: f32,
+    pub sample_amount: usize,
+}
+
+impl LossAccumulator {
+    pub fn new() -> LossAccumulator {
+        LossAccumulator::default()
+    }
+
+    pub fn add(&mut self, loss: &dyn Loss, output_activations: &ColumnVector, desired_output: &ColumnVector) {
+        self.add_value(loss.value(output_activations, desired_output));
+    }
+
+    pub fn add_value(&mut self, value: f32) {
+        self.total += value;
+        self.sample_amount += 1;
+    }
+
+    //combines the totals of two accumulators, e.g. ones filled by different batches.
+    pub fn merge(&mut self, other: &LossAccumulator) {
+        self.total += other.total;
+        self.sample_amount += other.sample_amount;
+    }
+
+    //0 when no samples were added.
+    pub fn mean(&self) -> f32 {
+        if self.sample_amount == 0 {
+            0.0
+        } else {
+            self.total / self.sample_amount as f32
+        }
+    }
+}
+
+//the cost minimized by backpropagation and reported by the trainer. Custom holds any other loss
+//and fails to serialize.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Cost {
+    SquaredError,
+    CrossEntropy,
+    MeanAbsoluteError,
+    Huber(f32),
+    #[serde(skip)]
+    Custom(Arc<dyn Loss>),
+}
+
+//custom losses only compute in single precision, other precisions go through casts.
+impl<T: Scalar> Loss<T> for Cost {
+    fn value(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> T {
+        match self {
+            Cost::SquaredError => SquaredError.value(output_activations, desired_output),
+            Cost::CrossEntropy => CrossEntropy.value(output_activations, desired_output),
+            Cost::MeanAbsoluteError => MeanAbsoluteError.value(output_activations, desired_output),
+            Cost::Huber(delta) => Huber { delta: *delta }.value(output_activations, desired_output),
+            Cost::Custom(loss) => T::from_f32(loss.value(&output_activations.cast(), &desired_output.cast())).unwrap(),
+        }
+    }
+
+    fn gradient(&self, output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> ColumnVector<T> {
+        match self {
+            Cost::SquaredError => SquaredError.gradient(output_activations, desired_output),
+            Cost::CrossEntropy => CrossEntropy.gradient(output_activations, desired_output),
+            Cost::MeanAbsoluteError => MeanAbsoluteError.gradient(output_activations, desired_output),
+            Cost::Huber(delta) => Huber { delta: *delta }.gradient(output_activations, desired_output),
+            Cost::Custom(loss) => loss.gradient(&output_activations.cast(), &desired_output.cast()).cast(),
+        }
+    }
+}
+
+//custom losses are only equal when they are the same instance.
+impl PartialEq for Cost {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Cost::Custom(a), Cost::Custom(b)) => Arc::ptr_eq(a, b),
+            (Cost::Huber(a), Cost::Huber(b)) => a == b,
+            (Cost::Custom(_), _) | (_, Cost::Custom(_)) => false,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Cost {
+    //whether the gradient with respect to the z values of the output layer is simply
+    //output - desired. true for cross entropy after softmax, which is both cheaper and
+    //more stable than chaining the two derivatives.
+    pub fn is_fused_with(&self, activation_function: &ActivationFunction) -> bool {
+        matches!((self, activation_function), (Cost::CrossEntropy, ActivationFunction::Softmax))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::iter::zip;
+    use matrix::{ColumnVector, Matrix};
+    use crate::{check_gradients, cross_entropy, smooth_labels, squared_error, ActivationFunction, Cost, Loss, LossAccumulator, NeuralNetwork};
+
+    fn check_against_finite_differences(network: NeuralNetwork) {
+        check_against_finite_differences_with_target(network, ColumnVector::from_vec(vec![0.0, 1.0, 0.0]));
+    }
+
+    fn check_against_finite_differences_with_target(mut network: NeuralNetwork, desired: ColumnVector) {
+        let input = ColumnVector::from_vec(vec![1.0, 0.5, 0.25]);
+        let check = check_gradients(&mut network, &input, &desired);
+        assert!(check.max_absolute_error < 2e-3, "{:?}", check);
+    }
+
+    fn test_network(output_activation: ActivationFunction) -> NeuralNetwork {
+        let weights = vec![
+            Matrix::from_vec(vec![vec![0.5, -0.2, 0.1], vec![0.3, 0.8, -0.4]]),
+            Matrix::from_vec(vec![vec![0.7, -0.6], vec![0.2, 0.9], vec![-0.5, 0.4]]),
+        ];
+        let mut network = NeuralNetwork::new_from_vecs(weights, None, None, None);
+        network.activation_functions = vec![ActivationFunction::Tanh, output_activation];
+        network.cost = Cost::CrossEntropy;
+        network
+    }
+
+    #[test]
+    fn cross_entropy_value() {
+        let output = ColumnVector::from_vec(vec![0.25, 0.5, 0.25]);
+        let desired = ColumnVector::from_vec(vec![0.0, 1.0, 0.0]);
+        assert!((cross_entropy(&output, &desired) - 2.0_f32.ln()).abs() < 1e-6);
+        //a zero probability for the right class is large but finite.
+        assert!(cross_entropy(&ColumnVector::from_vec(vec![1.0, 0.0, 0.0]), &desired).is_finite());
+    }
+
+    #[test]
+    fn fused_softmax_cross_entropy_gradient() {
+        let mut network = test_network(ActivationFunction::Softmax);
+        let input = ColumnVector::from_vec(vec![1.0, 0.5, 0.25]);
+        let desired = ColumnVector::from_vec(vec![0.0, 1.0, 0.0]);
+        let gradients = network.backpropagation(&input, &desired);
+        assert_eq!(gradients.biases[1], network.activation_values.back().unwrap() - &desired);
+        check_against_finite_differences(network);
+    }
+
+    #[test]
+    fn cross_entropy_after_sigmoid_against_finite_differences() {
+        check_against_finite_differences(test_network(ActivationFunction::Sigmoid));
+    }
+
+    #[test]
+    fn mean_absolute_error_and_huber() {
+        let output = ColumnVector::from_vec(vec![0.5, 3.0, -1.0, 2.0]);
+        let desired = ColumnVector::from_vec(vec![0.0, 1.0, 0.0, 2.0]);
+        assert_eq!(Cost::MeanAbsoluteError.value(&output, &desired), 3.5 / 4.0);
+        assert_eq!(Cost::MeanAbsoluteError.gradient(&output, &desired).data, vec![0.25, 0.25, -0.25, 0.0]);
+        //errors of 0.5 and 1 are quadratic, the error of 2 is linear.
+        assert_eq!(Cost::Huber(1.0).value(&output, &desired), (0.125 + 1.5 + 0.5) / 4.0);
+        assert_eq!(Cost::Huber(1.0).gradient(&output, &desired).data, vec![0.125, 0.25, -0.25, 0.0]);
+        assert_ne!(Cost::Huber(1.0), Cost::Huber(2.0));
+
+        for cost in [Cost::MeanAbsoluteError, Cost::Huber(0.3)] {
+            let mut network = test_network(ActivationFunction::Sigmoid);
+            network.cost = cost;
+            check_against_finite_differences(network);
+        }
+    }
+
+    #[test]
+    fn label_smoothing() {
+        let smoothed = smooth_labels(&ColumnVector::from_vec(vec![0.0, 1.0, 0.0, 0.0]), 0.2);
+        assert_eq!(smoothed.data, vec![0.05, 0.85, 0.05, 0.05]);
+        assert!((smoothed.data.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+
+        //the fused softmax gradient stays exact for soft targets.
+        let desired = smooth_labels(&ColumnVector::from_vec(vec![0.0, 1.0, 0.0]), 0.1);
+        check_against_finite_differences_with_target(test_network(ActivationFunction::Softmax), desired);
+    }
+
+    #[test]
+    fn streaming_loss_matches_materialized_loss() {
+        let mut network = test_network(ActivationFunction::Sigmoid);
+        network.cost = Cost::SquaredError;
+        let inputs: Vec<ColumnVector> = (0..5).map(|x| ColumnVector::from_vec(vec![x as f32 * 0.3, 1.0, -0.5])).collect();
+        let desired: Vec<ColumnVector> = (0..5).map(|x| ColumnVector::from_vec(vec![0.0, (x % 2) as f32, 1.0])).collect();
+        let expected = zip(&inputs, &desired).map(|(input, desired)| {
+            network.calculate_all_activation_values(input);
+            squared_error(network.activation_values.back().unwrap(), desired)
+        }).sum::<f32>() / 5.0;
+
+        //two batches accumulated separately and merged.
+        let mut accumulator = network.accumulate_loss(zip(&inputs[..2], &desired[..2]));
+        accumulator.merge(&network.accumulate_loss(zip(&inputs[2..], &desired[2..])));
+        assert_eq!(accumulator.sample_amount, 5);
+        assert!((accumulator.mean() - expected).abs() < 1e-6);
+        assert!((network.mean_loss(zip(&inputs, &desired)) - expected).abs() < 1e-6);
+        assert!((network.calculate_mean_square_error(&inputs, &desired) - expected).abs() < 1e-6);
+        assert_eq!(LossAccumulator::new().mean(), 0.0);
+    }
+
+    #[test]
+    fn classifier_defaults() {
+        let network = NeuralNetwork::new_classifier(&[4, 3, 2], None);
+        assert_eq!(network.cost, Cost::CrossEntropy);
+        assert_eq!(network.activation_functions, vec![ActivationFunction::Relu, ActivationFunction::Softmax]);
+        assert_eq!(NeuralNetwork::new_with_activations(&[4, 2], vec![ActivationFunction::Sigmoid], None).cost, Cost::SquaredError);
+    }
+}
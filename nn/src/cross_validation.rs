@@ -0,0 +1,84 @@
+use mnist_reader::{k_fold, one_hot, DataLoader, Dataset};
+use crate::{LossAccumulator, Mode, NeuralNetwork, Optimizer, Trainer};
+
+//validation losses of the models trained by cross_validate, one per fold.
+#[derive(PartialEq, Debug)]
+pub struct CrossValidation {
+    pub fold_losses: Vec<f32>,
+}
+
+impl CrossValidation {
+    pub fn mean(&self) -> f32 {
+        self.fold_losses.iter().sum::<f32>() / self.fold_losses.len() as f32
+    }
+
+    pub fn standard_deviation(&self) -> f32 {
+        let mean = self.mean();
+        (self.fold_losses.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / self.fold_losses.len() as f32).sqrt()
+    }
+}
+
+//mean cost of the network over a labeled dataset, evaluated in inference mode.
+pub fn dataset_loss<D: Dataset + ?Sized>(network: &mut NeuralNetwork, dataset: &D) -> f32 {
+    let previous_mode = network.mode;
+    network.mode = Mode::Inference;
+    let output_size = network.biases.last().unwrap().data.len();
+    let mut accumulator = LossAccumulator::new();
+    for index in 0..dataset.len() {
+        let (input, label) = dataset.get(index);
+        network.calculate_all_activation_values(&input);
+        accumulator.add(&network.cost, network.activation_values.back().unwrap(), &one_hot(label, output_size));
+    }
+    network.mode = previous_mode;
+    accumulator.mean()
+}
+
+//k fold cross validation: for every fold build returns a fresh network and trainer, which is
+//trained on the other folds and scored by its loss on the held out one.
+pub fn cross_validate<D, O, F>(dataset: &D, k: usize, seed: u64, mut build: F) -> CrossValidation
+where
+    D: Dataset + ?Sized,
+    O: Optimizer,
+    F: FnMut() -> (NeuralNetwork, Trainer<O>),
+{
+    let fold_losses = k_fold(dataset, k, seed).iter().enumerate().map(|(fold, (training, validation))| {
+        let (mut network, mut trainer) = build();
+        let loader = DataLoader::new_shuffled_with_seed(training, trainer.mini_batch_size, seed.wrapping_add(fold as u64));
+        trainer.train_with_loader(&mut network, &loader);
+        dataset_loss(&mut network, validation)
+    }).collect();
+    CrossValidation { fold_losses }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::ColumnVector;
+    use crate::{cross_validate, ActivationFunction, CrossValidation, NeuralNetwork, Trainer};
+
+    #[test]
+    fn cross_validation_trains_a_model_per_fold() {
+        let dataset: Vec<(ColumnVector, usize)> = (0..12)
+            .map(|x| (ColumnVector::from_vec(vec![(x % 2) as f32, 1.0 - (x % 2) as f32]), x % 2))
+            .collect();
+        let mut built = 0;
+        let build = |epochs: usize| {
+            move || {
+                let network = NeuralNetwork::new_with_seed(&[2, 3, 2], vec![ActivationFunction::Tanh, ActivationFunction::Sigmoid], 1);
+                (network, Trainer::new(2, 0.5, epochs))
+            }
+        };
+        let untrained = cross_validate(&dataset, 3, 2, || {
+            built += 1;
+            build(0)()
+        });
+        assert_eq!(built, 3);
+        let trained = cross_validate(&dataset, 3, 2, build(30));
+        assert_eq!(trained.fold_losses.len(), 3);
+        assert!(trained.mean() < untrained.mean());
+
+        let summary = CrossValidation { fold_losses: vec![1.0, 2.0, 3.0] };
+        assert_eq!(summary.mean(), 2.0);
+        assert!((summary.standard_deviation() - (2.0_f32 / 3.0).sqrt()).abs() < 1e-6);
+    }
+}
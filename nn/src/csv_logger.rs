@@ -0,0 +1,114 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use crate::{Callback, Control, EpochEnd};
+
+//appends a row per epoch to a csv file: the epoch, training loss, validation loss and accuracy,
+//the learning rate and the values of the trainer's metrics. a header is written when the
+//file is new or empty, so several runs can be logged one after the other.
+//validation columns stay empty when training without validation data.
+//logging is best effort like ProgressBar: a failed write is reported once on stderr and
+//training goes on.
+pub struct CsvLogger {
+    pub path: PathBuf,
+    reported_error: bool,
+}
+
+impl CsvLogger {
+    pub fn new<P: Into<PathBuf>>(path: P) -> CsvLogger {
+        CsvLogger { path: path.into(), reported_error: false }
+    }
+
+    fn header(epoch: &EpochEnd) -> String {
+        let mut columns = vec!["epoch".to_string(), "training_loss".to_string(), "validation_loss".to_string(),
+                               "validation_accuracy".to_string(), "learning_rate".to_string()];
+        if let Some(validation) = &epoch.validation {
+            columns.extend(validation.metrics.iter().map(|(name, _)| name.replace([',', '"', '\n'], " ")));
+        }
+        columns.join(",")
+    }
+
+    fn row(epoch: &EpochEnd) -> String {
+        let mut fields = vec![(epoch.epoch + 1).to_string(), epoch.training_loss.to_string()];
+        match &epoch.validation {
+            Some(validation) => {
+                fields.push(validation.loss.to_string());
+                fields.push(validation.accuracy.to_string());
+            }
+            None => fields.extend([String::new(), String::new()]),
+        }
+        fields.push(epoch.learning_rate.to_string());
+        if let Some(validation) = &epoch.validation {
+            fields.extend(validation.metrics.iter().map(|(_, value)| value.to_string()));
+        }
+        fields.join(",")
+    }
+}
+
+impl<N> Callback<N> for CsvLogger {
+    fn on_epoch_end(&mut self, _network: &mut N, epoch: &EpochEnd) -> Control {
+        let is_empty = fs::metadata(&self.path).map_or(true, |metadata| metadata.len() == 0);
+        let mut contents = String::new();
+        if is_empty {
+            contents += &(CsvLogger::header(epoch) + "\n");
+        }
+        contents += &(CsvLogger::row(epoch) + "\n");
+        let written = OpenOptions::new().create(true).append(true).open(&self.path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()));
+        if let Err(error) = written {
+            if !self.reported_error {
+                eprintln!("could not write to {}: {}.", self.path.display(), error);
+                self.reported_error = true;
+            }
+        }
+        Control::Continue
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use matrix::ColumnVector;
+    use mnist_reader::DataLoader;
+    use crate::{ActivationFunction, Callback, Control, CsvLogger, EpochEnd, NeuralNetwork, TopKAccuracy, Trainer};
+
+    #[test]
+    fn csv_rows_per_epoch() {
+        let path = std::env::temp_dir().join(format!("nn_csv_logger_{}.csv", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let dataset: Vec<(ColumnVector, usize)> = (0..4)
+            .map(|x| (ColumnVector::from_vec(vec![(x % 2) as f32, 1.0 - (x % 2) as f32]), x % 2))
+            .collect();
+        let mut network = NeuralNetwork::new_with_seed(&[2, 3, 2], vec![ActivationFunction::Tanh, ActivationFunction::Sigmoid], 4);
+        let mut trainer = Trainer::new(2, 0.5, 3);
+        trainer.metrics.push(Box::new(TopKAccuracy::new(2)));
+        trainer.callbacks.push(Box::new(CsvLogger::new(&path)));
+        let evaluations = trainer.train_with_validation(&mut network, &DataLoader::new(&dataset, 2), &dataset);
+        trainer.epochs = 1;
+        let mut data = vec![(dataset[0].0.clone(), mnist_reader::one_hot(0, 2))];
+        trainer.train(&mut network, &mut data);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "epoch,training_loss,validation_loss,validation_accuracy,learning_rate,top 2 accuracy");
+        let fields: Vec<&str> = lines[3].split(',').collect();
+        assert_eq!(fields[0], "3");
+        assert_eq!(fields[2].parse::<f32>().unwrap(), evaluations[2].loss);
+        assert_eq!((fields[4], fields[5]), ("0.5", "1"));
+        assert!(lines[4].starts_with("1,") && lines[4].ends_with(",,,0.5"));
+    }
+
+    #[test]
+    fn write_errors_do_not_stop_training() {
+        //a directory can not be opened for appending.
+        let mut logger = CsvLogger::new(std::env::temp_dir());
+        let mut network = NeuralNetwork::new_with_activations(&[2, 2], vec![ActivationFunction::Relu], Some(0.5));
+        let epoch = EpochEnd { epoch: 0, training_loss: 0.5, validation: None, learning_rate: 0.1 };
+        assert_eq!(logger.on_epoch_end(&mut network, &epoch), Control::Continue);
+        assert_eq!(logger.on_epoch_end(&mut network, &epoch), Control::Continue);
+        assert!(logger.reported_error);
+    }
+}
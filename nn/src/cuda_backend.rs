@@ -0,0 +1,281 @@
+use std::sync::Arc;
+use cudarc::cublas::{sys::cublasOperation_t, CudaBlas, Gemm, GemmConfig};
+use cudarc::driver::{CudaContext, CudaFunction, CudaSlice, CudaStream, LaunchConfig, PushKernelArg};
+use crate::device::activation_kind;
+use crate::{ActivationFunction, Backend, DeviceBuffer};
+
+//compiled with nvrtc when the backend is created. the element wise kernels run one thread per
+//element, softmax one per column and sum_rows one per row. kind selects the activation, see
+//activation_kind in device.rs. the kernels that read a second matrix take it after the one
+//they write.
+const KERNELS: &str = "
+__device__ float sigmoid(float x) {
+    return 1.0f / (1.0f + expf(-x));
+}
+
+extern \"C\" __global__ void add_bias(float* matrix, const float* bias, unsigned int m, unsigned int n) {
+    unsigned int index = blockIdx.x * blockDim.x + threadIdx.x;
+    if (index < m * n) {
+        matrix[index] += bias[index / n];
+    }
+}
+
+extern \"C\" __global__ void activate(float* matrix, unsigned int elements, unsigned int kind, float parameter) {
+    unsigned int index = blockIdx.x * blockDim.x + threadIdx.x;
+    if (index >= elements) {
+        return;
+    }
+    float x = matrix[index];
+    switch (kind) {
+        case 0: matrix[index] = fmaxf(x, 0.0f); break;
+        case 1: matrix[index] = sigmoid(x); break;
+        case 3: matrix[index] = x < 0.0f ? parameter * x : x; break;
+        case 4: matrix[index] = x < 0.0f ? parameter * (expf(x) - 1.0f) : x; break;
+        case 5: matrix[index] = 0.5f * x * (1.0f + tanhf(0.7978846f * (x + 0.044715f * x * x * x))); break;
+        case 6: matrix[index] = tanhf(x); break;
+        case 7: matrix[index] = x * sigmoid(x); break;
+        default: break;
+    }
+}
+
+extern \"C\" __global__ void activation_backward(float* gradient, const float* z_values, unsigned int elements, unsigned int kind, float parameter) {
+    unsigned int index = blockIdx.x * blockDim.x + threadIdx.x;
+    if (index >= elements) {
+        return;
+    }
+    float x = z_values[index];
+    float derivative = 1.0f;
+    switch (kind) {
+        case 0: derivative = x < 0.0f ? 0.0f : 1.0f; break;
+        case 1: derivative = sigmoid(x) * (1.0f - sigmoid(x)); break;
+        case 3: derivative = x < 0.0f ? parameter : 1.0f; break;
+        case 4: derivative = x < 0.0f ? parameter * expf(x) : 1.0f; break;
+        case 5: {
+            float t = tanhf(0.7978846f * (x + 0.044715f * x * x * x));
+            derivative = 0.5f * (1.0f + t) + 0.5f * x * (1.0f - t * t) * 0.7978846f * (1.0f + 3.0f * 0.044715f * x * x);
+            break;
+        }
+        case 6: derivative = 1.0f - tanhf(x) * tanhf(x); break;
+        case 7: derivative = sigmoid(x) + x * sigmoid(x) * (1.0f - sigmoid(x)); break;
+        default: break;
+    }
+    gradient[index] *= derivative;
+}
+
+extern \"C\" __global__ void softmax_backward(float* gradient, const float* z_values, unsigned int m, unsigned int n) {
+    unsigned int column = blockIdx.x * blockDim.x + threadIdx.x;
+    if (column >= n) {
+        return;
+    }
+    float max_value = z_values[column];
+    for (unsigned int row = 1; row < m; row++) {
+        max_value = fmaxf(max_value, z_values[row * n + column]);
+    }
+    float sum = 0.0f;
+    for (unsigned int row = 0; row < m; row++) {
+        sum += expf(z_values[row * n + column] - max_value);
+    }
+    float projection = 0.0f;
+    for (unsigned int row = 0; row < m; row++) {
+        projection += gradient[row * n + column] * expf(z_values[row * n + column] - max_value) / sum;
+    }
+    for (unsigned int row = 0; row < m; row++) {
+        unsigned int index = row * n + column;
+        gradient[index] = expf(z_values[index] - max_value) / sum * (gradient[index] - projection);
+    }
+}
+
+extern \"C\" __global__ void sum_rows(float* result, const float* matrix, unsigned int m, unsigned int n) {
+    unsigned int row = blockIdx.x * blockDim.x + threadIdx.x;
+    if (row >= m) {
+        return;
+    }
+    float sum = 0.0f;
+    for (unsigned int column = 0; column < n; column++) {
+        sum += matrix[row * n + column];
+    }
+    result[row] = sum;
+}
+
+extern \"C\" __global__ void add_scaled(float* destination, const float* source, unsigned int elements, float factor) {
+    unsigned int index = blockIdx.x * blockDim.x + threadIdx.x;
+    if (index < elements) {
+        destination[index] += factor * source[index];
+    }
+}
+
+extern \"C\" __global__ void softmax(float* matrix, unsigned int m, unsigned int n) {
+    unsigned int column = blockIdx.x * blockDim.x + threadIdx.x;
+    if (column >= n) {
+        return;
+    }
+    float max_value = matrix[column];
+    for (unsigned int row = 1; row < m; row++) {
+        max_value = fmaxf(max_value, matrix[row * n + column]);
+    }
+    float sum = 0.0f;
+    for (unsigned int row = 0; row < m; row++) {
+        float exponential = expf(matrix[row * n + column] - max_value);
+        matrix[row * n + column] = exponential;
+        sum += exponential;
+    }
+    for (unsigned int row = 0; row < m; row++) {
+        matrix[row * n + column] /= sum;
+    }
+}
+";
+
+//runs inference and training on an nvidia gpu, products through cublas and everything else
+//through the kernels above. all work goes onto one stream, download waits for it.
+pub struct CudaBackend {
+    stream: Arc<CudaStream>,
+    blas: CudaBlas,
+    device_name: String,
+    add_bias: CudaFunction,
+    activate: CudaFunction,
+    softmax: CudaFunction,
+    activation_backward: CudaFunction,
+    softmax_backward: CudaFunction,
+    sum_rows: CudaFunction,
+    add_scaled: CudaFunction,
+}
+
+impl CudaBackend {
+    //ordinal counts the nvidia gpus of the machine from 0.
+    pub fn new(ordinal: usize) -> Result<CudaBackend, String> {
+        //cudarc panics on the first call into a library it can not find.
+        //safe, this only looks for the libraries.
+        let present = unsafe {
+            cudarc::driver::sys::is_culib_present() && cudarc::cublas::sys::is_culib_present() && cudarc::nvrtc::sys::is_culib_present()
+        };
+        if !present {
+            return Err("the cuda driver, cublas or nvrtc is not installed.".to_string());
+        }
+        let context = CudaContext::new(ordinal).map_err(|error| format!("no cuda device {}: {}", ordinal, error))?;
+        let stream = context.default_stream();
+        let blas = CudaBlas::new(stream.clone()).map_err(|error| format!("cublas could not be loaded: {}", error))?;
+        let ptx = cudarc::nvrtc::compile_ptx(KERNELS).map_err(|error| format!("the cuda kernels failed to compile: {}", error))?;
+        let module = context.load_module(ptx).map_err(|error| format!("the cuda kernels could not be loaded: {}", error))?;
+        let function = |name: &str| module.load_function(name).map_err(|error| format!("the cuda kernel {} is missing: {}", name, error));
+        Ok(CudaBackend {
+            device_name: context.name().unwrap_or_default(),
+            add_bias: function("add_bias")?,
+            activate: function("activate")?,
+            softmax: function("softmax")?,
+            activation_backward: function("activation_backward")?,
+            softmax_backward: function("softmax_backward")?,
+            sum_rows: function("sum_rows")?,
+            add_scaled: function("add_scaled")?,
+            stream,
+            blas,
+        })
+    }
+
+    //operand is the matrix a kernel reads next to the one it writes, like the bias of add_bias.
+    fn launch(&self, function: &CudaFunction, matrix: &mut DeviceBuffer, arguments: &[u32], parameter: Option<f32>, threads: usize, operand: Option<&DeviceBuffer>) {
+        let mut builder = self.stream.launch_builder(function);
+        builder.arg(matrix.inner_mut::<CudaSlice<f32>>());
+        if let Some(operand) = operand {
+            builder.arg(operand.inner::<CudaSlice<f32>>());
+        }
+        for argument in arguments {
+            builder.arg(argument);
+        }
+        if let Some(parameter) = &parameter {
+            builder.arg(parameter);
+        }
+        //safe, the arguments match the signature of the kernel and every kernel checks its
+        //index against the size of the matrix.
+        unsafe { builder.launch(LaunchConfig::for_num_elems(threads as u32)) }.expect("the cuda kernel failed.");
+    }
+}
+
+impl CudaBackend {
+    //cublas is column major and a row major matrix is its transpose in column major, so
+    //lhs * rhs is computed as rhs^T * lhs^T, which is the row major result. kind is 1 to
+    //transpose lhs and 2 to transpose rhs, like in MATMUL_SHADER. a transposed operand is read
+    //with the transposing operation of cublas and its leading dimension swaps with it.
+    fn product(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize, kind: u32) -> DeviceBuffer {
+        let (transpose_lhs, transpose_rhs) = (kind == 1, kind == 2);
+        let operation = |transpose: bool| if transpose { cublasOperation_t::CUBLAS_OP_T } else { cublasOperation_t::CUBLAS_OP_N };
+        let mut result = self.stream.alloc_zeros::<f32>(m * n).expect("the gpu is out of memory.");
+        let config = GemmConfig {
+            transa: operation(transpose_rhs),
+            transb: operation(transpose_lhs),
+            m: n as i32,
+            n: m as i32,
+            k: k as i32,
+            alpha: 1.0,
+            lda: if transpose_rhs { k } else { n } as i32,
+            ldb: if transpose_lhs { m } else { k } as i32,
+            beta: 0.0,
+            ldc: n as i32,
+        };
+        //safe, the sizes in config match the buffers.
+        unsafe { self.blas.gemm(config, rhs.inner::<CudaSlice<f32>>(), lhs.inner::<CudaSlice<f32>>(), &mut result) }.expect("the cublas product failed.");
+        DeviceBuffer::new(m * n, result)
+    }
+}
+
+impl Backend for CudaBackend {
+    fn name(&self) -> String {
+        format!("cuda ({})", self.device_name)
+    }
+
+    fn upload(&self, data: &[f32]) -> DeviceBuffer {
+        DeviceBuffer::new(data.len(), self.stream.memcpy_stod(data).expect("the upload to the gpu failed."))
+    }
+
+    fn download(&self, buffer: &DeviceBuffer) -> Vec<f32> {
+        self.stream.memcpy_dtov(buffer.inner::<CudaSlice<f32>>()).expect("the download from the gpu failed.")
+    }
+
+    fn copy(&self, buffer: &DeviceBuffer) -> DeviceBuffer {
+        DeviceBuffer::new(buffer.len, self.stream.clone_dtod(buffer.inner::<CudaSlice<f32>>()).expect("the copy on the gpu failed."))
+    }
+
+    fn matmul(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize) -> DeviceBuffer {
+        self.product(lhs, rhs, m, k, n, 0)
+    }
+
+    fn matmul_transposed_lhs(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize) -> DeviceBuffer {
+        self.product(lhs, rhs, m, k, n, 1)
+    }
+
+    fn matmul_transposed_rhs(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize) -> DeviceBuffer {
+        self.product(lhs, rhs, m, k, n, 2)
+    }
+
+    fn add_bias(&self, matrix: &mut DeviceBuffer, bias: &DeviceBuffer, m: usize, n: usize) {
+        self.launch(&self.add_bias, matrix, &[m as u32, n as u32], None, m * n, Some(bias));
+    }
+
+    fn sum_rows(&self, matrix: &DeviceBuffer, m: usize, n: usize) -> DeviceBuffer {
+        let mut result = DeviceBuffer::new(m, self.stream.alloc_zeros::<f32>(m).expect("the gpu is out of memory."));
+        self.launch(&self.sum_rows, &mut result, &[m as u32, n as u32], None, m, Some(matrix));
+        result
+    }
+
+    fn add_scaled(&self, target: &mut DeviceBuffer, factor: f32, source: &DeviceBuffer) {
+        let elements = target.len;
+        self.launch(&self.add_scaled, target, &[elements as u32], Some(factor), elements, Some(source));
+    }
+
+    fn activate(&self, activation_function: &ActivationFunction, matrix: &mut DeviceBuffer, m: usize, n: usize) {
+        match activation_kind(activation_function) {
+            Some((kind, parameter)) => self.launch(&self.activate, matrix, &[(m * n) as u32, kind], Some(parameter), m * n, None),
+            None => self.launch(&self.softmax, matrix, &[m as u32, n as u32], None, n, None),
+        }
+    }
+
+    fn activation_backward(&self, activation_function: &ActivationFunction, z_values: &DeviceBuffer, gradient: &mut DeviceBuffer, m: usize, n: usize) {
+        match activation_kind(activation_function) {
+            Some((kind, parameter)) => self.launch(&self.activation_backward, gradient, &[(m * n) as u32, kind], Some(parameter), m * n, Some(z_values)),
+            None => self.launch(&self.softmax_backward, gradient, &[m as u32, n as u32], None, n, Some(z_values)),
+        }
+    }
+
+    fn supports(&self, activation_function: &ActivationFunction) -> bool {
+        !matches!(activation_function, ActivationFunction::Custom(_))
+    }
+}
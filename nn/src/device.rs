@@ -0,0 +1,438 @@
+use std::any::Any;
+use std::iter::zip;
+use matrix::{ColumnVector, Matrix};
+use mnist_reader::Preprocessing;
+use crate::{Activation, ActivationFunction, Cost, Loss, NeuralNetwork};
+
+//where a DeviceNetwork runs. the cpu is always available, the other devices need the
+//feature of their backend.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Device {
+    Cpu,
+    #[cfg(feature = "wgpu")]
+    Gpu,
+    //the first nvidia gpu.
+    #[cfg(feature = "cuda")]
+    Cuda,
+}
+
+impl Device {
+    //fails when the device is missing, e.g. a machine without a gpu.
+    pub fn backend(self) -> Result<Box<dyn Backend>, String> {
+        match self {
+            Device::Cpu => Ok(Box::new(CpuBackend)),
+            #[cfg(feature = "wgpu")]
+            Device::Gpu => Ok(Box::new(crate::WgpuBackend::new()?)),
+            #[cfg(feature = "cuda")]
+            Device::Cuda => Ok(Box::new(crate::CudaBackend::new(0)?)),
+        }
+    }
+}
+
+//memory owned by a backend, f32 elements in whatever form the backend keeps them.
+pub struct DeviceBuffer {
+    pub len: usize,
+    inner: Box<dyn Any + Send + Sync>,
+}
+
+impl DeviceBuffer {
+    pub fn new<B: Any + Send + Sync>(len: usize, inner: B) -> DeviceBuffer {
+        DeviceBuffer { len, inner: Box::new(inner) }
+    }
+
+    //the memory of the backend that created the buffer, panics for any other.
+    pub fn inner<B: Any>(&self) -> &B {
+        self.inner.downcast_ref().expect("the buffer belongs to another backend.")
+    }
+
+    pub fn inner_mut<B: Any>(&mut self) -> &mut B {
+        self.inner.downcast_mut().expect("the buffer belongs to another backend.")
+    }
+}
+
+//the operations inference and training need. matrices are row major, an m x n matrix holds row
+//after row of n elements. data only moves between the host and the device in upload and download.
+pub trait Backend: Send + Sync {
+    fn name(&self) -> String;
+    fn upload(&self, data: &[f32]) -> DeviceBuffer;
+    fn download(&self, buffer: &DeviceBuffer) -> Vec<f32>;
+    //a new buffer with the elements of buffer, without going through the host.
+    fn copy(&self, buffer: &DeviceBuffer) -> DeviceBuffer;
+    //lhs * rhs for an m x k lhs and a k x n rhs.
+    fn matmul(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize) -> DeviceBuffer;
+    //lhs^T * rhs for a k x m lhs and a k x n rhs, e.g. deltas propagated back through weights.
+    fn matmul_transposed_lhs(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize) -> DeviceBuffer;
+    //lhs * rhs^T for an m x k lhs and an n x k rhs, e.g. the weight gradients of a batch.
+    fn matmul_transposed_rhs(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize) -> DeviceBuffer;
+    //adds bias[row] to every element in that row of the m x n matrix.
+    fn add_bias(&self, matrix: &mut DeviceBuffer, bias: &DeviceBuffer, m: usize, n: usize);
+    //the sum of every row of the m x n matrix, e.g. the bias gradients of a batch.
+    fn sum_rows(&self, matrix: &DeviceBuffer, m: usize, n: usize) -> DeviceBuffer;
+    //target += factor * source element by element, e.g. a step of gradient descent.
+    fn add_scaled(&self, target: &mut DeviceBuffer, factor: f32, source: &DeviceBuffer);
+    //applies the activation function to every column of the m x n matrix.
+    fn activate(&self, activation_function: &ActivationFunction, matrix: &mut DeviceBuffer, m: usize, n: usize);
+    //turns the gradient with respect to the activations of every column of the m x n z values
+    //into the gradient with respect to the z values, like Activation::backward.
+    fn activation_backward(&self, activation_function: &ActivationFunction, z_values: &DeviceBuffer, gradient: &mut DeviceBuffer, m: usize, n: usize);
+    //custom activations only run on the cpu.
+    fn supports(&self, activation_function: &ActivationFunction) -> bool;
+}
+
+//the code the gpu kernels use for an element wise activation function, and its parameter.
+//softmax has a kernel of its own and custom activations can not leave the cpu.
+#[cfg_attr(not(any(feature = "wgpu", feature = "cuda")), allow(dead_code))]
+pub(crate) fn activation_kind(activation_function: &ActivationFunction) -> Option<(u32, f32)> {
+    Some(match activation_function {
+        ActivationFunction::Relu => (0, 0.0),
+        ActivationFunction::Sigmoid => (1, 0.0),
+        ActivationFunction::Identity => (2, 0.0),
+        ActivationFunction::LeakyRelu(slope) => (3, *slope),
+        ActivationFunction::Elu(alpha) => (4, *alpha),
+        ActivationFunction::Gelu => (5, 0.0),
+        ActivationFunction::Tanh => (6, 0.0),
+        ActivationFunction::Swish => (7, 0.0),
+        ActivationFunction::Softmax | ActivationFunction::Custom(_) => return None,
+    })
+}
+
+//runs everything through the matrix crate, the reference for the other backends.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct CpuBackend;
+
+fn to_matrix(data: &[f32], columns: usize) -> Matrix {
+    Matrix::from_row_major(data.len() / columns, columns, data.to_vec())
+}
+
+impl Backend for CpuBackend {
+    fn name(&self) -> String {
+        "cpu".to_string()
+    }
+
+    fn upload(&self, data: &[f32]) -> DeviceBuffer {
+        DeviceBuffer::new(data.len(), data.to_vec())
+    }
+
+    fn download(&self, buffer: &DeviceBuffer) -> Vec<f32> {
+        buffer.inner::<Vec<f32>>().clone()
+    }
+
+    fn copy(&self, buffer: &DeviceBuffer) -> DeviceBuffer {
+        DeviceBuffer::new(buffer.len, buffer.inner::<Vec<f32>>().clone())
+    }
+
+    fn matmul(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize) -> DeviceBuffer {
+        let product = &to_matrix(lhs.inner::<Vec<f32>>(), k) * &to_matrix(rhs.inner::<Vec<f32>>(), n);
+        DeviceBuffer::new(m * n, product.into_vec())
+    }
+
+    fn matmul_transposed_lhs(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, _k: usize, n: usize) -> DeviceBuffer {
+        let product = to_matrix(lhs.inner::<Vec<f32>>(), m).transposed_mul(&to_matrix(rhs.inner::<Vec<f32>>(), n));
+        DeviceBuffer::new(m * n, product.into_vec())
+    }
+
+    fn matmul_transposed_rhs(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize) -> DeviceBuffer {
+        let product = to_matrix(lhs.inner::<Vec<f32>>(), k).mul_transposed(&to_matrix(rhs.inner::<Vec<f32>>(), k));
+        DeviceBuffer::new(m * n, product.into_vec())
+    }
+
+    fn add_bias(&self, matrix: &mut DeviceBuffer, bias: &DeviceBuffer, _m: usize, n: usize) {
+        let bias = bias.inner::<Vec<f32>>();
+        for (row, bias_elem) in zip(matrix.inner_mut::<Vec<f32>>().chunks_mut(n), bias) {
+            row.iter_mut().for_each(|elem| *elem += bias_elem);
+        }
+    }
+
+    fn sum_rows(&self, matrix: &DeviceBuffer, m: usize, n: usize) -> DeviceBuffer {
+        DeviceBuffer::new(m, matrix.inner::<Vec<f32>>().chunks(n).map(|row| row.iter().sum()).collect::<Vec<f32>>())
+    }
+
+    fn add_scaled(&self, target: &mut DeviceBuffer, factor: f32, source: &DeviceBuffer) {
+        zip(target.inner_mut::<Vec<f32>>(), source.inner::<Vec<f32>>()).for_each(|(elem, source_elem)| *elem += factor * source_elem);
+    }
+
+    fn activate(&self, activation_function: &ActivationFunction, matrix: &mut DeviceBuffer, _m: usize, n: usize) {
+        let data = matrix.inner_mut::<Vec<f32>>();
+        let columns: Vec<ColumnVector> = to_matrix(data, n).columns().map(|column| activation_function.apply(&column)).collect();
+        *data = Matrix::from_columns(&columns).into_vec();
+    }
+
+    fn activation_backward(&self, activation_function: &ActivationFunction, z_values: &DeviceBuffer, gradient: &mut DeviceBuffer, _m: usize, n: usize) {
+        let z_values = to_matrix(z_values.inner::<Vec<f32>>(), n);
+        let data = gradient.inner_mut::<Vec<f32>>();
+        let columns: Vec<ColumnVector> = zip(z_values.columns(), to_matrix(data, n).columns())
+            .map(|(z, column)| activation_function.backward(&z, &column))
+            .collect();
+        *data = Matrix::from_columns(&columns).into_vec();
+    }
+
+    fn supports(&self, _activation_function: &ActivationFunction) -> bool {
+        true
+    }
+}
+
+//a network whose weights live on a device. batches are uploaded, run through every layer
+//there and only the output is downloaded again.
+pub struct DeviceNetwork {
+    backend: Box<dyn Backend>,
+    //the amount of inputs and then the size of every layer.
+    layer_sizes: Vec<usize>,
+    weights: Vec<DeviceBuffer>,
+    biases: Vec<DeviceBuffer>,
+    pub activation_functions: Vec<ActivationFunction>,
+    pub cost: Cost,
+    pub preprocessing: Option<Preprocessing>,
+    //one per layer, frozen layers are not changed by train_mini_batch.
+    pub frozen: Vec<bool>,
+}
+
+impl DeviceNetwork {
+    pub fn backend(&self) -> &dyn Backend {
+        self.backend.as_ref()
+    }
+
+    //the inputs, one per column, preprocessed and uploaded.
+    fn upload_inputs(&self, inputs: &Matrix) -> DeviceBuffer {
+        let input_data: Vec<f32> = inputs.as_slice().iter()
+            .map(|&elem| match &self.preprocessing {
+                Some(preprocessing) => preprocessing.apply_to_value(elem),
+                None => elem,
+            })
+            .collect();
+        self.backend.upload(&input_data)
+    }
+
+    //like NeuralNetwork::infer_batch, one input per column.
+    pub fn infer_batch(&self, inputs: &Matrix) -> Matrix {
+        let batch_size = inputs.width();
+        if batch_size == 0 {
+            return Matrix::from_vec(vec![Vec::new(); *self.layer_sizes.last().unwrap()]);
+        }
+        let mut activations = self.upload_inputs(inputs);
+        for (layer_index, (weights, biases)) in zip(&self.weights, &self.biases).enumerate() {
+            let (m, k) = (self.layer_sizes[layer_index + 1], self.layer_sizes[layer_index]);
+            activations = self.backend.matmul(weights, &activations, m, k, batch_size);
+            self.backend.add_bias(&mut activations, biases, m, batch_size);
+            self.backend.activate(&self.activation_functions[layer_index], &mut activations, m, batch_size);
+        }
+        to_matrix(&self.backend.download(&activations), batch_size)
+    }
+
+    //one step of gradient descent on the device, with the gradients averaged over the batch
+    //like Trainer::train_mini_batch with Sgd. the whole batch goes through every layer at once
+    //and only the outputs and their gradients pass through the host, where the cost is
+    //computed. returns the mean cost of the batch before the step.
+    pub fn train_mini_batch(&mut self, batch: &[(ColumnVector, ColumnVector)], learning_rate: f32) -> f32 {
+        let batch_size = batch.len();
+        if batch_size == 0 {
+            return 0.0;
+        }
+        let layer_amount = self.weights.len();
+        let inputs = Matrix::from_columns(&batch.iter().map(|(input, _)| input.clone()).collect::<Vec<_>>());
+        let mut activations = vec![self.upload_inputs(&inputs)];
+        let mut z_values = Vec::with_capacity(layer_amount);
+        for (layer_index, (weights, biases)) in zip(&self.weights, &self.biases).enumerate() {
+            let (m, k) = (self.layer_sizes[layer_index + 1], self.layer_sizes[layer_index]);
+            let mut z = self.backend.matmul(weights, activations.last().unwrap(), m, k, batch_size);
+            self.backend.add_bias(&mut z, biases, m, batch_size);
+            let mut activation = self.backend.copy(&z);
+            self.backend.activate(&self.activation_functions[layer_index], &mut activation, m, batch_size);
+            activations.push(activation);
+            z_values.push(z);
+        }
+
+        let outputs = to_matrix(&self.backend.download(activations.last().unwrap()), batch_size);
+        let output_activation = self.activation_functions.last().unwrap();
+        let fused = self.cost.is_fused_with(output_activation);
+        let output_z_values = if fused { None } else { Some(to_matrix(&self.backend.download(z_values.last().unwrap()), batch_size)) };
+        let mut cost = 0.0;
+        let deltas: Vec<ColumnVector> = batch.iter().enumerate().map(|(index, (_, desired))| {
+            let output = outputs.column(index);
+            cost += self.cost.value(&output, desired);
+            match &output_z_values {
+                None => &output - desired,
+                Some(z_values) => output_activation.backward(&z_values.column(index), &self.cost.gradient(&output, desired)),
+            }
+        }).collect();
+        let mut delta = self.backend.upload(Matrix::from_columns(&deltas).as_slice());
+
+        let step = -learning_rate / batch_size as f32;
+        for layer_index in (0..layer_amount).rev() {
+            let (m, k) = (self.layer_sizes[layer_index + 1], self.layer_sizes[layer_index]);
+            let weight_gradients = self.backend.matmul_transposed_rhs(&delta, &activations[layer_index], m, batch_size, k);
+            let bias_gradients = self.backend.sum_rows(&delta, m, batch_size);
+            if layer_index > 0 {
+                //propagated through the weights before they are updated.
+                let mut propagated = self.backend.matmul_transposed_lhs(&self.weights[layer_index], &delta, k, m, batch_size);
+                self.backend.activation_backward(&self.activation_functions[layer_index - 1], &z_values[layer_index - 1], &mut propagated, k, batch_size);
+                delta = propagated;
+            }
+            if !self.frozen.get(layer_index).copied().unwrap_or(false) {
+                self.backend.add_scaled(&mut self.weights[layer_index], step, &weight_gradients);
+                self.backend.add_scaled(&mut self.biases[layer_index], step, &bias_gradients);
+            }
+        }
+        cost / batch_size as f32
+    }
+
+    //downloads the weights into a network, for example to continue training.
+    pub fn to_network(&self) -> NeuralNetwork {
+        let weights = zip(&self.weights, self.layer_sizes.iter())
+            .map(|(weights, &inputs)| to_matrix(&self.backend.download(weights), inputs))
+            .collect();
+        let biases = self.biases.iter().map(|biases| ColumnVector::from_vec(self.backend.download(biases))).collect();
+        let mut network = NeuralNetwork::new_from_vecs(weights, Some(biases), None, None);
+        network.activation_functions = self.activation_functions.clone();
+        network.cost = self.cost.clone();
+        network.preprocessing = self.preprocessing;
+        network.frozen = self.frozen.clone();
+        network
+    }
+}
+
+impl NeuralNetwork {
+    //uploads the weights and biases for inference and training on device. dropout is left out.
+    pub fn to_device(&self, device: Device) -> Result<DeviceNetwork, String> {
+        if self.normalization.is_some() {
+            return Err("networks with normalization can not run on a device.".to_string());
+        }
+        let backend = device.backend()?;
+        if let Some(activation_function) = self.activation_functions.iter().find(|function| !backend.supports(function)) {
+            return Err(format!("the {} backend does not support the activation {:?}.", backend.name(), activation_function));
+        }
+        let mut layer_sizes = vec![self.weights[0].width()];
+        layer_sizes.extend(self.biases.iter().map(|biases| biases.data.len()));
+        Ok(DeviceNetwork {
+            weights: self.weights.iter().map(|weights| backend.upload(weights.as_slice())).collect(),
+            biases: self.biases.iter().map(|biases| backend.upload(&biases.data)).collect(),
+            layer_sizes,
+            activation_functions: self.activation_functions.clone(),
+            cost: self.cost.clone(),
+            preprocessing: self.preprocessing,
+            frozen: self.frozen.clone(),
+            backend,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::iter::zip;
+    use matrix::{ColumnVector, Matrix};
+    use mnist_reader::Preprocessing;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::{ActivationFunction, Cost, Device, DeviceNetwork, Initialization, LayerNorm, Normalization, NeuralNetwork, Trainer};
+
+    #[test]
+    fn device_inference() {
+        let mut network = NeuralNetwork::new_with_rng(&[6, 5, 4, 3], vec![ActivationFunction::Gelu, ActivationFunction::LeakyRelu(0.1), ActivationFunction::Softmax], Initialization::HeNormal, &mut StdRng::seed_from_u64(4));
+        network.preprocessing = Some(Preprocessing::Scale { max: 255.0 });
+        let inputs = Matrix::from_columns(&(0..7)
+            .map(|sample| ColumnVector::from_vec((0..6).map(|x| ((x * 41 + sample * 89) % 256) as f32).collect()))
+            .collect::<Vec<_>>());
+        let on_device = network.to_device(Device::Cpu).unwrap();
+        assert_eq!(on_device.backend().name(), "cpu");
+        let (outputs, expected) = (on_device.infer_batch(&inputs), network.infer_batch(&inputs));
+        assert_eq!(outputs.height(), 3);
+        for (output, expected) in zip(outputs.as_slice().iter(), expected.as_slice().iter()) {
+            assert!((output - expected).abs() < 1e-6);
+        }
+        let empty = on_device.infer_batch(&Matrix::from_vec(vec![Vec::new(); 6]));
+        assert_eq!((empty.height(), empty.width()), (3, 0));
+
+        let restored = on_device.to_network();
+        assert_eq!(restored.weights, network.weights);
+        assert_eq!(restored.biases, network.biases);
+
+        #[cfg(feature = "wgpu")]
+        if let Some(on_gpu) = available(&network, Device::Gpu) {
+            for (output, expected) in zip(on_gpu.infer_batch(&inputs).as_slice().iter(), expected.as_slice().iter()) {
+                assert!((output - expected).abs() < 1e-4);
+            }
+        }
+        #[cfg(feature = "cuda")]
+        if let Some(on_gpu) = available(&network, Device::Cuda) {
+            for (output, expected) in zip(on_gpu.infer_batch(&inputs).as_slice().iter(), expected.as_slice().iter()) {
+                assert!((output - expected).abs() < 1e-4);
+            }
+        }
+
+        network.normalization = Some(Normalization::Layer(LayerNorm::for_network(&network)));
+        assert!(network.to_device(Device::Cpu).is_err());
+    }
+
+    //the gpu backends need an adapter or a driver, which most machines running the tests lack.
+    //without one the comparison is skipped with a message, unless NN_REQUIRE_DEVICES is set,
+    //which makes a missing device fail the test on machines that are expected to have one.
+    #[cfg(any(feature = "wgpu", feature = "cuda"))]
+    fn available(network: &NeuralNetwork, device: Device) -> Option<DeviceNetwork> {
+        match network.to_device(device) {
+            Ok(on_device) => Some(on_device),
+            Err(error) if std::env::var_os("NN_REQUIRE_DEVICES").is_none() => {
+                eprintln!("skipping the {:?} comparison, the device is not available: {}", device, error);
+                None
+            }
+            Err(error) => panic!("{:?} is required but not available: {}", device, error),
+        }
+    }
+
+    //two steps on device against the trainer on the host, within tolerance of the device.
+    fn check_training(mut on_device: DeviceNetwork, network: &NeuralNetwork, tolerance: f32) {
+        let batch: Vec<(ColumnVector, ColumnVector)> = (0..5)
+            .map(|sample| (
+                ColumnVector::from_vec((0..4).map(|x| ((x * 3 + sample * 7) % 10) as f32 * 0.1 - 0.5).collect()),
+                ColumnVector::from_vec((0..3).map(|class| if class == sample % 3 { 1.0 } else { 0.0 }).collect()),
+            ))
+            .collect();
+        let mut expected = network.clone();
+        let mut trainer: Trainer = Trainer::new(5, 0.5, 1);
+        let expected_cost = expected.mean_loss(batch.iter().map(|(input, desired)| (input, desired)));
+        trainer.train_mini_batch(&mut expected, &batch, 0.5);
+        let cost = on_device.train_mini_batch(&batch, 0.5);
+        on_device.train_mini_batch(&batch[..3], 0.5);
+        trainer.train_mini_batch(&mut expected, &batch[..3], 0.5);
+        assert!((cost - expected_cost).abs() < tolerance, "{} {}", cost, expected_cost);
+
+        let trained = on_device.to_network();
+        for (value, expected_value) in zip(trained.weight_values(), expected.weight_values()) {
+            assert!((value - expected_value).abs() < tolerance, "{} {}", value, expected_value);
+        }
+        for (biases, expected_biases) in zip(&trained.biases, &expected.biases) {
+            for (value, expected_value) in zip(&biases.data, &expected_biases.data) {
+                assert!((value - expected_value).abs() < tolerance, "{} {}", value, expected_value);
+            }
+        }
+        assert_eq!(trained.weights[0], network.weights[0]);
+    }
+
+    #[test]
+    fn device_training() {
+        let mut network = NeuralNetwork::new_with_rng(&[4, 6, 5, 3], vec![ActivationFunction::Tanh, ActivationFunction::Elu(0.5), ActivationFunction::Softmax], Initialization::GlorotUniform, &mut StdRng::seed_from_u64(8));
+        network.cost = Cost::CrossEntropy;
+        network.freeze(0);
+        check_training(network.to_device(Device::Cpu).unwrap(), &network, 1e-6);
+        #[cfg(feature = "wgpu")]
+        if let Some(on_gpu) = available(&network, Device::Gpu) {
+            check_training(on_gpu, &network, 1e-4);
+        }
+        #[cfg(feature = "cuda")]
+        if let Some(on_gpu) = available(&network, Device::Cuda) {
+            check_training(on_gpu, &network, 1e-4);
+        }
+
+        //gradients that are not fused with the output activation are chained on the host.
+        network.cost = Cost::SquaredError;
+        network.activation_functions = vec![ActivationFunction::Gelu, ActivationFunction::Sigmoid, ActivationFunction::Swish];
+        check_training(network.to_device(Device::Cpu).unwrap(), &network, 1e-6);
+        #[cfg(feature = "wgpu")]
+        if let Some(on_gpu) = available(&network, Device::Gpu) {
+            check_training(on_gpu, &network, 1e-4);
+        }
+        #[cfg(feature = "cuda")]
+        if let Some(on_gpu) = available(&network, Device::Cuda) {
+            check_training(on_gpu, &network, 1e-4);
+        }
+    }
+}
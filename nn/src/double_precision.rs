@@ -0,0 +1,157 @@
+use std::iter::zip;
+use matrix::{ColumnVector, Matrix};
+use crate::{Activation, ActivationFunction, Cost, Differentiable, Gradients, Loss, NeuralNetwork};
+
+//a network with its parameters and every value of its forward and backward pass in double
+//precision, for gradient checking and numerical analysis that f32 rounding gets in the way of.
+//custom activations and losses still compute in f32.
+#[derive(PartialEq, Debug)]
+pub struct DoublePrecisionNetwork {
+    pub weights: Vec<Matrix<f64>>,
+    pub biases: Vec<ColumnVector<f64>>,
+    pub activation_functions: Vec<ActivationFunction>,
+    pub cost: Cost,
+}
+
+impl DoublePrecisionNetwork {
+    //the z values of every layer and the activations between them, the input being the first.
+    fn forward(&self, input: &ColumnVector<f64>) -> (Vec<ColumnVector<f64>>, Vec<ColumnVector<f64>>) {
+        let mut z_values = Vec::with_capacity(self.weights.len());
+        let mut activations = vec![input.clone()];
+        for ((weights, biases), activation_function) in zip(zip(&self.weights, &self.biases), &self.activation_functions) {
+            let mut z = ColumnVector::new_with_elements(weights.height(), 0.0);
+            activations.last().unwrap()._mul_matrix(weights, &mut z);
+            z += biases;
+            activations.push(activation_function.apply(&z));
+            z_values.push(z);
+        }
+        (z_values, activations)
+    }
+
+    //the output for input, like NeuralNetwork::infer.
+    pub fn infer(&self, input: &ColumnVector<f64>) -> ColumnVector<f64> {
+        self.forward(input).1.pop().unwrap()
+    }
+
+    pub fn cost_of(&self, input: &ColumnVector<f64>, desired: &ColumnVector<f64>) -> f64 {
+        self.cost.value(&self.infer(input), desired)
+    }
+
+    //the gradients of the cost of one sample, like NeuralNetwork::backpropagation.
+    pub fn backpropagation(&self, input: &ColumnVector<f64>, desired: &ColumnVector<f64>) -> Gradients<f64> {
+        let (z_values, activations) = self.forward(input);
+        let layer_amount = self.weights.len();
+        let output = activations.last().unwrap();
+        let output_activation = self.activation_functions.last().unwrap();
+        let mut delta = if self.cost.is_fused_with(output_activation) {
+            output - desired
+        } else {
+            output_activation.backward(&z_values[layer_amount - 1], &self.cost.gradient(output, desired))
+        };
+
+        let mut weight_gradients = Vec::with_capacity(layer_amount);
+        let mut bias_gradients = Vec::with_capacity(layer_amount);
+        for layer_index in (0..layer_amount).rev() {
+            weight_gradients.push(delta.outer(&activations[layer_index]));
+            let mut next_delta = ColumnVector::new_with_elements(activations[layer_index].data.len(), 0.0);
+            if layer_index > 0 {
+                delta._mul_transposed_matrix(&self.weights[layer_index], &mut next_delta);
+                next_delta = self.activation_functions[layer_index - 1].backward(&z_values[layer_index - 1], &next_delta);
+            }
+            bias_gradients.push(std::mem::replace(&mut delta, next_delta));
+        }
+        weight_gradients.reverse();
+        bias_gradients.reverse();
+        Gradients { weights: weight_gradients, biases: bias_gradients, gammas: Vec::new(), betas: Vec::new() }
+    }
+
+    //back to a single precision network, rounding every parameter.
+    pub fn to_network(&self) -> NeuralNetwork {
+        let mut network = NeuralNetwork::new_from_vecs(
+            self.weights.iter().map(Matrix::cast).collect(),
+            Some(self.biases.iter().map(ColumnVector::cast).collect()),
+            None,
+            None,
+        );
+        network.activation_functions = self.activation_functions.clone();
+        network.cost = self.cost.clone();
+        network
+    }
+}
+
+//every weight (row by row), then every bias, like the parameters of NeuralNetwork.
+impl Differentiable for DoublePrecisionNetwork {
+    type Scalar = f64;
+
+    fn parameters_mut(&mut self) -> impl Iterator<Item=&mut f64> {
+        self.weights.iter_mut()
+            .flat_map(|x| x.as_mut_slice().iter_mut())
+            .chain(self.biases.iter_mut().flat_map(|x| x.data.iter_mut()))
+    }
+
+    fn sample_gradients(&mut self, input: &ColumnVector<f64>, desired: &ColumnVector<f64>) -> Vec<f64> {
+        let gradients = self.backpropagation(input, desired);
+        gradients.weights.iter()
+            .flat_map(|x| x.as_slice().iter().copied())
+            .chain(gradients.biases.iter().flat_map(|x| x.data.iter().copied()))
+            .collect()
+    }
+
+    fn sample_cost(&mut self, input: &ColumnVector<f64>, desired: &ColumnVector<f64>) -> f64 {
+        self.cost_of(input, desired)
+    }
+}
+
+impl NeuralNetwork {
+    //the network in double precision, with the same activations and cost. dropout and
+    //preprocessing are left out, the network sees its inputs as they are.
+    pub fn to_double_precision(&self) -> DoublePrecisionNetwork {
+        if self.normalization.is_some() {
+            panic!("networks with normalization can not be converted to double precision.");
+        }
+        DoublePrecisionNetwork {
+            weights: self.weights.iter().map(Matrix::cast).collect(),
+            biases: self.biases.iter().map(ColumnVector::cast).collect(),
+            activation_functions: self.activation_functions.clone(),
+            cost: self.cost.clone(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::iter::zip;
+    use matrix::ColumnVector;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::{check_gradients, ActivationFunction, Cost, Initialization, NeuralNetwork};
+
+    #[test]
+    fn double_precision_gradient_check() {
+        let mut network = NeuralNetwork::new_with_rng(&[3, 4, 2], vec![ActivationFunction::Tanh, ActivationFunction::Softmax], Initialization::GlorotUniform, &mut StdRng::seed_from_u64(5));
+        network.cost = Cost::CrossEntropy;
+        let mut double = network.to_double_precision();
+        let input = ColumnVector::from_vec(vec![0.4, -0.8, 1.2]);
+        let desired = ColumnVector::from_vec(vec![0.0, 1.0]);
+
+        for (double_output, output) in zip(double.infer(&input.cast()).data, network.infer(&input).data) {
+            assert!((double_output - output as f64).abs() < 1e-6);
+        }
+        let double_check = check_gradients(&mut double, &input.cast(), &desired.cast());
+        let check = check_gradients(&mut network, &input, &desired);
+        assert!(double_check.max_relative_error < 1e-7, "{:?}", double_check);
+        assert!(double_check.max_relative_error < check.max_relative_error as f64 / 100.0, "{:?} {:?}", double_check, check);
+
+        let restored = double.to_network();
+        assert_eq!(restored.weights, network.weights);
+        assert_eq!((restored.activation_functions, restored.cost), (network.activation_functions, network.cost));
+    }
+
+    #[test]
+    fn squared_error_with_sigmoid() {
+        let network = NeuralNetwork::new_with_rng(&[2, 3, 3, 1], vec![ActivationFunction::Elu(0.7), ActivationFunction::Gelu, ActivationFunction::Sigmoid], Initialization::HeNormal, &mut StdRng::seed_from_u64(1));
+        let check = check_gradients(&mut network.to_double_precision(), &ColumnVector::from_vec(vec![0.3, -1.1]), &ColumnVector::from_vec(vec![1.0]));
+        assert!(check.max_relative_error < 1e-7, "{:?}", check);
+    }
+}
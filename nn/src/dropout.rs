@@ -0,0 +1,96 @@
+use std::iter::zip;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use matrix::ColumnVector;
+use serde::{Deserialize, Serialize};
+
+//whether the network is being trained or used for inference.
+//stochastic components like dropout are only active while training.
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Mode {
+    Training,
+    Inference,
+}
+
+//inverted dropout on the hidden layers: while training every hidden activation is zeroed
+//with the given probability and the kept ones are scaled by 1 / (1 - probability),
+//so inference can use the activations as they are.
+//serialization keeps only the probability, a deserialized dropout draws a fresh rng.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct Dropout {
+    pub probability: f32,
+    //masks of the last training forward pass, one per hidden layer.
+    #[serde(skip)]
+    masks: Vec<ColumnVector>,
+    #[serde(skip, default = "StdRng::from_entropy")]
+    pub rng: StdRng,
+}
+
+impl Dropout {
+    pub fn new(probability: f32) -> Dropout {
+        Dropout::new_with_rng(probability, StdRng::from_entropy())
+    }
+
+    //the same seed drops the same activations.
+    pub fn new_with_seed(probability: f32, seed: u64) -> Dropout {
+        Dropout::new_with_rng(probability, StdRng::seed_from_u64(seed))
+    }
+
+    fn new_with_rng(probability: f32, rng: StdRng) -> Dropout {
+        if !(0.0..1.0).contains(&probability) {
+            panic!("dropout probability must be in [0, 1).");
+        }
+        Dropout {
+            probability,
+            masks: Vec::new(),
+            rng,
+        }
+    }
+
+    pub fn apply(&mut self, layer_index: usize, activations: &mut ColumnVector) {
+        while self.masks.len() <= layer_index {
+            self.masks.push(ColumnVector::new_with_elements(0, 0.0));
+        }
+        drop_values(self.probability, &mut self.rng, &mut activations.data, &mut self.masks[layer_index].data);
+    }
+
+    //drops activations with the rng and the mask of the caller instead of the ones of the dropout,
+    //so threads sharing a network draw their own masks, e.g. for the columns of a batch.
+    pub(crate) fn apply_with_rng(&self, rng: &mut StdRng, activations: &mut [f32], mask: &mut Vec<f32>) {
+        drop_values(self.probability, rng, activations, mask);
+    }
+
+    pub fn mask(&self, layer_index: usize) -> &ColumnVector {
+        &self.masks[layer_index]
+    }
+}
+
+//mask gets the value every activation is scaled by, 0 for the dropped ones.
+fn drop_values(probability: f32, rng: &mut StdRng, activations: &mut [f32], mask: &mut Vec<f32>) {
+    let scale = 1.0 / (1.0 - probability);
+    mask.clear();
+    mask.extend((0..activations.len()).map(|_| {
+        if rng.gen::<f32>() < probability { 0.0 } else { scale }
+    }));
+    zip(activations.iter_mut(), mask.iter()).for_each(|(activation, mask_elem)| {
+        *activation *= mask_elem;
+    });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::ColumnVector;
+    use crate::Dropout;
+
+    #[test]
+    fn dropout_zeroes_and_scales() {
+        let mut dropout = Dropout::new(0.5);
+        let mut activations = ColumnVector::new_with_elements(1000, 1.0);
+        dropout.apply(1, &mut activations);
+        assert!(activations.data.iter().all(|&x| x == 0.0 || x == 2.0));
+        let dropped = activations.data.iter().filter(|&&x| x == 0.0).count();
+        assert!(dropped > 350 && dropped < 650);
+        assert_eq!(dropout.mask(1), &activations);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{Callback, Control, EpochEnd, Trainable};
+
+//stops training once the validation loss has not improved by at least min_delta for
+//patience epochs in a row. Without validation data the training loss is monitored instead.
+//with restore_best_weights the parameters (and batch normalization running statistics)
+//of the best epoch are put back into the network when it stops.
+pub struct EarlyStopping {
+    pub patience: usize,
+    pub min_delta: f32,
+    pub restore_best_weights: bool,
+    pub best_loss: Option<f32>,
+    pub best_epoch: Option<usize>,
+    //the epoch training was stopped after, if it was.
+    pub stopped_epoch: Option<usize>,
+    //epochs since the last improvement.
+    wait: usize,
+    best_parameters: Vec<f32>,
+    best_running_statistics: Vec<f32>,
+}
+
+impl EarlyStopping {
+    pub fn new(patience: usize, min_delta: f32) -> EarlyStopping {
+        if min_delta < 0.0 {
+            panic!("min delta must not be negative.");
+        }
+        EarlyStopping {
+            patience,
+            min_delta,
+            restore_best_weights: true,
+            best_loss: None,
+            best_epoch: None,
+            stopped_epoch: None,
+            wait: 0,
+            best_parameters: Vec::new(),
+            best_running_statistics: Vec::new(),
+        }
+    }
+
+    fn save<N: Trainable>(&mut self, network: &mut N) {
+        self.best_parameters.clear();
+        self.best_parameters.extend(network.parameters_mut().map(|x| *x));
+        self.best_running_statistics = network.running_statistics();
+    }
+
+    fn restore<N: Trainable>(&mut self, network: &mut N) {
+        network.parameters_mut().zip(&self.best_parameters).for_each(|(parameter, best)| *parameter = *best);
+        network.set_running_statistics(&self.best_running_statistics);
+    }
+}
+
+impl<N: Trainable> Callback<N> for EarlyStopping {
+    fn on_epoch_start(&mut self, _network: &N, epoch: usize) {
+        //a new training run starts from scratch.
+        if epoch == 0 {
+            self.best_loss = None;
+            self.best_epoch = None;
+            self.stopped_epoch = None;
+            self.wait = 0;
+        }
+    }
+
+    fn on_epoch_end(&mut self, network: &mut N, epoch: &EpochEnd) -> Control {
+        let loss = epoch.validation.as_ref().map_or(epoch.training_loss, |validation| validation.loss);
+        if self.best_loss.is_none_or(|best| loss < best - self.min_delta) {
+            self.best_loss = Some(loss);
+            self.best_epoch = Some(epoch.epoch);
+            self.wait = 0;
+            if self.restore_best_weights {
+                self.save(network);
+            }
+            return Control::Continue;
+        }
+        self.wait += 1;
+        if self.wait < self.patience {
+            return Control::Continue;
+        }
+        self.stopped_epoch = Some(epoch.epoch);
+        if self.restore_best_weights {
+            self.restore(network);
+        }
+        Control::Stop
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::ColumnVector;
+    use crate::{ActivationFunction, Callback, Control, EarlyStopping, EpochEnd, Evaluation, NeuralNetwork};
+
+    fn epoch_end(epoch: usize, validation_loss: f32) -> EpochEnd {
+        EpochEnd {
+            epoch,
+            training_loss: 1.0,
+            validation: Some(Evaluation { loss: validation_loss, accuracy: 0.0, metrics: Vec::new() }),
+            learning_rate: 0.1,
+        }
+    }
+
+    #[test]
+    fn stops_after_patience_and_restores_best_weights() {
+        let mut network = NeuralNetwork::new_with_activations(&[2, 2], vec![ActivationFunction::Relu], Some(0.0));
+        let mut early_stopping = EarlyStopping::new(2, 0.01);
+        early_stopping.on_epoch_start(&network, 0);
+        let losses = [1.0, 0.5, 0.495, 0.6];
+        let mut controls = Vec::new();
+        for (epoch, &loss) in losses.iter().enumerate() {
+            network.biases[0] = ColumnVector::new_with_elements(2, epoch as f32);
+            controls.push(early_stopping.on_epoch_end(&mut network, &epoch_end(epoch, loss)));
+        }
+        //0.495 is not better than 0.5 by min delta.
+        assert_eq!(controls, vec![Control::Continue, Control::Continue, Control::Continue, Control::Stop]);
+        assert_eq!(early_stopping.best_epoch, Some(1));
+        assert_eq!(early_stopping.best_loss, Some(0.5));
+        assert_eq!(early_stopping.stopped_epoch, Some(3));
+        assert_eq!(network.biases[0], ColumnVector::new_with_elements(2, 1.0));
+    }
+}
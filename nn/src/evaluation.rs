@@ -0,0 +1,159 @@
+use std::any::Any;
+use matrix::{ColumnVector, Matrix};
+use mnist_reader::{argmax, one_hot, top_k, Dataset};
+use crate::{ActivationFunction, ActivationLayer, Cost, LossAccumulator, Metric, Model, NeuralNetwork, Prediction};
+
+//samples fed through the network together by evaluate.
+const EVALUATION_BATCH_SIZE: usize = 256;
+
+//how well a network does on a labeled dataset.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Evaluation {
+    //mean cost of the network, without regularization penalties.
+    pub loss: f32,
+    //fraction of samples whose largest output is at their label.
+    pub accuracy: f32,
+    //names and values of the metrics passed to evaluate_with_metrics, in their order.
+    pub metrics: Vec<(String, f32)>,
+}
+
+//collects the loss, accuracy and metrics of outputs with their labels.
+struct Evaluator<'a> {
+    cost: &'a Cost,
+    output_activation: &'a ActivationFunction,
+    metrics: &'a mut [Box<dyn Metric>],
+    loss: LossAccumulator,
+    correct: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(cost: &'a Cost, output_activation: &'a ActivationFunction, metrics: &'a mut [Box<dyn Metric>]) -> Evaluator<'a> {
+        Evaluator { cost, output_activation, metrics, loss: LossAccumulator::new(), correct: 0 }
+    }
+
+    fn add(&mut self, output: ColumnVector, label: usize) {
+        self.loss.add(self.cost, &output, &one_hot(label, output.data.len()));
+        if argmax(&output) == label {
+            self.correct += 1;
+        }
+        if !self.metrics.is_empty() {
+            let prediction = Prediction::from_output(output, self.output_activation);
+            self.metrics.iter_mut().for_each(|metric| metric.update(&prediction, label));
+        }
+    }
+
+    fn finish(self, sample_amount: usize) -> Evaluation {
+        Evaluation {
+            loss: self.loss.mean(),
+            accuracy: if sample_amount == 0 { 0.0 } else { self.correct as f32 / sample_amount as f32 },
+            metrics: self.metrics.iter_mut().map(|metric| (metric.name(), metric.finalize())).collect(),
+        }
+    }
+}
+
+impl NeuralNetwork {
+    //calls visit with the output and label of every sample, feeding them through in batches.
+    fn visit_outputs<D: Dataset + ?Sized>(&self, dataset: &D, mut visit: impl FnMut(ColumnVector, usize)) {
+        let indices: Vec<usize> = (0..dataset.len()).collect();
+        for batch in indices.chunks(EVALUATION_BATCH_SIZE) {
+            let (inputs, labels): (Vec<ColumnVector>, Vec<usize>) = batch.iter().map(|&index| dataset.get(index)).unzip();
+            for (output, label) in self.infer_batch(&Matrix::from_columns(&inputs)).columns().zip(labels) {
+                visit(output, label);
+            }
+        }
+    }
+
+    //runs the network over the dataset in inference mode. an empty dataset evaluates to 0 loss and accuracy.
+    pub fn evaluate<D: Dataset + ?Sized>(&self, dataset: &D) -> Evaluation {
+        self.evaluate_with_metrics(dataset, &mut [])
+    }
+
+    //like evaluate, additionally feeding every prediction to the metrics and finalizing them at the end.
+    pub fn evaluate_with_metrics<D: Dataset + ?Sized>(&self, dataset: &D, metrics: &mut [Box<dyn Metric>]) -> Evaluation {
+        let mut evaluator = Evaluator::new(&self.cost, self.activation_functions.last().unwrap(), metrics);
+        self.visit_outputs(dataset, |output, label| evaluator.add(output, label));
+        evaluator.finish(dataset.len())
+    }
+
+    //fraction of samples whose label is among the k largest outputs. with k = 1 this is the accuracy.
+    pub fn top_k_accuracy<D: Dataset + ?Sized>(&self, dataset: &D, k: usize) -> f32 {
+        if dataset.is_empty() {
+            return 0.0;
+        }
+        let mut correct = 0;
+        self.visit_outputs(dataset, |output, label| {
+            if top_k(&output, k).contains(&label) {
+                correct += 1;
+            }
+        });
+        correct as f32 / dataset.len() as f32
+    }
+}
+
+
+impl Model {
+    //runs every sample through the model one at a time, like NeuralNetwork::evaluate.
+    pub fn evaluate<D: Dataset + ?Sized>(&mut self, dataset: &D) -> Evaluation {
+        self.evaluate_with_metrics(dataset, &mut [])
+    }
+
+    pub fn evaluate_with_metrics<D: Dataset + ?Sized>(&mut self, dataset: &D, metrics: &mut [Box<dyn Metric>]) -> Evaluation {
+        //the outputs of a model ending in a softmax layer already are probabilities.
+        let output_activation = self.layers.last()
+            .and_then(|layer| (layer.as_ref() as &dyn Any).downcast_ref::<ActivationLayer>())
+            .map_or(ActivationFunction::Identity, |layer| layer.activation_function.clone());
+        let cost = self.cost.clone();
+        let mut evaluator = Evaluator::new(&cost, &output_activation, metrics);
+        for index in 0..dataset.len() {
+            let (input, label) = dataset.get(index);
+            evaluator.add(self.forward(&input), label);
+        }
+        evaluator.finish(dataset.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix::{ColumnVector, Matrix};
+    use mnist_reader::one_hot;
+    use crate::{ActivationFunction, Evaluation, Metric, NeuralNetwork, TopKAccuracy};
+
+    #[test]
+    fn loss_and_accuracy() {
+        let weights = vec![Matrix::from_vec(vec![vec![1.0, -1.0], vec![-1.0, 1.0]])];
+        let mut network = NeuralNetwork::new_from_vecs(weights, None, None, None);
+        network.activation_functions = vec![ActivationFunction::Identity];
+        let dataset = vec![
+            (ColumnVector::from_vec(vec![1.0, 0.0]), 0),
+            (ColumnVector::from_vec(vec![0.0, 1.0]), 1),
+            (ColumnVector::from_vec(vec![0.0, 2.0]), 0),
+            (ColumnVector::from_vec(vec![3.0, 0.0]), 0),
+        ];
+        let evaluation = network.evaluate(&dataset);
+        assert_eq!(evaluation.accuracy, 0.75);
+        let targets: Vec<(ColumnVector, ColumnVector)> = dataset.iter().map(|(input, label)| (input.clone(), one_hot(*label, 2))).collect();
+        let expected_loss = network.mean_loss(targets.iter().map(|(input, desired)| (input, desired)));
+        assert!((evaluation.loss - expected_loss).abs() < 1e-6);
+        assert_eq!(network.evaluate(&Vec::new()), Evaluation { loss: 0.0, accuracy: 0.0, metrics: Vec::new() });
+    }
+
+    #[test]
+    fn top_k_accuracy() {
+        let weights = vec![Matrix::from_vec(vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.5, 0.5]])];
+        let mut network = NeuralNetwork::new_from_vecs(weights, None, None, None);
+        network.activation_functions = vec![ActivationFunction::Identity];
+        let dataset = vec![
+            (ColumnVector::from_vec(vec![1.0, 0.0]), 0),
+            (ColumnVector::from_vec(vec![1.0, 0.0]), 2),
+            (ColumnVector::from_vec(vec![1.0, 0.0]), 1),
+            (ColumnVector::from_vec(vec![0.0, 2.0]), 2),
+        ];
+        assert_eq!(network.top_k_accuracy(&dataset, 1), network.evaluate(&dataset).accuracy);
+        assert_eq!(network.top_k_accuracy(&dataset, 2), 0.75);
+        assert_eq!(network.top_k_accuracy(&dataset, 3), 1.0);
+        assert_eq!(network.predict(&dataset[3].0).top_k(2), vec![1, 2]);
+        let mut metrics: Vec<Box<dyn Metric>> = vec![Box::new(TopKAccuracy::new(2)), Box::new(TopKAccuracy::new(3))];
+        let evaluation = network.evaluate_with_metrics(&dataset, &mut metrics);
+        assert_eq!(evaluation.metrics, vec![("top 2 accuracy".to_string(), 0.75), ("top 3 accuracy".to_string(), 1.0)]);
+    }
+}
@@ -0,0 +1,188 @@
+use matrix::{ColumnVector, Scalar};
+use crate::{constant, GradientValues, Layer, Loss, NeuralNetwork};
+
+//step of the central finite differences, about the cube root of the rounding error of T: 1e-2
+//for f32 and 1e-5 for f64. smaller steps drown in rounding, larger ones in the curvature.
+fn step<T: Scalar>() -> T {
+    constant::<T>(2.0) * T::epsilon().cbrt()
+}
+
+//how far the gradients of backpropagation are from central finite differences, in the
+//precision of the network that was checked.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct GradientCheck<T = f32> {
+    pub max_absolute_error: T,
+    //|analytical - numerical| / (|analytical| + |numerical|). gradients smaller than the step
+    //are measured against the step instead, so the noise of the finite differences does not
+    //make tiny gradients look wrong.
+    pub max_relative_error: T,
+    //the index of the value with the largest relative error.
+    pub worst_index: usize,
+}
+
+impl<T: Scalar> GradientCheck<T> {
+    fn add(&mut self, index: usize, analytical: T, numerical: T) {
+        let absolute_error = (analytical - numerical).abs();
+        let relative_error = absolute_error / (analytical.abs() + numerical.abs()).max(step());
+        self.max_absolute_error = self.max_absolute_error.max(absolute_error);
+        if relative_error > self.max_relative_error {
+            self.max_relative_error = relative_error;
+            self.worst_index = index;
+        }
+    }
+}
+
+fn central_difference<T: Scalar>(mut cost_at: impl FnMut(T) -> T, original: T) -> T {
+    let step = step::<T>();
+    (cost_at(original + step) - cost_at(original - step)) / (constant::<T>(2.0) * step)
+}
+
+//a network check_gradients can check, in the precision it computes in. NeuralNetwork is
+//checked in f32 and DoublePrecisionNetwork in f64.
+pub trait Differentiable {
+    type Scalar: Scalar;
+
+    //every parameter in a fixed order.
+    fn parameters_mut(&mut self) -> impl Iterator<Item=&mut Self::Scalar>;
+
+    //the gradient of the cost of one sample for every parameter, in the order of parameters_mut.
+    fn sample_gradients(&mut self, input: &ColumnVector<Self::Scalar>, desired: &ColumnVector<Self::Scalar>) -> Vec<Self::Scalar>;
+
+    fn sample_cost(&mut self, input: &ColumnVector<Self::Scalar>, desired: &ColumnVector<Self::Scalar>) -> Self::Scalar;
+}
+
+impl Differentiable for NeuralNetwork {
+    type Scalar = f32;
+
+    fn parameters_mut(&mut self) -> impl Iterator<Item=&mut f32> {
+        NeuralNetwork::parameters_mut(self)
+    }
+
+    fn sample_gradients(&mut self, input: &ColumnVector, desired: &ColumnVector) -> Vec<f32> {
+        self.backpropagation(input, desired).values().copied().collect()
+    }
+
+    fn sample_cost(&mut self, input: &ColumnVector, desired: &ColumnVector) -> f32 {
+        self.calculate_all_activation_values(input);
+        self.cost.value(self.activation_values.back().unwrap(), desired)
+    }
+}
+
+//compares the gradients of backpropagation for one sample against finite differences of the
+//cost of the network, parameter by parameter in the order of parameters_mut.
+//meant for small networks, every parameter takes two forward passes. dropout has to be off,
+//its masks change with every forward pass.
+pub fn check_gradients<N: Differentiable>(network: &mut N, input: &ColumnVector<N::Scalar>, desired: &ColumnVector<N::Scalar>) -> GradientCheck<N::Scalar> {
+    let gradients = network.sample_gradients(input, desired);
+    check_parameter_gradients(network, &gradients, |network| network.sample_cost(input, desired))
+}
+
+//the same for gradients of any cost of the network, e.g. the loss of a whole batch.
+pub(crate) fn check_parameter_gradients<N: Differentiable>(network: &mut N, gradients: &[N::Scalar], mut cost: impl FnMut(&mut N) -> N::Scalar) -> GradientCheck<N::Scalar> {
+    let mut check = GradientCheck::default();
+    let parameter_amount = network.parameters_mut().count();
+    assert_eq!(parameter_amount, gradients.len(), "the gradients do not belong to the network.");
+    for (index, &analytical) in gradients.iter().enumerate() {
+        let original = *network.parameters_mut().nth(index).unwrap();
+        let numerical = central_difference(|value| {
+            *network.parameters_mut().nth(index).unwrap() = value;
+            cost(network)
+        }, original);
+        *network.parameters_mut().nth(index).unwrap() = original;
+        check.add(index, analytical, numerical);
+    }
+    check
+}
+
+//the same for a single layer, with half the squared sum of its outputs as the cost.
+//the parameters in the order of params come first, then the gradient with respect to every input.
+pub fn check_layer_gradients(layer: &mut dyn Layer, input: &ColumnVector) -> GradientCheck {
+    let cost = |layer: &mut dyn Layer, input: &ColumnVector| layer.forward(input).magnitude_squared() * 0.5;
+    layer.params().into_iter().for_each(|param| *param.gradient = 0.0);
+    let output = layer.forward(input);
+    let input_gradient = layer.backward(&output);
+    let parameter_gradients: Vec<f32> = layer.params().iter().map(|param| *param.gradient).collect();
+
+    let mut check = GradientCheck::default();
+    for (index, &analytical) in parameter_gradients.iter().enumerate() {
+        let original = *layer.params()[index].value;
+        let numerical = central_difference(|value| {
+            *layer.params()[index].value = value;
+            cost(layer, input)
+        }, original);
+        *layer.params()[index].value = original;
+        check.add(index, analytical, numerical);
+    }
+    for (index, &analytical) in input_gradient.data.iter().enumerate() {
+        let mut shifted = input.clone();
+        let numerical = central_difference(|value| {
+            shifted.data[index] = value;
+            cost(layer, &shifted)
+        }, input.data[index]);
+        check.add(parameter_gradients.len() + index, analytical, numerical);
+    }
+    check
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::ColumnVector;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::{check_gradients, check_layer_gradients, ActivationFunction, ActivationLayer, Conv2d, Cost, Dense, Initialization, Layer, LayerNorm, NeuralNetwork, Normalization, Residual};
+
+    #[test]
+    fn backpropagation_matches_finite_differences() {
+        let input = ColumnVector::from_vec(vec![0.4, -0.8, 1.2]);
+        let desired = ColumnVector::from_vec(vec![0.0, 1.0]);
+        for (seed, hidden) in [ActivationFunction::Tanh, ActivationFunction::Sigmoid, ActivationFunction::Gelu].into_iter().enumerate() {
+            let mut network = NeuralNetwork::new_with_rng(&[3, 4, 2], vec![hidden, ActivationFunction::Softmax], Initialization::GlorotUniform, &mut StdRng::seed_from_u64(seed as u64));
+            network.cost = Cost::CrossEntropy;
+            let check = check_gradients(&mut network, &input, &desired);
+            assert!(check.max_relative_error < 1e-2, "{:?}", check);
+        }
+        let mut network = NeuralNetwork::new_with_rng(&[3, 4, 2], vec![ActivationFunction::Tanh, ActivationFunction::Identity], Initialization::GlorotUniform, &mut StdRng::seed_from_u64(7));
+        network.normalization = Some(Normalization::Layer(LayerNorm::for_network(&network)));
+        let check = check_gradients(&mut network, &input, &desired);
+        assert!(check.max_relative_error < 1e-2, "{:?}", check);
+    }
+
+    #[test]
+    fn layers_match_finite_differences() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut residual = Residual::new(vec![
+            Box::new(Dense::random(4, 4, Initialization::StandardNormal, &mut rng)),
+            Box::new(ActivationLayer::new(ActivationFunction::Tanh)),
+        ]);
+        let check = check_layer_gradients(&mut residual, &ColumnVector::from_vec(vec![0.2, -0.4, 0.6, -0.8]));
+        assert!(check.max_relative_error < 1e-2, "{:?}", check);
+        let mut conv = Conv2d::random((2, 3, 3), 2, 2, 1, 1, Initialization::GlorotUniform, &mut rng);
+        let input = ColumnVector::from_vec((0..18).map(|x| (x as f32 * 0.7).cos()).collect());
+        let check = check_layer_gradients(&mut conv, &input);
+        assert!(check.max_relative_error < 1e-2, "{:?}", check);
+    }
+
+    //returns twice the correct input gradient.
+    #[derive(Debug)]
+    struct WrongBackward(ActivationLayer);
+
+    impl Layer for WrongBackward {
+        fn forward(&mut self, input: &ColumnVector) -> ColumnVector {
+            self.0.forward(input)
+        }
+
+        fn backward(&mut self, gradient: &ColumnVector) -> ColumnVector {
+            let propagated = self.0.backward(gradient);
+            propagated.map(|x| x * 2.0)
+        }
+    }
+
+    #[test]
+    fn wrong_gradients_are_reported() {
+        let mut layer = WrongBackward(ActivationLayer::new(ActivationFunction::Tanh));
+        let check = check_layer_gradients(&mut layer, &ColumnVector::from_vec(vec![0.5, -0.1, 2.0]));
+        //2g against g is a relative error of 1 / 3.
+        assert!((check.max_relative_error - 1.0 / 3.0).abs() < 1e-2, "{:?}", check);
+    }
+}
@@ -0,0 +1,288 @@
+use std::iter::zip;
+use matrix::{ColumnVector, Matrix, F16};
+use mnist_reader::{Dataset, Preprocessing};
+use crate::{Activation, ActivationFunction, Evaluation, GradientValues, Gradients, Loss, LossAccumulator, Metric, NeuralNetwork, Trainable};
+
+//a network with its weights, biases and the activations between its layers stored in half
+//precision, half the memory of the f32 network. every product is accumulated in f32 and only
+//the result of a layer is rounded, so the outputs stay within about 1e-3 of the original.
+pub struct HalfPrecisionNetwork {
+    pub weights: Vec<Matrix<F16>>,
+    pub biases: Vec<ColumnVector<F16>>,
+    pub activation_functions: Vec<ActivationFunction>,
+    pub preprocessing: Option<Preprocessing>,
+}
+
+impl HalfPrecisionNetwork {
+    fn preprocess(&self, input: &ColumnVector) -> ColumnVector {
+        ColumnVector::from_vec(input.data.iter().map(|&elem| match &self.preprocessing {
+            Some(preprocessing) => preprocessing.apply_to_value(elem),
+            None => elem,
+        }).collect())
+    }
+
+    //the output for input, like NeuralNetwork::infer.
+    pub fn infer(&self, input: &ColumnVector) -> ColumnVector {
+        let preprocessed = self.preprocess(input);
+        let mut activations = ColumnVector::<F16>::from_single(&preprocessed);
+        let mut output = preprocessed;
+        for ((weights, biases), activation_function) in zip(zip(&self.weights, &self.biases), &self.activation_functions) {
+            let mut z_values = weights.mul_accumulating(&activations);
+            z_values += &biases.to_single();
+            output = activation_function.apply(&z_values);
+            activations = ColumnVector::<F16>::from_single(&output);
+        }
+        //the last layer is handed out before rounding, there is nothing left to store.
+        output
+    }
+
+    //bytes taken up by the weights and biases.
+    pub fn parameter_bytes(&self) -> usize {
+        let weight_amount: usize = self.weights.iter().map(|weights| weights.as_slice().len()).sum();
+        let bias_amount: usize = self.biases.iter().map(|biases| biases.data.len()).sum();
+        (weight_amount + bias_amount) * size_of::<F16>()
+    }
+
+    //back to a single precision network, for example to continue training.
+    pub fn to_network(&self) -> NeuralNetwork {
+        let mut network = NeuralNetwork::new_from_vecs(
+            self.weights.iter().map(Matrix::to_single).collect(),
+            Some(self.biases.iter().map(ColumnVector::to_single).collect()),
+            None,
+            None,
+        );
+        network.activation_functions = self.activation_functions.clone();
+        network.preprocessing = self.preprocessing;
+        network
+    }
+}
+
+impl NeuralNetwork {
+    //the network in half precision for inference. dropout and the cost are training only
+    //and left out.
+    pub fn to_half_precision(&self) -> Result<HalfPrecisionNetwork, String> {
+        if self.normalization.is_some() {
+            return Err("networks with normalization can not be stored in half precision.".to_string());
+        }
+        Ok(self.rounded_to_half())
+    }
+
+    //to_half_precision without the check, for networks that already passed it.
+    fn rounded_to_half(&self) -> HalfPrecisionNetwork {
+        HalfPrecisionNetwork {
+            weights: self.weights.iter().map(Matrix::<F16>::from_single).collect(),
+            biases: self.biases.iter().map(ColumnVector::<F16>::from_single).collect(),
+            activation_functions: self.activation_functions.clone(),
+            preprocessing: self.preprocessing,
+        }
+    }
+}
+
+
+//trains a network with its weights and the activations between its layers in half precision,
+//like HalfPrecisionNetwork, while the optimizer updates the f32 weights of master. rounding
+//every update to a half would lose the small ones, so the halves are rounded from master
+//before every batch instead. the backward pass is in f32, only what is stored is rounded.
+//validation and the loss of the trainer use master.
+pub struct MixedPrecisionNetwork {
+    pub master: NeuralNetwork,
+    half: HalfPrecisionNetwork,
+}
+
+impl MixedPrecisionNetwork {
+    pub fn new(master: NeuralNetwork) -> Result<MixedPrecisionNetwork, String> {
+        if master.dropout.is_some() {
+            return Err("networks with dropout can not be trained in half precision.".to_string());
+        }
+        Ok(MixedPrecisionNetwork { half: master.to_half_precision()?, master })
+    }
+
+    //rounds the weights and biases of master into the half precision copy.
+    fn round_master(&mut self) {
+        self.half = self.master.rounded_to_half();
+    }
+
+    //the gradients of the cost of one sample and the cost, computed from the half precision
+    //copy of the weights as it was rounded before the current batch.
+    pub fn backpropagation(&self, input: &ColumnVector, desired: &ColumnVector) -> (Gradients, f32) {
+        let layer_amount = self.half.weights.len();
+        let mut activations = vec![ColumnVector::<F16>::from_single(&self.half.preprocess(input))];
+        let mut z_values = Vec::with_capacity(layer_amount);
+        for layer_index in 0..layer_amount {
+            let mut z = self.half.weights[layer_index].mul_accumulating(&activations[layer_index]);
+            z += &self.half.biases[layer_index].to_single();
+            if layer_index + 1 < layer_amount {
+                activations.push(ColumnVector::<F16>::from_single(&self.half.activation_functions[layer_index].apply(&z)));
+            }
+            z_values.push(z);
+        }
+
+        //like the output of infer, the output layer is not rounded. the z values of the hidden
+        //layers are only needed for the backward pass and are stored as halves.
+        let output_z = z_values.pop().unwrap();
+        let z_values: Vec<ColumnVector<F16>> = z_values.iter().map(ColumnVector::<F16>::from_single).collect();
+        let output_activation = self.half.activation_functions.last().unwrap();
+        let output = output_activation.apply(&output_z);
+        let cost = &self.master.cost;
+        let mut delta = if cost.is_fused_with(output_activation) {
+            &output - desired
+        } else {
+            output_activation.backward(&output_z, &cost.gradient(&output, desired))
+        };
+
+        let mut weight_gradients = Vec::with_capacity(layer_amount);
+        let mut bias_gradients = Vec::with_capacity(layer_amount);
+        for layer_index in (0..layer_amount).rev() {
+            weight_gradients.push(delta.outer(&activations[layer_index].to_single()));
+            if layer_index > 0 {
+                let propagated = self.half.weights[layer_index].transposed_mul_accumulating(&delta);
+                let next_delta = self.half.activation_functions[layer_index - 1].backward(&z_values[layer_index - 1].to_single(), &propagated);
+                bias_gradients.push(std::mem::replace(&mut delta, next_delta));
+            }
+        }
+        bias_gradients.push(delta);
+        weight_gradients.reverse();
+        bias_gradients.reverse();
+        let gradients = Gradients { weights: weight_gradients, biases: bias_gradients, gammas: Vec::new(), betas: Vec::new() };
+        (gradients, cost.value(&output, desired))
+    }
+
+    pub fn into_network(self) -> NeuralNetwork {
+        self.master
+    }
+}
+
+//everything but the gradients goes to master. the batch is not split over threads.
+impl Trainable for MixedPrecisionNetwork {
+    type Gradients = Gradients;
+
+    fn parameters_mut(&mut self) -> impl Iterator<Item=&mut f32> {
+        self.master.parameters_mut()
+    }
+
+    fn trainable_parameters(&self) -> Vec<bool> {
+        self.master.trainable_parameters()
+    }
+
+    fn zeroed_gradients(&self) -> Gradients {
+        Gradients::zeros_like(&self.master)
+    }
+
+    fn parallel_batch_gradients_and_loss(&mut self, batch: &[(ColumnVector, ColumnVector)], _threads: usize) -> (Gradients, LossAccumulator) {
+        self.round_master();
+        let mut gradients = self.zeroed_gradients();
+        let mut loss = LossAccumulator::new();
+        for (input, desired) in batch {
+            let (sample_gradients, cost) = self.backpropagation(input, desired);
+            gradients.accumulate(&sample_gradients);
+            loss.add_value(cost);
+        }
+        (gradients, loss)
+    }
+
+    fn penalty(&mut self, l2_lambda: f32, l1_lambda: f32) -> f32 {
+        Trainable::penalty(&mut self.master, l2_lambda, l1_lambda)
+    }
+
+    fn add_penalty_gradients(&mut self, gradients: &mut Gradients, l2_lambda: f32, l1_lambda: f32) {
+        self.master.add_penalty_gradients(gradients, l2_lambda, l1_lambda)
+    }
+
+    fn output_size(&self) -> usize {
+        self.master.output_size()
+    }
+
+    fn mean_loss(&mut self, data: &[(ColumnVector, ColumnVector)]) -> f32 {
+        Trainable::mean_loss(&mut self.master, data)
+    }
+
+    fn validate<D: Dataset + ?Sized>(&mut self, dataset: &D, metrics: &mut [Box<dyn Metric>]) -> Evaluation {
+        self.master.validate(dataset, metrics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::zip;
+    use matrix::ColumnVector;
+    use mnist_reader::Preprocessing;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::{ActivationFunction, Adam, Cost, Dropout, GradientValues, Initialization, LayerNorm, Loss, MixedPrecisionNetwork, NeuralNetwork, Normalization, Trainable, Trainer};
+
+    #[test]
+    fn half_precision_inference() {
+        let mut network = NeuralNetwork::new_with_rng(&[64, 32, 10], vec![ActivationFunction::Relu, ActivationFunction::Softmax], Initialization::HeNormal, &mut StdRng::seed_from_u64(2));
+        network.preprocessing = Some(Preprocessing::Scale { max: 255.0 });
+        let half = network.to_half_precision().unwrap();
+        assert_eq!(half.parameter_bytes(), (64 * 32 + 32 + 32 * 10 + 10) * 2);
+        for sample in 0..5 {
+            let input = ColumnVector::from_vec((0..64).map(|x| ((x * 7 + sample * 13) % 256) as f32).collect());
+            for (half_output, output) in zip(half.infer(&input).data, network.infer(&input).data) {
+                assert!((half_output - output).abs() < 1e-3, "{} {}", half_output, output);
+            }
+        }
+
+        //every weight survives the round trip up to the precision of a half.
+        let restored = half.to_network();
+        assert_eq!(restored.activation_functions, network.activation_functions);
+        assert_eq!(restored.preprocessing, network.preprocessing);
+        for (restored_weight, weight) in zip(restored.weight_values(), network.weight_values()) {
+            assert!((restored_weight - weight).abs() <= weight.abs() / 2048.0);
+        }
+    }
+
+    #[test]
+    fn mixed_precision_gradients_match_single_precision() {
+        let new_network = || {
+            let mut network = NeuralNetwork::new_with_rng(&[16, 12, 4], vec![ActivationFunction::Tanh, ActivationFunction::Softmax], Initialization::GlorotUniform, &mut StdRng::seed_from_u64(4));
+            network.cost = Cost::CrossEntropy;
+            network
+        };
+        let mut network = new_network();
+        let input = ColumnVector::from_vec((0..16).map(|x| (x as f32 * 0.4).sin()).collect());
+        let desired = ColumnVector::from_vec(vec![0.0, 0.0, 1.0, 0.0]);
+        let mixed = MixedPrecisionNetwork::new(new_network()).unwrap();
+        let (mixed_gradients, mixed_cost) = mixed.backpropagation(&input, &desired);
+        let gradients = network.backpropagation(&input, &desired);
+        let cost = network.cost.value(network.activation_values.back().unwrap(), &desired);
+        assert!((mixed_cost - cost).abs() < 1e-2 * cost);
+        for (mixed_gradient, gradient) in zip(mixed_gradients.values(), gradients.values()) {
+            assert!((mixed_gradient - gradient).abs() < 1e-2 * gradient.abs() + 1e-3, "{} {}", mixed_gradient, gradient);
+        }
+    }
+
+    #[test]
+    fn mixed_precision_training() {
+        let data: Vec<(ColumnVector, ColumnVector)> = [([0.0, 0.0], 0), ([0.0, 1.0], 1), ([1.0, 0.0], 1), ([1.0, 1.0], 0)].iter()
+            .map(|(input, class)| {
+                let mut desired = vec![0.0; 2];
+                desired[*class] = 1.0;
+                (ColumnVector::from_vec(input.to_vec()), ColumnVector::from_vec(desired))
+            })
+            .collect();
+        let mut network = NeuralNetwork::new_with_rng(&[2, 8, 2], vec![ActivationFunction::Tanh, ActivationFunction::Softmax], Initialization::GlorotUniform, &mut StdRng::seed_from_u64(3));
+        network.cost = Cost::CrossEntropy;
+        let mut mixed = MixedPrecisionNetwork::new(network).unwrap();
+        let mut trainer = Trainer::new_with_optimizer(4, 0.05, 300, Adam::default());
+        trainer.rng = StdRng::seed_from_u64(1);
+        let initial_loss = mixed.mean_loss(&data);
+        trainer.train(&mut mixed, &mut data.clone());
+        assert!(mixed.mean_loss(&data) < initial_loss * 0.1);
+        let network = mixed.into_network();
+        for (input, desired) in &data {
+            assert_eq!(network.infer(input).data[1] > 0.5, desired.data[1] == 1.0);
+        }
+    }
+
+    #[test]
+    fn unsupported_networks_are_errors() {
+        let mut network = NeuralNetwork::new_with_activations(&[2, 3, 2], vec![ActivationFunction::Relu; 2], Some(0.5));
+        network.normalization = Some(Normalization::Layer(LayerNorm::for_network(&network)));
+        assert_eq!(network.to_half_precision().err(), Some("networks with normalization can not be stored in half precision.".to_string()));
+        assert!(MixedPrecisionNetwork::new(network.clone()).is_err());
+        network.normalization = None;
+        network.dropout = Some(Dropout::new_with_seed(0.5, 1));
+        assert_eq!(MixedPrecisionNetwork::new(network).err(), Some("networks with dropout can not be trained in half precision.".to_string()));
+    }
+}
@@ -0,0 +1,442 @@
+use std::collections::HashSet;
+use std::io;
+use crate::invalid_data;
+
+//a reader for the part of hdf5 that keras weight files use: groups (old style symbol tables or
+//compact link messages), attributes of fixed length strings and float datasets stored contiguous
+//or compact. Chunked and compressed datasets, dense link and attribute storage and variable length
+//strings are not supported.
+
+const SIGNATURE: &[u8; 8] = b"\x89HDF\r\n\x1a\n";
+const UNDEFINED: u64 = u64::MAX;
+
+const DATASPACE: u16 = 0x1;
+const LINK_INFO: u16 = 0x2;
+const DATATYPE: u16 = 0x3;
+const LINK: u16 = 0x6;
+const LAYOUT: u16 = 0x8;
+const FILTER_PIPELINE: u16 = 0xb;
+const ATTRIBUTE: u16 = 0xc;
+const CONTINUATION: u16 = 0x10;
+const SYMBOL_TABLE: u16 = 0x11;
+
+const CLASS_FLOAT: u8 = 1;
+const CLASS_STRING: u8 = 3;
+
+pub(crate) struct Hdf5<'a> {
+    bytes: &'a [u8],
+    base: usize,
+    offset_size: usize,
+    length_size: usize,
+    pub root: u64,
+}
+
+//the contents of an attribute or dataset.
+#[derive(PartialEq, Debug)]
+pub(crate) enum Value {
+    Floats { shape: Vec<usize>, values: Vec<f32> },
+    Strings(Vec<String>),
+}
+
+//a position in the file that reads little endian values.
+struct Reader<'a, 'b> {
+    file: &'b Hdf5<'a>,
+    position: usize,
+}
+
+impl<'a, 'b> Reader<'a, 'b> {
+    fn bytes(&mut self, amount: usize) -> io::Result<&'a [u8]> {
+        let bytes = self.position.checked_add(amount).and_then(|end| self.file.bytes.get(self.position..end))
+            .ok_or_else(|| invalid_data("the hdf5 file is truncated.".to_string()))?;
+        self.position += amount;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, size: usize) -> io::Result<u64> {
+        Ok(self.bytes(size)?.iter().rev().fold(0, |value, &byte| value << 8 | byte as u64))
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(self.uint(2)? as u16)
+    }
+
+    fn offset(&mut self) -> io::Result<u64> {
+        let size = self.file.offset_size;
+        let value = self.uint(size)?;
+        Ok(if size < 8 && value == (1 << (size * 8)) - 1 { UNDEFINED } else { value })
+    }
+
+    fn length(&mut self) -> io::Result<u64> {
+        self.uint(self.file.length_size)
+    }
+
+    fn signature(&mut self, signature: &[u8]) -> io::Result<()> {
+        if self.bytes(signature.len())? != signature {
+            return Err(invalid_data(format!("expected {} in the hdf5 file.", String::from_utf8_lossy(signature))));
+        }
+        Ok(())
+    }
+}
+
+fn reader_of<'a, 'b>(file: &'b Hdf5<'a>, bytes: &'a [u8]) -> Reader<'a, 'b> {
+    //messages are parsed from slices of the file, so positions are taken relative to the start of it.
+    Reader { file, position: bytes.as_ptr() as usize - file.bytes.as_ptr() as usize }
+}
+
+//the amount of bytes of data with the given datatype and shape.
+fn data_size(datatype: &[u8], shape: &[usize]) -> io::Result<usize> {
+    if datatype.len() < 8 {
+        return Err(invalid_data("an hdf5 datatype message is too short.".to_string()));
+    }
+    let size = u32::from_le_bytes(datatype[4..8].try_into().unwrap()) as usize;
+    shape.iter().try_fold(size, |amount, &x| amount.checked_mul(x))
+        .ok_or_else(|| invalid_data("the size of hdf5 data overflows.".to_string()))
+}
+
+fn trim_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&x| x == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim_end().to_string()
+}
+
+impl<'a> Hdf5<'a> {
+    pub fn parse(bytes: &'a [u8]) -> io::Result<Hdf5<'a>> {
+        //the superblock is at 0 or after a user block of 512, 1024, 2048... bytes.
+        let base = std::iter::successors(Some(0usize), |x| Some(if *x == 0 { 512 } else { x * 2 }))
+            .take_while(|&x| x + SIGNATURE.len() <= bytes.len())
+            .find(|&x| &bytes[x..x + SIGNATURE.len()] == SIGNATURE)
+            .ok_or_else(|| invalid_data("not an hdf5 file.".to_string()))?;
+        let mut file = Hdf5 { bytes, base, offset_size: 8, length_size: 8, root: 0 };
+        let mut reader = Reader { file: &file, position: base + 8 };
+        let version = reader.u8()?;
+        let (offset_size, length_size) = match version {
+            0 | 1 => {
+                reader.position = base + 13;
+                (reader.u8()? as usize, reader.u8()? as usize)
+            }
+            2 | 3 => (reader.u8()? as usize, reader.u8()? as usize),
+            _ => return Err(invalid_data(format!("unsupported hdf5 superblock version {}.", version))),
+        };
+        if ![2, 4, 8].contains(&offset_size) || ![2, 4, 8].contains(&length_size) {
+            return Err(invalid_data("unsupported hdf5 offset size.".to_string()));
+        }
+        file.offset_size = offset_size;
+        file.length_size = length_size;
+        let mut reader = Reader { file: &file, position: 0 };
+        let root = match version {
+            0 | 1 => {
+                //flags and the b-tree constants, then base, free space, end of file and driver addresses
+                //and the symbol table entry of the root group.
+                reader.position = base + if version == 0 { 24 } else { 28 } + 4 * offset_size;
+                reader.offset()?;
+                reader.offset()?
+            }
+            _ => {
+                reader.position = base + 12 + 3 * offset_size;
+                reader.offset()?
+            }
+        };
+        file.root = root;
+        Ok(file)
+    }
+
+    fn at(&self, address: u64) -> io::Result<Reader<'a, '_>> {
+        match usize::try_from(address).ok().and_then(|x| self.base.checked_add(x)) {
+            Some(position) if address != UNDEFINED && position < self.bytes.len() => Ok(Reader { file: self, position }),
+            _ => Err(invalid_data(format!("address {} is outside the hdf5 file.", address))),
+        }
+    }
+
+    //every message of an object header, continuation blocks followed once each.
+    fn messages(&self, address: u64) -> io::Result<Vec<(u16, &'a [u8])>> {
+        let mut reader = self.at(address)?;
+        let mut messages = Vec::new();
+        let mut visited = HashSet::new();
+        let mut continuation = |data: &'a [u8]| -> io::Result<(usize, usize)> {
+            let mut continuation = reader_of(self, data);
+            let (address, length) = (continuation.offset()?, continuation.length()? as usize);
+            if !visited.insert(address) {
+                return Err(invalid_data("an hdf5 object header continues in a loop.".to_string()));
+            }
+            Ok((self.at(address)?.position, length))
+        };
+        let end = |start: usize, size: usize| start.checked_add(size)
+            .ok_or_else(|| invalid_data("an hdf5 object header is larger than the file.".to_string()));
+        if reader.file.bytes.get(reader.position..reader.position + 4) == Some(b"OHDR") {
+            reader.position += 4;
+            let _version = reader.u8()?;
+            let flags = reader.u8()?;
+            if flags & 0x20 != 0 {
+                reader.bytes(16)?;
+            }
+            if flags & 0x10 != 0 {
+                reader.bytes(4)?;
+            }
+            let size = reader.uint(1 << (flags & 3))? as usize;
+            let mut blocks = vec![(reader.position, size)];
+            while let Some((start, size)) = blocks.pop() {
+                reader.position = start;
+                let header_size = if flags & 0x04 != 0 { 6 } else { 4 };
+                let end = end(start, size)?;
+                while reader.position + header_size <= end {
+                    let message_type = reader.u8()? as u16;
+                    let message_size = reader.u16()? as usize;
+                    reader.bytes(header_size - 3)?;
+                    let data = reader.bytes(message_size)?;
+                    if message_type == CONTINUATION {
+                        let (position, length) = continuation(data)?;
+                        //skip the OCHK signature and leave out the checksum.
+                        let length = length.checked_sub(8)
+                            .ok_or_else(|| invalid_data("an hdf5 continuation block is too short.".to_string()))?;
+                        blocks.push((position + 4, length));
+                    } else {
+                        messages.push((message_type, data));
+                    }
+                }
+            }
+        } else {
+            let version = reader.u8()?;
+            if version != 1 {
+                return Err(invalid_data(format!("unsupported hdf5 object header version {}.", version)));
+            }
+            reader.u8()?;
+            let message_amount = reader.u16()? as usize;
+            reader.bytes(4)?;
+            let size = reader.uint(4)? as usize;
+            //the messages start 8 byte aligned after the 12 byte prefix.
+            let mut blocks = vec![(reader.position + 4, size)];
+            let mut read = 0;
+            while let Some((start, size)) = blocks.pop() {
+                reader.position = start;
+                let end = end(start, size)?;
+                while reader.position + 8 <= end && read < message_amount {
+                    let message_type = reader.u16()?;
+                    let message_size = reader.u16()? as usize;
+                    reader.bytes(4)?;
+                    let data = reader.bytes(message_size)?;
+                    read += 1;
+                    if message_type == CONTINUATION {
+                        blocks.push(continuation(data)?);
+                    } else {
+                        messages.push((message_type, data));
+                    }
+                }
+            }
+        }
+        Ok(messages)
+    }
+
+    //the name and object header address of every member of a group.
+    pub fn children(&self, address: u64) -> io::Result<Vec<(String, u64)>> {
+        let mut children = Vec::new();
+        for (message_type, data) in self.messages(address)? {
+            let mut reader = reader_of(self, data);
+            match message_type {
+                SYMBOL_TABLE => {
+                    let (tree, heap) = (reader.offset()?, reader.offset()?);
+                    let mut heap = self.at(heap)?;
+                    heap.signature(b"HEAP")?;
+                    heap.bytes(4)?;
+                    heap.length()?;
+                    heap.length()?;
+                    let names = heap.offset()?;
+                    self.symbol_tree(tree, None, names, &mut HashSet::new(), &mut children)?;
+                }
+                LINK => {
+                    reader.u8()?;
+                    let flags = reader.u8()?;
+                    let link_type = if flags & 0x08 != 0 { reader.u8()? } else { 0 };
+                    if flags & 0x04 != 0 {
+                        reader.bytes(8)?;
+                    }
+                    if flags & 0x10 != 0 {
+                        reader.u8()?;
+                    }
+                    let name_length = reader.uint(1 << (flags & 3))? as usize;
+                    let name = String::from_utf8_lossy(reader.bytes(name_length)?).to_string();
+                    //soft and external links are left out.
+                    if link_type == 0 {
+                        children.push((name, reader.offset()?));
+                    }
+                }
+                LINK_INFO => {
+                    reader.u8()?;
+                    let flags = reader.u8()?;
+                    if flags & 0x01 != 0 {
+                        reader.bytes(8)?;
+                    }
+                    if reader.offset()? != UNDEFINED {
+                        return Err(invalid_data("hdf5 groups with dense link storage are not supported.".to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(children)
+    }
+
+    //the nodes below a b-tree node are one level lower, which keeps malformed trees from looping.
+    fn symbol_tree(&self, address: u64, expected_level: Option<u8>, names: u64, visited: &mut HashSet<u64>, children: &mut Vec<(String, u64)>) -> io::Result<()> {
+        if !visited.insert(address) {
+            return Err(invalid_data("an hdf5 group refers to the same node twice.".to_string()));
+        }
+        let mut reader = self.at(address)?;
+        reader.signature(b"TREE")?;
+        reader.u8()?;
+        let level = reader.u8()?;
+        if expected_level.is_some_and(|expected| expected != level) {
+            return Err(invalid_data("an hdf5 group has a malformed b-tree.".to_string()));
+        }
+        let entries = reader.u16()?;
+        reader.offset()?;
+        reader.offset()?;
+        for _ in 0..entries {
+            reader.length()?;
+            let child = reader.offset()?;
+            if level > 0 {
+                self.symbol_tree(child, Some(level - 1), names, visited, children)?;
+                continue;
+            }
+            if !visited.insert(child) {
+                return Err(invalid_data("an hdf5 group refers to the same node twice.".to_string()));
+            }
+            let mut node = self.at(child)?;
+            node.signature(b"SNOD")?;
+            node.bytes(2)?;
+            for _ in 0..node.u16()? {
+                let name = node.offset()?;
+                let header = node.offset()?;
+                node.bytes(24)?;
+                let name = usize::try_from(name).ok()
+                    .and_then(|name| self.at(names).ok()?.position.checked_add(name))
+                    .and_then(|start| self.bytes.get(start..))
+                    .ok_or_else(|| invalid_data("an hdf5 link name is outside the file.".to_string()))?;
+                let name = trim_string(name);
+                children.push((name, header));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn child(&self, address: u64, name: &str) -> io::Result<Option<u64>> {
+        Ok(self.children(address)?.into_iter().find(|(child, _)| child == name).map(|(_, address)| address))
+    }
+
+    //follows a path like dense/kernel:0 from a group.
+    pub fn path(&self, mut address: u64, path: &str) -> io::Result<Option<u64>> {
+        for name in path.split('/').filter(|x| !x.is_empty()) {
+            match self.child(address, name)? {
+                Some(child) => address = child,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(address))
+    }
+
+    fn shape(&self, dataspace: &[u8]) -> io::Result<Vec<usize>> {
+        let mut reader = reader_of(self, dataspace);
+        let version = reader.u8()?;
+        let dimensionality = reader.u8()? as usize;
+        reader.u8()?;
+        reader.bytes(if version == 1 { 5 } else { 1 })?;
+        (0..dimensionality).map(|_| reader.length().map(|x| x as usize)).collect()
+    }
+
+    fn value(&self, datatype: &[u8], shape: Vec<usize>, data: &[u8]) -> io::Result<Option<Value>> {
+        let length = data_size(datatype, &shape)?;
+        let class = datatype[0] & 0x0f;
+        let size = u32::from_le_bytes(datatype[4..8].try_into().unwrap()) as usize;
+        if data.len() < length {
+            return Err(invalid_data("hdf5 data is shorter than its shape.".to_string()));
+        }
+        let data = &data[..length];
+        Ok(match (class, size) {
+            _ if class == CLASS_FLOAT && datatype[1] & 1 != 0 => {
+                return Err(invalid_data("big endian hdf5 floats are not supported.".to_string()));
+            }
+            (CLASS_FLOAT, 4) => Some(Value::Floats { shape, values: data.chunks(4).map(|x| f32::from_le_bytes(x.try_into().unwrap())).collect() }),
+            (CLASS_FLOAT, 8) => Some(Value::Floats { shape, values: data.chunks(8).map(|x| f64::from_le_bytes(x.try_into().unwrap()) as f32).collect() }),
+            (CLASS_STRING, _) if size > 0 => Some(Value::Strings(data.chunks(size).map(trim_string).collect())),
+            _ => None,
+        })
+    }
+
+    //the value of an attribute of an object, None when it is missing or of an unsupported type.
+    pub fn attribute(&self, address: u64, name: &str) -> io::Result<Option<Value>> {
+        for (message_type, data) in self.messages(address)? {
+            if message_type != ATTRIBUTE {
+                continue;
+            }
+            let mut reader = reader_of(self, data);
+            let version = reader.u8()?;
+            reader.u8()?;
+            let (name_size, datatype_size, dataspace_size) = (reader.u16()? as usize, reader.u16()? as usize, reader.u16()? as usize);
+            if version == 3 {
+                reader.u8()?;
+            }
+            let padded = |size: usize| if version == 1 { size.div_ceil(8) * 8 } else { size };
+            let attribute_name = trim_string(reader.bytes(padded(name_size))?);
+            let datatype = reader.bytes(padded(datatype_size))?;
+            let dataspace = reader.bytes(padded(dataspace_size))?;
+            if attribute_name != name || datatype.len() < 8 {
+                continue;
+            }
+            let shape = self.shape(dataspace)?;
+            let rest = self.bytes.get(reader.position..data.as_ptr() as usize - self.bytes.as_ptr() as usize + data.len())
+                .ok_or_else(|| invalid_data(format!("the attribute {} is longer than its message.", name)))?;
+            return self.value(datatype, shape, rest);
+        }
+        Ok(None)
+    }
+
+    //the values of a dataset, None when the object is no dataset of a supported type.
+    pub fn dataset(&self, address: u64) -> io::Result<Option<Value>> {
+        let messages = self.messages(address)?;
+        let find = |message_type: u16| messages.iter().find(|(x, _)| *x == message_type).map(|(_, data)| *data);
+        let (Some(dataspace), Some(datatype), Some(layout)) = (find(DATASPACE), find(DATATYPE), find(LAYOUT)) else {
+            return Ok(None);
+        };
+        if find(FILTER_PIPELINE).is_some() {
+            return Err(invalid_data("compressed hdf5 datasets are not supported.".to_string()));
+        }
+        let shape = self.shape(dataspace)?;
+        let mut reader = reader_of(self, layout);
+        let version = reader.u8()?;
+        let data = match version {
+            3 | 4 => match reader.u8()? {
+                0 => {
+                    let size = reader.u16()? as usize;
+                    reader.bytes(size)?
+                }
+                1 => {
+                    let (address, size) = (reader.offset()?, reader.length()? as usize);
+                    self.at(address)?.bytes(size)?
+                }
+                _ => return Err(invalid_data("chunked hdf5 datasets are not supported.".to_string())),
+            },
+            1 | 2 => {
+                let dimensionality = reader.u8()? as usize;
+                let class = reader.u8()?;
+                reader.bytes(5)?;
+                let address = if class != 0 { reader.offset()? } else { UNDEFINED };
+                reader.bytes(4 * dimensionality)?;
+                match class {
+                    0 => {
+                        let size = reader.uint(4)? as usize;
+                        reader.bytes(size)?
+                    }
+                    1 => {
+                        let size = data_size(datatype, &shape)?;
+                        self.at(address)?.bytes(size)?
+                    }
+                    _ => return Err(invalid_data("chunked hdf5 datasets are not supported.".to_string())),
+                }
+            }
+            _ => return Err(invalid_data(format!("unsupported hdf5 layout version {}.", version))),
+        };
+        self.value(datatype, shape, data)
+    }
+}
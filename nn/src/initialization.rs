@@ -0,0 +1,133 @@
+use matrix::{ColumnVector, Matrix};
+use rand::distributions::Uniform;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+
+//how the weights and biases of a new layer are drawn. the fan in of a layer is the
+//width of its weight matrix, the fan out its height.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Initialization {
+    //every weight and bias uniform in [low, high].
+    Uniform { low: f32, high: f32 },
+    //every weight and bias from a normal distribution.
+    Normal { mean: f32, standard_deviation: f32 },
+    //every weight and bias from a standard normal distribution.
+    StandardNormal,
+    //weights uniform in [-limit, limit] with limit = sqrt(6 / (fan in + fan out)) and zero biases.
+    //keeps the variance of the activations about the same from layer to layer, which sigmoid
+    //and tanh networks need to not saturate right away.
+    GlorotUniform,
+    //weights normal with mean 0 and standard deviation sqrt(2 / fan in) and zero biases.
+    //relu zeroes about half of its inputs, the doubled variance makes up for that so the
+    //activations neither explode nor vanish through deep relu stacks.
+    HeNormal,
+}
+
+//symmetric around 0, so a new network starts with positive and negative weights alike.
+impl Default for Initialization {
+    fn default() -> Self {
+        Initialization::Uniform { low: -0.5, high: 0.5 }
+    }
+}
+
+fn sample_matrix<R: Rng + ?Sized>(height: usize, width: usize, rng: &mut R, distribution: &impl Distribution<f32>) -> Matrix {
+    Matrix::from_vec((0..height)
+        .map(|_| (0..width).map(|_| distribution.sample(rng)).collect())
+        .collect())
+}
+
+fn sample_vector<R: Rng + ?Sized>(size: usize, rng: &mut R, distribution: &impl Distribution<f32>) -> ColumnVector {
+    ColumnVector::from_vec((0..size).map(|_| distribution.sample(rng)).collect())
+}
+
+impl Initialization {
+    //the weights and biases of a layer with the given height and width.
+    pub fn generate<R: Rng + ?Sized>(&self, height: usize, width: usize, rng: &mut R) -> (Matrix, ColumnVector) {
+        match *self {
+            Initialization::Uniform { low, high } => {
+                if low > high {
+                    panic!("the initialization range [{}, {}] is empty.", low, high);
+                }
+                let uniform = Uniform::new_inclusive(low, high);
+                (sample_matrix(height, width, rng, &uniform), sample_vector(height, rng, &uniform))
+            }
+            Initialization::Normal { mean, standard_deviation } => {
+                let normal = Normal::new(mean, standard_deviation)
+                    .unwrap_or_else(|_| panic!("{} is not a valid standard deviation.", standard_deviation));
+                (sample_matrix(height, width, rng, &normal), sample_vector(height, rng, &normal))
+            }
+            Initialization::StandardNormal => {
+                (Matrix::new_with_random_number_from_rng(height, width, rng), ColumnVector::new_with_random_number_from_rng(height, rng))
+            }
+            Initialization::GlorotUniform => {
+                let limit = (6.0 / (width + height) as f32).sqrt();
+                (sample_matrix(height, width, rng, &Uniform::new_inclusive(-limit, limit)), ColumnVector::new_with_elements(height, 0.0))
+            }
+            Initialization::HeNormal => {
+                let normal = Normal::new(0.0, (2.0 / width as f32).sqrt()).unwrap();
+                (sample_matrix(height, width, rng, &normal), ColumnVector::new_with_elements(height, 0.0))
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use matrix::ColumnVector;
+    use crate::{ActivationFunction, Initialization, NeuralNetwork};
+
+    #[test]
+    fn symmetric_default_and_custom_ranges() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let (weights, biases) = Initialization::default().generate(20, 30, &mut rng);
+        let values: Vec<f32> = weights.as_slice().iter().chain(&biases.data).cloned().collect();
+        assert!(values.iter().all(|x| x.abs() <= 0.5));
+        assert!(values.iter().filter(|&&x| x < 0.0).count() > values.len() / 3);
+        assert!(values.iter().filter(|&&x| x > 0.0).count() > values.len() / 3);
+
+        let (weights, biases) = Initialization::Uniform { low: 1.0, high: 2.0 }.generate(5, 4, &mut rng);
+        assert!(weights.as_slice().iter().chain(&biases.data).all(|x| (1.0..=2.0).contains(x)));
+        let (weights, _) = Initialization::Normal { mean: 3.0, standard_deviation: 0.1 }.generate(50, 40, &mut rng);
+        let mean = weights.as_slice().iter().sum::<f32>() / 2000.0;
+        assert!((mean - 3.0).abs() < 0.01, "mean {}", mean);
+    }
+
+    #[test]
+    #[should_panic]
+    fn empty_range() {
+        Initialization::Uniform { low: 0.5, high: -0.5 }.generate(2, 2, &mut StdRng::seed_from_u64(0));
+    }
+
+    #[test]
+    fn glorot_uniform() {
+        let (weights, biases) = Initialization::GlorotUniform.generate(30, 70, &mut StdRng::seed_from_u64(0));
+        assert_eq!((weights.height(), weights.width()), (30, 70));
+        assert_eq!(biases, ColumnVector::new_with_elements(30, 0.0));
+        //the limit is sqrt(6 / 100), the variance of the uniform distribution limit^2 / 3 = 0.02.
+        let limit = 0.06f32.sqrt();
+        let values: Vec<f32> = weights.as_slice().to_vec();
+        assert!(values.iter().all(|x| x.abs() <= limit));
+        assert!(values.iter().any(|&x| x < 0.0) && values.iter().any(|&x| x > 0.0));
+        let variance = values.iter().map(|x| x * x).sum::<f32>() / values.len() as f32;
+        assert!((variance - 0.02).abs() < 0.002, "variance {}", variance);
+    }
+
+    #[test]
+    fn he_normal() {
+        let (weights, biases) = Initialization::HeNormal.generate(40, 200, &mut StdRng::seed_from_u64(1));
+        assert_eq!((weights.height(), weights.width()), (40, 200));
+        assert_eq!(biases, ColumnVector::new_with_elements(40, 0.0));
+        let values: Vec<f32> = weights.as_slice().to_vec();
+        let variance = values.iter().map(|x| x * x).sum::<f32>() / values.len() as f32;
+        assert!((variance - 0.01).abs() < 0.001, "variance {}", variance);
+
+        //the activations keep their scale through a deep relu stack.
+        let network = NeuralNetwork::new_with_rng(&[100; 11], vec![ActivationFunction::Relu; 10], Initialization::HeNormal, &mut StdRng::seed_from_u64(2));
+        let input = ColumnVector::new_with_elements(100, 1.0);
+        let mean_square = network.infer(&input).magnitude_squared() / 100.0;
+        assert!(mean_square > 0.05 && mean_square < 20.0, "mean square {}", mean_square);
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde_json::Value as Json;
+use matrix::{ColumnVector, Matrix};
+use crate::hdf5::{Hdf5, Value};
+use crate::{invalid_data, ActivationFunction, Cost, NeuralNetwork};
+
+//keras files written by model.save("model.h5") or model.save_weights("model.h5") keep one group per layer,
+//listed in the layer_names attribute, whose weight_names attribute names the kernel and bias datasets.
+//keras stores the kernel as [inputs, outputs], so it is transposed into the weights of a layer here.
+
+fn strings(file: &Hdf5, address: u64, name: &str) -> io::Result<Option<Vec<String>>> {
+    Ok(match file.attribute(address, name)? {
+        Some(Value::Strings(strings)) => Some(strings),
+        _ => None,
+    })
+}
+
+//dense, dense_1, dense_2, ..., dense_10 in the order keras created them.
+fn natural_order(name: &str) -> (String, usize) {
+    match name.rsplit_once('_').and_then(|(prefix, number)| Some((prefix, number.parse::<usize>().ok()?))) {
+        Some((prefix, number)) => (prefix.to_string(), number + 1),
+        None => (name.to_string(), 0),
+    }
+}
+
+//every dataset below a group with its path, used when a layer has no weight_names attribute.
+//groups are visited once each, hard links may point back to a group above.
+fn datasets(file: &Hdf5, address: u64) -> io::Result<Vec<(String, u64)>> {
+    let mut found = Vec::new();
+    let mut visited = HashSet::from([address]);
+    let mut groups = vec![(address, String::new())];
+    while let Some((address, path)) = groups.pop() {
+        for (name, child) in file.children(address)? {
+            let child_path = if path.is_empty() { name } else { format!("{}/{}", path, name) };
+            if !visited.insert(child) {
+                continue;
+            }
+            if file.children(child)?.is_empty() {
+                found.push((child_path, child));
+            } else {
+                groups.push((child, child_path));
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn floats(file: &Hdf5, address: u64, name: &str) -> io::Result<(Vec<usize>, Vec<f32>)> {
+    match file.dataset(address)? {
+        Some(Value::Floats { shape, values }) => Ok((shape, values)),
+        _ => Err(invalid_data(format!("{} is not a float dataset.", name))),
+    }
+}
+
+//the kernel and bias of every layer that has weights, in the order of the model.
+fn dense_layers(file: &Hdf5) -> io::Result<Vec<(String, Matrix, ColumnVector)>> {
+    let weights_group = file.child(file.root, "model_weights")?.unwrap_or(file.root);
+    let children = file.children(weights_group)?;
+    let layer_names = match strings(file, weights_group, "layer_names")? {
+        Some(names) => names,
+        None => {
+            let mut names: Vec<String> = children.iter().map(|(name, _)| name.clone()).collect();
+            names.sort_by_key(|name| natural_order(name));
+            names
+        }
+    };
+    let mut layers = Vec::new();
+    for layer_name in layer_names {
+        let layer = children.iter().find(|(name, _)| *name == layer_name).map(|(_, address)| *address)
+            .ok_or_else(|| invalid_data(format!("the layer group {} is missing.", layer_name)))?;
+        let weights = match strings(file, layer, "weight_names")? {
+            Some(names) => names.into_iter()
+                .map(|name| match file.path(layer, &name)? {
+                    Some(address) => Ok((name, address)),
+                    None => Err(invalid_data(format!("the dataset {} of layer {} is missing.", name, layer_name))),
+                })
+                .collect::<io::Result<Vec<_>>>()?,
+            None => datasets(file, layer)?,
+        };
+        if weights.is_empty() {
+            continue;
+        }
+        let named = |prefix: &str| weights.iter().find(|(name, _)| name.rsplit('/').next().is_some_and(|x| x.starts_with(prefix)));
+        let (kernel_name, kernel) = named("kernel")
+            .ok_or_else(|| invalid_data(format!("layer {} has weights but no kernel, only dense layers are supported.", layer_name)))?;
+        let (shape, values) = floats(file, *kernel, kernel_name)?;
+        if shape.len() != 2 || shape.contains(&0) {
+            return Err(invalid_data(format!("the kernel of layer {} must be a non empty matrix, got shape {:?}.", layer_name, shape)));
+        }
+        let (inputs, outputs) = (shape[0], shape[1]);
+        let matrix = Matrix::from_vec((0..outputs).map(|output| (0..inputs).map(|input| values[input * outputs + output]).collect()).collect());
+        let bias = match named("bias") {
+            Some((bias_name, bias)) => {
+                let (shape, values) = floats(file, *bias, bias_name)?;
+                if shape != [outputs] {
+                    return Err(invalid_data(format!("the bias of layer {} has shape {:?}, expected [{}].", layer_name, shape, outputs)));
+                }
+                ColumnVector::from_vec(values)
+            }
+            None => ColumnVector::new_with_elements(outputs, 0.0),
+        };
+        if layers.last().is_some_and(|(_, previous, _): &(String, Matrix, ColumnVector)| previous.height() != inputs) {
+            return Err(invalid_data(format!("layer {} has {} inputs but the previous layer has a different amount of outputs.", layer_name, inputs)));
+        }
+        layers.push((layer_name, matrix, bias));
+    }
+    Ok(layers)
+}
+
+fn activation_of(name: &str) -> io::Result<ActivationFunction> {
+    Ok(match name {
+        "linear" => ActivationFunction::Identity,
+        "relu" => ActivationFunction::Relu,
+        "sigmoid" => ActivationFunction::Sigmoid,
+        "tanh" => ActivationFunction::Tanh,
+        "softmax" => ActivationFunction::Softmax,
+        "elu" => ActivationFunction::Elu(1.0),
+        "leaky_relu" => ActivationFunction::LeakyRelu(0.2),
+        "gelu" => ActivationFunction::Gelu,
+        "swish" | "silu" => ActivationFunction::Swish,
+        _ => return Err(invalid_data(format!("unsupported keras activation {}.", name))),
+    })
+}
+
+//the activation of every dense layer from the model_config attribute, following activation layers
+//like Activation("softmax") or LeakyReLU() apply to the dense layer before them.
+fn activations(model_config: &str) -> io::Result<Vec<(String, ActivationFunction)>> {
+    let config: Json = serde_json::from_str(model_config)
+        .map_err(|error| invalid_data(format!("the model_config attribute is not json: {}.", error)))?;
+    let layers = match &config["config"] {
+        Json::Array(layers) => layers,
+        other => other["layers"].as_array()
+            .ok_or_else(|| invalid_data("the model_config attribute has no layers.".to_string()))?,
+    };
+    let mut activations: Vec<(String, ActivationFunction)> = Vec::new();
+    for layer in layers {
+        let config = &layer["config"];
+        let parameter = |keys: &[&str], default: f32| keys.iter().find_map(|key| config[*key].as_f64()).map_or(default, |x| x as f32);
+        let activation = match layer["class_name"].as_str().unwrap_or_default() {
+            "Dense" => {
+                let name = config["name"].as_str().unwrap_or_default().to_string();
+                activations.push((name, activation_of(config["activation"].as_str().unwrap_or("linear"))?));
+                continue;
+            }
+            "Activation" => activation_of(config["activation"].as_str().unwrap_or("linear"))?,
+            "ReLU" => ActivationFunction::Relu,
+            "Softmax" => ActivationFunction::Softmax,
+            "LeakyReLU" => ActivationFunction::LeakyRelu(parameter(&["negative_slope", "alpha"], 0.3)),
+            "ELU" => ActivationFunction::Elu(parameter(&["alpha"], 1.0)),
+            _ => continue,
+        };
+        match activations.last_mut() {
+            Some((_, previous)) if *previous == ActivationFunction::Identity => *previous = activation,
+            _ => return Err(invalid_data("an activation layer must follow a dense layer without activation.".to_string())),
+        }
+    }
+    Ok(activations)
+}
+
+impl NeuralNetwork {
+    //reads dense networks saved by keras in the hdf5 format. The activations come from the model_config
+    //attribute of full model files, files of model.save_weights only hold the parameters and use relu
+    //like new_from_vecs. A final softmax makes cross entropy the cost.
+    pub fn from_keras_h5(bytes: &[u8]) -> io::Result<NeuralNetwork> {
+        let file = Hdf5::parse(bytes)?;
+        let layers = dense_layers(&file)?;
+        if layers.is_empty() {
+            return Err(invalid_data("the keras file has no dense layer.".to_string()));
+        }
+        let activations = match strings(&file, file.root, "model_config")? {
+            Some(config) => Some(activations(&config.concat())?),
+            None => None,
+        };
+        let names: Vec<String> = layers.iter().map(|(name, _, _)| name.clone()).collect();
+        let (weights, biases) = layers.into_iter().map(|(_, weights, biases)| (weights, biases)).unzip();
+        let mut network = NeuralNetwork::new_from_vecs(weights, Some(biases), None, None);
+        if let Some(activations) = activations {
+            network.activation_functions = names.iter()
+                .map(|name| activations.iter().find(|(layer, _)| layer == name).map(|(_, activation)| activation.clone())
+                    .ok_or_else(|| invalid_data(format!("layer {} is not a dense layer of the model_config attribute.", name))))
+                .collect::<io::Result<_>>()?;
+        }
+        if network.activation_functions.last() == Some(&ActivationFunction::Softmax) {
+            network.cost = Cost::CrossEntropy;
+        }
+        Ok(network)
+    }
+
+    pub fn load_keras_h5<P: AsRef<Path>>(file_path: P) -> io::Result<NeuralNetwork> {
+        NeuralNetwork::from_keras_h5(&fs::read(file_path)?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use matrix::{ColumnVector, Matrix};
+    use crate::{ActivationFunction, Cost, NeuralNetwork};
+
+    const UNDEFINED: u64 = u64::MAX;
+
+    //writes the hdf5 subset h5py produces by default: a version 0 superblock, version 1 object headers,
+    //symbol table groups and contiguous datasets.
+    struct Writer {
+        bytes: Vec<u8>,
+    }
+
+    fn padded(mut bytes: Vec<u8>) -> Vec<u8> {
+        bytes.resize(bytes.len().div_ceil(8) * 8, 0);
+        bytes
+    }
+
+    fn dataspace(shape: &[usize]) -> Vec<u8> {
+        let mut bytes = vec![1, shape.len() as u8, 0, 0, 0, 0, 0, 0];
+        bytes.extend(shape.iter().flat_map(|&x| (x as u64).to_le_bytes()));
+        bytes
+    }
+
+    impl Writer {
+        fn new() -> Writer {
+            //the superblock is filled in by finish.
+            Writer { bytes: vec![0; 96] }
+        }
+
+        fn append(&mut self, bytes: &[u8]) -> u64 {
+            self.bytes.resize(self.bytes.len().div_ceil(8) * 8, 0);
+            let address = self.bytes.len() as u64;
+            self.bytes.extend(bytes);
+            address
+        }
+
+        fn object_header(&mut self, messages: Vec<(u16, Vec<u8>)>) -> u64 {
+            let messages: Vec<(u16, Vec<u8>)> = messages.into_iter().map(|(message_type, data)| (message_type, padded(data))).collect();
+            let size: usize = messages.iter().map(|(_, data)| 8 + data.len()).sum();
+            let mut bytes = vec![1, 0];
+            bytes.extend((messages.len() as u16).to_le_bytes());
+            bytes.extend(1u32.to_le_bytes());
+            bytes.extend((size as u32).to_le_bytes());
+            bytes.extend([0; 4]);
+            for (message_type, data) in messages {
+                bytes.extend(message_type.to_le_bytes());
+                bytes.extend((data.len() as u16).to_le_bytes());
+                bytes.extend([0; 4]);
+                bytes.extend(data);
+            }
+            self.append(&bytes)
+        }
+
+        fn string_attribute(name: &str, strings: &[&str], shape: &[usize]) -> (u16, Vec<u8>) {
+            let size = strings.iter().map(|x| x.len()).max().unwrap_or(0).max(1);
+            let mut datatype = vec![0x13, 0, 0, 0];
+            datatype.extend((size as u32).to_le_bytes());
+            let mut name = name.as_bytes().to_vec();
+            name.push(0);
+            let dataspace = dataspace(shape);
+            let mut bytes = vec![1, 0];
+            bytes.extend((name.len() as u16).to_le_bytes());
+            bytes.extend((datatype.len() as u16).to_le_bytes());
+            bytes.extend((dataspace.len() as u16).to_le_bytes());
+            bytes.extend(padded(name));
+            bytes.extend(padded(datatype));
+            bytes.extend(padded(dataspace));
+            for string in strings {
+                let mut string = string.as_bytes().to_vec();
+                string.resize(size, 0);
+                bytes.extend(string);
+            }
+            (0xc, bytes)
+        }
+
+        fn dataset(&mut self, shape: &[usize], values: &[f32]) -> u64 {
+            let data: Vec<u8> = values.iter().flat_map(|x| x.to_le_bytes()).collect();
+            let address = self.append(&data);
+            let mut datatype = vec![0x11, 0x20, 0x1f, 0];
+            datatype.extend(4u32.to_le_bytes());
+            datatype.extend([0, 0, 32, 0, 23, 8, 0, 23]);
+            datatype.extend(127u32.to_le_bytes());
+            let mut layout = vec![3, 1];
+            layout.extend(address.to_le_bytes());
+            layout.extend((data.len() as u64).to_le_bytes());
+            self.object_header(vec![(0x1, dataspace(shape)), (0x3, datatype), (0x8, layout)])
+        }
+
+        fn group(&mut self, children: &[(&str, u64)], attributes: Vec<(u16, Vec<u8>)>) -> u64 {
+            let mut names = vec![0; 8];
+            let mut entries = Vec::new();
+            for (name, address) in children {
+                let offset = names.len() as u64;
+                names.extend(name.as_bytes());
+                names = padded([names, vec![0]].concat());
+                entries.extend(offset.to_le_bytes());
+                entries.extend(address.to_le_bytes());
+                entries.extend([0; 24]);
+            }
+            let data = self.append(&names);
+            let mut heap = b"HEAP\0\0\0\0".to_vec();
+            heap.extend((names.len() as u64).to_le_bytes());
+            heap.extend(UNDEFINED.to_le_bytes());
+            heap.extend(data.to_le_bytes());
+            let heap = self.append(&heap);
+            let mut node = b"SNOD\x01\0".to_vec();
+            node.extend((children.len() as u16).to_le_bytes());
+            node.extend(entries);
+            let node = self.append(&node);
+            let mut tree = b"TREE\0\0\x01\0".to_vec();
+            tree.extend(UNDEFINED.to_le_bytes());
+            tree.extend(UNDEFINED.to_le_bytes());
+            tree.extend(0u64.to_le_bytes());
+            tree.extend(node.to_le_bytes());
+            tree.extend(0u64.to_le_bytes());
+            let tree = self.append(&tree);
+            let mut symbol_table = tree.to_le_bytes().to_vec();
+            symbol_table.extend(heap.to_le_bytes());
+            self.object_header([vec![(0x11, symbol_table)], attributes].concat())
+        }
+
+        fn finish(mut self, root: u64) -> Vec<u8> {
+            let mut superblock = b"\x89HDF\r\n\x1a\n".to_vec();
+            superblock.extend([0, 0, 0, 0, 0, 8, 8, 0, 4, 0, 16, 0, 0, 0, 0, 0]);
+            superblock.extend(0u64.to_le_bytes());
+            superblock.extend(UNDEFINED.to_le_bytes());
+            superblock.extend((self.bytes.len() as u64).to_le_bytes());
+            superblock.extend(UNDEFINED.to_le_bytes());
+            superblock.extend(0u64.to_le_bytes());
+            superblock.extend(root.to_le_bytes());
+            superblock.resize(96, 0);
+            self.bytes[..96].copy_from_slice(&superblock);
+            self.bytes
+        }
+    }
+
+    fn keras_file(model_config: Option<&str>) -> Vec<u8> {
+        let mut writer = Writer::new();
+        //a kernel of 3 inputs and 2 outputs, then one of 2 inputs and 2 outputs.
+        let kernel = writer.dataset(&[3, 2], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let bias = writer.dataset(&[2], &[0.5, -0.5]);
+        let inner = writer.group(&[("bias:0", bias), ("kernel:0", kernel)], vec![]);
+        let dense = writer.group(&[("dense", inner)], vec![
+            Writer::string_attribute("weight_names", &["dense/kernel:0", "dense/bias:0"], &[2]),
+        ]);
+        let dropout = writer.group(&[], vec![Writer::string_attribute("weight_names", &[], &[0])]);
+        let kernel = writer.dataset(&[2, 2], &[1.0, 0.0, 0.0, -1.0]);
+        let bias = writer.dataset(&[2], &[0.0, 1.0]);
+        let inner = writer.group(&[("kernel:0", kernel), ("bias:0", bias)], vec![]);
+        let dense_1 = writer.group(&[("dense_1", inner)], vec![
+            Writer::string_attribute("weight_names", &["dense_1/kernel:0", "dense_1/bias:0"], &[2]),
+        ]);
+        let weights = writer.group(&[("dense", dense), ("dense_1", dense_1), ("dropout", dropout)], vec![
+            Writer::string_attribute("layer_names", &["dense", "dropout", "dense_1"], &[3]),
+        ]);
+        let attributes = model_config.map(|config| vec![Writer::string_attribute("model_config", &[config], &[])]).unwrap_or_default();
+        let root = writer.group(&[("model_weights", weights)], attributes);
+        writer.finish(root)
+    }
+
+    #[test]
+    fn keras_import() {
+        let model_config = r#"{"class_name": "Sequential", "config": {"name": "sequential", "layers": [
+            {"class_name": "InputLayer", "config": {"batch_input_shape": [null, 3]}},
+            {"class_name": "Dense", "config": {"name": "dense", "units": 2, "activation": "relu"}},
+            {"class_name": "Dropout", "config": {"name": "dropout", "rate": 0.2}},
+            {"class_name": "Dense", "config": {"name": "dense_1", "units": 2, "activation": "linear"}},
+            {"class_name": "Activation", "config": {"name": "activation", "activation": "softmax"}}]}}"#;
+        let network = NeuralNetwork::from_keras_h5(&keras_file(Some(model_config))).unwrap();
+        assert_eq!(network.weights[0], Matrix::from_vec(vec![vec![1.0, 3.0, 5.0], vec![2.0, 4.0, 6.0]]));
+        assert_eq!(network.biases[0].data, vec![0.5, -0.5]);
+        assert_eq!(network.weights[1], Matrix::from_vec(vec![vec![1.0, 0.0], vec![0.0, -1.0]]));
+        assert_eq!(network.activation_functions, vec![ActivationFunction::Relu, ActivationFunction::Softmax]);
+        assert_eq!(network.cost, Cost::CrossEntropy);
+        let output = network.infer(&ColumnVector::from_vec(vec![1.0, 0.0, 0.0]));
+        assert!((output.data.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+
+        let weights_only = NeuralNetwork::from_keras_h5(&keras_file(None)).unwrap();
+        assert_eq!(weights_only.activation_functions, vec![ActivationFunction::Relu, ActivationFunction::Relu]);
+        assert!(NeuralNetwork::from_keras_h5(b"not an hdf5 file").is_err());
+    }
+
+    fn invalid(bytes: &[u8]) -> bool {
+        NeuralNetwork::from_keras_h5(bytes).is_err_and(|error| error.kind() == io::ErrorKind::InvalidData)
+    }
+
+    //a file of one dense layer without weight_names, so its datasets are found by walking the group.
+    fn layer_file(mut writer: Writer, kernel_shape: &[usize], extra: Option<&str>) -> Vec<u8> {
+        let kernel = writer.dataset(kernel_shape, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let bias = writer.dataset(&[2], &[0.5, -0.5]);
+        let mut children = vec![("kernel:0", kernel), ("bias:0", bias)];
+        if let Some(name) = extra {
+            //a hard link back to the layer group itself, at the address the group is written to.
+            let mut probe = Writer { bytes: writer.bytes.clone() };
+            let address = probe.group(&[children.clone(), vec![(name, 0)]].concat(), vec![]);
+            children.push((name, address));
+        }
+        let dense = writer.group(&children, vec![]);
+        let root = writer.group(&[("dense", dense)], vec![]);
+        writer.finish(root)
+    }
+
+    #[test]
+    fn malformed_files() {
+        let file = keras_file(None);
+        for length in 0..file.len() {
+            assert!(invalid(&file[..length]), "a file truncated to {} bytes", length);
+        }
+        assert!(invalid(&layer_file(Writer::new(), &[1 << 40, 1 << 40], None)));
+
+        //a group that contains itself is walked once.
+        let network = NeuralNetwork::from_keras_h5(&layer_file(Writer::new(), &[3, 2], Some("loop"))).unwrap();
+        assert_eq!(network.weights[0], Matrix::from_vec(vec![vec![1.0, 3.0, 5.0], vec![2.0, 4.0, 6.0]]));
+
+        //an object header whose continuation block is its own messages, read again and again.
+        let mut writer = Writer::new();
+        let address = writer.bytes.len() as u64;
+        let mut continuation = (address + 16).to_le_bytes().to_vec();
+        continuation.extend(48u64.to_le_bytes());
+        let root = writer.object_header(vec![(0x10, continuation), (0x0, vec![0; 16])]);
+        writer.bytes[root as usize + 2..root as usize + 4].copy_from_slice(&u16::MAX.to_le_bytes());
+        let error = NeuralNetwork::from_keras_h5(&writer.finish(root)).unwrap_err();
+        assert!(error.to_string().contains("loop"), "{}", error);
+
+        //a version 2 object header whose continuation block is too short for its checksum.
+        let mut writer = Writer::new();
+        let address = writer.bytes.len() as u64;
+        let mut header = b"OHDR\x02\0\x14\x10\x10\0\0".to_vec();
+        header.extend(address.to_le_bytes());
+        header.extend(4u64.to_le_bytes());
+        header.extend([0; 4]);
+        let root = writer.append(&header);
+        assert!(invalid(&writer.finish(root)));
+
+        //a symbol table b-tree whose only node is itself.
+        let mut writer = Writer::new();
+        let address = writer.bytes.len() as u64;
+        let mut tree = b"TREE\0\x01\x01\0".to_vec();
+        tree.extend(UNDEFINED.to_le_bytes());
+        tree.extend(UNDEFINED.to_le_bytes());
+        tree.extend(0u64.to_le_bytes());
+        tree.extend(address.to_le_bytes());
+        tree.extend(0u64.to_le_bytes());
+        let tree = writer.append(&tree);
+        let mut heap = b"HEAP\0\0\0\0".to_vec();
+        heap.extend(0u64.to_le_bytes());
+        heap.extend(UNDEFINED.to_le_bytes());
+        heap.extend(0u64.to_le_bytes());
+        let heap = writer.append(&heap);
+        let root = writer.object_header(vec![(0x11, [tree.to_le_bytes(), heap.to_le_bytes()].concat())]);
+        assert!(invalid(&writer.finish(root)));
+    }
+}
@@ -0,0 +1,581 @@
+use std::any::Any;
+use std::fmt;
+use std::fmt::Debug;
+use std::iter::zip;
+use matrix::{ColumnVector, Matrix};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::{Activation, ActivationFunction, BatchNorm, Dropout, Initialization, LayerNorm, Mode, NeuralNetwork, Normalization, NormalizedValues};
+
+//a parameter of a layer together with the gradient accumulated for it.
+pub struct Param<'a> {
+    pub value: &'a mut f32,
+    pub gradient: &'a mut f32,
+    //whether the l2 and l1 penalties of the trainer apply. weights and kernels are regularized,
+    //biases are not, like in NeuralNetwork::l2_penalty.
+    pub regularized: bool,
+}
+
+//how the values passed between layers are laid out. they are always flattened into a
+//ColumnVector, images channel by channel and every channel row by row.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Shape {
+    Flat(usize),
+    Image { channels: usize, height: usize, width: usize },
+}
+
+impl Shape {
+    pub fn image(channels: usize, height: usize, width: usize) -> Shape {
+        Shape::Image { channels, height, width }
+    }
+
+    //amount of values.
+    pub fn size(&self) -> usize {
+        match *self {
+            Shape::Flat(size) => size,
+            Shape::Image { channels, height, width } => channels * height * width,
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shape::Flat(size) => write!(f, "({})", size),
+            Shape::Image { channels, height, width } => write!(f, "({}, {}, {})", channels, height, width),
+        }
+    }
+}
+
+//one step of a stack of layers. forward remembers whatever backward needs,
+//so backward always refers to the last forward pass.
+pub trait Layer: Any + Debug + Send + Sync {
+    fn forward(&mut self, input: &ColumnVector) -> ColumnVector;
+
+    //takes the gradient with respect to the output, adds the gradients of the parameters to the
+    //ones accumulated so far and returns the gradient with respect to the input.
+    fn backward(&mut self, gradient: &ColumnVector) -> ColumnVector;
+
+    //the shape of the output for an input of input_shape, or why the layer does not accept it.
+    //layers that keep the shape of their input keep the default.
+    fn output_shape(&self, input_shape: Shape) -> Result<Shape, String> {
+        Ok(input_shape)
+    }
+
+    //every parameter with its gradient, layers without parameters keep the default.
+    fn params(&mut self) -> Vec<Param<'_>> {
+        Vec::new()
+    }
+
+    //the amount of values params returns.
+    fn parameter_count(&self) -> usize {
+        0
+    }
+
+    //shown by Model::summary, the name of the type by default.
+    fn name(&self) -> String {
+        std::any::type_name::<Self>().rsplit("::").next().unwrap().to_string()
+    }
+
+    //layers that behave differently while training, like dropout, override this.
+    fn set_mode(&mut self, _mode: Mode) {}
+
+    //draws the randomness used while training, like dropout masks, from rng.
+    fn reseed(&mut self, _rng: &mut StdRng) {}
+
+    //a copy of the layer for another thread, see Model::parallel_batch_gradients_and_loss. layers
+    //with randomness seed the copy from their own rng, so every copy draws different values.
+    //layers that can not be copied keep the default and the model stays on the calling thread.
+    fn fork(&mut self) -> Option<Box<dyn Layer>> {
+        None
+    }
+}
+
+//sets the accumulated gradients of every layer back to 0.
+pub fn zero_gradients(layers: &mut [Box<dyn Layer>]) {
+    layers.iter_mut()
+        .flat_map(|layer| layer.params())
+        .for_each(|param| *param.gradient = 0.0);
+}
+
+//plain gradient descent step on the accumulated gradients of every layer.
+pub fn apply_gradients(layers: &mut [Box<dyn Layer>], learning_rate: f32) {
+    layers.iter_mut()
+        .flat_map(|layer| layer.params())
+        .for_each(|param| *param.value -= learning_rate * *param.gradient);
+}
+
+//a fully connected layer without activation, z = weights * input + biases.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Dense {
+    pub weights: Matrix,
+    pub biases: ColumnVector,
+    pub weight_gradients: Matrix,
+    pub bias_gradients: ColumnVector,
+    input: ColumnVector,
+}
+
+impl Dense {
+    pub fn new(weights: Matrix, biases: ColumnVector) -> Dense {
+        if weights.height() != biases.data.len() {
+            panic!("expected {} biases, got {}.", weights.height(), biases.data.len());
+        }
+        Dense {
+            weight_gradients: Matrix::zeros(weights.height(), weights.width()),
+            bias_gradients: ColumnVector::new_with_elements(biases.data.len(), 0.0),
+            input: ColumnVector::new_with_elements(weights.width(), 0.0),
+            weights,
+            biases,
+        }
+    }
+
+    pub fn random<R: Rng + ?Sized>(input_size: usize, output_size: usize, initialization: Initialization, rng: &mut R) -> Dense {
+        let (weights, biases) = initialization.generate(output_size, input_size, rng);
+        Dense::new(weights, biases)
+    }
+}
+
+impl Layer for Dense {
+    fn forward(&mut self, input: &ColumnVector) -> ColumnVector {
+        self.input = input.clone();
+        let mut z_values = ColumnVector::new_with_elements(self.biases.data.len(), 0.0);
+        input._mul_matrix(&self.weights, &mut z_values);
+        z_values += &self.biases;
+        z_values
+    }
+
+    fn backward(&mut self, gradient: &ColumnVector) -> ColumnVector {
+        gradient._add_outer(&self.input, &mut self.weight_gradients);
+        self.bias_gradients += gradient;
+        let mut propagated = ColumnVector::new_with_elements(self.input.data.len(), 0.0);
+        gradient._mul_transposed_matrix(&self.weights, &mut propagated);
+        propagated
+    }
+
+    fn output_shape(&self, input_shape: Shape) -> Result<Shape, String> {
+        let input_size = self.weights.width();
+        match input_shape {
+            Shape::Flat(size) if size == input_size => Ok(Shape::Flat(self.biases.data.len())),
+            Shape::Flat(size) => Err(format!("a dense layer with {} inputs can not follow {} outputs.", input_size, size)),
+            Shape::Image { .. } => Err(format!("a dense layer can not follow an image of {}, flatten it first.", input_shape)),
+        }
+    }
+
+    //every weight row by row and then every bias, like NeuralNetwork::parameters_mut.
+    fn params(&mut self) -> Vec<Param<'_>> {
+        let weights = zip(self.weights.as_mut_slice(), self.weight_gradients.as_mut_slice())
+            .map(|(value, gradient)| Param { value, gradient, regularized: true });
+        let biases = zip(&mut self.biases.data, &mut self.bias_gradients.data)
+            .map(|(value, gradient)| Param { value, gradient, regularized: false });
+        weights.chain(biases).collect()
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.weights.as_slice().len() + self.biases.data.len()
+    }
+
+    fn fork(&mut self) -> Option<Box<dyn Layer>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+//applies an activation function to every input, without parameters.
+#[derive(Debug, Clone)]
+pub struct ActivationLayer {
+    pub activation_function: ActivationFunction,
+    z_values: ColumnVector,
+}
+
+impl ActivationLayer {
+    pub fn new(activation_function: ActivationFunction) -> ActivationLayer {
+        ActivationLayer {
+            activation_function,
+            z_values: ColumnVector::new_with_elements(0, 0.0),
+        }
+    }
+}
+
+impl Layer for ActivationLayer {
+    fn forward(&mut self, input: &ColumnVector) -> ColumnVector {
+        self.z_values = input.clone();
+        self.activation_function.apply(input)
+    }
+
+    fn backward(&mut self, gradient: &ColumnVector) -> ColumnVector {
+        self.activation_function.backward(&self.z_values, gradient)
+    }
+
+    fn name(&self) -> String {
+        format!("Activation({})", self.activation_function.name().unwrap_or_else(|| "custom".to_string()))
+    }
+
+    fn fork(&mut self) -> Option<Box<dyn Layer>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+//inverted dropout of the inputs while training, like Dropout on the hidden layers of a network.
+//in inference the inputs are passed on as they are.
+#[derive(Debug, Clone)]
+pub struct DropoutLayer {
+    pub dropout: Dropout,
+    mode: Mode,
+}
+
+impl DropoutLayer {
+    pub fn new(dropout: Dropout) -> DropoutLayer {
+        DropoutLayer { dropout, mode: Mode::Inference }
+    }
+}
+
+impl Layer for DropoutLayer {
+    fn forward(&mut self, input: &ColumnVector) -> ColumnVector {
+        let mut output = input.clone();
+        if self.mode == Mode::Training {
+            self.dropout.apply(0, &mut output);
+        }
+        output
+    }
+
+    fn backward(&mut self, gradient: &ColumnVector) -> ColumnVector {
+        match self.mode {
+            Mode::Training => gradient.hadamard(self.dropout.mask(0)),
+            Mode::Inference => gradient.clone(),
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("Dropout({})", self.dropout.probability)
+    }
+
+    fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    fn reseed(&mut self, rng: &mut StdRng) {
+        self.dropout.rng = StdRng::seed_from_u64(rng.gen());
+    }
+
+    fn fork(&mut self) -> Option<Box<dyn Layer>> {
+        let mut copy = self.clone();
+        copy.dropout.rng = StdRng::seed_from_u64(self.dropout.rng.gen());
+        Some(Box::new(copy))
+    }
+}
+
+//batch or layer normalization of the inputs followed by a gamma and beta per input, like the
+//normalization of a hidden layer of a network. the normalization holds a single layer. a layer
+//only sees one sample at a time, so batch normalization always uses its running statistics,
+//which training leaves as they are.
+#[derive(Debug, Clone)]
+pub struct NormalizationLayer {
+    pub normalization: Normalization,
+    pub gamma_gradients: ColumnVector,
+    pub beta_gradients: ColumnVector,
+    values: Option<NormalizedValues>,
+}
+
+impl NormalizationLayer {
+    pub fn new(normalization: Normalization) -> NormalizationLayer {
+        let gammas = normalization.gammas();
+        if gammas.len() != 1 || normalization.betas().len() != 1 || normalization.betas()[0].data.len() != gammas[0].data.len() {
+            panic!("a normalization layer needs the gammas and betas of exactly one layer.");
+        }
+        let size = gammas[0].data.len();
+        NormalizationLayer {
+            normalization,
+            gamma_gradients: ColumnVector::new_with_elements(size, 0.0),
+            beta_gradients: ColumnVector::new_with_elements(size, 0.0),
+            values: None,
+        }
+    }
+
+    pub fn batch_norm(size: usize) -> NormalizationLayer {
+        NormalizationLayer::new(Normalization::Batch(BatchNorm::new(&[size])))
+    }
+
+    pub fn layer_norm(size: usize) -> NormalizationLayer {
+        NormalizationLayer::new(Normalization::Layer(LayerNorm::new(&[size])))
+    }
+
+    fn size(&self) -> usize {
+        self.gamma_gradients.data.len()
+    }
+}
+
+impl Layer for NormalizationLayer {
+    fn forward(&mut self, input: &ColumnVector) -> ColumnVector {
+        let values = self.normalization.forward(0, input);
+        let output = values.pre_activation.clone();
+        self.values = Some(values);
+        output
+    }
+
+    fn backward(&mut self, gradient: &ColumnVector) -> ColumnVector {
+        let values = self.values.as_ref().expect("backward needs a forward pass first.");
+        let (propagated, gamma_gradient, beta_gradient) = self.normalization.backward(0, values, gradient.clone());
+        self.gamma_gradients += &gamma_gradient;
+        self.beta_gradients += &beta_gradient;
+        propagated
+    }
+
+    fn output_shape(&self, input_shape: Shape) -> Result<Shape, String> {
+        match input_shape {
+            Shape::Flat(size) if size == self.size() => Ok(input_shape),
+            _ => Err(format!("a normalization layer of {} values can not follow {}.", self.size(), input_shape)),
+        }
+    }
+
+    //every gamma and then every beta, none of them regularized.
+    fn params(&mut self) -> Vec<Param<'_>> {
+        let (gammas, betas) = self.normalization.gammas_and_betas_mut();
+        let gammas = zip(&mut gammas[0].data, &mut self.gamma_gradients.data)
+            .map(|(value, gradient)| Param { value, gradient, regularized: false });
+        let betas = zip(&mut betas[0].data, &mut self.beta_gradients.data)
+            .map(|(value, gradient)| Param { value, gradient, regularized: false });
+        gammas.chain(betas).collect()
+    }
+
+    fn parameter_count(&self) -> usize {
+        2 * self.size()
+    }
+
+    fn name(&self) -> String {
+        match self.normalization {
+            Normalization::Batch(_) => "BatchNorm".to_string(),
+            Normalization::Layer(_) => "LayerNorm".to_string(),
+        }
+    }
+
+    fn fork(&mut self) -> Option<Box<dyn Layer>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+//turns images into flat values for dense layers. the values are flat already,
+//so only the shape changes.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Flatten;
+
+impl Layer for Flatten {
+    fn forward(&mut self, input: &ColumnVector) -> ColumnVector {
+        input.clone()
+    }
+
+    fn backward(&mut self, gradient: &ColumnVector) -> ColumnVector {
+        gradient.clone()
+    }
+
+    fn output_shape(&self, input_shape: Shape) -> Result<Shape, String> {
+        Ok(Shape::Flat(input_shape.size()))
+    }
+
+    fn fork(&mut self) -> Option<Box<dyn Layer>> {
+        Some(Box::new(Flatten))
+    }
+}
+
+//a skip connection around a block of layers: the output is the input of the block added
+//to the output of its last layer, so the block has to keep the shape of its input.
+#[derive(Debug)]
+pub struct Residual {
+    pub layers: Vec<Box<dyn Layer>>,
+}
+
+impl Residual {
+    pub fn new(layers: Vec<Box<dyn Layer>>) -> Residual {
+        Residual { layers }
+    }
+}
+
+impl Layer for Residual {
+    fn forward(&mut self, input: &ColumnVector) -> ColumnVector {
+        let mut output = self.layers.iter_mut().fold(input.clone(), |x, layer| layer.forward(&x));
+        output += input;
+        output
+    }
+
+    //the gradient reaches the input along both branches.
+    fn backward(&mut self, gradient: &ColumnVector) -> ColumnVector {
+        let mut propagated = self.layers.iter_mut().rev().fold(gradient.clone(), |x, layer| layer.backward(&x));
+        propagated += gradient;
+        propagated
+    }
+
+    fn output_shape(&self, input_shape: Shape) -> Result<Shape, String> {
+        let output_shape = self.layers.iter().try_fold(input_shape, |shape, layer| layer.output_shape(shape))?;
+        if output_shape != input_shape {
+            return Err(format!("a residual block has to keep the shape {}, its output is {}.", input_shape, output_shape));
+        }
+        Ok(output_shape)
+    }
+
+    fn params(&mut self) -> Vec<Param<'_>> {
+        self.layers.iter_mut().flat_map(|layer| layer.params()).collect()
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.layers.iter().map(|layer| layer.parameter_count()).sum()
+    }
+
+    fn set_mode(&mut self, mode: Mode) {
+        self.layers.iter_mut().for_each(|layer| layer.set_mode(mode));
+    }
+
+    fn reseed(&mut self, rng: &mut StdRng) {
+        self.layers.iter_mut().for_each(|layer| layer.reseed(rng));
+    }
+
+    fn fork(&mut self) -> Option<Box<dyn Layer>> {
+        let layers = self.layers.iter_mut().map(|layer| layer.fork()).collect::<Option<Vec<_>>>()?;
+        Some(Box::new(Residual::new(layers)))
+    }
+}
+
+impl NeuralNetwork {
+    //a dense layer followed by an activation layer for every layer of the network, with a
+    //normalization layer before the activation and a dropout layer after it on the hidden layers
+    //that have them. preprocessing is left out, it has to be applied separately. a network whose
+    //biases, activations or normalization do not fit its weights is an error.
+    pub fn to_layers(&self) -> Result<Vec<Box<dyn Layer>>, String> {
+        let layer_amount = self.weights.len();
+        if self.biases.len() != layer_amount || self.activation_functions.len() != layer_amount {
+            return Err(format!("the network has {} weight matrices, {} bias vectors and {} activation functions.", layer_amount, self.biases.len(), self.activation_functions.len()));
+        }
+        if let Some(index) = zip(&self.weights, &self.biases).position(|(weights, biases)| weights.height() != biases.data.len()) {
+            return Err(format!("layer {} has {} outputs but {} biases.", index, self.weights[index].height(), self.biases[index].data.len()));
+        }
+        if let Some(normalization) = &self.normalization {
+            let hidden_layer_sizes = self.hidden_layer_sizes();
+            let fits = |vectors: &[ColumnVector]| vectors.iter().map(|x| x.data.len()).eq(hidden_layer_sizes.iter().copied());
+            let statistics_fit = match normalization {
+                Normalization::Batch(batch_norm) => fits(&batch_norm.running_means) && fits(&batch_norm.running_variances),
+                Normalization::Layer(_) => true,
+            };
+            if !fits(normalization.gammas()) || !fits(normalization.betas()) || !statistics_fit {
+                return Err("the normalization does not fit the hidden layers of the network.".to_string());
+            }
+        }
+        //every dropout layer draws its own masks.
+        let mut rng = self.dropout.as_ref().map(|dropout| dropout.rng.clone());
+        let mut layers: Vec<Box<dyn Layer>> = Vec::new();
+        for (layer_index, ((weights, biases), activation_function)) in zip(zip(&self.weights, &self.biases), &self.activation_functions).enumerate() {
+            let hidden = layer_index < layer_amount - 1;
+            layers.push(Box::new(Dense::new(weights.clone(), biases.clone())));
+            if let (true, Some(normalization)) = (hidden, &self.normalization) {
+                layers.push(Box::new(NormalizationLayer::new(normalization.layer(layer_index))));
+            }
+            layers.push(Box::new(ActivationLayer::new(activation_function.clone())));
+            if let (true, Some(dropout), Some(rng)) = (hidden, &self.dropout, &mut rng) {
+                let mut layer = DropoutLayer::new(Dropout::new_with_seed(dropout.probability, rng.gen()));
+                layer.set_mode(self.mode);
+                layers.push(Box::new(layer));
+            }
+        }
+        Ok(layers)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::ColumnVector;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::{apply_gradients, check_layer_gradients, zero_gradients, ActivationFunction, ActivationLayer, Cost, Dense, Dropout, DropoutLayer, Flatten, Initialization, Layer, LayerNorm, Loss, Mode, NeuralNetwork, Normalization, NormalizationLayer, Residual, Shape};
+
+    #[test]
+    fn layers_match_the_network() {
+        let mut network = NeuralNetwork::new_with_rng(&[3, 4, 2], vec![ActivationFunction::Tanh, ActivationFunction::Sigmoid], Initialization::GlorotUniform, &mut StdRng::seed_from_u64(4));
+        let mut layers = network.to_layers().unwrap();
+        assert_eq!(layers.len(), 4);
+        let input = ColumnVector::from_vec(vec![0.5, -1.0, 2.0]);
+        let desired = ColumnVector::from_vec(vec![0.0, 1.0]);
+        let output = layers.iter_mut().fold(input.clone(), |x, layer| layer.forward(&x));
+        assert_eq!(output, network.infer(&input));
+
+        let gradients = network.backpropagation(&input, &desired);
+        let gradient = Cost::SquaredError.gradient(&output, &desired);
+        layers.iter_mut().rev().fold(gradient, |x, layer| layer.backward(&x));
+        let layer_gradients: Vec<f32> = layers.iter_mut().flat_map(|layer| layer.params()).map(|param| *param.gradient).collect();
+        let mut expected: Vec<f32> = gradients.weights[0].as_slice().iter().chain(&gradients.biases[0].data).cloned().collect();
+        expected.extend(gradients.weights[1].as_slice().iter().chain(&gradients.biases[1].data));
+        assert_eq!(layer_gradients.len(), expected.len());
+        for (actual, expected) in layer_gradients.iter().zip(&expected) {
+            assert!((actual - expected).abs() < 1e-6);
+        }
+
+        network.apply_gradients(&gradients, 0.1);
+        apply_gradients(&mut layers, 0.1);
+        let output = layers.iter_mut().fold(input.clone(), |x, layer| layer.forward(&x));
+        assert_eq!(output, network.infer(&input));
+        zero_gradients(&mut layers);
+        assert!(layers.iter_mut().flat_map(|layer| layer.params()).all(|param| *param.gradient == 0.0));
+    }
+
+    #[test]
+    fn shapes() {
+        let dense = Dense::random(12, 5, Initialization::default(), &mut StdRng::seed_from_u64(0));
+        assert_eq!(dense.output_shape(Shape::Flat(12)), Ok(Shape::Flat(5)));
+        assert!(dense.output_shape(Shape::Flat(11)).is_err());
+        assert_eq!(dense.output_shape(Shape::image(3, 2, 2)), Err("a dense layer can not follow an image of (3, 2, 2), flatten it first.".to_string()));
+        assert_eq!(Flatten.output_shape(Shape::image(3, 2, 2)), Ok(Shape::Flat(12)));
+    }
+
+    #[test]
+    fn residual_gradients() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let mut residual = Residual::new(vec![
+            Box::new(Dense::random(3, 3, Initialization::StandardNormal, &mut rng)),
+            Box::new(ActivationLayer::new(ActivationFunction::Tanh)),
+        ]);
+        assert_eq!(residual.output_shape(Shape::Flat(3)), Ok(Shape::Flat(3)));
+        let check = check_layer_gradients(&mut residual, &ColumnVector::from_vec(vec![0.3, -0.7, 1.1]));
+        assert!(check.max_relative_error < 1e-2, "{:?}", check);
+        assert_eq!(residual.params().len(), 3 * 3 + 3);
+        assert_eq!(residual.parameter_count(), 3 * 3 + 3);
+
+        let narrowing = Residual::new(vec![Box::new(Dense::random(3, 2, Initialization::default(), &mut rng))]);
+        assert_eq!(narrowing.output_shape(Shape::Flat(3)), Err("a residual block has to keep the shape (3), its output is (2).".to_string()));
+    }
+
+    #[test]
+    fn normalization_and_dropout_layers() {
+        let input = ColumnVector::from_vec(vec![0.3, -0.7, 1.1]);
+        for mut layer in [NormalizationLayer::layer_norm(3), NormalizationLayer::batch_norm(3)] {
+            layer.params().iter_mut().enumerate().for_each(|(index, param)| *param.value += 0.1 * index as f32);
+            let check = check_layer_gradients(&mut layer, &input);
+            assert!(check.max_relative_error < 1e-2, "{} {:?}", layer.name(), check);
+            assert_eq!(layer.parameter_count(), 6);
+            assert!(layer.output_shape(Shape::Flat(4)).is_err());
+        }
+
+        let mut dropout = DropoutLayer::new(Dropout::new_with_seed(0.5, 2));
+        let input = ColumnVector::new_with_elements(100, 1.0);
+        assert_eq!(dropout.forward(&input), input);
+        dropout.set_mode(Mode::Training);
+        let output = dropout.forward(&input);
+        assert!(output.data.iter().all(|&x| x == 0.0 || x == 2.0) && output != input.map(|x| 2.0 * x));
+        assert_eq!(dropout.backward(&input), output);
+        //a copy for another thread draws other masks.
+        assert_ne!(dropout.fork().unwrap().forward(&input), dropout.forward(&input));
+    }
+
+    #[test]
+    fn normalized_networks_with_dropout_become_layers() {
+        let mut network = NeuralNetwork::new_with_seed(&[3, 4, 4, 2], vec![ActivationFunction::Tanh, ActivationFunction::Relu, ActivationFunction::Sigmoid], 3);
+        network.normalization = Some(Normalization::Layer(LayerNorm::for_network(&network)));
+        network.dropout = Some(Dropout::new_with_seed(0.2, 4));
+        let mut layers = network.to_layers().unwrap();
+        let names: Vec<String> = layers.iter().map(|layer| layer.name()).collect();
+        assert_eq!(names, ["Dense", "LayerNorm", "Activation(tanh)", "Dropout(0.2)", "Dense", "LayerNorm", "Activation(relu)", "Dropout(0.2)", "Dense", "Activation(sigmoid)"]);
+        let input = ColumnVector::from_vec(vec![0.5, -1.0, 2.0]);
+        assert_eq!(layers.iter_mut().fold(input.clone(), |x, layer| layer.forward(&x)), network.infer(&input));
+
+        network.normalization = Some(Normalization::Layer(LayerNorm::new(&[4])));
+        assert_eq!(network.to_layers().unwrap_err(), "the normalization does not fit the hidden layers of the network.");
+        network.normalization = None;
+        network.activation_functions.pop();
+        assert!(network.to_layers().is_err());
+    }
+}
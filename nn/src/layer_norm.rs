@@ -0,0 +1,89 @@
+use std::iter::zip;
+use matrix::ColumnVector;
+use serde::{Deserialize, Serialize};
+use crate::NeuralNetwork;
+
+//layer normalization of the z values of every hidden layer, before the nonlinearity.
+//each sample is normalized across its own features, so it behaves the same
+//for any batch size and in training and inference.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct LayerNorm {
+    pub epsilon: f32,
+    pub gammas: Vec<ColumnVector>,
+    pub betas: Vec<ColumnVector>,
+}
+
+impl LayerNorm {
+    pub fn new(hidden_layer_sizes: &[usize]) -> LayerNorm {
+        LayerNorm {
+            epsilon: 1e-5,
+            gammas: hidden_layer_sizes.iter().map(|&x| ColumnVector::new_with_elements(x, 1.0)).collect(),
+            betas: hidden_layer_sizes.iter().map(|&x| ColumnVector::new_with_elements(x, 0.0)).collect(),
+        }
+    }
+
+    //one set of parameters for every hidden layer of the network.
+    pub fn for_network(network: &NeuralNetwork) -> LayerNorm {
+        LayerNorm::new(&network.hidden_layer_sizes())
+    }
+
+    //returns the normalized values together with 1 / sqrt(variance + epsilon).
+    pub fn normalize(&self, z_values: &ColumnVector) -> (ColumnVector, f32) {
+        let mean = z_values.average();
+        let variance = z_values.data.iter().map(|z| (z - mean).powi(2)).sum::<f32>() / z_values.data.len() as f32;
+        let inverse_std = 1.0 / (variance + self.epsilon).sqrt();
+        (z_values.map(|z| (z - mean) * inverse_std), inverse_std)
+    }
+
+    pub fn scale_and_shift(&self, layer_index: usize, normalized: &ColumnVector) -> ColumnVector {
+        ColumnVector::from_vec(zip(zip(&normalized.data, &self.gammas[layer_index].data), &self.betas[layer_index].data)
+            .map(|((x, gamma), beta)| gamma * x + beta)
+            .collect())
+    }
+
+    //gradient with respect to the z values given the gradient with respect to the scaled and shifted values.
+    pub fn backward(&self, layer_index: usize, scaled_delta: &ColumnVector, normalized: &ColumnVector, inverse_std: f32) -> ColumnVector {
+        let size = normalized.data.len() as f32;
+        let normalized_deltas: Vec<f32> = zip(&scaled_delta.data, &self.gammas[layer_index].data)
+            .map(|(delta, gamma)| delta * gamma)
+            .collect();
+        let delta_sum: f32 = normalized_deltas.iter().sum();
+        let delta_dot: f32 = zip(&normalized_deltas, &normalized.data).map(|(delta, x)| delta * x).sum();
+        ColumnVector::from_vec(zip(&normalized_deltas, &normalized.data)
+            .map(|(delta, x)| inverse_std / size * (size * delta - delta_sum - x * delta_dot))
+            .collect())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::{ColumnVector, Matrix};
+    use crate::{check_gradients, LayerNorm, NeuralNetwork, Normalization};
+
+    #[test]
+    fn layer_norm_normalizes_each_sample() {
+        let layer_norm = LayerNorm::new(&[4]);
+        let (normalized, _) = layer_norm.normalize(&ColumnVector::from_vec(vec![1.0, 2.0, 3.0, 6.0]));
+        assert!(normalized.average().abs() < 1e-6);
+        assert!((normalized.magnitude_squared() / 4.0 - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn layer_norm_gradients_against_finite_differences() {
+        let weights = vec![
+            Matrix::from_vec(vec![vec![0.5, -0.2, 0.1], vec![0.3, 0.8, -0.4], vec![-0.6, 0.1, 0.9]]),
+            Matrix::from_vec(vec![vec![0.7, 0.6, -0.3], vec![-0.2, 0.9, 0.4]]),
+        ];
+        let mut network = NeuralNetwork::new_from_vecs(weights, None, None, None);
+        let mut layer_norm = LayerNorm::for_network(&network);
+        layer_norm.gammas[0] = ColumnVector::from_vec(vec![1.2, 0.8, 1.1]);
+        layer_norm.betas[0] = ColumnVector::from_vec(vec![0.5, 0.4, 0.3]);
+        network.normalization = Some(Normalization::Layer(layer_norm));
+
+        let input = ColumnVector::from_vec(vec![1.0, 0.5, 0.25]);
+        let desired = ColumnVector::from_vec(vec![0.0, 1.0]);
+        let check = check_gradients(&mut network, &input, &desired);
+        assert!(check.max_absolute_error < 1e-2, "{:?}", check);
+    }
+}
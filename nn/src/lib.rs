@@ -1,46 +1,149 @@
 use std::collections::VecDeque;
 use std::iter::{zip};
 // use std::ops::Deref;
-use rand::Rng;
-use matrix::{ColumnVector, Matrix};
+use matrix::{ColumnVector, Matrix, Scalar};
 use std::{fmt};
 use std::fmt::Debug;
-use std::io::{BufReader, Read, Write};
+use std::io::{self, BufReader, Read, Write};
 use crate::SerializerIteratorNNState::{Biases, LayerAmount, LayerSizes, Weights};
 use itertools::{Itertools};
-use rand::thread_rng;
-use rand::seq::SliceRandom;
+use mnist_reader::Preprocessing;
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
+mod activation;
+mod batch_norm;
+mod callback;
+mod checkpoint;
+mod classification_report;
+mod confusion_matrix;
+mod convolution;
+mod cost;
+mod cross_validation;
+mod csv_logger;
+#[cfg(feature = "cuda")]
+mod cuda_backend;
+mod device;
+mod double_precision;
+mod dropout;
+mod early_stopping;
+mod evaluation;
+mod gradient_check;
+mod half_precision;
+mod hdf5;
+mod initialization;
+mod keras;
+mod layer;
+mod layer_norm;
+mod metric;
+mod model;
+mod model_file;
+mod normalization;
+mod npz;
+mod onnx;
+mod optimizer;
+mod pooling;
+mod prediction;
+mod progress_bar;
+mod protobuf;
+mod quantization;
+mod resume;
+mod safetensors_file;
+mod scheduler;
+mod summary;
+mod tensorboard;
+mod trainable;
+mod trainer;
+#[cfg(feature = "wgpu")]
+mod wgpu_backend;
 
-fn sigmoid(z: f32) -> f32 {
-    1.0 / (1.0 + std::f32::consts::E.powf(-z))
+pub use activation::{Activation, ActivationFunction, Elu, Gelu, Identity, LeakyRelu, Relu, Sigmoid, Softmax, Swish, Tanh};
+pub use batch_norm::BatchNorm;
+pub use callback::{BatchEnd, Callback, Control, EpochEnd};
+pub use checkpoint::{Keep, ModelCheckpoint, Monitor};
+pub use classification_report::{ClassMetrics, ClassificationReport};
+pub use confusion_matrix::ConfusionMatrix;
+pub use convolution::Conv2d;
+pub use cost::{Cost, CrossEntropy, Huber, Loss, LossAccumulator, MeanAbsoluteError, SquaredError};
+pub use cross_validation::{cross_validate, dataset_loss, CrossValidation};
+pub use csv_logger::CsvLogger;
+#[cfg(feature = "cuda")]
+pub use cuda_backend::CudaBackend;
+pub use device::{Backend, CpuBackend, Device, DeviceBuffer, DeviceNetwork};
+pub use double_precision::DoublePrecisionNetwork;
+pub use dropout::{Dropout, Mode};
+pub use early_stopping::EarlyStopping;
+pub use evaluation::Evaluation;
+pub use gradient_check::{check_gradients, check_layer_gradients, Differentiable, GradientCheck};
+pub use half_precision::{HalfPrecisionNetwork, MixedPrecisionNetwork};
+pub use initialization::Initialization;
+pub use layer::{apply_gradients, zero_gradients, ActivationLayer, Dense, DropoutLayer, Flatten, Layer, NormalizationLayer, Param, Residual, Shape};
+pub use layer_norm::LayerNorm;
+pub use metric::{Metric, TopKAccuracy};
+pub use model::{Model, SequentialBuilder};
+pub use model_file::{ModelMetadata, FORMAT_VERSION};
+pub use normalization::{Normalization, NormalizedValues};
+pub use optimizer::{Adam, Momentum, Optimizer, OptimizerState, RmsProp, Sgd};
+pub use pooling::{AvgPool2d, MaxPool2d, PoolWindows};
+pub use prediction::Prediction;
+pub use progress_bar::ProgressBar;
+pub use quantization::{QuantizationParameters, QuantizedLayer, QuantizedNetwork};
+pub use resume::TrainingState;
+pub use scheduler::{ConstantLr, CosineAnnealing, ExponentialDecay, LrScheduler, StepDecay, Warmup};
+pub use summary::{LayerSummary, Summary};
+pub use tensorboard::TensorBoard;
+pub use trainable::{GradientValues, Trainable};
+pub use trainer::{GradientClipping, Trainer};
+#[cfg(feature = "wgpu")]
+pub use wgpu_backend::WgpuBackend;
+
+
+pub fn sigmoid<T: Scalar>(z: T) -> T {
+    T::one() / (T::one() + constant::<T>(std::f64::consts::E).powf(-z))
+}
+
+pub fn sigmoid_deriv<T: Scalar>(z: T) -> T {
+    let activation = sigmoid(z);
+    activation * (T::one() - activation)
+}
+
+//a constant of the math shared by every precision, e.g. 0.5 in the squared error.
+pub(crate) fn constant<T: Scalar>(value: f64) -> T {
+    T::from_f64(value).unwrap()
+}
+
+//the error of every model file reader for a file that does not follow its format.
+pub(crate) fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
 }
 
-fn cost_deriv(Y: &ColumnVector, A: &ColumnVector) -> ColumnVector {
-    Y - A
+//derivative of the squared error with respect to the output activations.
+fn cost_deriv<T: Scalar>(output_activations: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> ColumnVector<T> {
+    output_activations - desired_output
 }
 
-fn relu_deriv(z: f32) -> f32 {
-    if z < 0.0 {
-        0.0
+pub fn relu_deriv<T: Scalar>(z: T) -> T {
+    if z < T::zero() {
+        T::zero()
     } else {
-        1.0
+        T::one()
     }
 }
 
-fn relu_deriv_vec(z: &ColumnVector) -> ColumnVector {
-    z.apply(|x| { relu(x) })
+pub fn relu_deriv_vec(z: &ColumnVector) -> ColumnVector {
+    z.apply(relu_deriv)
 }
 
-fn relu(z: f32) -> f32 {
-    if z < 0.0 {
-        0.0
+pub fn relu<T: Scalar>(z: T) -> T {
+    if z < T::zero() {
+        T::zero()
     } else {
         z
     }
 }
 
-fn relu_vec(z: &ColumnVector) -> ColumnVector {
+pub fn relu_vec(z: &ColumnVector) -> ColumnVector {
     let mut inner_vec = z.data.clone();
     inner_vec.iter_mut().for_each(|x| {
         *x = relu(*x);
@@ -48,49 +151,260 @@ fn relu_vec(z: &ColumnVector) -> ColumnVector {
     ColumnVector::from_vec(inner_vec)
 }
 
-fn softmax(z: &ColumnVector, index: usize) -> f32 {
-    z.data[index] / z.average()
+//subtracting the largest element first keeps exp from overflowing, the result is the same.
+pub fn softmax<T: Scalar>(z: &ColumnVector<T>) -> ColumnVector<T> {
+    let max = z.data.iter().cloned().fold(T::neg_infinity(), T::max);
+    let exponentials: Vec<T> = z.data.iter().map(|&x| (x - max).exp()).collect();
+    let sum: T = exponentials.iter().copied().sum();
+    ColumnVector::from_vec(exponentials.iter().map(|&x| x / sum).collect())
 }
 
-fn squared_error(output_vector: &ColumnVector, desired_output: &ColumnVector) -> f32 {
-    (output_vector - desired_output).magnitude_squared() * 0.5
+pub fn squared_error<T: Scalar>(output_vector: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> T {
+    (output_vector - desired_output).magnitude_squared() * constant(0.5)
 }
 
-#[derive(PartialEq, Debug)]
-struct NeuralNetwork {
+//outputs are clamped to this before taking the log so a zero probability stays finite.
+const CROSS_ENTROPY_EPSILON: f64 = 1e-7;
+
+//turns a one hot target into a soft one: every class gets epsilon / classes and the
+//remaining 1 - epsilon goes to the original target, so the result still sums to 1.
+pub fn smooth_labels(desired_output: &ColumnVector, epsilon: f32) -> ColumnVector {
+    if !(0.0..=1.0).contains(&epsilon) {
+        panic!("label smoothing epsilon must be in [0, 1].");
+    }
+    let uniform = epsilon / desired_output.data.len() as f32;
+    ColumnVector::from_vec(desired_output.data.iter().map(|x| x * (1.0 - epsilon) + uniform).collect())
+}
+
+pub fn cross_entropy<T: Scalar>(output_vector: &ColumnVector<T>, desired_output: &ColumnVector<T>) -> T {
+    -zip(&output_vector.data, &desired_output.data)
+        .map(|(&output, &desired)| desired * output.max(constant(CROSS_ENTROPY_EPSILON)).ln())
+        .sum::<T>()
+}
+
+//every part of a network can be serialized with serde, the parameters, layer sizes and settings
+//alike, except for custom activations and losses. the values cached by the last forward pass
+//are scratch space and are left out, a deserialized network allocates them on its first pass.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NeuralNetwork {
     pub weights: Vec<Matrix>,
+    #[serde(skip)]
     pub activation_values: VecDeque<ColumnVector>,
+    #[serde(skip)]
     pub z_values: VecDeque<ColumnVector>,
     pub biases: Vec<ColumnVector>,
+    pub mode: Mode,
+    pub dropout: Option<Dropout>,
+    pub normalization: Option<Normalization>,
+    //one per layer, the output layer included.
+    pub activation_functions: Vec<ActivationFunction>,
+    pub cost: Cost,
+    //applied to every input before the first layer, in training and in inference alike.
+    pub preprocessing: Option<Preprocessing>,
+    //one per layer, see freeze. layers without an entry are not frozen.
+    #[serde(default)]
+    pub frozen: Vec<bool>,
+    //one per thread of the last batch, see parallel_batch_gradients_and_loss.
+    #[serde(skip)]
+    batch_buffers: Vec<BatchBuffers>,
+}
+
+//networks are equal when their parameters and settings are, whatever their last forward pass was.
+impl PartialEq for NeuralNetwork {
+    fn eq(&self, other: &Self) -> bool {
+        self.weights == other.weights
+            && self.biases == other.biases
+            && self.mode == other.mode
+            && self.dropout == other.dropout
+            && self.normalization == other.normalization
+            && self.activation_functions == other.activation_functions
+            && self.cost == other.cost
+            && self.preprocessing == other.preprocessing
+            && self.frozen == other.frozen
+    }
+}
+
+//inference only needs &self and no part of a network is interior mutable, so a trained one
+//can be shared between threads behind an Arc without a lock. this fails to compile otherwise.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<NeuralNetwork>();
+};
+
+//storage reused by NeuralNetwork::infer_with, so repeated inference does not allocate the
+//z values of every layer again. the values of the last call stay readable afterwards.
+#[derive(PartialEq, Debug, Default)]
+pub struct InferenceBuffers {
+    pub z_values: Vec<ColumnVector>,
+    //the preprocessed input followed by the activations of every layer.
+    pub activation_values: Vec<ColumnVector>,
+}
+
+//what the backward pass of a batch needs from its forward pass, one column per sample, and the
+//gradients it computes. the matrices are kept from batch to batch and only allocated again
+//when the size of the batch or the network changes.
+#[derive(Default)]
+struct BatchBuffers {
+    //the preprocessed inputs followed by the activations of every layer.
+    activations: Vec<Matrix>,
+    z_values: Vec<Matrix>,
+    //one entry per sample and hidden layer, empty without normalization.
+    normalized: Vec<Vec<NormalizedValues>>,
+    //the dropout mask of every hidden layer, row major like the activations.
+    masks: Vec<Vec<f32>>,
+    //the error of every layer, and the error of the layer above sent back through its weights.
+    deltas: Vec<Matrix>,
+    propagated: Vec<Matrix>,
+    gradients: Option<Gradients>,
+}
+
+//scratch space, a clone allocates its own on its first batch.
+impl Clone for BatchBuffers {
+    fn clone(&self) -> Self {
+        BatchBuffers::default()
+    }
+}
+
+impl Debug for BatchBuffers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchBuffers").finish_non_exhaustive()
+    }
+}
+
+//makes matrix a height x width matrix, keeping it when it already is one.
+fn reuse_matrix(matrix: &mut Matrix, height: usize, width: usize) {
+    if matrix.height() != height || matrix.width() != width {
+        *matrix = Matrix::zeros(height, width);
+    }
+}
+
+fn set_column(matrix: &mut Matrix, index: usize, column: &ColumnVector) {
+    zip(matrix.rows_mut(), &column.data).for_each(|(row, value)| row[index] = *value);
+}
+
+//gradients of the cost with respect to each weight matrix and bias vector, one entry per layer.
+//gammas and betas hold one entry per hidden layer when the network uses normalization
+//and are empty otherwise. T is the precision of the network they belong to.
+#[derive(PartialEq, Debug)]
+pub struct Gradients<T: Scalar = f32> {
+    pub weights: Vec<Matrix<T>>,
+    pub biases: Vec<ColumnVector<T>>,
+    pub gammas: Vec<ColumnVector<T>>,
+    pub betas: Vec<ColumnVector<T>>,
+}
+
+impl Gradients {
+    pub fn zeros_like(network: &NeuralNetwork) -> Gradients {
+        Gradients {
+            weights: network.weights.iter()
+                .map(|x| Matrix::zeros(x.height(), x.width()))
+                .collect(),
+            biases: network.biases.iter()
+                .map(|x| ColumnVector::new_with_elements(x.data.len(), 0.0))
+                .collect(),
+            gammas: network.normalization.iter()
+                .flat_map(|x| x.gammas().iter())
+                .map(|x| ColumnVector::new_with_elements(x.data.len(), 0.0))
+                .collect(),
+            betas: network.normalization.iter()
+                .flat_map(|x| x.betas().iter())
+                .map(|x| ColumnVector::new_with_elements(x.data.len(), 0.0))
+                .collect(),
+        }
+    }
+
+    //adds the gradient of NeuralNetwork::l2_penalty to the weight gradients.
+    pub fn add_l2_penalty(&mut self, network: &NeuralNetwork, lambda: f32) {
+        let gradient_iter = self.weights.iter_mut()
+            .flat_map(|x| x.as_mut_slice().iter_mut());
+        zip(gradient_iter, network.weight_values()).for_each(|(gradient, weight)| {
+            *gradient += lambda * weight;
+        });
+    }
+
+    //adds the (sub)gradient of NeuralNetwork::l1_penalty to the weight gradients.
+    //weights that are exactly zero get no push in either direction.
+    pub fn add_l1_penalty(&mut self, network: &NeuralNetwork, lambda: f32) {
+        let gradient_iter = self.weights.iter_mut()
+            .flat_map(|x| x.as_mut_slice().iter_mut());
+        zip(gradient_iter, network.weight_values()).for_each(|(gradient, &weight)| {
+            if weight != 0.0 {
+                *gradient += lambda * weight.signum();
+            }
+        });
+    }
+}
+
+impl GradientValues for Gradients {
+    //every weight gradient (row by row), then every bias, gamma and beta gradient.
+    //this is the same order as NeuralNetwork::parameters_mut.
+    fn values(&self) -> impl Iterator<Item=&f32> {
+        self.weights.iter()
+            .flat_map(|x| x.as_slice().iter())
+            .chain(self.biases.iter().flat_map(|x| x.data.iter()))
+            .chain(self.gammas.iter().flat_map(|x| x.data.iter()))
+            .chain(self.betas.iter().flat_map(|x| x.data.iter()))
+    }
+
+    fn values_mut(&mut self) -> impl Iterator<Item=&mut f32> {
+        self.weights.iter_mut()
+            .flat_map(|x| x.as_mut_slice().iter_mut())
+            .chain(self.biases.iter_mut().flat_map(|x| x.data.iter_mut()))
+            .chain(self.gammas.iter_mut().flat_map(|x| x.data.iter_mut()))
+            .chain(self.betas.iter_mut().flat_map(|x| x.data.iter_mut()))
+    }
 }
 
 impl NeuralNetwork {
-    fn _forward_pass_one_step<'a>(&mut self, layer_index: usize) {
+    fn _forward_pass_one_step(&mut self, layer_index: usize) {
         //The moves may still trigger memory allocation.
         let input = self.activation_values.pop_front().unwrap();
         let mut z_values = self.z_values.pop_front().unwrap();
-        let mut activations = self.activation_values.pop_front().unwrap();
+        //the activations of this layer are recomputed, the old slot is dropped.
+        self.activation_values.pop_front().unwrap();
         let weights = &self.weights[layer_index];
         let bias = &mut self.biases[layer_index];
+        let activation_function = &self.activation_functions[layer_index];
         input._mul_matrix(weights, &mut z_values);
         z_values += bias;
-        z_values._apply(relu, &mut activations);
+        let mut activations = match &self.normalization {
+            Some(normalization) if layer_index < self.weights.len() - 1 => {
+                activation_function.apply(&normalization.forward(layer_index, &z_values).pre_activation)
+            }
+            _ => activation_function.apply(&z_values),
+        };
+        if self.mode == Mode::Training && layer_index < self.weights.len() - 1 {
+            if let Some(dropout) = &mut self.dropout {
+                dropout.apply(layer_index, &mut activations);
+            }
+        }
         self.activation_values.push_back(input);
         self.activation_values.push_front(activations);
         self.z_values.push_back(z_values);
     }
 
-    // fn _backwords_pass_first_step(&mut self, input_vector: &ColumnVector, desired_vector: &ColumnVector) -> (Matrix, ColumnVector) {
-    //     if layer_index == self.weights.len() {
-    //         cost_deriv(self.activation_values.iter().last().unwrap(), desired_vector)*relu_deriv(
-    //     } else {
-    //
-    //     }
-    // }
+    //(re)allocates the values cached by the forward pass when they do not fit the layers,
+    //e.g. after deserialization.
+    fn allocate_cached_values(&mut self) {
+        if self.z_values.len() == self.weights.len() && self.activation_values.len() == self.weights.len() + 1 {
+            return;
+        }
+        self.activation_values = std::iter::once(self.weights[0].width())
+            .chain(self.weights.iter().map(|x| x.height()))
+            .map(|size| ColumnVector::new_with_elements(size, 0.0))
+            .collect();
+        self.z_values = self.weights.iter()
+            .map(|x| ColumnVector::new_with_elements(x.height(), 0.0))
+            .collect();
+    }
 
     pub fn calculate_all_activation_values(&mut self, input: &ColumnVector) {
+        self.allocate_cached_values();
         for (index, elem) in input.data.iter().enumerate() {
-            self.activation_values[0].data[index] = *elem;
+            self.activation_values[0].data[index] = match &self.preprocessing {
+                Some(preprocessing) => preprocessing.apply_to_value(*elem),
+                None => *elem,
+            };
         }
 
 
@@ -101,14 +415,139 @@ impl NeuralNetwork {
         self.activation_values.push_back(output);
     }
 
-    pub fn calculate_mean_square_error(mut self, inputs: &Vec<ColumnVector>, expected_outputs: &Vec<ColumnVector>) -> f32 {
-        zip(inputs, expected_outputs).map(|(input, expected)| {
+    //the output for input in inference mode. unlike calculate_all_activation_values this
+    //leaves the network untouched, so it can be shared between callers.
+    pub fn infer(&self, input: &ColumnVector) -> ColumnVector {
+        let mut buffers = InferenceBuffers::default();
+        self.infer_with(input, &mut buffers);
+        buffers.activation_values.pop().unwrap()
+    }
+
+    //like infer, but keeps the intermediate values in buffers, which can be reused for the next input.
+    pub fn infer_with<'a>(&self, input: &ColumnVector, buffers: &'a mut InferenceBuffers) -> &'a ColumnVector {
+        let layer_amount = self.weights.len();
+        buffers.z_values.resize_with(layer_amount, || ColumnVector::new_with_elements(0, 0.0));
+        buffers.activation_values.resize_with(layer_amount + 1, || ColumnVector::new_with_elements(0, 0.0));
+        let first = &mut buffers.activation_values[0];
+        first.data.clear();
+        first.data.extend(input.data.iter().map(|&elem| match &self.preprocessing {
+            Some(preprocessing) => preprocessing.apply_to_value(elem),
+            None => elem,
+        }));
+
+        for (layer_index, (weights, bias)) in zip(&self.weights, &self.biases).enumerate() {
+            let z_values = &mut buffers.z_values[layer_index];
+            z_values.data.resize(bias.data.len(), 0.0);
+            buffers.activation_values[layer_index]._mul_matrix(weights, z_values);
+            *z_values += bias;
+            let activation_function = &self.activation_functions[layer_index];
+            buffers.activation_values[layer_index + 1] = match &self.normalization {
+                Some(normalization) if layer_index < layer_amount - 1 => {
+                    activation_function.apply(&normalization.forward(layer_index, z_values).pre_activation)
+                }
+                _ => activation_function.apply(z_values),
+            };
+        }
+        buffers.activation_values.last().unwrap()
+    }
+
+    //infer for a whole batch, one input per column. every layer is a single matrix
+    //multiplication instead of one matrix vector product per sample.
+    pub fn infer_batch(&self, inputs: &Matrix) -> Matrix {
+        if inputs.width() == 0 {
+            return Matrix::from_vec(vec![Vec::new(); self.biases.last().unwrap().data.len()]);
+        }
+        let mut activations = self.preprocessing.as_ref().map(|preprocessing| {
+            Matrix::from_row_major(inputs.height(), inputs.width(), inputs.as_slice().iter().map(|&elem| preprocessing.apply_to_value(elem)).collect())
+        });
+        let layer_amount = self.weights.len();
+        for (layer_index, (weights, bias)) in zip(&self.weights, &self.biases).enumerate() {
+            let mut z_values = weights * activations.as_ref().unwrap_or(inputs);
+            for (row, bias_elem) in zip(z_values.rows_mut(), &bias.data) {
+                row.iter_mut().for_each(|z| *z += bias_elem);
+            }
+            let activation_function = &self.activation_functions[layer_index];
+            let normalized = self.normalization.is_some() && layer_index < layer_amount - 1;
+            match activation_function.element_wise() {
+                Some(activation) if !normalized => {
+                    z_values.map_inplace(activation);
+                    activations = Some(z_values);
+                }
+                //softmax, normalization and custom activations work on whole samples, so they are applied column by column.
+                _ => {
+                    let columns: Vec<ColumnVector> = z_values.columns()
+                        .map(|z_values| match &self.normalization {
+                            Some(normalization) if normalized => {
+                                activation_function.apply(&normalization.forward(layer_index, &z_values).pre_activation)
+                            }
+                            _ => activation_function.apply(&z_values),
+                        })
+                        .collect();
+                    activations = Some(Matrix::from_columns(&columns));
+                }
+            }
+        }
+        activations.unwrap()
+    }
+
+    pub fn calculate_mean_square_error(&mut self, inputs: &[ColumnVector], expected_outputs: &[ColumnVector]) -> f32 {
+        let mut accumulator = LossAccumulator::new();
+        for (input, expected) in zip(inputs, expected_outputs) {
+            self.calculate_all_activation_values(input);
+            accumulator.add(&SquaredError, self.activation_values.back().unwrap(), expected);
+        }
+        accumulator.mean()
+    }
+
+    //feeds the samples through the network one at a time and sums up their cost.
+    //accepts any iterator so the samples never have to be collected into memory together.
+    pub fn accumulate_loss<'a>(&mut self, samples: impl IntoIterator<Item=(&'a ColumnVector, &'a ColumnVector)>) -> LossAccumulator {
+        let mut accumulator = LossAccumulator::new();
+        for (input, desired) in samples {
             self.calculate_all_activation_values(input);
-            (self.activation_values.back().unwrap() - expected).magnitude_squared()
-        }).reduce(|acc, x| acc + x).unwrap() / (2.0 * (expected_outputs.len() as f32))
+            accumulator.add(&self.cost, self.activation_values.back().unwrap(), desired);
+        }
+        accumulator
+    }
+
+    pub fn mean_loss<'a>(&mut self, samples: impl IntoIterator<Item=(&'a ColumnVector, &'a ColumnVector)>) -> f32 {
+        self.accumulate_loss(samples).mean()
+    }
+
+    //relu hidden layers and a softmax output trained with cross entropy.
+    pub fn new_classifier(layer_sizes: &[usize], default_value: Option<f32>) -> NeuralNetwork {
+        let mut activation_functions = vec![ActivationFunction::Relu; layer_sizes.len().saturating_sub(2)];
+        activation_functions.push(ActivationFunction::Softmax);
+        let mut network = NeuralNetwork::new_with_activations(layer_sizes, activation_functions, default_value);
+        network.cost = Cost::CrossEntropy;
+        network
+    }
+
+    pub fn new_with_activations(layer_sizes: &[usize], activation_functions: Vec<ActivationFunction>, default_value: Option<f32>) -> NeuralNetwork {
+        match default_value {
+            Some(value) => NeuralNetwork::new_with_generator(layer_sizes, activation_functions, |height, width| {
+                (Matrix::new_with_elements(height, width, value), ColumnVector::new_with_elements(height, value))
+            }),
+            None => NeuralNetwork::new_with_initialization(layer_sizes, activation_functions, Initialization::default()),
+        }
+    }
+
+    pub fn new_with_initialization(layer_sizes: &[usize], activation_functions: Vec<ActivationFunction>, initialization: Initialization) -> NeuralNetwork {
+        NeuralNetwork::new_with_rng(layer_sizes, activation_functions, initialization, &mut thread_rng())
     }
 
-    pub fn new(&self, layer_sizes: &[usize], default_value: Option<f32>) -> NeuralNetwork {
+    //random initialization that is the same for the same seed.
+    pub fn new_with_seed(layer_sizes: &[usize], activation_functions: Vec<ActivationFunction>, seed: u64) -> NeuralNetwork {
+        NeuralNetwork::new_with_rng(layer_sizes, activation_functions, Initialization::default(), &mut StdRng::seed_from_u64(seed))
+    }
+
+    //every weight and bias is drawn from rng, layer by layer, so a seeded rng gives the same network every time.
+    pub fn new_with_rng<R: Rng + ?Sized>(layer_sizes: &[usize], activation_functions: Vec<ActivationFunction>, initialization: Initialization, rng: &mut R) -> NeuralNetwork {
+        NeuralNetwork::new_with_generator(layer_sizes, activation_functions, |height, width| initialization.generate(height, width, rng))
+    }
+
+    //generate returns the weights and biases of a layer given its height and width.
+    fn new_with_generator(layer_sizes: &[usize], activation_functions: Vec<ActivationFunction>, mut generate: impl FnMut(usize, usize) -> (Matrix, ColumnVector)) -> NeuralNetwork {
         if layer_sizes.len() < 2 {
             panic!("Cannot generate neural network with less than 2 layers.");
         } else {
@@ -119,20 +558,18 @@ impl NeuralNetwork {
             activation_values.push(ColumnVector::new_with_elements(layer_sizes[0], 0.0));
 
             for (index, &size) in layer_sizes[0..layer_sizes.len() - 1].iter().enumerate() {
-                match default_value {
-                    Some(value) => {
-                        weights.push(Matrix::new_with_elements(layer_sizes[index + 1], size, value));
-                        biases.push(ColumnVector::new_with_elements(layer_sizes[index + 1], value));
-                    }
-                    None => {
-                        weights.push(Matrix::new_with_random_number(layer_sizes[index + 1], size));
-                        biases.push(ColumnVector::new_with_random_number(layer_sizes[index + 1]));
-                    }
-                };
+                let (weight, bias) = generate(layer_sizes[index + 1], size);
+                weights.push(weight);
+                biases.push(bias);
                 activation_values.push(ColumnVector::new_with_elements(layer_sizes[index + 1], 0.0));
                 z_values.push(ColumnVector::new_with_elements(layer_sizes[index + 1], 0.0));
             }
-            NeuralNetwork::new_from_vecs(weights, Some(biases), Some(activation_values), Some(z_values))
+            if activation_functions.len() != weights.len() {
+                panic!("expected {} activation functions, got {}.", weights.len(), activation_functions.len());
+            }
+            let mut network = NeuralNetwork::new_from_vecs(weights, Some(biases), Some(activation_values), Some(z_values));
+            network.activation_functions = activation_functions;
+            network
         }
     }
 
@@ -144,7 +581,7 @@ impl NeuralNetwork {
                 None => {
                     let mut acc: Vec<ColumnVector> = Vec::with_capacity(amount_of_weight_matrices);
                     for matrix in &weights {
-                        acc.push(ColumnVector::new_with_elements(matrix.data[0].len(), 0.0));
+                        acc.push(ColumnVector::new_with_elements(matrix.height(), 0.0));
                     }
                     acc
                 }
@@ -152,9 +589,11 @@ impl NeuralNetwork {
             activation_values: match activation_values {
                 Some(values) => VecDeque::from(values),
                 None => {
-                    let mut acc: VecDeque<ColumnVector> = VecDeque::with_capacity(weights.len());
+                    //one extra slot at the front for the input layer.
+                    let mut acc: VecDeque<ColumnVector> = VecDeque::with_capacity(weights.len() + 1);
+                    acc.push_back(ColumnVector::new_with_elements(weights[0].width(), 0.0));
                     for matrix in &weights {
-                        acc.push_back(ColumnVector::new_with_elements(matrix.data[0].len(), 0.0));
+                        acc.push_back(ColumnVector::new_with_elements(matrix.height(), 0.0));
                     }
                     acc
                 }
@@ -164,22 +603,30 @@ impl NeuralNetwork {
                 None => {
                     let mut acc: VecDeque<ColumnVector> = VecDeque::with_capacity(weights.len());
                     for matrix in &weights {
-                        acc.push_back(ColumnVector::new_with_elements(matrix.data[0].len(), 0.0));
+                        acc.push_back(ColumnVector::new_with_elements(matrix.height(), 0.0));
                     }
                     acc
                 }
             },
+            activation_functions: vec![ActivationFunction::Relu; amount_of_weight_matrices],
             weights,
+            mode: Mode::Inference,
+            dropout: None,
+            normalization: None,
+            cost: Cost::SquaredError,
+            preprocessing: None,
+            frozen: vec![false; amount_of_weight_matrices],
+            batch_buffers: Vec::new(),
         }
     }
-    fn serialize_iter(&self) -> SerializerIteratorNN {
+    fn serialize_iter(&self) -> SerializerIteratorNN<'_> {
         SerializerIteratorNN {
             neural_network: self,
             state: Some(LayerAmount),
         }
     }
 
-    pub fn serialize_to_file(self, file_path: &str) -> () {
+    pub fn serialize_to_file(&self, file_path: &str) {
         let buffer: Vec<u8> = self.serialize_iter().flat_map(|x| {
             match x {
                 NNSerializationValues::Value(v) => {
@@ -193,6 +640,7 @@ impl NeuralNetwork {
         std::fs::OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(true)
             .open(file_path)
             .unwrap()
             .write_all(&buffer)
@@ -212,7 +660,7 @@ impl NeuralNetwork {
             .step_by(1)
             .map(|(w, x)| u16::from_be_bytes([w, x]))
             .take(amount_of_layers as usize)
-            .map(|x| NNSerializationValues::Size(x)).collect();
+            .map(NNSerializationValues::Size).collect();
         // .copied();
         let data = byte_iterator
             .map(|x| x.unwrap())
@@ -221,8 +669,8 @@ impl NeuralNetwork {
             .map(|(w, x, y, z)| {
                 f32::from_be_bytes([w, x, y, z])
             })
-            .map(|x| NNSerializationValues::Value(x));
-        Box::new([NNSerializationValues::Size(amount_of_layers)].into_iter().chain(layer_sizes.into_iter()).chain(data))
+            .map(NNSerializationValues::Value);
+        Box::new([NNSerializationValues::Size(amount_of_layers)].into_iter().chain(layer_sizes).chain(data))
     }
     pub fn create_nn_from_deserialized_values(mut data_iterator: Box<dyn Iterator<Item=NNSerializationValues>>) -> NeuralNetwork {
         let layer_amount = match data_iterator.next().unwrap() {
@@ -236,12 +684,13 @@ impl NeuralNetwork {
             .windows(2)
             .take((layer_amount - 1) as usize)
             .map(|x| {
-                let h = x[0];
-                let w = x[1];
+                //a weight matrix maps the previous layer (its width) to the next one (its height).
+                let w = x[0];
+                let h = x[1];
                 match (h, w) {
                     (NNSerializationValues::Size(h), NNSerializationValues::Size(w)) => {
                         (Matrix::new_with_elements(h as usize, w as usize, 0.0),
-                         ColumnVector::new_with_elements(w as usize, 0.0),
+                         ColumnVector::new_with_elements(h as usize, 0.0),
                         )
                     }
                     _ => panic!("Wrong type")
@@ -255,9 +704,7 @@ impl NeuralNetwork {
             }
         });
 
-        let weight_iter = weights.iter_mut().flat_map(|x| {
-            x.data.iter_mut()
-        }).flat_map(|x| x.iter_mut());
+        let weight_iter = weights.iter_mut().flat_map(|x| x.as_mut_slice().iter_mut());
         let bias_iter = biases.iter_mut().flat_map(|x| x.data.iter_mut());
 
         zip(weight_iter.chain(bias_iter), values).for_each(|(elem, v)| {
@@ -266,39 +713,394 @@ impl NeuralNetwork {
         let mut activation_values: VecDeque<ColumnVector> = biases.iter()
             .map(|x| ColumnVector::new_with_elements(x.data.len(), 0.0))
             .collect();
-        activation_values.push_front(ColumnVector::new_with_elements(weights[0].data[0].len(), 0.0));
+        activation_values.push_front(ColumnVector::new_with_elements(weights[0].width(), 0.0));
 
         let z_values: VecDeque<ColumnVector> = biases.iter()
             .map(|x| ColumnVector::new_with_elements(x.data.len(), 0.0))
             .collect();
         NeuralNetwork {
+            activation_functions: vec![ActivationFunction::Relu; weights.len()],
             weights,
             biases,
             activation_values,
             z_values,
+            mode: Mode::Inference,
+            dropout: None,
+            normalization: None,
+            cost: Cost::SquaredError,
+            preprocessing: None,
+            frozen: vec![false; layer_amount as usize - 1],
+            batch_buffers: Vec::new(),
         }
     }
 
-    // pub fn stochastic_gradient_descent(
-    //     self,
-    //     training_data: &mut [(ColumnVector, ColumnVector)],
-    //     mini_batch_size: usize,
-    //     learning_rate: usize,
-    // ) {
-    //     let mut rng = rand::thread_rng();
-    //     training_data.shuffle(&mut rng);
-    //     training_data.windows(mini_batch_size).step_by(mini_batch_size).for_each(|batch| {
-    //         batch.iter().for_each(|(input_vector, desired_vector)| {
-    //
-    //         });
-    //     });
-    // }
-    //
-    // pub fn backpropagation(mut self, input_vector: &ColumnVector, desired_vector: &ColumnVector) {
-    //     self.calculate_all_activation_values(input_vector);
-    // }
-
-    fn deserialize_from_file(file_path: &str) -> NeuralNetwork {
+    //amount of neurons of every layer between the input and the output layer.
+    pub fn hidden_layer_sizes(&self) -> Vec<usize> {
+        self.weights[..self.weights.len() - 1].iter()
+            .map(|x| x.height())
+            .collect()
+    }
+
+    //every weight, row by row. Biases are not regularized.
+    pub fn weight_values(&self) -> impl Iterator<Item=&f32> {
+        self.weights.iter()
+            .flat_map(|x| x.as_slice().iter())
+    }
+
+    //0.5 * lambda * the sum of every squared weight.
+    pub fn l2_penalty(&self, lambda: f32) -> f32 {
+        0.5 * lambda * self.weight_values().map(|x| x * x).sum::<f32>()
+    }
+
+    //lambda * the sum of every absolute weight.
+    pub fn l1_penalty(&self, lambda: f32) -> f32 {
+        lambda * self.weight_values().map(|x| x.abs()).sum::<f32>()
+    }
+
+    //every weight (row by row), then every bias, and the normalization gammas and betas if any.
+    pub fn parameters_mut(&mut self) -> impl Iterator<Item=&mut f32> {
+        let (gammas, betas) = match &mut self.normalization {
+            Some(normalization) => normalization.gammas_and_betas_mut(),
+            None => (&mut [][..], &mut [][..]),
+        };
+        self.weights.iter_mut()
+            .flat_map(|x| x.as_mut_slice().iter_mut())
+            .chain(self.biases.iter_mut().flat_map(|x| x.data.iter_mut()))
+            .chain(gammas.iter_mut().flat_map(|x| x.data.iter_mut()))
+            .chain(betas.iter_mut().flat_map(|x| x.data.iter_mut()))
+    }
+
+    //swaps the output layer for a new one with output_size outputs, drawn with initialization.
+    //the hidden layers are kept, so the features learned for one task can be reused for another,
+    //like mnist digits for emnist letters. the output activation and the cost stay the same.
+    pub fn replace_head<R: Rng + ?Sized>(&mut self, output_size: usize, initialization: Initialization, rng: &mut R) {
+        let input_size = self.weights.last().unwrap().width();
+        let (weights, biases) = initialization.generate(output_size, input_size, rng);
+        *self.weights.last_mut().unwrap() = weights;
+        *self.biases.last_mut().unwrap() = biases;
+        if let (Some(activations), Some(z_values)) = (self.activation_values.back_mut(), self.z_values.back_mut()) {
+            *activations = ColumnVector::new_with_elements(output_size, 0.0);
+            *z_values = ColumnVector::new_with_elements(output_size, 0.0);
+        }
+        if let Some(frozen) = self.frozen.get_mut(self.weights.len() - 1) {
+            *frozen = false;
+        }
+    }
+
+    //the optimizers leave the weights, biases and normalization parameters of a frozen layer alone,
+    //for fine tuning only some layers of a trained network. layer_index counts the weight matrices.
+    pub fn freeze(&mut self, layer_index: usize) {
+        self.set_frozen(layer_index, true);
+    }
+
+    pub fn unfreeze(&mut self, layer_index: usize) {
+        self.set_frozen(layer_index, false);
+    }
+
+    //freezes every layer but the output layer, so only the output layer is retrained.
+    pub fn freeze_all_but_output(&mut self) {
+        (0..self.weights.len() - 1).for_each(|index| self.freeze(index));
+    }
+
+    fn set_frozen(&mut self, layer_index: usize, frozen: bool) {
+        if layer_index >= self.weights.len() {
+            panic!("there is no layer {}, the network has {}.", layer_index, self.weights.len());
+        }
+        self.frozen.resize(self.weights.len(), false);
+        self.frozen[layer_index] = frozen;
+    }
+
+    pub fn is_frozen(&self, layer_index: usize) -> bool {
+        self.frozen.get(layer_index).copied().unwrap_or(false)
+    }
+
+    //whether every parameter is trained, in the order of parameters_mut.
+    pub fn trainable_parameters(&self) -> Vec<bool> {
+        let per_layer = |vectors: &[ColumnVector]| -> Vec<bool> {
+            vectors.iter().enumerate()
+                .flat_map(|(index, x)| std::iter::repeat_n(!self.is_frozen(index), x.data.len()))
+                .collect()
+        };
+        let mut trainable: Vec<bool> = self.weights.iter().enumerate()
+            .flat_map(|(index, x)| std::iter::repeat_n(!self.is_frozen(index), x.as_slice().len()))
+            .collect();
+        trainable.extend(per_layer(&self.biases));
+        if let Some(normalization) = &self.normalization {
+            trainable.extend(per_layer(normalization.gammas()));
+            trainable.extend(per_layer(normalization.betas()));
+        }
+        trainable
+    }
+
+    //plain gradient descent step: every parameter that is not frozen moves against its gradient.
+    pub fn apply_gradients(&mut self, gradients: &Gradients, learning_rate: f32) {
+        let trainable = self.trainable_parameters();
+        zip(zip(self.parameters_mut(), gradients.values()), trainable)
+            .filter(|(_, trainable)| *trainable)
+            .for_each(|((elem, gradient), _)| {
+                *elem -= learning_rate * gradient;
+            });
+    }
+
+    //runs a forward pass for input_vector and returns the gradient of the squared error
+    //with respect to every weight matrix and bias vector.
+    pub fn backpropagation(&mut self, input_vector: &ColumnVector, desired_vector: &ColumnVector) -> Gradients {
+        self.calculate_all_activation_values(input_vector);
+        self.backward(desired_vector)
+    }
+
+    //summed, not averaged, gradients of every (input, desired output) pair in the batch.
+    pub fn batch_gradients(&mut self, batch: &[(ColumnVector, ColumnVector)]) -> Gradients {
+        self.batch_gradients_and_loss(batch).0
+    }
+
+    //like batch_gradients, also returning the cost of the outputs the gradients were computed from.
+    pub fn batch_gradients_and_loss(&mut self, batch: &[(ColumnVector, ColumnVector)]) -> (Gradients, LossAccumulator) {
+        if let (Mode::Training, Some(Normalization::Batch(_))) = (self.mode, &self.normalization) {
+            return self.batch_norm_gradients_and_loss(batch);
+        }
+        if batch.is_empty() {
+            return (Gradients::zeros_like(self), LossAccumulator::new());
+        }
+        let mut rng = self.batch_rng();
+        let mut buffers = std::mem::take(&mut self.batch_buffers);
+        buffers.resize_with(buffers.len().max(1), BatchBuffers::default);
+        let loss = self.batch_pass(batch, &mut buffers[0], &mut rng);
+        let gradients = buffers[0].gradients.take().unwrap();
+        self.batch_buffers = buffers;
+        (gradients, loss)
+    }
+
+    //the rng the dropout masks of one batch pass are drawn with, seeded from the dropout of the network.
+    fn batch_rng(&mut self) -> StdRng {
+        StdRng::seed_from_u64(self.dropout.as_mut().map_or(0, |dropout| dropout.rng.gen()))
+    }
+
+    //the forward and backward pass of a batch, leaving the summed gradients in buffers.
+    fn batch_pass(&self, batch: &[(ColumnVector, ColumnVector)], buffers: &mut BatchBuffers, rng: &mut StdRng) -> LossAccumulator {
+        self.batch_forward(batch, buffers, rng);
+        let mut loss = LossAccumulator::new();
+        for (output, (_, desired_vector)) in zip(buffers.activations.last().unwrap().columns(), batch) {
+            loss.add(&self.cost, &output, desired_vector);
+        }
+        self.batch_backward(batch, buffers);
+        loss
+    }
+
+    //forward pass for a whole batch, one sample per column. every layer is one matrix
+    //multiplication, the activation functions and the normalization still go sample by sample.
+    fn batch_forward(&self, batch: &[(ColumnVector, ColumnVector)], buffers: &mut BatchBuffers, rng: &mut StdRng) {
+        let layer_amount = self.weights.len();
+        let batch_size = batch.len();
+        buffers.activations.resize_with(layer_amount + 1, || Matrix::zeros(0, 0));
+        buffers.z_values.resize_with(layer_amount, || Matrix::zeros(0, 0));
+        buffers.masks.resize_with(layer_amount - 1, Vec::new);
+        buffers.normalized.clear();
+        let inputs = &mut buffers.activations[0];
+        reuse_matrix(inputs, self.weights[0].width(), batch_size);
+        for (index, (input, _)) in batch.iter().enumerate() {
+            set_column(inputs, index, input);
+        }
+        if let Some(preprocessing) = &self.preprocessing {
+            inputs.map_inplace(|elem| preprocessing.apply_to_value(elem));
+        }
+        for layer_index in 0..layer_amount {
+            let (inputs, outputs) = buffers.activations.split_at_mut(layer_index + 1);
+            let (inputs, activations) = (&inputs[layer_index], &mut outputs[0]);
+            let z_values = &mut buffers.z_values[layer_index];
+            let size = self.weights[layer_index].height();
+            reuse_matrix(z_values, size, batch_size);
+            reuse_matrix(activations, size, batch_size);
+            self.weights[layer_index]._mul(inputs, z_values);
+            for (row, bias_elem) in zip(z_values.rows_mut(), &self.biases[layer_index].data) {
+                row.iter_mut().for_each(|z| *z += bias_elem);
+            }
+            let activation_function = &self.activation_functions[layer_index];
+            let hidden = layer_index < layer_amount - 1;
+            match &self.normalization {
+                Some(normalization) if hidden => {
+                    let normalized: Vec<NormalizedValues> = z_values.columns()
+                        .map(|z| normalization.forward(layer_index, &z))
+                        .collect();
+                    for (index, values) in normalized.iter().enumerate() {
+                        set_column(activations, index, &activation_function.apply(&values.pre_activation));
+                    }
+                    buffers.normalized.push(normalized);
+                }
+                _ => for (index, z) in z_values.columns().enumerate() {
+                    set_column(activations, index, &activation_function.apply(&z));
+                },
+            }
+            if let (Mode::Training, true, Some(dropout)) = (self.mode, hidden, &self.dropout) {
+                dropout.apply_with_rng(rng, activations.as_mut_slice(), &mut buffers.masks[layer_index]);
+            }
+        }
+    }
+
+    //the summed gradients of the batch batch_forward computed values for. the weight gradients
+    //are delta * input^T and the error goes back through weights^T * delta, both for every
+    //sample at once.
+    fn batch_backward(&self, batch: &[(ColumnVector, ColumnVector)], buffers: &mut BatchBuffers) {
+        let layer_amount = self.weights.len();
+        let batch_size = batch.len();
+        let BatchBuffers { activations, z_values, normalized, masks, deltas, propagated, gradients } = buffers;
+        let fits = |gradients: &Gradients| gradients.weights.len() == layer_amount
+            && zip(&gradients.weights, &self.weights).all(|(gradient, weights)| gradient.is_same_shape(weights))
+            && gradients.gammas.len() == self.normalization.as_ref().map_or(0, |x| x.gammas().len());
+        if !gradients.as_ref().is_some_and(fits) {
+            *gradients = Some(Gradients::zeros_like(self));
+        }
+        let gradients = gradients.as_mut().unwrap();
+        gradients.gammas.iter_mut().chain(&mut gradients.betas).for_each(|x| x.data.fill(0.0));
+        deltas.resize_with(layer_amount, || Matrix::zeros(0, 0));
+        propagated.resize_with(layer_amount - 1, || Matrix::zeros(0, 0));
+
+        let output_deltas = &mut deltas[layer_amount - 1];
+        reuse_matrix(output_deltas, self.weights[layer_amount - 1].height(), batch_size);
+        let outputs = zip(z_values[layer_amount - 1].columns(), activations[layer_amount].columns());
+        for (index, ((z, output), (_, desired_vector))) in zip(outputs, batch).enumerate() {
+            set_column(output_deltas, index, &self.output_delta(&z, &output, desired_vector));
+        }
+
+        for layer_index in (0..layer_amount).rev() {
+            let delta = &deltas[layer_index];
+            delta._mul_transposed(&activations[layer_index], &mut gradients.weights[layer_index]);
+            for (bias_gradient, row) in zip(&mut gradients.biases[layer_index].data, delta.rows()) {
+                *bias_gradient = row.iter().sum();
+            }
+            if layer_index == 0 {
+                break;
+            }
+            let hidden_index = layer_index - 1;
+            let size = self.biases[hidden_index].data.len();
+            let propagated = &mut propagated[hidden_index];
+            reuse_matrix(propagated, size, batch_size);
+            self.weights[layer_index]._transposed_mul(delta, propagated);
+            if let (Mode::Training, Some(_)) = (self.mode, &self.dropout) {
+                zip(propagated.as_mut_slice(), &masks[hidden_index]).for_each(|(x, mask_elem)| {
+                    *x *= mask_elem;
+                });
+            }
+            let activation_function = &self.activation_functions[hidden_index];
+            let delta = &mut deltas[hidden_index];
+            reuse_matrix(delta, size, batch_size);
+            match &self.normalization {
+                Some(normalization) => {
+                    for (index, (propagated, normalized)) in zip(propagated.columns(), &normalized[hidden_index]).enumerate() {
+                        let scaled_delta = activation_function.backward(&normalized.pre_activation, &propagated);
+                        let (z_delta, gamma, beta) = normalization.backward(hidden_index, normalized, scaled_delta);
+                        gradients.gammas[hidden_index] += &gamma;
+                        gradients.betas[hidden_index] += &beta;
+                        set_column(delta, index, &z_delta);
+                    }
+                }
+                None => for (index, (z, propagated)) in zip(z_values[hidden_index].columns(), propagated.columns()).enumerate() {
+                    set_column(delta, index, &activation_function.backward(&z, &propagated));
+                },
+            }
+        }
+    }
+
+    //batch_gradients_and_loss with the batch split into one contiguous part per thread. the
+    //threads share the network read only, each with buffers of its own that are kept for the
+    //next batch. the parts are added up in order, so the result depends on the amount of
+    //threads but not on their timing. every thread draws its dropout masks with its own rng
+    //seeded from the one of the network. training batch normalization needs the whole batch
+    //at once and stays on the calling thread.
+    pub fn parallel_batch_gradients_and_loss(&mut self, batch: &[(ColumnVector, ColumnVector)], threads: usize) -> (Gradients, LossAccumulator) {
+        let batch_norm_training = matches!((self.mode, &self.normalization), (Mode::Training, Some(Normalization::Batch(_))));
+        if threads <= 1 || batch.len() < 2 || batch_norm_training {
+            return self.batch_gradients_and_loss(batch);
+        }
+        let parts: Vec<&[(ColumnVector, ColumnVector)]> = batch.chunks(batch.len().div_ceil(threads)).collect();
+        let mut rngs: Vec<StdRng> = parts.iter().map(|_| self.batch_rng()).collect();
+        let mut buffers = std::mem::take(&mut self.batch_buffers);
+        buffers.resize_with(buffers.len().max(parts.len()), BatchBuffers::default);
+        let network = &*self;
+        let losses: Vec<LossAccumulator> = std::thread::scope(|scope| {
+            let handles: Vec<_> = zip(zip(parts, &mut rngs), &mut buffers)
+                .map(|((part, rng), buffers)| scope.spawn(move || network.batch_pass(part, buffers, rng)))
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        let mut gradients = Gradients::zeros_like(self);
+        let mut loss = LossAccumulator::new();
+        for (part_buffers, part_loss) in zip(&buffers, &losses) {
+            gradients.accumulate(part_buffers.gradients.as_ref().unwrap());
+            loss.merge(part_loss);
+        }
+        self.batch_buffers = buffers;
+        (gradients, loss)
+    }
+
+    //gradient of the cost with respect to the z values of the output layer.
+    fn output_delta(&self, z_values: &ColumnVector, output: &ColumnVector, desired_vector: &ColumnVector) -> ColumnVector {
+        let activation_function = self.activation_functions.last().unwrap();
+        if self.cost.is_fused_with(activation_function) {
+            output - desired_vector
+        } else {
+            activation_function.backward(z_values, &self.cost.gradient(output, desired_vector))
+        }
+    }
+
+    //uses the activation and z values cached by the last call to calculate_all_activation_values.
+    pub fn backward(&self, desired_vector: &ColumnVector) -> Gradients {
+        let layer_amount = self.weights.len();
+        let mut weight_gradients = Vec::with_capacity(layer_amount);
+        let mut bias_gradients = Vec::with_capacity(layer_amount);
+        let mut gamma_gradients = Vec::new();
+        let mut beta_gradients = Vec::new();
+
+        let output = self.activation_values.back().unwrap();
+        let mut delta = self.output_delta(&self.z_values[layer_amount - 1], output, desired_vector);
+
+        for layer_index in (0..layer_amount).rev() {
+            let layer_input = &self.activation_values[layer_index];
+            weight_gradients.push(delta.outer(layer_input));
+
+            if layer_index > 0 {
+                //propagate the error backwards through the transpose of the weight matrix.
+                let mut propagated = ColumnVector::new_with_elements(layer_input.data.len(), 0.0);
+                delta._mul_transposed_matrix(&self.weights[layer_index], &mut propagated);
+                if let (Mode::Training, Some(dropout)) = (self.mode, &self.dropout) {
+                    zip(propagated.data.iter_mut(), &dropout.mask(layer_index - 1).data).for_each(|(x, mask_elem)| {
+                        *x *= mask_elem;
+                    });
+                }
+                let next_delta;
+                match &self.normalization {
+                    Some(normalization) => {
+                        let hidden_index = layer_index - 1;
+                        let values = normalization.forward(hidden_index, &self.z_values[hidden_index]);
+                        let scaled_delta = self.activation_functions[hidden_index].backward(&values.pre_activation, &propagated);
+                        let (z_delta, gamma_gradient, beta_gradient) = normalization.backward(hidden_index, &values, scaled_delta);
+                        next_delta = z_delta;
+                        gamma_gradients.push(gamma_gradient);
+                        beta_gradients.push(beta_gradient);
+                    }
+                    None => {
+                        next_delta = self.activation_functions[layer_index - 1].backward(&self.z_values[layer_index - 1], &propagated);
+                    }
+                }
+                bias_gradients.push(delta);
+                delta = next_delta;
+            } else {
+                bias_gradients.push(delta.clone());
+            }
+        }
+        weight_gradients.reverse();
+        bias_gradients.reverse();
+        gamma_gradients.reverse();
+        beta_gradients.reverse();
+        Gradients {
+            weights: weight_gradients,
+            biases: bias_gradients,
+            gammas: gamma_gradients,
+            betas: beta_gradients,
+        }
+    }
+
+    pub fn deserialize_from_file(file_path: &str) -> NeuralNetwork {
         let data_iterator = NeuralNetwork::deserialize_from_file_to_values(file_path);
         NeuralNetwork::create_nn_from_deserialized_values(data_iterator)
     }
@@ -323,7 +1125,7 @@ struct SerializerIteratorNN<'a> {
 }
 
 #[derive(PartialEq, Debug, Copy, Clone)]
-enum NNSerializationValues {
+pub enum NNSerializationValues {
     Value(f32),
     Size(u16),
 }
@@ -341,9 +1143,9 @@ impl<'a> Iterator for SerializerIteratorNN<'a> {
                         self.state = Some(LayerSizes(0));
                         Some(NNSerializationValues::Size((self.neural_network.biases.len() + 1) as u16))
                     }
-                    LayerSizes(layer_size_index) if layer_size_index == 0 => {
+                    LayerSizes(0) => {
                         self.state = Some(LayerSizes(1));
-                        Some(NNSerializationValues::Size(self.neural_network.weights[0].data[0].len() as u16))
+                        Some(NNSerializationValues::Size(self.neural_network.weights[0].width() as u16))
                     }
                     LayerSizes(layer_size_index)
                     // if layer_size_index < self.neural_network.weights.len() && layer_size_index > 0
@@ -356,9 +1158,8 @@ impl<'a> Iterator for SerializerIteratorNN<'a> {
                         Some(NNSerializationValues::Size(self.neural_network.biases[(layer_size_index - 1) as usize].data.len() as u16))
                     }
                     Weights(index, row, col) => {
-                        let matrix = &self.neural_network.weights[index as usize].data;
-                        let width = matrix[0].len();
-                        let height = matrix.len();
+                        let matrix = &self.neural_network.weights[index as usize];
+                        let (height, width) = (matrix.height(), matrix.width());
                         if col < (width - 1) as u16 {
                             self.state = Some(Weights(index, row, col + 1));
                         } else if row < (height - 1) as u16 {
@@ -368,14 +1169,14 @@ impl<'a> Iterator for SerializerIteratorNN<'a> {
                         } else {
                             self.state = Some(Biases(0, 0));
                         }
-                        Some(NNSerializationValues::Value(matrix[row as usize][col as usize]))
+                        Some(NNSerializationValues::Value(matrix[(row as usize, col as usize)]))
                     }
                     Biases(index, elem_index) => {
                         let vector = &self.neural_network.biases[index as usize].data;
                         let total = vector.len();
                         if elem_index < (total - 1) as u16 {
                             self.state = Some(Biases(index, elem_index + 1));
-                        } else if index < (vector.len() - 1) as u16 {
+                        } else if index < (self.neural_network.biases.len() - 1) as u16 {
                             self.state = Some(Biases(index + 1, 0));
                         } else {
                             self.state = None;
@@ -401,7 +1202,11 @@ impl fmt::Display for NeuralNetwork {
 #[cfg(test)]
 mod tests {
     use matrix::ColumnVector;
-    use crate::{NeuralNetwork, NNSerializationValues};
+    use mnist_reader::Preprocessing;
+    use std::sync::Arc;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::{check_gradients, sigmoid, ActivationFunction, BatchNorm, Cost, Dropout, GradientValues, Gradients, InferenceBuffers, Initialization, LayerNorm, LossAccumulator, Mode, NNSerializationValues, NeuralNetwork, Normalization, Relu};
     use super::Matrix;
 
     #[test]
@@ -414,7 +1219,7 @@ mod tests {
         }
         weights.push(Matrix::zeros(matrix_size, matrix_size));
 
-        let mut test_nn = NeuralNetwork::new_from_vecs(weights, None, None);
+        let mut test_nn = NeuralNetwork::new_from_vecs(weights, None, None, None);
         let input_vector = ColumnVector::new_with_elements(matrix_size, 1.0);
         let input_vector2 = input_vector.clone();
         let zero_input = ColumnVector::new_with_elements(matrix_size, 0.0);
@@ -425,13 +1230,231 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_backpropagation_against_finite_differences() {
+        let weights = vec![
+            Matrix::from_vec(vec![vec![0.5, 0.2, 0.1], vec![0.3, 0.8, 0.4]]),
+            Matrix::from_vec(vec![vec![0.7, 0.6], vec![0.2, 0.9]]),
+        ];
+        let biases = vec![ColumnVector::from_vec(vec![0.1, 0.2]), ColumnVector::from_vec(vec![0.3, 0.1])];
+        let mut test_nn = NeuralNetwork::new_from_vecs(weights, Some(biases), None, None);
+        let input = ColumnVector::from_vec(vec![1.0, 0.5, 0.25]);
+        let desired = ColumnVector::from_vec(vec![0.0, 1.0]);
+        let check = check_gradients(&mut test_nn, &input, &desired);
+        assert!(check.max_absolute_error < 1e-2, "{:?}", check);
+    }
+
+    #[test]
+    fn preprocessing_is_applied_before_the_first_layer() {
+        let input = ColumnVector::from_vec(vec![255.0, 51.0, 0.0]);
+        let desired = ColumnVector::from_vec(vec![1.0, 0.0]);
+        let mut plain = NeuralNetwork::new_with_seed(&[3, 2, 2], vec![ActivationFunction::Tanh, ActivationFunction::Sigmoid], 2);
+        let mut preprocessed = NeuralNetwork::new_with_seed(&[3, 2, 2], vec![ActivationFunction::Tanh, ActivationFunction::Sigmoid], 2);
+        preprocessed.preprocessing = Some(Preprocessing::unit_range());
+        let expected = plain.backpropagation(&Preprocessing::unit_range().apply(&input), &desired);
+        assert_eq!(preprocessed.backpropagation(&input, &desired), expected);
+        assert_eq!(preprocessed.activation_values, plain.activation_values);
+    }
+
+    #[test]
+    fn inference_leaves_the_network_untouched() {
+        let mut network = NeuralNetwork::new_with_seed(&[3, 4, 2], vec![ActivationFunction::Tanh, ActivationFunction::Softmax], 5);
+        network.preprocessing = Some(Preprocessing::unit_range());
+        let inputs = [ColumnVector::from_vec(vec![255.0, 51.0, 0.0]), ColumnVector::from_vec(vec![10.0, 0.0, 100.0])];
+        let stored_activations = network.activation_values.clone();
+        let outputs: Vec<ColumnVector> = inputs.iter().map(|input| network.infer(input)).collect();
+        assert_eq!(network.activation_values, stored_activations);
+
+        let mut buffers = InferenceBuffers::default();
+        for (input, output) in inputs.iter().zip(&outputs).rev() {
+            assert_eq!(network.infer_with(input, &mut buffers), output);
+            network.calculate_all_activation_values(input);
+            assert_eq!(network.activation_values.back().unwrap(), output);
+        }
+        assert_eq!(buffers.activation_values[0], Preprocessing::unit_range().apply(&inputs[0]));
+    }
+
+    #[test]
+    fn batched_inference_matches_single_samples() {
+        let mut network = NeuralNetwork::new_with_seed(&[3, 5, 4, 2], vec![ActivationFunction::Relu, ActivationFunction::Tanh, ActivationFunction::Softmax], 6);
+        network.preprocessing = Some(Preprocessing::unit_range());
+        let inputs: Vec<ColumnVector> = (0..7).map(|i| ColumnVector::from_vec(vec![i as f32 * 30.0, 255.0 - i as f32, 12.0])).collect();
+        let outputs = network.infer_batch(&Matrix::from_columns(&inputs));
+        assert_eq!(outputs.columns().collect::<Vec<_>>(), inputs.iter().map(|input| network.infer(input)).collect::<Vec<_>>());
+        let empty = network.infer_batch(&Matrix::from_columns(&[]));
+        assert_eq!((empty.height(), empty.width()), (2, 0));
+    }
+
+    #[test]
+    fn batched_gradients_match_single_samples() {
+        let batch: Vec<(ColumnVector, ColumnVector)> = (0..5)
+            .map(|i| (ColumnVector::from_vec(vec![i as f32 * 40.0, 200.0 - i as f32 * 10.0, 30.0]), ColumnVector::from_vec(vec![(i % 2) as f32, 1.0 - (i % 2) as f32])))
+            .collect();
+        let mut network = NeuralNetwork::new_with_seed(&[3, 6, 4, 2], vec![ActivationFunction::Relu, ActivationFunction::Tanh, ActivationFunction::Softmax], 4);
+        network.cost = Cost::CrossEntropy;
+        network.preprocessing = Some(Preprocessing::unit_range());
+        network.normalization = Some(Normalization::Layer(LayerNorm::for_network(&network)));
+        let mut expected = Gradients::zeros_like(&network);
+        let mut expected_loss = LossAccumulator::new();
+        for (input, desired) in &batch {
+            expected.accumulate(&network.backpropagation(input, desired));
+            expected_loss.add(&network.cost, network.activation_values.back().unwrap(), desired);
+        }
+        let (gradients, loss) = network.batch_gradients_and_loss(&batch);
+        assert_eq!((gradients.weights.len(), gradients.gammas.len()), (3, 2));
+        assert!(std::iter::zip(expected.values(), gradients.values()).all(|(a, b)| (a - b).abs() < 1e-5));
+        assert!((loss.total - expected_loss.total).abs() < 1e-5);
+        assert_eq!(network.batch_gradients(&[]), Gradients::zeros_like(&network));
+    }
+
+    #[test]
+    fn parallel_gradients() {
+        let batch: Vec<(ColumnVector, ColumnVector)> = (0..7)
+            .map(|i| (ColumnVector::from_vec(vec![i as f32 * 0.3, 1.0 - i as f32 * 0.2, 0.5]), ColumnVector::from_vec(vec![(i % 2) as f32, 1.0 - (i % 2) as f32])))
+            .collect();
+        let mut network = NeuralNetwork::new_with_seed(&[3, 6, 2], vec![ActivationFunction::Tanh, ActivationFunction::Sigmoid], 2);
+        let (serial, serial_loss) = network.batch_gradients_and_loss(&batch);
+        let (parallel, parallel_loss) = network.parallel_batch_gradients_and_loss(&batch, 3);
+        assert!(std::iter::zip(serial.values(), parallel.values()).all(|(a, b)| (a - b).abs() < 1e-5));
+        assert!((serial_loss.mean() - parallel_loss.mean()).abs() < 1e-6);
+        //the buffers of the threads are reused by the next batch.
+        assert_eq!(network.parallel_batch_gradients_and_loss(&batch, 3).0, parallel);
+
+        //the dropout masks of the threads are drawn from the seed of the network.
+        network.mode = Mode::Training;
+        let mut gradients_with_dropout = |seed: u64| {
+            network.dropout = Some(Dropout::new_with_seed(0.5, seed));
+            network.parallel_batch_gradients_and_loss(&batch, 3).0
+        };
+        let first = gradients_with_dropout(9);
+        assert_eq!(first, gradients_with_dropout(9));
+        assert_ne!(first, gradients_with_dropout(10));
+
+        network.mode = Mode::Inference;
+        network.dropout = None;
+        //also when the network changes.
+        let widened: Vec<(ColumnVector, ColumnVector)> = batch.iter()
+            .map(|(input, desired)| (input.clone(), ColumnVector::from_vec(vec![desired.data[0], desired.data[1], 0.0])))
+            .collect();
+        let mut wider = network.clone();
+        wider.replace_head(3, Initialization::default(), &mut StdRng::seed_from_u64(4));
+        network.replace_head(3, Initialization::default(), &mut StdRng::seed_from_u64(4));
+        assert_eq!(network.parallel_batch_gradients_and_loss(&widened, 3).0, wider.parallel_batch_gradients_and_loss(&widened, 3).0);
+        let (parallel, serial) = (network.parallel_batch_gradients_and_loss(&widened[..4], 2).0, wider.batch_gradients_and_loss(&widened[..4]).0);
+        assert!(std::iter::zip(serial.values(), parallel.values()).all(|(a, b)| (a - b).abs() < 1e-5));
+    }
+
+    #[test]
+    fn construction_from_an_injected_rng() {
+        let activation_functions = vec![ActivationFunction::Relu, ActivationFunction::Softmax];
+        let first = NeuralNetwork::new_with_rng(&[4, 3, 2], activation_functions.clone(), Initialization::GlorotUniform, &mut StdRng::seed_from_u64(8));
+        let second = NeuralNetwork::new_with_rng(&[4, 3, 2], activation_functions.clone(), Initialization::GlorotUniform, &mut StdRng::seed_from_u64(8));
+        assert_eq!(first, second);
+        assert_eq!(NeuralNetwork::new_with_seed(&[4, 3, 2], activation_functions.clone(), 8),
+                   NeuralNetwork::new_with_rng(&[4, 3, 2], activation_functions.clone(), Initialization::default(), &mut StdRng::seed_from_u64(8)));
+
+        //the rng keeps its state, so networks built one after the other from it differ.
+        let mut rng = StdRng::seed_from_u64(8);
+        let first = NeuralNetwork::new_with_rng(&[4, 3, 2], activation_functions.clone(), Initialization::HeNormal, &mut rng);
+        let second = NeuralNetwork::new_with_rng(&[4, 3, 2], activation_functions, Initialization::HeNormal, &mut rng);
+        assert_ne!(first.weights, second.weights);
+    }
+
+    #[test]
+    fn replace_head() {
+        let mut network = NeuralNetwork::new_with_seed(&[4, 3, 2], vec![ActivationFunction::Relu, ActivationFunction::Softmax], 3);
+        network.freeze_all_but_output();
+        network.freeze(1);
+        let hidden_weights = network.weights[0].clone();
+        network.replace_head(5, Initialization::GlorotUniform, &mut StdRng::seed_from_u64(1));
+        assert_eq!(network.weights[0], hidden_weights);
+        assert_eq!((network.weights[1].height(), network.weights[1].width()), (5, 3));
+        assert_eq!(network.biases[1], ColumnVector::new_with_elements(5, 0.0));
+        assert!(network.is_frozen(0) && !network.is_frozen(1));
+
+        let input = ColumnVector::from_vec(vec![0.5, -1.0, 2.0, 0.0]);
+        let output = network.infer(&input);
+        assert_eq!(output.data.len(), 5);
+        let gradients = network.backpropagation(&input, &ColumnVector::from_vec(vec![0.0, 0.0, 1.0, 0.0, 0.0]));
+        assert_eq!(network.activation_values.back().unwrap(), &output);
+        assert_eq!(gradients.biases[1].data.len(), 5);
+    }
+
+    #[test]
+    fn per_layer_activation_functions() {
+        let mut test_nn = NeuralNetwork::new_with_activations(&[3, 2, 2], vec![ActivationFunction::Relu, ActivationFunction::Sigmoid], Some(-0.5));
+        test_nn.calculate_all_activation_values(&ColumnVector::from_vec(vec![1.0, 0.5, 0.25]));
+        //the hidden layer is all negative so relu zeroes it and the output is sigmoid(-0.5).
+        assert_eq!(test_nn.activation_values[1].data, vec![0.0, 0.0]);
+        assert_eq!(test_nn.activation_values.back().unwrap().data, vec![sigmoid(-0.5); 2]);
+
+        let weights = vec![
+            Matrix::from_vec(vec![vec![0.5, -0.2, 0.1], vec![0.3, 0.8, -0.4]]),
+            Matrix::from_vec(vec![vec![0.7, -0.6], vec![0.2, 0.9]]),
+        ];
+        let mut test_nn = NeuralNetwork::new_from_vecs(weights, None, None, None);
+        test_nn.activation_functions = vec![ActivationFunction::Identity, ActivationFunction::Sigmoid];
+        let input = ColumnVector::from_vec(vec![1.0, 0.5, 0.25]);
+        let desired = ColumnVector::from_vec(vec![0.0, 1.0]);
+        let check = check_gradients(&mut test_nn, &input, &desired);
+        assert!(check.max_absolute_error < 1e-3, "{:?}", check);
+    }
+
+    #[test]
+    fn dropout_only_applies_while_training() {
+        let weights = vec![Matrix::identity(200), Matrix::identity(200)];
+        let mut test_nn = NeuralNetwork::new_from_vecs(weights, None, None, None);
+        test_nn.dropout = Some(Dropout::new(0.5));
+        let input = ColumnVector::new_with_elements(200, 1.0);
+        let desired = ColumnVector::new_with_elements(200, 0.0);
+
+        test_nn.calculate_all_activation_values(&input);
+        assert_eq!(test_nn.activation_values[1], input);
+
+        test_nn.mode = Mode::Training;
+        let gradients = test_nn.backpropagation(&input, &desired);
+        let hidden = &test_nn.activation_values[1];
+        assert!(hidden.data.contains(&0.0));
+        for (index, &activation) in hidden.data.iter().enumerate() {
+            assert!(activation == 0.0 || activation == 2.0);
+            //dropped units must not receive any gradient.
+            if activation == 0.0 {
+                assert!(gradients.weights[0].row(index).iter().all(|&x| x == 0.0));
+                assert_eq!(gradients.biases[0].data[index], 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn gradient_clipping() {
+        let gradients = || Gradients {
+            weights: vec![Matrix::from_vec(vec![vec![3.0, 0.0]])],
+            biases: vec![ColumnVector::from_vec(vec![-4.0])],
+            gammas: vec![],
+            betas: vec![],
+        };
+        let mut by_norm = gradients();
+        assert_eq!(by_norm.global_norm(), 5.0);
+        by_norm.clip_by_global_norm(10.0);
+        assert_eq!(by_norm, gradients());
+        by_norm.clip_by_global_norm(1.0);
+        assert!((by_norm.global_norm() - 1.0).abs() < 1e-6);
+        assert!((by_norm.weights[0][(0, 0)] - 0.6).abs() < 1e-6);
+        assert!((by_norm.biases[0].data[0] + 0.8).abs() < 1e-6);
+
+        let mut by_value = gradients();
+        by_value.clip_by_value(1.0);
+        assert_eq!(by_value.weights[0], Matrix::from_vec(vec![vec![1.0, 0.0]]));
+        assert_eq!(by_value.biases[0], ColumnVector::from_vec(vec![-1.0]));
+    }
+
     #[test]
     fn check_serialization_and_deserialization() {
         let m1 = Matrix::identity(2);
-        let m2 = Matrix::from_vec(m1.data.clone());
+        let m2 = m1.clone();
         let b1 = ColumnVector::from_vec(vec![1.0, 1.0]);
         let b2 = ColumnVector::from_vec(b1.data.clone());
-        let nn = NeuralNetwork::new_from_vecs(vec![m1, m2], Some(vec![b1, b2]), None);
+        let nn = NeuralNetwork::new_from_vecs(vec![m1, m2], Some(vec![b1, b2]), None, None);
         let test_match = vec![NNSerializationValues::Size(3), //layers
                               NNSerializationValues::Size(2), //size of first layer
                               NNSerializationValues::Size(2), //size of second layer
@@ -467,8 +1490,33 @@ mod tests {
         let matchvals = NeuralNetwork::deserialize_from_file_to_values("test.nn");
         let matchvals2 = NeuralNetwork::deserialize_from_file_to_values("test.nn");
         let see: Vec<NNSerializationValues> = matchvals2.collect();
+        assert_eq!(see, test_match);
         let nn3 = NeuralNetwork::create_nn_from_deserialized_values(matchvals);
         let nn4 = NeuralNetwork::create_nn_from_deserialized_values(Box::new(test_match.into_iter()));
         assert_eq!(nn3, nn4);
     }
+
+    #[test]
+    fn serde_round_trip() {
+        let mut network = NeuralNetwork::new_classifier(&[3, 4, 2], None);
+        network.activation_functions[0] = ActivationFunction::LeakyRelu(0.1);
+        network.normalization = Some(Normalization::Batch(BatchNorm::for_network(&network)));
+        network.preprocessing = Some(Preprocessing::Scale { max: 255.0 });
+        network.dropout = Some(Dropout::new(0.25));
+        let json = serde_json::to_string(&network).unwrap();
+        assert!(!json.contains("activation_values") && !json.contains("z_values"));
+        let mut deserialized: NeuralNetwork = serde_json::from_str(&json).unwrap();
+        let input = ColumnVector::from_vec(vec![10.0, 200.0, 30.0]);
+        network.calculate_all_activation_values(&input);
+        deserialized.calculate_all_activation_values(&input);
+        assert_eq!(deserialized.activation_values, network.activation_values);
+        assert_eq!(deserialized.dropout.as_ref().unwrap().probability, 0.25);
+        //the rng of dropout is not persisted.
+        deserialized.dropout = None;
+        network.dropout = None;
+        assert_eq!(deserialized, network);
+
+        network.activation_functions[0] = ActivationFunction::Custom(Arc::new(Relu));
+        assert!(serde_json::to_string(&network).is_err());
+    }
 }
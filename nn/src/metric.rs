@@ -0,0 +1,68 @@
+use std::fmt::Debug;
+use crate::Prediction;
+
+//a quantity computed over the predictions of a whole dataset, e.g. by the trainer after
+//every epoch. update sees every sample once, finalize returns the result and starts over.
+pub trait Metric: Debug + Send {
+    //shown next to the value when the metric is reported.
+    fn name(&self) -> String;
+    fn update(&mut self, prediction: &Prediction, target: usize);
+    fn finalize(&mut self) -> f32;
+}
+
+//fraction of samples whose label is among the k most probable classes.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TopKAccuracy {
+    pub k: usize,
+    correct: usize,
+    total: usize,
+}
+
+impl TopKAccuracy {
+    pub fn new(k: usize) -> TopKAccuracy {
+        TopKAccuracy {
+            k,
+            correct: 0,
+            total: 0,
+        }
+    }
+}
+
+impl Metric for TopKAccuracy {
+    fn name(&self) -> String {
+        format!("top {} accuracy", self.k)
+    }
+
+    fn update(&mut self, prediction: &Prediction, target: usize) {
+        if prediction.top_k(self.k).contains(&target) {
+            self.correct += 1;
+        }
+        self.total += 1;
+    }
+
+    fn finalize(&mut self) -> f32 {
+        let value = if self.total == 0 { 0.0 } else { self.correct as f32 / self.total as f32 };
+        self.correct = 0;
+        self.total = 0;
+        value
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::ColumnVector;
+    use crate::{ActivationFunction, Metric, Prediction, TopKAccuracy};
+
+    #[test]
+    fn top_k_metric() {
+        let mut metric = TopKAccuracy::new(2);
+        let prediction = Prediction::from_output(ColumnVector::from_vec(vec![0.2, 0.5, 0.3]), &ActivationFunction::Softmax);
+        for target in 0..3 {
+            metric.update(&prediction, target);
+        }
+        assert_eq!(metric.name(), "top 2 accuracy");
+        assert_eq!(metric.finalize(), 2.0 / 3.0);
+        assert_eq!(metric.finalize(), 0.0);
+    }
+}
@@ -0,0 +1,662 @@
+use std::any::Any;
+use std::iter::zip;
+use matrix::ColumnVector;
+use mnist_reader::Dataset;
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use crate::layer::zero_gradients;
+use crate::{ActivationFunction, ActivationLayer, AvgPool2d, Conv2d, Cost, Dense, Dropout, DropoutLayer, Evaluation, Flatten, GradientValues, Initialization, Layer, Loss, LossAccumulator, MaxPool2d, Metric, Mode, NeuralNetwork, Normalization, NormalizationLayer, Optimizer, Residual, Shape, Trainable};
+
+//a stack of layers that are run one after the other, trained against cost.
+#[derive(Debug)]
+pub struct Model {
+    pub layers: Vec<Box<dyn Layer>>,
+    pub cost: Cost,
+    pub input_shape: Shape,
+    //one per layer, see freeze.
+    pub frozen: Vec<bool>,
+    //what the layers were last switched to, see Trainable::set_mode.
+    pub mode: Mode,
+}
+
+//collects the layers of a Model, see Model::sequential. every layer is checked against the
+//shape of the layer before it as it is added, so a model that builds fits together.
+pub struct SequentialBuilder {
+    layers: Vec<Box<dyn Layer>>,
+    cost: Cost,
+    initialization: Initialization,
+    rng: StdRng,
+    input_shape: Option<Shape>,
+    //the output shape of the last layer, unknown as long as the input shape is.
+    shape: Option<Shape>,
+    //the first mistake in the layers added so far, build returns it.
+    error: Option<String>,
+}
+
+impl Model {
+    //Model::sequential().dense(784, 128).relu().dense(128, 10).softmax().build()?
+    pub fn sequential() -> SequentialBuilder {
+        SequentialBuilder {
+            layers: Vec::new(),
+            cost: Cost::SquaredError,
+            initialization: Initialization::default(),
+            rng: StdRng::from_rng(thread_rng()).unwrap(),
+            input_shape: None,
+            shape: None,
+            error: None,
+        }
+    }
+
+    //the input shape followed by the output shape of every layer.
+    pub fn shapes(&self) -> Vec<Shape> {
+        let mut shapes = vec![self.input_shape];
+        for layer in &self.layers {
+            shapes.push(layer.output_shape(*shapes.last().unwrap()).unwrap());
+        }
+        shapes
+    }
+
+    pub fn forward(&mut self, input: &ColumnVector) -> ColumnVector {
+        if input.data.len() != self.input_shape.size() {
+            panic!("expected {} inputs, got {}.", self.input_shape.size(), input.data.len());
+        }
+        self.layers.iter_mut().fold(input.clone(), |x, layer| layer.forward(&x))
+    }
+
+    //runs input through the model and adds the gradients of the cost for desired to the
+    //accumulated ones of every layer. returns the cost.
+    pub fn backpropagation(&mut self, input: &ColumnVector, desired: &ColumnVector) -> f32 {
+        let output = self.forward(input);
+        let gradient = self.cost.gradient(&output, desired);
+        self.layers.iter_mut().rev().fold(gradient, |x, layer| layer.backward(&x));
+        self.cost.value(&output, desired)
+    }
+
+    //the optimizers leave the parameters of a frozen layer alone, the gradient still flows through it.
+    pub fn freeze(&mut self, layer_index: usize) {
+        self.frozen[layer_index] = true;
+    }
+
+    pub fn unfreeze(&mut self, layer_index: usize) {
+        self.frozen[layer_index] = false;
+    }
+
+    //one step of optimizer with the gradients averaged over the batch. returns the mean cost.
+    //a Trainer adds shuffling, learning rate schedules, regularization and callbacks to this.
+    pub fn train_batch<O: Optimizer<Model>>(&mut self, batch: &[(ColumnVector, ColumnVector)], optimizer: &mut O, learning_rate: f32) -> f32 {
+        let (mut gradients, loss) = self.parallel_batch_gradients_and_loss(batch, 1);
+        gradients.scale(1.0 / batch.len() as f32);
+        optimizer.step(self, &gradients, learning_rate);
+        loss.mean()
+    }
+
+    //the layers of NeuralNetwork::to_layers with the cost, mode and frozen layers of network.
+    pub fn from_network(network: &NeuralNetwork) -> Result<Model, String> {
+        let layers = network.to_layers()?;
+        //every layer of the network starts with its dense layer.
+        let frozen = layers.iter()
+            .scan(0, |dense_amount, layer| {
+                *dense_amount += (layer.as_ref() as &dyn Any).is::<Dense>() as usize;
+                Some(network.is_frozen(*dense_amount - 1))
+            })
+            .collect();
+        Ok(Model {
+            frozen,
+            input_shape: Shape::Flat(network.weights[0].width()),
+            cost: network.cost.clone(),
+            mode: network.mode,
+            layers,
+        })
+    }
+
+    //the layer at index if it is a T.
+    fn layer_of<T: Layer>(&self, index: usize) -> Option<&T> {
+        self.layers.get(index).and_then(|layer| (layer.as_ref() as &dyn Any).downcast_ref::<T>())
+    }
+
+    //the network of a model laid out like NeuralNetwork::to_layers: dense layers that are each followed
+    //by an activation layer, on the hidden layers optionally with a normalization layer before and a
+    //dropout layer after the activation, so the model can be saved in any model file format or moved
+    //to a device. the parameters are copied.
+    pub fn to_network(&self) -> Result<NeuralNetwork, String> {
+        let (mut weights, mut biases, mut activation_functions, mut frozen) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let (mut normalizations, mut dropouts) = (Vec::new(), Vec::new());
+        let mut index = 0;
+        while index < self.layers.len() {
+            let dense = self.layer_of::<Dense>(index)
+                .ok_or_else(|| format!("layer {} is a {}, a network only has dense layers there.", index, self.layers[index].name()))?;
+            weights.push(dense.weights.clone());
+            biases.push(dense.biases.clone());
+            frozen.push(self.frozen[index]);
+            index += 1;
+            normalizations.push(self.layer_of::<NormalizationLayer>(index).map(|layer| layer.normalization.clone()));
+            index += normalizations.last().unwrap().is_some() as usize;
+            let activation = self.layer_of::<ActivationLayer>(index).ok_or_else(|| match self.layers.get(index) {
+                Some(layer) => format!("layer {} is a {}, a network only has activation layers there.", index, layer.name()),
+                None => "a network needs an activation layer after its last dense layer.".to_string(),
+            })?;
+            activation_functions.push(activation.activation_function.clone());
+            index += 1;
+            dropouts.push(self.layer_of::<DropoutLayer>(index).map(|layer| layer.dropout.clone()));
+            index += dropouts.last().unwrap().is_some() as usize;
+        }
+        let hidden_amount = weights.len() - 1;
+        if normalizations[hidden_amount].is_some() || dropouts[hidden_amount].is_some() {
+            return Err("a network has no normalization or dropout on its output layer.".to_string());
+        }
+        let normalizations: Vec<Normalization> = normalizations.into_iter().flatten().collect();
+        let normalization = match normalizations.len() {
+            0 => None,
+            amount if amount == hidden_amount => Some(Normalization::join(&normalizations)?),
+            _ => return Err("a network normalizes all of its hidden layers or none of them.".to_string()),
+        };
+        let dropouts: Vec<Dropout> = dropouts.into_iter().flatten().collect();
+        if !dropouts.is_empty() && (dropouts.len() != hidden_amount || dropouts.iter().any(|dropout| dropout.probability != dropouts[0].probability)) {
+            return Err("a network drops out of all of its hidden layers with the same probability or of none of them.".to_string());
+        }
+        let mut network = NeuralNetwork::new_from_vecs(weights, Some(biases), None, None);
+        network.activation_functions = activation_functions;
+        network.normalization = normalization;
+        network.dropout = dropouts.into_iter().next();
+        network.cost = self.cost.clone();
+        network.mode = self.mode;
+        network.frozen = frozen;
+        Ok(network)
+    }
+
+    //a copy of the model for another thread, None when one of its layers can not be copied.
+    fn fork(&mut self) -> Option<Model> {
+        Some(Model {
+            layers: self.layers.iter_mut().map(|layer| layer.fork()).collect::<Option<Vec<_>>>()?,
+            cost: self.cost.clone(),
+            input_shape: self.input_shape,
+            frozen: self.frozen.clone(),
+            mode: self.mode,
+        })
+    }
+
+    fn batch_gradients_and_loss(&mut self, batch: &[(ColumnVector, ColumnVector)]) -> (Vec<f32>, LossAccumulator) {
+        zero_gradients(&mut self.layers);
+        let mut loss = LossAccumulator::new();
+        for (input, desired) in batch {
+            loss.add_value(self.backpropagation(input, desired));
+        }
+        let gradients = self.layers.iter_mut()
+            .flat_map(|layer| layer.params())
+            .map(|param| *param.gradient)
+            .collect();
+        (gradients, loss)
+    }
+}
+
+//the penalties apply to the parameters Param::regularized marks.
+impl Trainable for Model {
+    type Gradients = Vec<f32>;
+
+    fn parameters_mut(&mut self) -> impl Iterator<Item=&mut f32> {
+        self.layers.iter_mut()
+            .flat_map(|layer| layer.params())
+            .map(|param| param.value)
+    }
+
+    fn trainable_parameters(&self) -> Vec<bool> {
+        zip(&self.layers, &self.frozen)
+            .flat_map(|(layer, frozen)| std::iter::repeat_n(!frozen, layer.parameter_count()))
+            .collect()
+    }
+
+    fn zeroed_gradients(&self) -> Vec<f32> {
+        vec![0.0; self.layers.iter().map(|layer| layer.parameter_count()).sum()]
+    }
+
+    //on one thread the layers keep the values of the last forward pass of the batch. with more,
+    //every thread runs its part of the batch through copies of the layers made by Layer::fork and
+    //the parts are added up in order. a model with a layer that can not be copied stays on the
+    //calling thread.
+    fn parallel_batch_gradients_and_loss(&mut self, batch: &[(ColumnVector, ColumnVector)], threads: usize) -> (Vec<f32>, LossAccumulator) {
+        if threads <= 1 || batch.len() < 2 {
+            return self.batch_gradients_and_loss(batch);
+        }
+        let parts: Vec<&[(ColumnVector, ColumnVector)]> = batch.chunks(batch.len().div_ceil(threads)).collect();
+        let Some(mut copies) = parts.iter().map(|_| self.fork()).collect::<Option<Vec<Model>>>() else {
+            return self.batch_gradients_and_loss(batch);
+        };
+        let results: Vec<(Vec<f32>, LossAccumulator)> = std::thread::scope(|scope| {
+            let handles: Vec<_> = zip(&mut copies, parts)
+                .map(|(copy, part)| scope.spawn(move || copy.batch_gradients_and_loss(part)))
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        let mut gradients = self.zeroed_gradients();
+        let mut loss = LossAccumulator::new();
+        for (part_gradients, part_loss) in &results {
+            zip(&mut gradients, part_gradients).for_each(|(gradient, part_gradient)| *gradient += part_gradient);
+            loss.merge(part_loss);
+        }
+        (gradients, loss)
+    }
+
+    fn penalty(&mut self, l2_lambda: f32, l1_lambda: f32) -> f32 {
+        let weights: Vec<f32> = self.layers.iter_mut()
+            .flat_map(|layer| layer.params())
+            .filter(|param| param.regularized)
+            .map(|param| *param.value)
+            .collect();
+        0.5 * l2_lambda * weights.iter().map(|x| x * x).sum::<f32>() + l1_lambda * weights.iter().map(|x| x.abs()).sum::<f32>()
+    }
+
+    fn add_penalty_gradients(&mut self, gradients: &mut Vec<f32>, l2_lambda: f32, l1_lambda: f32) {
+        let params = self.layers.iter_mut().flat_map(|layer| layer.params());
+        zip(gradients.iter_mut(), params)
+            .filter(|(_, param)| param.regularized)
+            .for_each(|(gradient, param)| {
+                let weight = *param.value;
+                *gradient += l2_lambda * weight;
+                if weight != 0.0 {
+                    *gradient += l1_lambda * weight.signum();
+                }
+            });
+    }
+
+    fn output_size(&self) -> usize {
+        self.shapes().last().unwrap().size()
+    }
+
+    fn mean_loss(&mut self, data: &[(ColumnVector, ColumnVector)]) -> f32 {
+        let mut loss = LossAccumulator::new();
+        for (input, desired) in data {
+            let output = self.forward(input);
+            loss.add(&self.cost, &output, desired);
+        }
+        loss.mean()
+    }
+
+    fn validate<D: Dataset + ?Sized>(&mut self, dataset: &D, metrics: &mut [Box<dyn Metric>]) -> Evaluation {
+        self.evaluate_with_metrics(dataset, metrics)
+    }
+
+    fn set_mode(&mut self, mode: Mode) -> Mode {
+        self.layers.iter_mut().for_each(|layer| layer.set_mode(mode));
+        std::mem::replace(&mut self.mode, mode)
+    }
+
+    fn reseed(&mut self, rng: &mut StdRng) {
+        self.layers.iter_mut().for_each(|layer| layer.reseed(rng));
+    }
+}
+
+impl SequentialBuilder {
+    //only needed when the first layer does not tell, a dense layer does.
+    pub fn input(mut self, shape: Shape) -> SequentialBuilder {
+        if !self.layers.is_empty() {
+            return self.fail("the input shape has to be set before the first layer.".to_string());
+        }
+        self.input_shape = Some(shape);
+        self.shape = Some(shape);
+        self
+    }
+
+    //layers added after a mistake are ignored, build returns the first one.
+    pub fn layer(mut self, layer: impl Layer + 'static) -> SequentialBuilder {
+        if self.error.is_some() {
+            return self;
+        }
+        if let Some(shape) = self.shape {
+            match layer.output_shape(shape) {
+                Ok(shape) => self.shape = Some(shape),
+                Err(message) => return self.fail(message),
+            }
+        }
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    fn fail(mut self, message: String) -> SequentialBuilder {
+        self.error.get_or_insert(message);
+        self
+    }
+
+    //the output shape of the layers so far as an image, for the layers that work on images.
+    fn image_shape(&self) -> Result<(usize, usize, usize), String> {
+        match self.shape {
+            Some(Shape::Image { channels, height, width }) => Ok((channels, height, width)),
+            Some(shape) => Err(format!("expected an image, got {}.", shape)),
+            None => Err("the input shape is unknown, set it with input.".to_string()),
+        }
+    }
+
+    //a dense layer drawn with the current initialization.
+    pub fn dense(mut self, input_size: usize, output_size: usize) -> SequentialBuilder {
+        if self.layers.is_empty() && self.input_shape.is_none() {
+            self = self.input(Shape::Flat(input_size));
+        }
+        let dense = Dense::random(input_size, output_size, self.initialization, &mut self.rng);
+        self.layer(dense)
+    }
+
+    //a convolution of the images so far, drawn with the current initialization.
+    pub fn conv2d(mut self, output_channels: usize, kernel_size: usize, stride: usize, padding: usize) -> SequentialBuilder {
+        match self.image_shape() {
+            Ok(shape) => {
+                let conv = Conv2d::random(shape, output_channels, kernel_size, stride, padding, self.initialization, &mut self.rng);
+                self.layer(conv)
+            }
+            Err(message) => self.fail(message),
+        }
+    }
+
+    pub fn max_pool2d(self, pool_size: usize, stride: usize) -> SequentialBuilder {
+        match self.image_shape() {
+            Ok(shape) => self.layer(MaxPool2d::new(shape, pool_size, stride)),
+            Err(message) => self.fail(message),
+        }
+    }
+
+    pub fn avg_pool2d(self, pool_size: usize, stride: usize) -> SequentialBuilder {
+        match self.image_shape() {
+            Ok(shape) => self.layer(AvgPool2d::new(shape, pool_size, stride)),
+            Err(message) => self.fail(message),
+        }
+    }
+
+    pub fn flatten(self) -> SequentialBuilder {
+        self.layer(Flatten)
+    }
+
+    //the amount of flat values the layers so far output, for the layers that work on those.
+    fn flat_size(&self) -> Result<usize, String> {
+        match self.shape {
+            Some(Shape::Flat(size)) => Ok(size),
+            Some(shape) => Err(format!("expected flat values, got {}, flatten them first.", shape)),
+            None => Err("the input shape is unknown, set it with input.".to_string()),
+        }
+    }
+
+    pub fn batch_norm(self) -> SequentialBuilder {
+        match self.flat_size() {
+            Ok(size) => self.layer(NormalizationLayer::batch_norm(size)),
+            Err(message) => self.fail(message),
+        }
+    }
+
+    pub fn layer_norm(self) -> SequentialBuilder {
+        match self.flat_size() {
+            Ok(size) => self.layer(NormalizationLayer::layer_norm(size)),
+            Err(message) => self.fail(message),
+        }
+    }
+
+    //dropout seeded from the rng of the builder, so seeded builds drop the same values.
+    pub fn dropout(mut self, probability: f32) -> SequentialBuilder {
+        let dropout = Dropout::new_with_seed(probability, self.rng.gen());
+        self.layer(DropoutLayer::new(dropout))
+    }
+
+    //a skip connection around the layers block adds to the builder it is given, which
+    //starts from the current shape. Model::sequential().dense(784, 64).relu()
+    //.residual(|block| block.dense(64, 64).relu()).dense(64, 10).softmax().build()?
+    pub fn residual(mut self, block: impl FnOnce(SequentialBuilder) -> SequentialBuilder) -> SequentialBuilder {
+        let Some(shape) = self.shape else {
+            return self.fail("the input shape is unknown, set it with input.".to_string());
+        };
+        let inner = SequentialBuilder {
+            layers: Vec::new(),
+            cost: Cost::SquaredError,
+            initialization: self.initialization,
+            rng: std::mem::replace(&mut self.rng, StdRng::seed_from_u64(0)),
+            input_shape: Some(shape),
+            shape: Some(shape),
+            error: None,
+        };
+        let inner = block(inner);
+        self.rng = inner.rng;
+        match inner.error {
+            Some(message) => self.fail(message),
+            None => self.layer(Residual::new(inner.layers)),
+        }
+    }
+
+    pub fn activation(self, activation_function: ActivationFunction) -> SequentialBuilder {
+        self.layer(ActivationLayer::new(activation_function))
+    }
+
+    pub fn relu(self) -> SequentialBuilder {
+        self.activation(ActivationFunction::Relu)
+    }
+
+    pub fn sigmoid(self) -> SequentialBuilder {
+        self.activation(ActivationFunction::Sigmoid)
+    }
+
+    pub fn tanh(self) -> SequentialBuilder {
+        self.activation(ActivationFunction::Tanh)
+    }
+
+    pub fn softmax(self) -> SequentialBuilder {
+        self.activation(ActivationFunction::Softmax)
+    }
+
+    //used for the dense and convolutional layers added after it.
+    pub fn initialization(mut self, initialization: Initialization) -> SequentialBuilder {
+        self.initialization = initialization;
+        self
+    }
+
+    //makes the initialization of the layers added after it the same on every run.
+    pub fn seed(mut self, seed: u64) -> SequentialBuilder {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn cost(mut self, cost: Cost) -> SequentialBuilder {
+        self.cost = cost;
+        self
+    }
+
+    //the first mistake made while adding the layers, like layers that do not fit together, is an error.
+    pub fn build(self) -> Result<Model, String> {
+        if let Some(message) = self.error {
+            return Err(message);
+        }
+        if self.layers.is_empty() {
+            return Err("a model needs at least one layer.".to_string());
+        }
+        let input_shape = self.input_shape.ok_or("the input shape is unknown, set it with input.")?;
+        Ok(Model {
+            frozen: vec![false; self.layers.len()],
+            layers: self.layers,
+            cost: self.cost,
+            input_shape,
+            mode: Mode::Inference,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix::ColumnVector;
+    use mnist_reader::DataLoader;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::{ActivationFunction, Adam, BatchNorm, Cost, Dropout, EarlyStopping, Initialization, Model, NeuralNetwork, Normalization, Sgd, Shape, Trainable, Trainer};
+
+    fn xor_data() -> Vec<(ColumnVector, ColumnVector)> {
+        [([0.0, 0.0], 0), ([0.0, 1.0], 1), ([1.0, 0.0], 1), ([1.0, 1.0], 0)].iter()
+            .map(|(input, class)| {
+                let mut desired = vec![0.0; 2];
+                desired[*class] = 1.0;
+                (ColumnVector::from_vec(input.to_vec()), ColumnVector::from_vec(desired))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sequential_model_learns_xor() {
+        let mut model = Model::sequential()
+            .seed(3)
+            .initialization(Initialization::GlorotUniform)
+            .dense(2, 8).tanh()
+            .dense(8, 2).softmax()
+            .cost(Cost::CrossEntropy)
+            .build().unwrap();
+        assert_eq!(model.layers.len(), 4);
+        let data = xor_data();
+        let cost_before = model.train_batch(&data, &mut Sgd, 0.5);
+        let cost_after = (0..1000).map(|_| model.train_batch(&data, &mut Sgd, 0.5)).last().unwrap();
+        assert!(cost_after < cost_before * 0.1, "{} {}", cost_before, cost_after);
+        for (input, desired) in &data {
+            let output = model.forward(input);
+            assert_eq!(output.data[1] > output.data[0], desired.data[1] == 1.0);
+        }
+    }
+
+    #[test]
+    fn seeded_builds_are_equal() {
+        let build = || Model::sequential().seed(9).dense(3, 4).relu().dense(4, 2).build().unwrap();
+        let input = ColumnVector::from_vec(vec![0.1, 0.2, 0.3]);
+        assert_eq!(build().forward(&input), build().forward(&input));
+    }
+
+    #[test]
+    fn small_cnn_learns() {
+        //a vertical or a horizontal line in a 4 by 4 image.
+        let data: Vec<(ColumnVector, ColumnVector)> = (0..4)
+            .flat_map(|line| {
+                let vertical = (0..16).map(|i| if i % 4 == line { 1.0 } else { 0.0 }).collect();
+                let horizontal = (0..16).map(|i| if i / 4 == line { 1.0 } else { 0.0 }).collect();
+                [(ColumnVector::from_vec(vertical), ColumnVector::from_vec(vec![1.0, 0.0])),
+                 (ColumnVector::from_vec(horizontal), ColumnVector::from_vec(vec![0.0, 1.0]))]
+            })
+            .collect();
+        let mut model = Model::sequential()
+            .seed(5)
+            .initialization(Initialization::HeNormal)
+            .input(Shape::image(1, 4, 4))
+            .conv2d(2, 3, 1, 1).relu()
+            .max_pool2d(2, 2)
+            .flatten()
+            .dense(2 * 2 * 2, 2).softmax()
+            .cost(Cost::CrossEntropy)
+            .build().unwrap();
+        assert_eq!(model.shapes(), vec![Shape::image(1, 4, 4), Shape::image(2, 4, 4), Shape::image(2, 4, 4), Shape::image(2, 2, 2), Shape::Flat(8), Shape::Flat(2), Shape::Flat(2)]);
+        let cost_before = model.train_batch(&data, &mut Sgd, 0.2);
+        let cost_after = (0..300).map(|_| model.train_batch(&data, &mut Sgd, 0.2)).last().unwrap();
+        assert!(cost_after < cost_before * 0.1, "{} {}", cost_before, cost_after);
+    }
+
+    #[test]
+    fn dense_layers_must_fit_together() {
+        assert!(Model::sequential().dense(784, 128).relu().dense(100, 10).build().is_err());
+    }
+
+    #[test]
+    fn images_must_be_flattened() {
+        let model = Model::sequential().input(Shape::image(1, 28, 28)).conv2d(4, 3, 1, 0).relu().dense(4 * 26 * 26, 10).build();
+        assert_eq!(model.unwrap_err(), "a dense layer can not follow an image of (4, 26, 26), flatten it first.");
+    }
+
+    #[test]
+    fn convolutions_need_images() {
+        //the first mistake is reported, not the ones that follow from it.
+        let model = Model::sequential().input(Shape::Flat(784)).conv2d(4, 3, 1, 0).flatten().dense(10, 2).build();
+        assert_eq!(model.unwrap_err(), "expected an image, got (784).");
+        assert_eq!(Model::sequential().relu().build().unwrap_err(), "the input shape is unknown, set it with input.");
+        assert_eq!(Model::sequential().build().unwrap_err(), "a model needs at least one layer.");
+    }
+
+    #[test]
+    fn residual_blocks() {
+        let build = |seed| Model::sequential()
+            .seed(seed)
+            .dense(3, 4).relu()
+            .residual(|block| block.dense(4, 4).relu().dense(4, 4))
+            .dense(4, 2)
+            .build().unwrap();
+        let mut model = build(2);
+        assert_eq!(model.layers.len(), 4);
+        assert_eq!(model.shapes().last(), Some(&Shape::Flat(2)));
+        //the block draws from the rng of the builder, so seeded builds stay the same.
+        let input = ColumnVector::from_vec(vec![0.1, 0.2, 0.3]);
+        assert_eq!(model.forward(&input), build(2).forward(&input));
+    }
+
+    #[test]
+    fn residual_blocks_keep_their_shape() {
+        let model = Model::sequential().dense(3, 4).residual(|block| block.dense(4, 3)).build();
+        assert_eq!(model.unwrap_err(), "a residual block has to keep the shape (4), its output is (3).");
+    }
+
+    #[test]
+    fn frozen_layers_keep_their_parameters() {
+        let mut model = Model::sequential().seed(4).dense(2, 3).tanh().dense(3, 2).build().unwrap();
+        model.freeze(0);
+        let frozen_before: Vec<f32> = model.layers[0].params().iter().map(|param| *param.value).collect();
+        let trained_before: Vec<f32> = model.layers[2].params().iter().map(|param| *param.value).collect();
+        let data = vec![(ColumnVector::from_vec(vec![1.0, 0.5]), ColumnVector::from_vec(vec![0.0, 1.0]))];
+        model.train_batch(&data, &mut Sgd, 0.1);
+        assert_eq!(model.layers[0].params().iter().map(|param| *param.value).collect::<Vec<_>>(), frozen_before);
+        assert_ne!(model.layers[2].params().iter().map(|param| *param.value).collect::<Vec<_>>(), trained_before);
+    }
+
+    #[test]
+    fn trainer_trains_models() {
+        let mut model = Model::sequential()
+            .seed(3)
+            .initialization(Initialization::GlorotUniform)
+            .dense(2, 8).tanh()
+            .dense(8, 2).softmax()
+            .cost(Cost::CrossEntropy)
+            .build().unwrap();
+        let dataset: Vec<(ColumnVector, usize)> = xor_data().into_iter()
+            .map(|(input, desired)| (input, if desired.data[1] == 1.0 { 1 } else { 0 }))
+            .collect();
+        let mut trainer = Trainer::new_with_optimizer(4, 0.05, 300, Adam::default());
+        trainer.rng = StdRng::seed_from_u64(1);
+        trainer.l2_lambda = 1e-4;
+        trainer.callbacks.push(Box::new(EarlyStopping::new(300, 0.0)));
+        let evaluations = trainer.train_with_validation(&mut model, &DataLoader::new(&dataset, 4), &dataset);
+        assert_eq!(evaluations.len(), 300);
+        assert!(evaluations.last().unwrap().loss < evaluations[0].loss * 0.1);
+        assert_eq!(model.evaluate(&dataset).accuracy, 1.0);
+    }
+
+    #[test]
+    fn threads_split_the_batch() {
+        let mut model = Model::sequential().seed(6).dense(2, 5).layer_norm().tanh().dense(5, 2).softmax().cost(Cost::CrossEntropy).build().unwrap();
+        let data: Vec<(ColumnVector, ColumnVector)> = (0..5).flat_map(|_| xor_data()).collect();
+        let (single, single_loss) = model.parallel_batch_gradients_and_loss(&data, 1);
+        let (threaded, threaded_loss) = model.parallel_batch_gradients_and_loss(&data, 3);
+        assert!((single_loss.mean() - threaded_loss.mean()).abs() < 1e-6);
+        assert!(single.iter().zip(&threaded).all(|(a, b)| (a - b).abs() < 1e-5), "{:?} {:?}", single, threaded);
+    }
+
+    #[test]
+    fn models_and_networks_convert() {
+        let mut network = NeuralNetwork::new_with_seed(&[3, 4, 2], vec![ActivationFunction::Tanh, ActivationFunction::Sigmoid], 4);
+        network.cost = Cost::CrossEntropy;
+        network.freeze(0);
+        let mut model = Model::from_network(&network).unwrap();
+        assert_eq!(model.frozen, vec![true, true, false, false]);
+        let input = ColumnVector::from_vec(vec![0.1, 0.2, 0.3]);
+        assert_eq!(model.forward(&input), network.infer(&input));
+        assert_eq!(model.to_network(), Ok(network));
+
+        //normalization and dropout come back too.
+        let mut network = NeuralNetwork::new_with_seed(&[3, 4, 4, 2], vec![ActivationFunction::Tanh, ActivationFunction::Relu, ActivationFunction::Sigmoid], 4);
+        network.normalization = Some(Normalization::Batch(BatchNorm::for_network(&network)));
+        network.dropout = Some(Dropout::new_with_seed(0.25, 5));
+        network.freeze(1);
+        let model = Model::from_network(&network).unwrap();
+        assert_eq!(model.frozen, vec![false, false, false, false, true, true, true, true, false, false]);
+        let converted = model.to_network().unwrap();
+        assert_eq!((&converted.normalization, converted.dropout.as_ref().map(|dropout| dropout.probability)), (&network.normalization, Some(0.25)));
+        assert_eq!((&converted.weights, &converted.frozen), (&network.weights, &vec![false, true, false]));
+        let misplaced_dropout = Model::sequential().dense(3, 4).dropout(0.5).relu().build().unwrap();
+        assert!(misplaced_dropout.to_network().is_err());
+
+        let convolution = Model::sequential().input(Shape::image(1, 4, 4)).conv2d(2, 3, 1, 1).flatten().dense(32, 2).relu().build().unwrap();
+        assert_eq!(convolution.to_network().unwrap_err(), "layer 0 is a Conv2d, a network only has dense layers there.");
+        let unfinished = Model::sequential().dense(3, 4).relu().dense(4, 2).build().unwrap();
+        assert!(unfinished.to_network().is_err());
+    }
+}
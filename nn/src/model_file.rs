@@ -0,0 +1,301 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use matrix::{ColumnVector, Matrix};
+use mnist_reader::Preprocessing;
+use crate::{invalid_data, ActivationFunction, BatchNorm, Cost, LayerNorm, NeuralNetwork, Normalization};
+
+//layout of a saved network, every number little endian:
+//the magic bytes and the format version (u32),
+//the amount of layers (u32), the size of every layer (u32 each),
+//the name of the activation of every layer after the input (u32 length and utf8 bytes each),
+//the cost (u8 code and f32 parameter),
+//the preprocessing (u8 code, 0 for none, and two f32 parameters),
+//the normalization (u8 code, 0 for none, its constants and then its vectors for every hidden layer),
+//the training metadata (u32 epochs, u8 1 if an accuracy follows and the f32 accuracy),
+//every weight row by row and then every bias (f32 each).
+const MAGIC: &[u8; 8] = b"mnistnn\0";
+//raised whenever the layout changes, files of other versions are rejected.
+pub const FORMAT_VERSION: u32 = 1;
+
+//what is known about the training of a saved network.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct ModelMetadata {
+    pub epochs: usize,
+    pub accuracy: Option<f32>,
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32<R: Read>(reader: &mut R) -> io::Result<f32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_vector<R: Read>(reader: &mut R, size: usize) -> io::Result<ColumnVector> {
+    (0..size).map(|_| read_f32(reader)).collect::<io::Result<Vec<_>>>().map(ColumnVector::from_vec)
+}
+
+fn read_vectors<R: Read>(reader: &mut R, sizes: &[usize]) -> io::Result<Vec<ColumnVector>> {
+    sizes.iter().map(|&size| read_vector(reader, size)).collect()
+}
+
+fn read_activation<R: Read>(reader: &mut R) -> io::Result<ActivationFunction> {
+    let length = read_u32(reader)? as usize;
+    //read through take, a corrupt length must not allocate gigabytes up front.
+    let mut name = Vec::new();
+    reader.take(length as u64).read_to_end(&mut name)?;
+    if name.len() != length {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the file ends within an activation name."));
+    }
+    let name = String::from_utf8(name).map_err(|_| invalid_data("an activation name is not utf8.".to_string()))?;
+    ActivationFunction::from_name(&name).ok_or_else(|| invalid_data(format!("unknown activation {}.", name)))
+}
+
+fn cost_code(cost: &Cost) -> io::Result<(u8, f32)> {
+    Ok(match cost {
+        Cost::SquaredError => (0, 0.0),
+        Cost::CrossEntropy => (1, 0.0),
+        Cost::MeanAbsoluteError => (2, 0.0),
+        Cost::Huber(delta) => (3, *delta),
+        Cost::Custom(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "custom losses can not be saved.")),
+    })
+}
+
+fn cost_from_code(code: u8, parameter: f32) -> io::Result<Cost> {
+    Ok(match code {
+        0 => Cost::SquaredError,
+        1 => Cost::CrossEntropy,
+        2 => Cost::MeanAbsoluteError,
+        3 => Cost::Huber(parameter),
+        _ => return Err(invalid_data(format!("unknown cost code {}.", code))),
+    })
+}
+
+impl NeuralNetwork {
+    //the layer sizes, activations, cost, preprocessing, normalization, weights and biases.
+    //Dropout is only used while training and is not part of the file.
+    pub fn save<P: AsRef<Path>>(&self, file_path: P) -> io::Result<()> {
+        self.save_with_metadata(file_path, ModelMetadata::default())
+    }
+
+    pub fn save_with_metadata<P: AsRef<Path>>(&self, file_path: P, metadata: ModelMetadata) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        self.write_with_metadata(&mut writer, metadata)?;
+        writer.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(file_path: P) -> io::Result<NeuralNetwork> {
+        NeuralNetwork::load_with_metadata(file_path).map(|(network, _)| network)
+    }
+
+    pub fn load_with_metadata<P: AsRef<Path>>(file_path: P) -> io::Result<(NeuralNetwork, ModelMetadata)> {
+        NeuralNetwork::read_with_metadata(BufReader::new(File::open(file_path)?))
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_with_metadata(writer, ModelMetadata::default())
+    }
+
+    pub fn read_from<R: Read>(reader: R) -> io::Result<NeuralNetwork> {
+        NeuralNetwork::read_with_metadata(reader).map(|(network, _)| network)
+    }
+
+    pub fn write_with_metadata<W: Write>(&self, mut writer: W, metadata: ModelMetadata) -> io::Result<()> {
+        let mut layer_sizes = vec![self.weights[0].width()];
+        layer_sizes.extend(self.biases.iter().map(|x| x.data.len()));
+        //checked before anything is written so a failed save leaves no half written header.
+        let activations = self.activation_functions.iter()
+            .map(|x| x.name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "custom activations can not be saved.")))
+            .collect::<io::Result<Vec<_>>>()?;
+        let (cost, cost_parameter) = cost_code(&self.cost)?;
+        let epochs = u32::try_from(metadata.epochs)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "more than u32::MAX epochs can not be saved."))?;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&(layer_sizes.len() as u32).to_le_bytes())?;
+        for size in layer_sizes {
+            writer.write_all(&(size as u32).to_le_bytes())?;
+        }
+        for name in activations {
+            writer.write_all(&(name.len() as u32).to_le_bytes())?;
+            writer.write_all(name.as_bytes())?;
+        }
+        writer.write_all(&[cost])?;
+        writer.write_all(&cost_parameter.to_le_bytes())?;
+        let (code, parameters) = match self.preprocessing {
+            None => (0, [0.0, 0.0]),
+            Some(Preprocessing::Scale { max }) => (1, [max, 0.0]),
+            Some(Preprocessing::Standardize { mean, standard_deviation }) => (2, [mean, standard_deviation]),
+        };
+        writer.write_all(&[code])?;
+        for parameter in parameters {
+            writer.write_all(&parameter.to_le_bytes())?;
+        }
+        let (code, constants, vectors): (u8, Vec<f32>, Vec<&ColumnVector>) = match &self.normalization {
+            None => (0, vec![], vec![]),
+            Some(Normalization::Batch(batch_norm)) => (1, vec![batch_norm.epsilon, batch_norm.momentum],
+                [&batch_norm.gammas, &batch_norm.betas, &batch_norm.running_means, &batch_norm.running_variances].into_iter().flatten().collect()),
+            Some(Normalization::Layer(layer_norm)) => (2, vec![layer_norm.epsilon],
+                [&layer_norm.gammas, &layer_norm.betas].into_iter().flatten().collect()),
+        };
+        writer.write_all(&[code])?;
+        for value in constants.iter().chain(vectors.into_iter().flat_map(|x| x.data.iter())) {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&epochs.to_le_bytes())?;
+        writer.write_all(&[metadata.accuracy.is_some() as u8])?;
+        writer.write_all(&metadata.accuracy.unwrap_or(0.0).to_le_bytes())?;
+        for value in self.weight_values().chain(self.biases.iter().flat_map(|x| x.data.iter())) {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read_with_metadata<R: Read>(mut reader: R) -> io::Result<(NeuralNetwork, ModelMetadata)> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a saved network.".to_string()));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != FORMAT_VERSION {
+            return Err(invalid_data(format!("the network was saved in format version {}, but only version {} can be loaded.", version, FORMAT_VERSION)));
+        }
+        let layer_amount = read_u32(&mut reader)? as usize;
+        if layer_amount < 2 {
+            return Err(invalid_data(format!("a network needs at least 2 layers, got {}.", layer_amount)));
+        }
+        let layer_sizes = (0..layer_amount).map(|_| read_u32(&mut reader).map(|x| x as usize)).collect::<io::Result<Vec<_>>>()?;
+        if layer_sizes.contains(&0) {
+            return Err(invalid_data("layers must not be empty.".to_string()));
+        }
+        let activation_functions = (1..layer_amount).map(|_| read_activation(&mut reader)).collect::<io::Result<Vec<_>>>()?;
+        let cost = cost_from_code(read_u8(&mut reader)?, read_f32(&mut reader)?)?;
+        let code = read_u8(&mut reader)?;
+        let parameters = [read_f32(&mut reader)?, read_f32(&mut reader)?];
+        let preprocessing = match code {
+            0 => None,
+            1 => Some(Preprocessing::Scale { max: parameters[0] }),
+            2 => Some(Preprocessing::Standardize { mean: parameters[0], standard_deviation: parameters[1] }),
+            _ => return Err(invalid_data(format!("unknown preprocessing code {}.", code))),
+        };
+        let hidden_layer_sizes = &layer_sizes[1..layer_amount - 1];
+        let normalization = match read_u8(&mut reader)? {
+            0 => None,
+            1 => Some(Normalization::Batch(BatchNorm {
+                epsilon: read_f32(&mut reader)?,
+                momentum: read_f32(&mut reader)?,
+                gammas: read_vectors(&mut reader, hidden_layer_sizes)?,
+                betas: read_vectors(&mut reader, hidden_layer_sizes)?,
+                running_means: read_vectors(&mut reader, hidden_layer_sizes)?,
+                running_variances: read_vectors(&mut reader, hidden_layer_sizes)?,
+            })),
+            2 => Some(Normalization::Layer(LayerNorm {
+                epsilon: read_f32(&mut reader)?,
+                gammas: read_vectors(&mut reader, hidden_layer_sizes)?,
+                betas: read_vectors(&mut reader, hidden_layer_sizes)?,
+            })),
+            code => return Err(invalid_data(format!("unknown normalization code {}.", code))),
+        };
+        let epochs = read_u32(&mut reader)? as usize;
+        let has_accuracy = read_u8(&mut reader)? != 0;
+        let accuracy = read_f32(&mut reader)?;
+        let metadata = ModelMetadata { epochs, accuracy: has_accuracy.then_some(accuracy) };
+        let weights = layer_sizes.windows(2)
+            .map(|sizes| (0..sizes[1])
+                .map(|_| (0..sizes[0]).map(|_| read_f32(&mut reader)).collect())
+                .collect::<io::Result<Vec<Vec<f32>>>>()
+                .map(Matrix::from_vec))
+            .collect::<io::Result<Vec<_>>>()?;
+        let biases = read_vectors(&mut reader, &layer_sizes[1..])?;
+        let mut network = NeuralNetwork::new_from_vecs(weights, Some(biases), None, None);
+        network.activation_functions = activation_functions;
+        network.cost = cost;
+        network.preprocessing = preprocessing;
+        network.normalization = normalization;
+        Ok((network, metadata))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::Arc;
+    use matrix::ColumnVector;
+    use mnist_reader::Preprocessing;
+    use crate::model_file::{ModelMetadata, FORMAT_VERSION};
+    use crate::{ActivationFunction, BatchNorm, NeuralNetwork, Normalization, Relu};
+
+    #[test]
+    fn save_and_load() {
+        let mut network = NeuralNetwork::new_classifier(&[4, 3, 2], None);
+        network.activation_functions[0] = ActivationFunction::LeakyRelu(0.1);
+        let file_path = std::env::temp_dir().join(format!("nn_save_{}.nn", std::process::id()));
+        network.save(&file_path).unwrap();
+        let loaded = NeuralNetwork::load(&file_path).unwrap();
+        //"leaky_relu(0.1)" and "softmax" are 15 and 7 bytes long.
+        assert_eq!(std::fs::metadata(&file_path).unwrap().len(), 8 + 4 + 4 + 3 * 4 + 2 * 4 + 15 + 7 + 5 + 9 + 1 + 9 + (4 * 3 + 3 * 2 + 3 + 2) * 4);
+        std::fs::remove_file(&file_path).unwrap();
+        assert_eq!(loaded, network);
+        let input = ColumnVector::from_vec(vec![0.5, -1.0, 2.0, 0.0]);
+        assert_eq!(loaded.infer(&input), network.infer(&input));
+
+        let mut bytes = Vec::new();
+        network.write_to(&mut bytes).unwrap();
+        assert!(NeuralNetwork::read_from(&bytes[..bytes.len() - 1]).is_err());
+        bytes[0] = b'x';
+        assert!(NeuralNetwork::read_from(&bytes[..]).is_err());
+        network.activation_functions[1] = ActivationFunction::Custom(Arc::new(Relu));
+        assert!(network.write_to(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn versions_and_metadata() {
+        let mut network = NeuralNetwork::new_classifier(&[4, 3, 2], None);
+        let mut batch_norm = BatchNorm::for_network(&network);
+        batch_norm.running_means[0] = ColumnVector::from_vec(vec![0.1, 0.2, 0.3]);
+        network.normalization = Some(Normalization::Batch(batch_norm));
+        network.preprocessing = Some(Preprocessing::Standardize { mean: 0.13, standard_deviation: 0.31 });
+        let metadata = ModelMetadata { epochs: 12, accuracy: Some(0.97) };
+        let mut bytes = Vec::new();
+        network.write_with_metadata(&mut bytes, metadata).unwrap();
+        let (loaded, loaded_metadata) = NeuralNetwork::read_with_metadata(&bytes[..]).unwrap();
+        assert_eq!(loaded, network);
+        assert_eq!(loaded_metadata, metadata);
+
+        bytes[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let error = NeuralNetwork::read_from(&bytes[..]).unwrap_err();
+        assert_eq!(error.to_string(), format!("the network was saved in format version {}, but only version {} can be loaded.", FORMAT_VERSION + 1, FORMAT_VERSION));
+
+        let metadata = ModelMetadata { epochs: u32::MAX as usize + 1, accuracy: None };
+        let error = network.write_with_metadata(&mut Vec::new(), metadata).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn corrupt_activation_names() {
+        let network = NeuralNetwork::new_classifier(&[4, 3, 2], None);
+        let mut bytes = Vec::new();
+        network.write_to(&mut bytes).unwrap();
+        //the length of the first activation name follows the magic, the version and 3 layer sizes.
+        let start = 8 + 4 + 4 + 3 * 4;
+        bytes[start..start + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(NeuralNetwork::read_from(&bytes[..]).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        bytes[start..start + 4].copy_from_slice(&3u32.to_le_bytes());
+        assert_eq!(NeuralNetwork::read_from(&bytes[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,141 @@
+use std::iter::zip;
+use matrix::ColumnVector;
+use serde::{Deserialize, Serialize};
+use crate::{BatchNorm, LayerNorm};
+
+//normalization applied to the z values of every hidden layer. A network uses at most one kind.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub enum Normalization {
+    Batch(BatchNorm),
+    Layer(LayerNorm),
+}
+
+//what a single sample forward pass through the normalization leaves for the backward pass.
+#[derive(Debug, Clone)]
+pub struct NormalizedValues {
+    pub normalized: ColumnVector,
+    //the values handed to the nonlinearity.
+    pub pre_activation: ColumnVector,
+    //only used by layer normalization, batch normalization keeps its own per feature.
+    inverse_std: f32,
+}
+
+impl Normalization {
+    pub fn gammas(&self) -> &[ColumnVector] {
+        match self {
+            Normalization::Batch(batch_norm) => &batch_norm.gammas,
+            Normalization::Layer(layer_norm) => &layer_norm.gammas,
+        }
+    }
+
+    pub fn betas(&self) -> &[ColumnVector] {
+        match self {
+            Normalization::Batch(batch_norm) => &batch_norm.betas,
+            Normalization::Layer(layer_norm) => &layer_norm.betas,
+        }
+    }
+
+    pub fn gammas_and_betas_mut(&mut self) -> (&mut [ColumnVector], &mut [ColumnVector]) {
+        match self {
+            Normalization::Batch(batch_norm) => (&mut batch_norm.gammas, &mut batch_norm.betas),
+            Normalization::Layer(layer_norm) => (&mut layer_norm.gammas, &mut layer_norm.betas),
+        }
+    }
+
+    //the normalization of the hidden layer layer_index alone, as a NormalizationLayer holds it.
+    pub fn layer(&self, layer_index: usize) -> Normalization {
+        match self {
+            Normalization::Batch(batch_norm) => Normalization::Batch(BatchNorm {
+                gammas: vec![batch_norm.gammas[layer_index].clone()],
+                betas: vec![batch_norm.betas[layer_index].clone()],
+                running_means: vec![batch_norm.running_means[layer_index].clone()],
+                running_variances: vec![batch_norm.running_variances[layer_index].clone()],
+                ..*batch_norm
+            }),
+            Normalization::Layer(layer_norm) => Normalization::Layer(LayerNorm {
+                gammas: vec![layer_norm.gammas[layer_index].clone()],
+                betas: vec![layer_norm.betas[layer_index].clone()],
+                ..*layer_norm
+            }),
+        }
+    }
+
+    //the normalizations of single layers joined back into one for all of them, in order.
+    //they have to be of the same kind with the same settings.
+    pub fn join(layers: &[Normalization]) -> Result<Normalization, String> {
+        let mismatch = || "the normalizations of the hidden layers differ in their kind or settings.".to_string();
+        match layers.first() {
+            Some(Normalization::Batch(first)) => {
+                let mut joined = BatchNorm { gammas: Vec::new(), betas: Vec::new(), running_means: Vec::new(), running_variances: Vec::new(), ..*first };
+                for layer in layers {
+                    match layer {
+                        Normalization::Batch(batch_norm) if batch_norm.epsilon == first.epsilon && batch_norm.momentum == first.momentum => {
+                            joined.gammas.extend_from_slice(&batch_norm.gammas);
+                            joined.betas.extend_from_slice(&batch_norm.betas);
+                            joined.running_means.extend_from_slice(&batch_norm.running_means);
+                            joined.running_variances.extend_from_slice(&batch_norm.running_variances);
+                        }
+                        _ => return Err(mismatch()),
+                    }
+                }
+                Ok(Normalization::Batch(joined))
+            }
+            Some(Normalization::Layer(first)) => {
+                let mut joined = LayerNorm { gammas: Vec::new(), betas: Vec::new(), ..*first };
+                for layer in layers {
+                    match layer {
+                        Normalization::Layer(layer_norm) if layer_norm.epsilon == first.epsilon => {
+                            joined.gammas.extend_from_slice(&layer_norm.gammas);
+                            joined.betas.extend_from_slice(&layer_norm.betas);
+                        }
+                        _ => return Err(mismatch()),
+                    }
+                }
+                Ok(Normalization::Layer(joined))
+            }
+            None => Err("there are no normalizations to join.".to_string()),
+        }
+    }
+
+    //normalizes a single sample. Batch normalization uses its running statistics here.
+    pub fn forward(&self, layer_index: usize, z_values: &ColumnVector) -> NormalizedValues {
+        match self {
+            Normalization::Batch(batch_norm) => {
+                let normalized = batch_norm.normalize_with_running_statistics(layer_index, z_values);
+                NormalizedValues {
+                    pre_activation: batch_norm.scale_and_shift(layer_index, &normalized),
+                    normalized,
+                    inverse_std: 0.0,
+                }
+            }
+            Normalization::Layer(layer_norm) => {
+                let (normalized, inverse_std) = layer_norm.normalize(z_values);
+                NormalizedValues {
+                    pre_activation: layer_norm.scale_and_shift(layer_index, &normalized),
+                    normalized,
+                    inverse_std,
+                }
+            }
+        }
+    }
+
+    //given the gradient with respect to the pre activation of a single sample, returns
+    //the gradients with respect to the z values, gamma and beta of the layer.
+    pub fn backward(&self, layer_index: usize, values: &NormalizedValues, scaled_delta: ColumnVector) -> (ColumnVector, ColumnVector, ColumnVector) {
+        let delta = match self {
+            Normalization::Batch(batch_norm) => {
+                let inverse_std = batch_norm.running_inverse_std(layer_index);
+                ColumnVector::from_vec(zip(zip(&scaled_delta.data, &batch_norm.gammas[layer_index].data), &inverse_std.data)
+                    .map(|((delta, gamma), inverse_std)| delta * gamma * inverse_std)
+                    .collect())
+            }
+            Normalization::Layer(layer_norm) => {
+                layer_norm.backward(layer_index, &scaled_delta, &values.normalized, values.inverse_std)
+            }
+        };
+        let gamma_gradient = ColumnVector::from_vec(zip(&scaled_delta.data, &values.normalized.data)
+            .map(|(delta, normalized)| delta * normalized)
+            .collect());
+        (delta, gamma_gradient, scaled_delta)
+    }
+}
@@ -0,0 +1,168 @@
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use matrix::{ColumnVector, Matrix};
+use crate::{invalid_data, NeuralNetwork};
+
+//arrays are named like the tensors of the safetensors files: layers.<index>.weight with shape
+//[outputs, inputs] and layers.<index>.bias with shape [outputs], so numpy.load(path)["layers.0.weight"]
+//is the first weight matrix.
+
+//a version 1.0 .npy file of little endian f32 values in c order.
+fn npy(shape: &[usize], values: impl Iterator<Item=f32>) -> Vec<u8> {
+    let shape = match shape {
+        [size] => format!("({},)", size),
+        _ => format!("({})", shape.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")),
+    };
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}", shape);
+    //the magic, version and header length take 10 bytes and the data starts 64 byte aligned.
+    let padding = 63 - (10 + header.len()) % 64;
+    header += &" ".repeat(padding);
+    header.push('\n');
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend((header.len() as u16).to_le_bytes());
+    bytes.extend(header.as_bytes());
+    bytes.extend(values.flat_map(|x| x.to_le_bytes()));
+    bytes
+}
+
+//the value of a key in the header dictionary, up to the next key.
+fn header_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{}':", key))? + key.len() + 3;
+    let value = header[start..].trim_start();
+    let end = if value.starts_with('(') { value.find(')')? + 1 } else { value.find(',')? };
+    Some(value[..end].trim())
+}
+
+//the shape and values of a .npy file of f4 or f8 values, fortran ordered matrices are transposed into c order.
+fn parse_npy(bytes: &[u8], name: &str) -> io::Result<(Vec<usize>, Vec<f32>)> {
+    let error = |message: &str| invalid_data(format!("{} is not a supported npy array: {}.", name, message));
+    if bytes.len() < 10 || &bytes[..6] != b"\x93NUMPY" {
+        return Err(error("missing magic"));
+    }
+    let (header_length, header_start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize, 12),
+        _ => return Err(error("unknown version")),
+    };
+    let header = bytes.get(header_start..header_start + header_length)
+        .and_then(|header| std::str::from_utf8(header).ok())
+        .ok_or_else(|| error("truncated header"))?;
+    let descr = header_value(header, "descr").ok_or_else(|| error("no descr"))?;
+    let fortran_order = header_value(header, "fortran_order").ok_or_else(|| error("no fortran_order"))? == "True";
+    let shape = header_value(header, "shape").ok_or_else(|| error("no shape"))?
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.parse::<usize>().map_err(|_| error("bad shape")))
+        .collect::<io::Result<Vec<_>>>()?;
+    let data = &bytes[header_start + header_length..];
+    let mut values: Vec<f32> = match descr {
+        "'<f4'" => data.chunks_exact(4).map(|x| f32::from_le_bytes(x.try_into().unwrap())).collect(),
+        "'<f8'" => data.chunks_exact(8).map(|x| f64::from_le_bytes(x.try_into().unwrap()) as f32).collect(),
+        _ => return Err(error(&format!("dtype {} is not float32 or float64", descr))),
+    };
+    let size = shape.iter().try_fold(1usize, |size, &dim| size.checked_mul(dim))
+        .ok_or_else(|| error("the shape overflows"))?;
+    if values.len() != size {
+        return Err(error("the data does not match the shape"));
+    }
+    if fortran_order && shape.len() == 2 {
+        let (rows, columns) = (shape[0], shape[1]);
+        values = (0..rows).flat_map(|row| (0..columns).map(move |column| column * rows + row))
+            .map(|index| values[index])
+            .collect();
+    }
+    Ok((shape, values))
+}
+
+fn read_array<R: Read + io::Seek>(archive: &mut ZipArchive<R>, name: &str) -> io::Result<Option<(Vec<usize>, Vec<f32>)>> {
+    let mut file = match archive.by_name(&format!("{}.npy", name)) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(error) => return Err(invalid_data(format!("could not read {}: {}.", name, error))),
+    };
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    parse_npy(&bytes, name).map(Some)
+}
+
+impl NeuralNetwork {
+    //an uncompressed archive like numpy.savez writes.
+    pub fn to_npz(&self) -> io::Result<Vec<u8>> {
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (index, (weights, biases)) in self.weights.iter().zip(&self.biases).enumerate() {
+            archive.start_file(format!("layers.{}.weight.npy", index), options)?;
+            archive.write_all(&npy(&[weights.height(), weights.width()], weights.as_slice().iter().copied()))?;
+            archive.start_file(format!("layers.{}.bias.npy", index), options)?;
+            archive.write_all(&npy(&[biases.data.len()], biases.data.iter().copied()))?;
+        }
+        Ok(archive.finish()?.into_inner())
+    }
+
+    //reads archives written by numpy.savez or numpy.savez_compressed, float64 arrays are narrowed to f32.
+    //the archive only holds the parameters, so every layer uses relu like new_from_vecs.
+    pub fn from_npz(bytes: &[u8]) -> io::Result<NeuralNetwork> {
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(|error| invalid_data(format!("not an npz archive: {}.", error)))?;
+        let mut weights: Vec<Matrix> = Vec::new();
+        let mut biases = Vec::new();
+        while let Some((shape, values)) = read_array(&mut archive, &format!("layers.{}.weight", weights.len()))? {
+            let index = weights.len();
+            if shape.len() != 2 || shape.contains(&0) {
+                return Err(invalid_data(format!("layers.{}.weight must be a non empty matrix, got shape {:?}.", index, shape)));
+            }
+            if weights.last().is_some_and(|previous| previous.height() != shape[1]) {
+                return Err(invalid_data(format!("layer {} has {} inputs but the previous one has a different amount of outputs.", index, shape[1])));
+            }
+            let bias = read_array(&mut archive, &format!("layers.{}.bias", index))?
+                .ok_or_else(|| invalid_data(format!("layers.{}.bias is missing.", index)))?;
+            if bias.0 != shape[..1] {
+                return Err(invalid_data(format!("layers.{}.bias has shape {:?}, expected [{}].", index, bias.0, shape[0])));
+            }
+            weights.push(Matrix::from_vec(values.chunks(shape[1]).map(|x| x.to_vec()).collect()));
+            biases.push(ColumnVector::from_vec(bias.1));
+        }
+        if weights.is_empty() {
+            return Err(invalid_data("no array layers.0.weight in the archive.".to_string()));
+        }
+        Ok(NeuralNetwork::new_from_vecs(weights, Some(biases), None, None))
+    }
+
+    pub fn save_npz<P: AsRef<Path>>(&self, file_path: P) -> io::Result<()> {
+        fs::write(file_path, self.to_npz()?)
+    }
+
+    pub fn load_npz<P: AsRef<Path>>(file_path: P) -> io::Result<NeuralNetwork> {
+        NeuralNetwork::from_npz(&fs::read(file_path)?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::npz::{npy, parse_npy};
+    use crate::{ActivationFunction, NeuralNetwork};
+
+    #[test]
+    fn npz_round_trip() {
+        let bytes = npy(&[2, 3], (0..6).map(|x| x as f32));
+        assert_eq!(&bytes[..10], b"\x93NUMPY\x01\x00\x76\x00");
+        assert!(std::str::from_utf8(&bytes[10..128]).unwrap().starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }"));
+        assert_eq!(parse_npy(&bytes, "x").unwrap(), (vec![2, 3], (0..6).map(|x| x as f32).collect()));
+        //the same matrix stored column by column as float64.
+        let mut fortran = b"\x93NUMPY\x01\x00\x3a\x00{'descr': '<f8', 'fortran_order': True, 'shape': (2, 3), }".to_vec();
+        fortran.extend([0.0f64, 3.0, 1.0, 4.0, 2.0, 5.0].iter().flat_map(|x| x.to_le_bytes()));
+        assert_eq!(parse_npy(&fortran, "x").unwrap().1, (0..6).map(|x| x as f32).collect::<Vec<_>>());
+        //a shape whose product overflows is rejected instead of wrapping around to the 0 values stored.
+        let overflowing = npy(&[1 << 32, 1 << 32], std::iter::empty());
+        assert_eq!(parse_npy(&overflowing, "x").unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+        let network = NeuralNetwork::new_with_activations(&[4, 3, 2], vec![ActivationFunction::Relu; 2], None);
+        let loaded = NeuralNetwork::from_npz(&network.to_npz().unwrap()).unwrap();
+        assert_eq!(loaded, network);
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::iter::zip;
+use std::path::Path;
+use matrix::{ColumnVector, Matrix};
+use mnist_reader::Preprocessing;
+use crate::protobuf::{parse, Field, Message};
+use crate::{invalid_data, ActivationFunction, Cost, NeuralNetwork, Normalization};
+
+//opset 17 is the first with LayerNormalization.
+const OPSET_VERSION: i64 = 17;
+const IR_VERSION: i64 = 8;
+//TensorProto.DataType.FLOAT
+const FLOAT: i64 = 1;
+//AttributeProto.AttributeType
+const ATTRIBUTE_FLOAT: i64 = 1;
+const ATTRIBUTE_INT: i64 = 2;
+
+enum Attribute {
+    Float(&'static str, f32),
+    Int(&'static str, i64),
+}
+
+//the nodes and initializers of the graph, every node output gets a fresh name.
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Message>,
+    initializers: Vec<Message>,
+    value_amount: usize,
+}
+
+impl Graph {
+    fn node(&mut self, op_type: &str, inputs: &[&str], attributes: &[Attribute]) -> String {
+        self.value_amount += 1;
+        let output = format!("{}_{}", op_type.to_lowercase(), self.value_amount);
+        let mut node = Message::new();
+        inputs.iter().for_each(|input| { node.string(1, input); });
+        node.string(2, &output).string(3, &output).string(4, op_type);
+        for attribute in attributes {
+            let mut message = Message::new();
+            match attribute {
+                Attribute::Float(name, value) => message.string(1, name).float(2, *value).int(20, ATTRIBUTE_FLOAT),
+                Attribute::Int(name, value) => message.string(1, name).int(3, *value).int(20, ATTRIBUTE_INT),
+            };
+            node.message(5, &message);
+        }
+        self.nodes.push(node);
+        output
+    }
+
+    //names the final value "output".
+    fn output(&mut self, input: &str) {
+        let mut node = Message::new();
+        node.string(1, input).string(2, "output").string(3, "output").string(4, "Identity");
+        self.nodes.push(node);
+    }
+
+    fn initializer(&mut self, name: &str, dims: &[usize], values: &[f32]) -> String {
+        let mut tensor = Message::new();
+        dims.iter().for_each(|&dim| { tensor.int(1, dim as i64); });
+        let raw: Vec<u8> = values.iter().flat_map(|x| x.to_le_bytes()).collect();
+        tensor.int(2, FLOAT).string(8, name).bytes(9, &raw);
+        self.initializers.push(tensor);
+        name.to_string()
+    }
+
+    fn constant(&mut self, name: &str, value: f32) -> String {
+        self.initializer(name, &[], &[value])
+    }
+
+    fn activation(&mut self, activation: &ActivationFunction, input: &str, layer_index: usize) -> io::Result<String> {
+        Ok(match activation {
+            ActivationFunction::Relu => self.node("Relu", &[input], &[]),
+            ActivationFunction::Sigmoid => self.node("Sigmoid", &[input], &[]),
+            ActivationFunction::Identity => input.to_string(),
+            ActivationFunction::LeakyRelu(slope) => self.node("LeakyRelu", &[input], &[Attribute::Float("alpha", *slope)]),
+            ActivationFunction::Elu(alpha) => self.node("Elu", &[input], &[Attribute::Float("alpha", *alpha)]),
+            ActivationFunction::Tanh => self.node("Tanh", &[input], &[]),
+            ActivationFunction::Softmax => self.node("Softmax", &[input], &[Attribute::Int("axis", -1)]),
+            ActivationFunction::Swish => {
+                let sigmoid = self.node("Sigmoid", &[input], &[]);
+                self.node("Mul", &[input, &sigmoid], &[])
+            }
+            //the tanh approximation the network uses: 0.5 * x * (1 + tanh(sqrt(2 / pi) * (x + 0.044715 * x^3))).
+            ActivationFunction::Gelu => {
+                let coefficient = self.constant(&format!("layers.{}.gelu_coefficient", layer_index), 0.044715);
+                let scale = self.constant(&format!("layers.{}.gelu_scale", layer_index), (2.0 / std::f32::consts::PI).sqrt());
+                let one = self.constant(&format!("layers.{}.gelu_one", layer_index), 1.0);
+                let half = self.constant(&format!("layers.{}.gelu_half", layer_index), 0.5);
+                let square = self.node("Mul", &[input, input], &[]);
+                let cube = self.node("Mul", &[&square, input], &[]);
+                let scaled_cube = self.node("Mul", &[&cube, &coefficient], &[]);
+                let sum = self.node("Add", &[input, &scaled_cube], &[]);
+                let inner = self.node("Mul", &[&sum, &scale], &[]);
+                let tanh = self.node("Tanh", &[&inner], &[]);
+                let shifted = self.node("Add", &[&tanh, &one], &[]);
+                let product = self.node("Mul", &[input, &shifted], &[]);
+                self.node("Mul", &[&product, &half], &[])
+            }
+            ActivationFunction::Custom(_) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "custom activations can not be exported to onnx."));
+            }
+        })
+    }
+}
+
+//a float tensor with a dynamic batch dimension, as graph input or output.
+fn value_info(name: &str, features: usize) -> Message {
+    let mut shape = Message::new();
+    shape.message(1, Message::new().string(2, "batch"));
+    shape.message(1, Message::new().int(1, features as i64));
+    let mut tensor_type = Message::new();
+    tensor_type.int(1, FLOAT).message(2, &shape);
+    let mut value_info = Message::new();
+    value_info.string(1, name).message(2, Message::new().message(1, &tensor_type));
+    value_info
+}
+
+//the fields of a NodeProto the importer looks at.
+struct Node<'a> {
+    op_type: &'a str,
+    inputs: Vec<&'a str>,
+    output: &'a str,
+    attributes: HashMap<&'a str, Field<'a>>,
+}
+
+impl<'a> Node<'a> {
+    fn parse(bytes: &'a [u8]) -> io::Result<Node<'a>> {
+        let mut node = Node { op_type: "", inputs: Vec::new(), output: "", attributes: HashMap::new() };
+        for (field, value) in parse(bytes)? {
+            match field {
+                1 => node.inputs.push(value.string().unwrap_or("")),
+                2 => node.output = value.string().unwrap_or(""),
+                4 => node.op_type = value.string().unwrap_or(""),
+                5 => {
+                    let attribute = parse(value.bytes().unwrap_or(&[]))?;
+                    let name = attribute.iter().find(|(field, _)| *field == 1).and_then(|(_, name)| name.string());
+                    //the value is f for floats and i for ints.
+                    let value = attribute.iter().find(|(field, _)| *field == 2 || *field == 3).map(|(_, value)| *value);
+                    if let (Some(name), Some(value)) = (name, value) {
+                        node.attributes.insert(name, value);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(node)
+    }
+
+    fn int(&self, name: &str, default: i64) -> i64 {
+        self.attributes.get(name).and_then(|x| x.int()).unwrap_or(default)
+    }
+
+    fn float(&self, name: &str, default: f32) -> f32 {
+        self.attributes.get(name).and_then(|x| x.float()).unwrap_or(default)
+    }
+}
+
+//a float initializer with its dimensions.
+struct Tensor {
+    dims: Vec<usize>,
+    values: Vec<f32>,
+}
+
+fn parse_tensor(bytes: &[u8]) -> io::Result<(String, Tensor)> {
+    let mut name = String::new();
+    let mut tensor = Tensor { dims: Vec::new(), values: Vec::new() };
+    let mut data_type = 0;
+    for (field, value) in parse(bytes)? {
+        match field {
+            1 => {
+                for dim in value.ints()? {
+                    tensor.dims.push(usize::try_from(dim).map_err(|_| invalid_data(format!("initializer dimension {} is negative.", dim)))?);
+                }
+            }
+            2 => data_type = value.int().unwrap_or(0),
+            4 => tensor.values.extend(value.floats()?),
+            8 => name = value.string().unwrap_or("").to_string(),
+            9 => tensor.values = Field::Bytes(value.bytes().unwrap_or(&[])).floats()?,
+            _ => {}
+        }
+    }
+    if data_type != FLOAT {
+        return Err(invalid_data(format!("initializer {} is not a float tensor, only float models can be imported.", name)));
+    }
+    let size = tensor.dims.iter().try_fold(1usize, |size, &dim| size.checked_mul(dim))
+        .ok_or_else(|| invalid_data(format!("initializer {} has dimensions {:?} that overflow.", name, tensor.dims)))?;
+    if tensor.values.len() != size {
+        return Err(invalid_data(format!("initializer {} has {} values for dimensions {:?}.", name, tensor.values.len(), tensor.dims)));
+    }
+    Ok((name, tensor))
+}
+
+fn activation_of(node: &Node) -> io::Result<Option<ActivationFunction>> {
+    Ok(Some(match node.op_type {
+        "Relu" => ActivationFunction::Relu,
+        "Sigmoid" => ActivationFunction::Sigmoid,
+        "Tanh" => ActivationFunction::Tanh,
+        "LeakyRelu" => ActivationFunction::LeakyRelu(node.float("alpha", 0.01)),
+        "Elu" => ActivationFunction::Elu(node.float("alpha", 1.0)),
+        "Softmax" => {
+            //opset 13 and later default to the last axis, which is the only one of a [batch, features] tensor.
+            if !matches!(node.int("axis", -1), -1 | 1) {
+                return Err(invalid_data("softmax must be over the features.".to_string()));
+            }
+            ActivationFunction::Softmax
+        }
+        _ => return Ok(None),
+    }))
+}
+
+//the layers of the chain read so far.
+#[derive(Default)]
+struct Layers {
+    weights: Vec<Matrix>,
+    biases: Vec<ColumnVector>,
+    activation_functions: Vec<Option<ActivationFunction>>,
+}
+
+impl Layers {
+    //rows of the weight matrix are the outputs, like the weights of a network.
+    fn push(&mut self, weights: Vec<Vec<f32>>, biases: Vec<f32>) -> io::Result<()> {
+        if let Some(previous) = self.biases.last() {
+            if previous.data.len() != weights[0].len() {
+                return Err(invalid_data(format!("a layer with {} inputs follows one with {} outputs.", weights[0].len(), previous.data.len())));
+            }
+        }
+        self.weights.push(Matrix::from_vec(weights));
+        self.biases.push(ColumnVector::from_vec(biases));
+        self.activation_functions.push(None);
+        Ok(())
+    }
+}
+
+fn matrix_rows(tensor: &Tensor, name: &str) -> io::Result<Vec<Vec<f32>>> {
+    if tensor.dims.len() != 2 || tensor.dims.contains(&0) {
+        return Err(invalid_data(format!("initializer {} must be a non empty matrix, got dimensions {:?}.", name, tensor.dims)));
+    }
+    Ok(tensor.values.chunks(tensor.dims[1]).map(|x| x.to_vec()).collect())
+}
+
+fn transpose(rows: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    (0..rows[0].len()).map(|column| rows.iter().map(|row| row[column]).collect()).collect()
+}
+
+impl NeuralNetwork {
+    //reads an onnx model that is a chain of dense layers: Gemm nodes, or MatMul nodes optionally followed
+    //by an Add of the biases, each optionally followed by Relu, Sigmoid, Tanh, LeakyRelu, Elu or Softmax.
+    //weights and biases must be float initializers. Flatten, Dropout and Identity nodes are skipped and
+    //a Div, or a Sub and a Div, by constants in front of the first layer becomes the preprocessing, so
+    //models written by to_onnx without normalization and with these activations come back as they were.
+    //a network ending in softmax gets the cross entropy cost.
+    pub fn from_onnx(bytes: &[u8]) -> io::Result<NeuralNetwork> {
+        let model = parse(bytes)?;
+        let graph = model.iter().find(|(field, _)| *field == 7).and_then(|(_, graph)| graph.bytes())
+            .ok_or_else(|| invalid_data("the onnx model has no graph.".to_string()))?;
+        let graph = parse(graph)?;
+        let initializers = graph.iter().filter(|(field, _)| *field == 5)
+            .map(|(_, tensor)| parse_tensor(tensor.bytes().unwrap_or(&[])))
+            .collect::<io::Result<HashMap<String, Tensor>>>()?;
+        //the first graph input that is not an initializer, older exporters list initializers as inputs too.
+        let mut value = graph.iter().filter(|(field, _)| *field == 11)
+            .filter_map(|(_, input)| parse(input.bytes()?).ok()?.iter().find(|(field, _)| *field == 1)?.1.string())
+            .find(|name| !initializers.contains_key(*name))
+            .ok_or_else(|| invalid_data("the onnx graph has no input.".to_string()))?;
+        let constant = |name: &str| initializers.get(name).filter(|tensor| tensor.values.len() == 1).map(|tensor| tensor.values[0]);
+        let initializer = |name: &str| initializers.get(name).ok_or_else(|| invalid_data(format!("{} is not an initializer.", name)));
+
+        let mut layers = Layers::default();
+        let mut shift = None;
+        let mut preprocessing = None;
+        for (_, node) in graph.iter().filter(|(field, _)| *field == 1) {
+            let node = Node::parse(node.bytes().unwrap_or(&[]))?;
+            //the operand next to the running value, for nodes with two inputs.
+            let other = match node.inputs.as_slice() {
+                [first] if *first == value => None,
+                [first, second, ..] if *first == value => Some(*second),
+                //only an addition gives the same result with its operands the other way around.
+                [first, second] if *second == value && node.op_type == "Add" => Some(*first),
+                [_, second] if *second == value => {
+                    return Err(invalid_data(format!("the {} node {} takes the running value as its second input, which is not supported.", node.op_type, node.output)));
+                }
+                _ => return Err(invalid_data(format!("the {} node {} is not part of a single chain.", node.op_type, node.output))),
+            };
+            let ends_with_activation = layers.activation_functions.last().is_some_and(|x| x.is_some());
+            match (node.op_type, other) {
+                ("Flatten" | "Dropout" | "Identity", _) => {}
+                ("Sub", Some(other)) if layers.weights.is_empty() && shift.is_none() && preprocessing.is_none() => {
+                    shift = Some(constant(other).ok_or_else(|| invalid_data("only constants can be subtracted from the input.".to_string()))?);
+                }
+                ("Div", Some(other)) if layers.weights.is_empty() && preprocessing.is_none() => {
+                    let divisor = constant(other).ok_or_else(|| invalid_data("the input can only be divided by a constant.".to_string()))?;
+                    preprocessing = Some(match shift {
+                        Some(mean) => Preprocessing::Standardize { mean, standard_deviation: divisor },
+                        None => Preprocessing::Scale { max: divisor },
+                    });
+                }
+                ("Gemm", Some(other)) => {
+                    if node.int("transA", 0) != 0 {
+                        return Err(invalid_data("Gemm nodes with transA are not supported.".to_string()));
+                    }
+                    let rows = matrix_rows(initializer(other)?, other)?;
+                    let (alpha, beta) = (node.float("alpha", 1.0), node.float("beta", 1.0));
+                    let weights: Vec<Vec<f32>> = if node.int("transB", 0) != 0 { rows } else { transpose(&rows) }.into_iter()
+                        .map(|row| row.into_iter().map(|x| alpha * x).collect())
+                        .collect();
+                    let biases = match node.inputs.get(2) {
+                        Some(name) if !name.is_empty() => {
+                            let biases = initializer(name)?;
+                            if biases.values.len() != weights.len() {
+                                return Err(invalid_data(format!("{} has {} values for {} outputs.", name, biases.values.len(), weights.len())));
+                            }
+                            biases.values.iter().map(|x| beta * x).collect()
+                        }
+                        _ => vec![0.0; weights.len()],
+                    };
+                    layers.push(weights, biases)?;
+                }
+                ("MatMul", Some(other)) => {
+                    let weights = transpose(&matrix_rows(initializer(other)?, other)?);
+                    let outputs = weights.len();
+                    layers.push(weights, vec![0.0; outputs])?;
+                }
+                ("Add", Some(other)) if !layers.weights.is_empty() && !ends_with_activation => {
+                    let biases = initializer(other)?;
+                    let layer = layers.biases.last_mut().unwrap();
+                    if biases.values.len() != layer.data.len() {
+                        return Err(invalid_data(format!("{} has {} values for {} outputs.", other, biases.values.len(), layer.data.len())));
+                    }
+                    zip(&mut layer.data, &biases.values).for_each(|(bias, value)| *bias += value);
+                }
+                (_, None) if !layers.weights.is_empty() && !ends_with_activation => {
+                    let activation = activation_of(&node)?
+                        .ok_or_else(|| invalid_data(format!("unsupported onnx operator {}.", node.op_type)))?;
+                    *layers.activation_functions.last_mut().unwrap() = Some(activation);
+                }
+                _ => return Err(invalid_data(format!("unsupported onnx operator {} at this point of the graph.", node.op_type))),
+            }
+            value = node.output;
+        }
+        if layers.weights.is_empty() {
+            return Err(invalid_data("the onnx graph has no dense layer.".to_string()));
+        }
+        if shift.is_some() && preprocessing.is_none() {
+            return Err(invalid_data("the input is shifted but not scaled, which is not supported.".to_string()));
+        }
+        let mut network = NeuralNetwork::new_from_vecs(layers.weights, Some(layers.biases), None, None);
+        network.activation_functions = layers.activation_functions.into_iter()
+            .map(|x| x.unwrap_or(ActivationFunction::Identity))
+            .collect();
+        if network.activation_functions.last() == Some(&ActivationFunction::Softmax) {
+            network.cost = Cost::CrossEntropy;
+        }
+        network.preprocessing = preprocessing;
+        Ok(network)
+    }
+
+    pub fn load_onnx<P: AsRef<Path>>(file_path: P) -> io::Result<NeuralNetwork> {
+        NeuralNetwork::from_onnx(&fs::read(file_path)?)
+    }
+
+    //an onnx model taking a float tensor "input" of shape [batch, inputs] to "output" of shape
+    //[batch, outputs]. Every layer is a MatMul with the transposed weights followed by an Add of the
+    //biases and its activation. Preprocessing becomes Sub and Div nodes in front, batch normalization
+    //is folded into the weights and layer normalization becomes LayerNormalization nodes.
+    //dropout only matters in training and is left out.
+    pub fn to_onnx(&self) -> io::Result<Vec<u8>> {
+        let mut graph = Graph::default();
+        let mut value = "input".to_string();
+        match self.preprocessing {
+            Some(Preprocessing::Scale { max }) => {
+                let max = graph.constant("preprocessing.max", max);
+                value = graph.node("Div", &[&value, &max], &[]);
+            }
+            Some(Preprocessing::Standardize { mean, standard_deviation }) => {
+                let mean = graph.constant("preprocessing.mean", mean);
+                let standard_deviation = graph.constant("preprocessing.standard_deviation", standard_deviation);
+                let centered = graph.node("Sub", &[&value, &mean], &[]);
+                value = graph.node("Div", &[&centered, &standard_deviation], &[]);
+            }
+            None => {}
+        }
+
+        let layer_amount = self.weights.len();
+        for (layer_index, (weights, biases)) in zip(&self.weights, &self.biases).enumerate() {
+            let mut weights = weights.clone();
+            let mut biases = biases.data.clone();
+            let hidden = layer_index < layer_amount - 1;
+            if let (Some(Normalization::Batch(batch_norm)), true) = (&self.normalization, hidden) {
+                //gamma * (z - mean) / std + beta as a scale of the weights and a new bias.
+                let inverse_std = batch_norm.running_inverse_std(layer_index);
+                let scales = zip(&batch_norm.gammas[layer_index].data, &inverse_std.data).map(|(gamma, inverse_std)| gamma * inverse_std);
+                for (((row, bias), scale), (mean, beta)) in weights.rows_mut().zip(&mut biases).zip(scales)
+                    .zip(zip(&batch_norm.running_means[layer_index].data, &batch_norm.betas[layer_index].data)) {
+                    row.iter_mut().for_each(|weight| *weight *= scale);
+                    *bias = scale * (*bias - mean) + beta;
+                }
+            }
+            let (outputs, inputs) = (weights.height(), weights.width());
+            let transposed = weights.transpose().into_vec();
+            let weight_name = graph.initializer(&format!("layers.{}.weight", layer_index), &[inputs, outputs], &transposed);
+            let bias_name = graph.initializer(&format!("layers.{}.bias", layer_index), &[outputs], &biases);
+            let product = graph.node("MatMul", &[&value, &weight_name], &[]);
+            value = graph.node("Add", &[&product, &bias_name], &[]);
+            if let (Some(Normalization::Layer(layer_norm)), true) = (&self.normalization, hidden) {
+                let gamma = graph.initializer(&format!("layers.{}.gamma", layer_index), &[outputs], &layer_norm.gammas[layer_index].data);
+                let beta = graph.initializer(&format!("layers.{}.beta", layer_index), &[outputs], &layer_norm.betas[layer_index].data);
+                value = graph.node("LayerNormalization", &[&value, &gamma, &beta], &[Attribute::Int("axis", -1), Attribute::Float("epsilon", layer_norm.epsilon)]);
+            }
+            value = graph.activation(&self.activation_functions[layer_index], &value, layer_index)?;
+        }
+        graph.output(&value);
+
+        let mut graph_message = Message::new();
+        graph.nodes.iter().for_each(|node| { graph_message.message(1, node); });
+        graph_message.string(2, "mnist_rust");
+        graph.initializers.iter().for_each(|initializer| { graph_message.message(5, initializer); });
+        graph_message.message(11, &value_info("input", self.weights[0].width()));
+        graph_message.message(12, &value_info("output", self.biases[layer_amount - 1].data.len()));
+
+        let mut model = Message::new();
+        model.int(1, IR_VERSION)
+            .string(2, "mnist_rust")
+            .string(3, env!("CARGO_PKG_VERSION"))
+            .message(7, &graph_message)
+            .message(8, Message::new().string(1, "").int(2, OPSET_VERSION));
+        Ok(model.bytes)
+    }
+
+    pub fn save_onnx<P: AsRef<Path>>(&self, file_path: P) -> io::Result<()> {
+        fs::write(file_path, self.to_onnx()?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use matrix::ColumnVector;
+    use mnist_reader::Preprocessing;
+    use crate::protobuf::Message;
+    use crate::{ActivationFunction, BatchNorm, Cost, NeuralNetwork, Normalization, Relu};
+
+    fn position(bytes: &[u8], pattern: &[u8]) -> Option<usize> {
+        bytes.windows(pattern.len()).position(|window| window == pattern)
+    }
+
+    #[test]
+    fn onnx_export() {
+        let mut network = NeuralNetwork::new_classifier(&[4, 3, 2], None);
+        network.normalization = Some(Normalization::Batch(BatchNorm::for_network(&network)));
+        let bytes = network.to_onnx().unwrap();
+        //nodes come first in the graph, in the order they are evaluated.
+        let operators: Vec<usize> = [&b"MatMul"[..], b"Add", b"Relu", b"MatMul", b"Add", b"Softmax", b"Identity"].iter()
+            .scan(0, |start, pattern| {
+                let found = *start + position(&bytes[*start..], pattern)?;
+                *start = found + 1;
+                Some(found)
+            })
+            .collect();
+        assert_eq!(operators.len(), 7);
+        assert!(position(&bytes, b"layers.1.bias").is_some());
+        //the transposed first weight matrix, with batch normalization folded in, as raw data.
+        let scale = 1.0 / (1.0f32 + 1e-5).sqrt();
+        let raw: Vec<u8> = [network.weights[0][(0, 0)] * scale, network.weights[0][(1, 0)] * scale].iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        assert!(position(&bytes, &raw).is_some());
+
+        network.activation_functions[0] = ActivationFunction::Custom(Arc::new(Relu));
+        assert!(network.to_onnx().is_err());
+    }
+
+    #[test]
+    fn onnx_import() {
+        let mut network = NeuralNetwork::new_classifier(&[4, 3, 2], None);
+        network.activation_functions[0] = ActivationFunction::LeakyRelu(0.2);
+        network.preprocessing = Some(Preprocessing::Standardize { mean: 0.5, standard_deviation: 2.0 });
+        let imported = NeuralNetwork::from_onnx(&network.to_onnx().unwrap()).unwrap();
+        assert_eq!(imported, network);
+
+        //a Gemm with the weights as [outputs, inputs], like pytorch exports nn.Linear, and no activation.
+        let tensor = |name: &str, dims: &[i64], values: &[f32]| {
+            let mut tensor = Message::new();
+            dims.iter().for_each(|&dim| { tensor.int(1, dim); });
+            values.iter().for_each(|&value| { tensor.float(4, value); });
+            tensor.int(2, 1).string(8, name);
+            tensor
+        };
+        let mut gemm = Message::new();
+        gemm.string(1, "x").string(1, "w").string(1, "b").string(2, "y").string(4, "Gemm")
+            .message(5, Message::new().string(1, "transB").int(3, 1).int(20, 2));
+        let mut graph = Message::new();
+        graph.message(1, &gemm)
+            .message(5, &tensor("w", &[2, 3], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]))
+            .message(5, &tensor("b", &[2], &[0.5, -0.5]))
+            .message(11, Message::new().string(1, "w"))
+            .message(11, Message::new().string(1, "x"));
+        let imported = NeuralNetwork::from_onnx(&Message::new().message(7, &graph).bytes).unwrap();
+        assert_eq!(imported.activation_functions, vec![ActivationFunction::Identity]);
+        assert_eq!(imported.cost, Cost::SquaredError);
+        assert_eq!(imported.infer(&ColumnVector::from_vec(vec![1.0, 0.0, 1.0])), ColumnVector::from_vec(vec![4.5, 9.5]));
+
+        network.activation_functions[0] = ActivationFunction::Gelu;
+        assert!(NeuralNetwork::from_onnx(&network.to_onnx().unwrap()).is_err());
+
+        //negative dimensions and dimensions whose product overflows are rejected.
+        for dims in [&[-2, 3][..], &[1 << 40, 1 << 40][..]] {
+            let mut graph = Message::new();
+            graph.message(1, &gemm)
+                .message(5, &tensor("w", dims, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]))
+                .message(5, &tensor("b", &[2], &[0.5, -0.5]));
+            let error = NeuralNetwork::from_onnx(&Message::new().message(7, &graph).bytes).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        }
+
+        //w times x is not x times w, only the operands of an Add may be swapped.
+        let matmul_graph = |first: &str, second: &str| {
+            let mut graph = Message::new();
+            graph.message(1, Message::new().string(1, first).string(1, second).string(2, "y").string(4, "MatMul"))
+                .message(1, Message::new().string(1, "b").string(1, "y").string(2, "z").string(4, "Add"))
+                .message(5, &tensor("w", &[3, 2], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]))
+                .message(5, &tensor("b", &[2], &[0.5, -0.5]))
+                .message(11, Message::new().string(1, "x"));
+            Message::new().message(7, &graph).bytes.clone()
+        };
+        let error = NeuralNetwork::from_onnx(&matmul_graph("w", "x")).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("second input"), "{}", error);
+        let imported = NeuralNetwork::from_onnx(&matmul_graph("x", "w")).unwrap();
+        assert_eq!(imported.infer(&ColumnVector::from_vec(vec![1.0, 0.0, 1.0])), ColumnVector::from_vec(vec![6.5, 7.5]));
+    }
+}
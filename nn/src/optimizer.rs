@@ -0,0 +1,303 @@
+use std::io::{self, Error, ErrorKind};
+use std::iter::zip;
+use crate::{GradientValues, NeuralNetwork, Trainable};
+
+//update rule applied by the trainer with the averaged gradients of every mini batch.
+//implement this to plug a custom update rule into the trainer. parameters of frozen layers
+//must be left alone, see Trainable::trainable_parameters. the optimizers of this crate train
+//anything Trainable, a custom one may implement it for NeuralNetwork only.
+pub trait Optimizer<N: Trainable = NeuralNetwork> {
+    fn step(&mut self, network: &mut N, gradients: &N::Gradients, learning_rate: f32);
+
+    //the buffers the optimizer carries between steps, so training can be resumed exactly.
+    //stateless optimizers keep the defaults, which only accept the state of a stateless optimizer.
+    fn state(&self) -> OptimizerState {
+        OptimizerState::default()
+    }
+
+    //a state of another kind of optimizer or of a network of another shape is an
+    //InvalidInput error and leaves the optimizer untouched.
+    fn load_state(&mut self, _network: &N, state: OptimizerState) -> io::Result<()> {
+        checked_buffers::<N>(None, state, "", 0).map(|_| ())
+    }
+}
+
+//the kind of optimizer that wrote it, like adam and empty for stateless ones, the amount of steps
+//taken and every buffer flattened in parameter order, see Trainable::parameters_mut.
+//a buffer that was not created yet is empty.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct OptimizerState {
+    pub kind: String,
+    pub steps: u64,
+    pub buffers: Vec<Vec<f32>>,
+}
+
+//a buffer of the optimizer, created with a 0 for every parameter on the first step.
+fn created(buffer: &mut Vec<f32>, parameter_amount: usize) -> &mut Vec<f32> {
+    if buffer.is_empty() {
+        buffer.resize(parameter_amount, 0.0);
+    }
+    buffer
+}
+
+//the buffers of a saved state once it is known to come from an optimizer of kind with
+//amount buffers, each of them empty or with a value for every parameter of network.
+fn checked_buffers<N: Trainable>(network: Option<&N>, state: OptimizerState, kind: &str, amount: usize) -> io::Result<Vec<Vec<f32>>> {
+    if state.kind != kind {
+        let name = |kind: &str| if kind.is_empty() { "a stateless optimizer".to_string() } else { kind.to_string() };
+        return Err(Error::new(ErrorKind::InvalidInput, format!("the optimizer state is of {}, not of {}", name(&state.kind), name(kind))));
+    }
+    let parameter_amount = network.map_or(0, |network| network.trainable_parameters().len());
+    if state.buffers.len() != amount || state.buffers.iter().any(|buffer| !buffer.is_empty() && buffer.len() != parameter_amount) {
+        return Err(Error::new(ErrorKind::InvalidInput, "optimizer state does not fit the network"));
+    }
+    Ok(state.buffers)
+}
+
+//plain stochastic gradient descent, without any state.
+pub struct Sgd;
+
+impl<N: Trainable> Optimizer<N> for Sgd {
+    fn step(&mut self, network: &mut N, gradients: &N::Gradients, learning_rate: f32) {
+        let trainable = network.trainable_parameters();
+        zip(zip(network.parameters_mut(), gradients.values()), trainable)
+            .filter(|(_, trainable)| *trainable)
+            .for_each(|((parameter, gradient), _)| *parameter -= learning_rate * gradient);
+    }
+}
+
+//the moment buffers are created on the first step so the same optimizer
+//can be constructed before the network it will train.
+pub struct Adam {
+    pub beta1: f32,
+    pub beta2: f32,
+    pub epsilon: f32,
+    first_moments: Vec<f32>,
+    second_moments: Vec<f32>,
+    step_count: i32,
+}
+
+impl Adam {
+    pub fn new(beta1: f32, beta2: f32, epsilon: f32) -> Adam {
+        Adam {
+            beta1,
+            beta2,
+            epsilon,
+            first_moments: Vec::new(),
+            second_moments: Vec::new(),
+            step_count: 0,
+        }
+    }
+}
+
+impl<N: Trainable> Optimizer<N> for Adam {
+    fn step(&mut self, network: &mut N, gradients: &N::Gradients, learning_rate: f32) {
+        let trainable = network.trainable_parameters();
+        let first_moments = created(&mut self.first_moments, trainable.len());
+        let second_moments = created(&mut self.second_moments, trainable.len());
+        self.step_count += 1;
+        let first_correction = 1.0 - self.beta1.powi(self.step_count);
+        let second_correction = 1.0 - self.beta2.powi(self.step_count);
+        let (beta1, beta2, epsilon) = (self.beta1, self.beta2, self.epsilon);
+
+        let moments = zip(first_moments.iter_mut(), second_moments.iter_mut());
+        zip(zip(zip(network.parameters_mut(), gradients.values()), moments), trainable)
+            .filter(|(_, trainable)| *trainable)
+            .for_each(|(((parameter, gradient), (first_moment, second_moment)), _)| {
+                *first_moment = beta1 * *first_moment + (1.0 - beta1) * gradient;
+                *second_moment = beta2 * *second_moment + (1.0 - beta2) * gradient * gradient;
+                let first_corrected = *first_moment / first_correction;
+                let second_corrected = *second_moment / second_correction;
+                *parameter -= learning_rate * first_corrected / (second_corrected.sqrt() + epsilon);
+            });
+    }
+
+    fn state(&self) -> OptimizerState {
+        OptimizerState {
+            kind: "adam".to_string(),
+            steps: self.step_count as u64,
+            buffers: vec![self.first_moments.clone(), self.second_moments.clone()],
+        }
+    }
+
+    fn load_state(&mut self, network: &N, state: OptimizerState) -> io::Result<()> {
+        let steps = state.steps;
+        let [first_moments, second_moments]: [Vec<f32>; 2] = checked_buffers(Some(network), state, "adam", 2)?.try_into().unwrap();
+        self.step_count = steps as i32;
+        self.first_moments = first_moments;
+        self.second_moments = second_moments;
+        Ok(())
+    }
+}
+
+impl Default for Adam {
+    fn default() -> Self {
+        Adam::new(0.9, 0.999, 1e-8)
+    }
+}
+
+//keeps a running average of the squared gradient of every weight and bias
+//and divides each update by its root.
+pub struct RmsProp {
+    pub decay: f32,
+    pub epsilon: f32,
+    squared_averages: Vec<f32>,
+}
+
+impl RmsProp {
+    pub fn new(decay: f32, epsilon: f32) -> RmsProp {
+        RmsProp {
+            decay,
+            epsilon,
+            squared_averages: Vec::new(),
+        }
+    }
+}
+
+impl<N: Trainable> Optimizer<N> for RmsProp {
+    fn step(&mut self, network: &mut N, gradients: &N::Gradients, learning_rate: f32) {
+        let trainable = network.trainable_parameters();
+        let squared_averages = created(&mut self.squared_averages, trainable.len());
+        let (decay, epsilon) = (self.decay, self.epsilon);
+        zip(zip(zip(network.parameters_mut(), gradients.values()), squared_averages.iter_mut()), trainable)
+            .filter(|(_, trainable)| *trainable)
+            .for_each(|(((parameter, gradient), squared_average), _)| {
+                *squared_average = decay * *squared_average + (1.0 - decay) * gradient * gradient;
+                *parameter -= learning_rate * gradient / (squared_average.sqrt() + epsilon);
+            });
+    }
+
+    fn state(&self) -> OptimizerState {
+        OptimizerState {
+            kind: "rms_prop".to_string(),
+            steps: 0,
+            buffers: vec![self.squared_averages.clone()],
+        }
+    }
+
+    fn load_state(&mut self, network: &N, state: OptimizerState) -> io::Result<()> {
+        self.squared_averages = checked_buffers(Some(network), state, "rms_prop", 1)?.remove(0);
+        Ok(())
+    }
+}
+
+impl Default for RmsProp {
+    fn default() -> Self {
+        RmsProp::new(0.9, 1e-8)
+    }
+}
+
+//sgd with a velocity buffer per parameter. With nesterov set the update
+//looks ahead along the velocity before applying the gradient.
+pub struct Momentum {
+    pub coefficient: f32,
+    pub nesterov: bool,
+    velocities: Vec<f32>,
+}
+
+impl Momentum {
+    pub fn new(coefficient: f32, nesterov: bool) -> Momentum {
+        Momentum {
+            coefficient,
+            nesterov,
+            velocities: Vec::new(),
+        }
+    }
+}
+
+impl<N: Trainable> Optimizer<N> for Momentum {
+    fn step(&mut self, network: &mut N, gradients: &N::Gradients, learning_rate: f32) {
+        let trainable = network.trainable_parameters();
+        let velocities = created(&mut self.velocities, trainable.len());
+        let (coefficient, nesterov) = (self.coefficient, self.nesterov);
+        zip(zip(zip(network.parameters_mut(), gradients.values()), velocities.iter_mut()), trainable)
+            .filter(|(_, trainable)| *trainable)
+            .for_each(|(((parameter, gradient), velocity), _)| {
+                *velocity = coefficient * *velocity - learning_rate * gradient;
+                *parameter += if nesterov {
+                    coefficient * *velocity - learning_rate * gradient
+                } else {
+                    *velocity
+                };
+            });
+    }
+
+    fn state(&self) -> OptimizerState {
+        OptimizerState {
+            kind: "momentum".to_string(),
+            steps: 0,
+            buffers: vec![self.velocities.clone()],
+        }
+    }
+
+    fn load_state(&mut self, network: &N, state: OptimizerState) -> io::Result<()> {
+        self.velocities = checked_buffers(Some(network), state, "momentum", 1)?.remove(0);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::{ColumnVector, Matrix};
+    use crate::{Adam, Gradients, Momentum, NeuralNetwork, Optimizer, RmsProp};
+
+    #[test]
+    fn adam_first_step_moves_by_learning_rate() {
+        let mut network = NeuralNetwork::new_from_vecs(vec![Matrix::zeros(1, 2)], None, None, None);
+        let gradients = Gradients {
+            weights: vec![Matrix::from_vec(vec![vec![4.0, -0.5]])],
+            biases: vec![ColumnVector::from_vec(vec![2.0])],
+            gammas: vec![],
+            betas: vec![],
+        };
+        let mut adam = Adam::default();
+        adam.step(&mut network, &gradients, 0.1);
+        //bias correction makes the first update exactly learning_rate * sign(gradient).
+        assert!((network.weights[0][(0, 0)] + 0.1).abs() < 1e-5);
+        assert!((network.weights[0][(0, 1)] - 0.1).abs() < 1e-5);
+        assert!((network.biases[0].data[0] + 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rms_prop_first_step_is_scaled_by_decay() {
+        let mut network = NeuralNetwork::new_from_vecs(vec![Matrix::zeros(1, 2)], None, None, None);
+        let gradients = Gradients {
+            weights: vec![Matrix::from_vec(vec![vec![4.0, -0.5]])],
+            biases: vec![ColumnVector::from_vec(vec![2.0])],
+            gammas: vec![],
+            betas: vec![],
+        };
+        let mut rms_prop = RmsProp::new(0.75, 1e-8);
+        rms_prop.step(&mut network, &gradients, 0.1);
+        //the running average starts at zero so the first update is learning_rate * sign(gradient) / sqrt(1 - decay).
+        assert!((network.weights[0][(0, 0)] + 0.2).abs() < 1e-5);
+        assert!((network.weights[0][(0, 1)] - 0.2).abs() < 1e-5);
+        assert!((network.biases[0].data[0] + 0.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn momentum_accumulates_velocity() {
+        let gradients = Gradients {
+            weights: vec![Matrix::from_vec(vec![vec![1.0]])],
+            biases: vec![ColumnVector::from_vec(vec![-1.0])],
+            gammas: vec![],
+            betas: vec![],
+        };
+        let mut classical_network = NeuralNetwork::new_from_vecs(vec![Matrix::zeros(1, 1)], None, None, None);
+        let mut classical = Momentum::new(0.5, false);
+        classical.step(&mut classical_network, &gradients, 0.1);
+        classical.step(&mut classical_network, &gradients, 0.1);
+        //velocities are -0.1 then -0.15.
+        assert!((classical_network.weights[0][(0, 0)] + 0.25).abs() < 1e-6);
+        assert!((classical_network.biases[0].data[0] - 0.25).abs() < 1e-6);
+
+        let mut nesterov_network = NeuralNetwork::new_from_vecs(vec![Matrix::zeros(1, 1)], None, None, None);
+        let mut nesterov = Momentum::new(0.5, true);
+        nesterov.step(&mut nesterov_network, &gradients, 0.1);
+        nesterov.step(&mut nesterov_network, &gradients, 0.1);
+        //each step adds 0.5 * velocity - 0.1 * gradient: -0.15 then -0.175.
+        assert!((nesterov_network.weights[0][(0, 0)] + 0.325).abs() < 1e-6);
+        assert!((nesterov_network.biases[0].data[0] - 0.325).abs() < 1e-6);
+    }
+}
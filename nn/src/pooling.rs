@@ -0,0 +1,186 @@
+use matrix::ColumnVector;
+use crate::{Layer, Shape};
+
+//the windows of a pooling layer, laid out like the feature maps of Conv2d.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct PoolWindows {
+    pub channels: usize,
+    pub input_height: usize,
+    pub input_width: usize,
+    pub pool_size: usize,
+    pub stride: usize,
+}
+
+impl PoolWindows {
+    fn new(input_shape: (usize, usize, usize), pool_size: usize, stride: usize) -> PoolWindows {
+        let (channels, input_height, input_width) = input_shape;
+        if stride == 0 {
+            panic!("the stride of a pooling layer must be at least 1.");
+        }
+        if pool_size == 0 || pool_size > input_height || pool_size > input_width {
+            panic!("a pool of size {} does not fit an input of {} by {}.", pool_size, input_height, input_width);
+        }
+        PoolWindows { channels, input_height, input_width, pool_size, stride }
+    }
+
+    //(channels, height, width) of the output.
+    pub fn output_dimensions(&self) -> (usize, usize, usize) {
+        let size = |input: usize| (input - self.pool_size) / self.stride + 1;
+        (self.channels, size(self.input_height), size(self.input_width))
+    }
+
+    //the input indices of every window, one window per output in output order.
+    fn windows(&self) -> impl Iterator<Item=Vec<usize>> + '_ {
+        let (channels, height, width) = self.output_dimensions();
+        (0..channels * height * width).map(move |output_index| {
+            let channel = output_index / (height * width);
+            let y = output_index / width % height * self.stride;
+            let x = output_index % width * self.stride;
+            (0..self.pool_size * self.pool_size)
+                .map(|offset| (channel * self.input_height + y + offset / self.pool_size) * self.input_width + x + offset % self.pool_size)
+                .collect()
+        })
+    }
+
+    fn output_shape(&self, input_shape: Shape) -> Result<Shape, String> {
+        let expected = Shape::image(self.channels, self.input_height, self.input_width);
+        if input_shape != expected {
+            return Err(format!("a pooling layer of {} can not follow {}.", expected, input_shape));
+        }
+        let (channels, height, width) = self.output_dimensions();
+        Ok(Shape::image(channels, height, width))
+    }
+
+    fn check_input(&self, input: &ColumnVector) {
+        if input.data.len() != self.channels * self.input_height * self.input_width {
+            panic!("expected {} inputs, got {}.", self.channels * self.input_height * self.input_width, input.data.len());
+        }
+    }
+}
+
+//the largest value of every window. the gradient only flows back to that value.
+#[derive(PartialEq, Debug, Clone)]
+pub struct MaxPool2d {
+    pub windows: PoolWindows,
+    //the input index of the largest value of every window in the last forward pass.
+    max_indices: Vec<usize>,
+}
+
+//the mean of every window. the gradient is spread evenly over the window.
+#[derive(PartialEq, Debug, Clone)]
+pub struct AvgPool2d {
+    pub windows: PoolWindows,
+}
+
+impl MaxPool2d {
+    //input_shape is (channels, height, width).
+    pub fn new(input_shape: (usize, usize, usize), pool_size: usize, stride: usize) -> MaxPool2d {
+        MaxPool2d { windows: PoolWindows::new(input_shape, pool_size, stride), max_indices: Vec::new() }
+    }
+}
+
+impl AvgPool2d {
+    //input_shape is (channels, height, width).
+    pub fn new(input_shape: (usize, usize, usize), pool_size: usize, stride: usize) -> AvgPool2d {
+        AvgPool2d { windows: PoolWindows::new(input_shape, pool_size, stride) }
+    }
+}
+
+impl Layer for MaxPool2d {
+    fn forward(&mut self, input: &ColumnVector) -> ColumnVector {
+        self.windows.check_input(input);
+        //the first of equal values wins.
+        self.max_indices = self.windows.windows()
+            .map(|window| window.into_iter().reduce(|max, index| if input.data[index] > input.data[max] { index } else { max }).unwrap())
+            .collect();
+        ColumnVector::from_vec(self.max_indices.iter().map(|&index| input.data[index]).collect())
+    }
+
+    fn backward(&mut self, gradient: &ColumnVector) -> ColumnVector {
+        let windows = &self.windows;
+        let mut propagated = ColumnVector::new_with_elements(windows.channels * windows.input_height * windows.input_width, 0.0);
+        for (&index, gradient_elem) in self.max_indices.iter().zip(&gradient.data) {
+            propagated.data[index] += gradient_elem;
+        }
+        propagated
+    }
+
+    fn output_shape(&self, input_shape: Shape) -> Result<Shape, String> {
+        self.windows.output_shape(input_shape)
+    }
+
+    fn fork(&mut self) -> Option<Box<dyn Layer>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+impl Layer for AvgPool2d {
+    fn forward(&mut self, input: &ColumnVector) -> ColumnVector {
+        self.windows.check_input(input);
+        let area = (self.windows.pool_size * self.windows.pool_size) as f32;
+        ColumnVector::from_vec(self.windows.windows()
+            .map(|window| window.into_iter().map(|index| input.data[index]).sum::<f32>() / area)
+            .collect())
+    }
+
+    fn backward(&mut self, gradient: &ColumnVector) -> ColumnVector {
+        let windows = &self.windows;
+        let area = (windows.pool_size * windows.pool_size) as f32;
+        let mut propagated = ColumnVector::new_with_elements(windows.channels * windows.input_height * windows.input_width, 0.0);
+        for (window, gradient_elem) in windows.windows().zip(&gradient.data) {
+            window.into_iter().for_each(|index| propagated.data[index] += gradient_elem / area);
+        }
+        propagated
+    }
+
+    fn output_shape(&self, input_shape: Shape) -> Result<Shape, String> {
+        self.windows.output_shape(input_shape)
+    }
+
+    fn fork(&mut self) -> Option<Box<dyn Layer>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::ColumnVector;
+    use crate::{AvgPool2d, Layer, MaxPool2d};
+
+    fn input() -> ColumnVector {
+        //two channels of 4 by 4, the second is the first negated.
+        let channel: Vec<f32> = vec![
+            1.0, 3.0, 2.0, 0.0,
+            4.0, 2.0, 1.0, 5.0,
+            0.0, 1.0, 7.0, 2.0,
+            6.0, 2.0, 3.0, 1.0,
+        ];
+        ColumnVector::from_vec(channel.iter().cloned().chain(channel.iter().map(|x| -x)).collect())
+    }
+
+    #[test]
+    fn max_pooling() {
+        let mut pool = MaxPool2d::new((2, 4, 4), 2, 2);
+        assert_eq!(pool.windows.output_dimensions(), (2, 2, 2));
+        assert_eq!(pool.forward(&input()).data, vec![4.0, 5.0, 6.0, 7.0, -1.0, 0.0, 0.0, -1.0]);
+        let gradient = pool.backward(&ColumnVector::from_vec(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]));
+        let mut expected = vec![0.0; 32];
+        for (index, value) in [(4, 1.0), (7, 2.0), (12, 3.0), (10, 4.0), (16, 5.0), (19, 6.0), (24, 7.0), (31, 8.0)] {
+            expected[index] = value;
+        }
+        assert_eq!(gradient.data, expected);
+    }
+
+    #[test]
+    fn average_pooling_with_overlapping_windows() {
+        let mut pool = AvgPool2d::new((2, 4, 4), 3, 1);
+        assert_eq!(pool.windows.output_dimensions(), (2, 2, 2));
+        let output = pool.forward(&input());
+        assert_eq!(output.data[..4], [21.0 / 9.0, 23.0 / 9.0, 26.0 / 9.0, 24.0 / 9.0]);
+        assert_eq!(output.data[4..], [-21.0 / 9.0, -23.0 / 9.0, -26.0 / 9.0, -24.0 / 9.0]);
+        let gradient = pool.backward(&ColumnVector::new_with_elements(8, 9.0));
+        //the middle of every channel is part of all 4 windows, the corners of one.
+        assert_eq!(gradient.data[..16], [1.0, 2.0, 2.0, 1.0, 2.0, 4.0, 4.0, 2.0, 2.0, 4.0, 4.0, 2.0, 1.0, 2.0, 2.0, 1.0]);
+    }
+}
@@ -0,0 +1,112 @@
+use matrix::{ColumnVector, Matrix};
+use mnist_reader::{argmax, top_k};
+use crate::{softmax, ActivationFunction, NeuralNetwork};
+
+//the class a network picks for an input, together with how sure it is.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Prediction {
+    pub class: usize,
+    //one probability per class, summing up to 1.
+    pub probabilities: ColumnVector,
+    //the probability of the predicted class.
+    pub confidence: f32,
+}
+
+impl Prediction {
+    //outputs of a softmax layer already are probabilities, any other outputs are turned
+    //into probabilities with a softmax.
+    pub fn from_output(output: ColumnVector, output_activation: &ActivationFunction) -> Prediction {
+        let probabilities = match output_activation {
+            ActivationFunction::Softmax => output,
+            _ => softmax(&output),
+        };
+        let class = argmax(&probabilities);
+        Prediction {
+            class,
+            confidence: probabilities.data[class],
+            probabilities,
+        }
+    }
+
+    //the k most probable classes, most probable first.
+    pub fn top_k(&self, k: usize) -> Vec<usize> {
+        top_k(&self.probabilities, k)
+    }
+}
+
+impl NeuralNetwork {
+    //classifies a single input in inference mode, leaving the network untouched.
+    pub fn predict(&self, input: &ColumnVector) -> Prediction {
+        Prediction::from_output(self.infer(input), self.activation_functions.last().unwrap())
+    }
+
+    pub fn predict_batch(&self, inputs: &[ColumnVector]) -> Vec<Prediction> {
+        self.predict_columns(&Matrix::from_columns(inputs))
+    }
+
+    //like predict_batch for a matrix holding one input per column.
+    pub fn predict_columns(&self, inputs: &Matrix) -> Vec<Prediction> {
+        let output_activation = self.activation_functions.last().unwrap();
+        self.infer_batch(inputs).columns().map(|output| Prediction::from_output(output, output_activation)).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use matrix::{ColumnVector, Matrix};
+    use crate::{softmax, ActivationFunction, NeuralNetwork};
+
+    #[test]
+    fn predictions() {
+        let weights = vec![
+            Matrix::from_vec(vec![vec![0.5, -0.2], vec![0.3, 0.8], vec![-0.6, 0.1]]),
+            Matrix::from_vec(vec![vec![0.7, 0.6, -0.3], vec![-0.2, 0.9, 0.4]]),
+        ];
+        let mut network = NeuralNetwork::new_from_vecs(weights, None, None, None);
+        let input = ColumnVector::from_vec(vec![1.0, 0.5]);
+        let prediction = network.predict(&input);
+        network.calculate_all_activation_values(&input);
+        let output = network.activation_values.back().unwrap().clone();
+        //relu outputs go through a softmax.
+        assert_eq!(prediction.probabilities, softmax(&output));
+        assert_eq!(prediction.class, 0);
+        assert_eq!(prediction.confidence, prediction.probabilities.data[0]);
+
+        network.activation_functions[1] = ActivationFunction::Softmax;
+        network.calculate_all_activation_values(&input);
+        let prediction = network.predict(&input);
+        assert_eq!(&prediction.probabilities, network.activation_values.back().unwrap());
+        assert!((prediction.probabilities.total() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn batch_predictions() {
+        let weights = vec![
+            Matrix::from_vec(vec![vec![1.0, -1.0], vec![-1.0, 1.0]]),
+        ];
+        let mut network = NeuralNetwork::new_from_vecs(weights, None, None, None);
+        network.activation_functions = vec![ActivationFunction::Softmax];
+        let inputs = vec![ColumnVector::from_vec(vec![2.0, 0.0]), ColumnVector::from_vec(vec![0.0, 3.0])];
+        let predictions = network.predict_batch(&inputs);
+        assert_eq!(predictions.iter().map(|x| x.class).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(predictions[1], network.predict(&inputs[1]));
+        let columns = Matrix::from_vec(vec![vec![2.0, 0.0], vec![0.0, 3.0]]);
+        assert_eq!(network.predict_columns(&columns), predictions);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let network = Arc::new(NeuralNetwork::new_with_seed(&[4, 8, 3], vec![ActivationFunction::Relu, ActivationFunction::Softmax], 9));
+        let inputs: Vec<ColumnVector> = (0..8).map(|i| ColumnVector::from_vec(vec![i as f32, 1.0, -0.5, 0.25 * i as f32])).collect();
+        let expected = network.predict_batch(&inputs);
+        let handles: Vec<_> = inputs.into_iter().map(|input| {
+            let network = Arc::clone(&network);
+            thread::spawn(move || network.predict(&input))
+        }).collect();
+        let predictions: Vec<_> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+        assert_eq!(predictions, expected);
+    }
+}
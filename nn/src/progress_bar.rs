@@ -0,0 +1,122 @@
+use std::io::{self, Stderr, Write};
+use std::time::{Duration, Instant};
+use crate::{BatchEnd, Callback, Control, EpochEnd};
+
+const BAR_WIDTH: usize = 30;
+
+//shows the progress of every epoch on a single line that is redrawn after every batch:
+//batches done, the loss of the last batch, throughput and the estimated time left.
+//a summary of the epoch is kept on its own line.
+pub struct ProgressBar<W: Write = Stderr> {
+    pub writer: W,
+    epoch_start: Instant,
+    samples: usize,
+}
+
+impl ProgressBar<Stderr> {
+    pub fn new() -> ProgressBar<Stderr> {
+        ProgressBar::new_with_writer(io::stderr())
+    }
+}
+
+impl Default for ProgressBar<Stderr> {
+    fn default() -> Self {
+        ProgressBar::new()
+    }
+}
+
+//seconds as e.g. 42s, 3m07s or 1h02m.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+impl<W: Write> ProgressBar<W> {
+    pub fn new_with_writer(writer: W) -> ProgressBar<W> {
+        ProgressBar {
+            writer,
+            epoch_start: Instant::now(),
+            samples: 0,
+        }
+    }
+
+    //the line for a batch, given the time since the epoch started.
+    fn batch_line(&self, batch: &BatchEnd, elapsed: Duration) -> String {
+        let done = batch.batch_index + 1;
+        let per_second = self.samples as f32 / elapsed.as_secs_f32().max(1e-6);
+        match batch.batch_amount {
+            Some(batch_amount) => {
+                let filled = (BAR_WIDTH * done / batch_amount.max(1)).min(BAR_WIDTH);
+                let remaining = elapsed.mul_f64(batch_amount.saturating_sub(done) as f64 / done as f64);
+                format!("epoch {} [{}{}] {}/{} loss {:.4} {:.0} samples/s eta {}",
+                        batch.epoch + 1, "=".repeat(filled), " ".repeat(BAR_WIDTH - filled), done, batch_amount,
+                        batch.loss, per_second, format_duration(remaining))
+            }
+            None => format!("epoch {} {} batches loss {:.4} {:.0} samples/s", batch.epoch + 1, done, batch.loss, per_second),
+        }
+    }
+}
+
+impl<W: Write, N> Callback<N> for ProgressBar<W> {
+    fn on_epoch_start(&mut self, _network: &N, _epoch: usize) {
+        self.epoch_start = Instant::now();
+        self.samples = 0;
+    }
+
+    fn on_batch_end(&mut self, _network: &N, batch: &BatchEnd) {
+        self.samples += batch.samples;
+        let line = self.batch_line(batch, self.epoch_start.elapsed());
+        //progress output is best effort and never interrupts training.
+        let _ = write!(self.writer, "\r{}", line);
+        let _ = self.writer.flush();
+    }
+
+    fn on_epoch_end(&mut self, _network: &mut N, epoch: &EpochEnd) -> Control {
+        let mut line = format!("epoch {} done in {} loss {:.4}", epoch.epoch + 1, format_duration(self.epoch_start.elapsed()), epoch.training_loss);
+        if let Some(validation) = &epoch.validation {
+            line += &format!(" validation loss {:.4} accuracy {:.4}", validation.loss, validation.accuracy);
+        }
+        //padded to overwrite what is left of the progress line.
+        let _ = writeln!(self.writer, "\r{:<width$}", line, width = BAR_WIDTH + 60);
+        Control::Continue
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::progress_bar::format_duration;
+    use crate::{ActivationFunction, BatchEnd, Callback, EpochEnd, Evaluation, NeuralNetwork, ProgressBar};
+
+    #[test]
+    fn progress_lines() {
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(187)), "3m07s");
+        assert_eq!(format_duration(Duration::from_secs(3720)), "1h02m");
+
+        let mut progress_bar = ProgressBar::new_with_writer(Vec::new());
+        progress_bar.samples = 200;
+        let batch = BatchEnd { epoch: 0, batch_index: 1, batch_amount: Some(6), samples: 100, loss: 0.5, learning_rate: 0.1 };
+        assert_eq!(progress_bar.batch_line(&batch, Duration::from_secs(2)),
+                   format!("epoch 1 [{}{}] 2/6 loss 0.5000 100 samples/s eta 4s", "=".repeat(10), " ".repeat(20)));
+        let batch = BatchEnd { batch_amount: None, ..batch };
+        assert_eq!(progress_bar.batch_line(&batch, Duration::from_secs(2)), "epoch 1 2 batches loss 0.5000 100 samples/s");
+
+        let mut network = NeuralNetwork::new_with_activations(&[2, 2], vec![ActivationFunction::Relu], Some(0.5));
+        let mut progress_bar = ProgressBar::new_with_writer(Vec::new());
+        progress_bar.on_epoch_start(&network, 0);
+        progress_bar.on_batch_end(&network, &batch);
+        let validation = Some(Evaluation { loss: 0.25, accuracy: 0.5, metrics: Vec::new() });
+        progress_bar.on_epoch_end(&mut network, &EpochEnd { epoch: 0, training_loss: 0.5, validation, learning_rate: 0.1 });
+        let output = String::from_utf8(progress_bar.writer).unwrap();
+        assert!(output.starts_with("\repoch 1 2 batches loss 0.5000 "));
+        let summary = output.rsplit('\r').next().unwrap();
+        assert_eq!(summary.trim_end(), "epoch 1 done in 0s loss 0.5000 validation loss 0.2500 accuracy 0.5000");
+        assert!(summary.ends_with('\n'));
+    }
+}
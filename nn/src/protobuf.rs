@@ -0,0 +1,197 @@
+use std::io;
+use crate::invalid_data;
+
+//the subset of the protocol buffers wire format needed to write tensorboard events and
+//to read and write onnx models.
+
+//a message being encoded, fields are appended in the order they are added.
+#[derive(Default)]
+pub(crate) struct Message {
+    pub bytes: Vec<u8>,
+}
+
+fn varint(mut value: u64, bytes: &mut Vec<u8>) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+impl Message {
+    pub fn new() -> Message {
+        Message::default()
+    }
+
+    fn tag(&mut self, field: u64, wire_type: u64) {
+        varint(field << 3 | wire_type, &mut self.bytes);
+    }
+
+    //int32, int64, uint64 and enums. negative values take ten bytes, like protobuf does.
+    pub fn int(&mut self, field: u64, value: i64) -> &mut Message {
+        self.tag(field, 0);
+        varint(value as u64, &mut self.bytes);
+        self
+    }
+
+    pub fn double(&mut self, field: u64, value: f64) -> &mut Message {
+        self.tag(field, 1);
+        self.bytes.extend(value.to_le_bytes());
+        self
+    }
+
+    pub fn float(&mut self, field: u64, value: f32) -> &mut Message {
+        self.tag(field, 5);
+        self.bytes.extend(value.to_le_bytes());
+        self
+    }
+
+    pub fn bytes(&mut self, field: u64, data: &[u8]) -> &mut Message {
+        self.tag(field, 2);
+        varint(data.len() as u64, &mut self.bytes);
+        self.bytes.extend(data);
+        self
+    }
+
+    pub fn string(&mut self, field: u64, value: &str) -> &mut Message {
+        self.bytes(field, value.as_bytes())
+    }
+
+    pub fn message(&mut self, field: u64, message: &Message) -> &mut Message {
+        self.bytes(field, &message.bytes)
+    }
+}
+
+//a decoded field, interpreting it is up to the schema of the message.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub(crate) enum Field<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+fn malformed(message: &str) -> io::Error {
+    invalid_data(format!("malformed protobuf: {}.", message))
+}
+
+fn read_varint(bytes: &mut &[u8]) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or_else(|| malformed("truncated varint"))?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(malformed("varint is too long"))
+}
+
+fn take<'a>(bytes: &mut &'a [u8], amount: usize) -> io::Result<&'a [u8]> {
+    if bytes.len() < amount {
+        return Err(malformed("truncated field"));
+    }
+    let (taken, rest) = bytes.split_at(amount);
+    *bytes = rest;
+    Ok(taken)
+}
+
+//every field of a message with its number, in the order they appear.
+pub(crate) fn parse(mut bytes: &[u8]) -> io::Result<Vec<(u64, Field<'_>)>> {
+    let mut fields = Vec::new();
+    while !bytes.is_empty() {
+        let tag = read_varint(&mut bytes)?;
+        let field = match tag & 7 {
+            0 => Field::Varint(read_varint(&mut bytes)?),
+            1 => Field::Fixed64(u64::from_le_bytes(take(&mut bytes, 8)?.try_into().unwrap())),
+            2 => {
+                let length = read_varint(&mut bytes)? as usize;
+                Field::Bytes(take(&mut bytes, length)?)
+            }
+            5 => Field::Fixed32(u32::from_le_bytes(take(&mut bytes, 4)?.try_into().unwrap())),
+            _ => return Err(malformed("unsupported wire type")),
+        };
+        fields.push((tag >> 3, field));
+    }
+    Ok(fields)
+}
+
+impl<'a> Field<'a> {
+    pub fn int(&self) -> Option<i64> {
+        match self {
+            Field::Varint(value) => Some(*value as i64),
+            _ => None,
+        }
+    }
+
+    pub fn float(&self) -> Option<f32> {
+        match self {
+            Field::Fixed32(value) => Some(f32::from_bits(*value)),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            Field::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.bytes()?).ok()
+    }
+
+    //repeated integers are either packed into one field or one field each.
+    pub fn ints(&self) -> io::Result<Vec<i64>> {
+        match self {
+            Field::Varint(value) => Ok(vec![*value as i64]),
+            Field::Bytes(mut bytes) => {
+                let mut values = Vec::new();
+                while !bytes.is_empty() {
+                    values.push(read_varint(&mut bytes)? as i64);
+                }
+                Ok(values)
+            }
+            _ => Err(malformed("expected integers")),
+        }
+    }
+
+    pub fn floats(&self) -> io::Result<Vec<f32>> {
+        match self {
+            Field::Fixed32(value) => Ok(vec![f32::from_bits(*value)]),
+            Field::Bytes(bytes) if bytes.len() % 4 == 0 => {
+                Ok(bytes.chunks_exact(4).map(|x| f32::from_le_bytes(x.try_into().unwrap())).collect())
+            }
+            _ => Err(malformed("expected floats")),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::protobuf::{parse, Field, Message};
+
+    #[test]
+    fn encoding_and_decoding() {
+        //the examples of the protobuf encoding guide.
+        assert_eq!(Message::new().int(1, 150).bytes, vec![0x08, 0x96, 0x01]);
+        assert_eq!(Message::new().string(2, "testing").bytes, b"\x12\x07testing".to_vec());
+        let mut inner = Message::new();
+        inner.int(1, 150);
+        assert_eq!(Message::new().message(3, &inner).bytes, vec![0x1a, 0x03, 0x08, 0x96, 0x01]);
+        assert_eq!(Message::new().int(1, -1).bytes.len(), 11);
+
+        let mut message = Message::new();
+        message.int(1, 150).message(3, &inner).float(4, 0.5).double(5, 2.0);
+        let fields = parse(&message.bytes).unwrap();
+        assert_eq!(fields.iter().map(|(field, _)| *field).collect::<Vec<_>>(), vec![1, 3, 4, 5]);
+        assert_eq!(fields[0].1.int(), Some(150));
+        assert_eq!(parse(fields[1].1.bytes().unwrap()).unwrap(), vec![(1, Field::Varint(150))]);
+        assert_eq!(fields[2].1.float(), Some(0.5));
+        assert_eq!(Field::Bytes(&[0x03, 0x8e, 0x02]).ints().unwrap(), vec![3, 270]);
+        assert!(parse(&message.bytes[..message.bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use std::iter::zip;
+use matrix::{ColumnVector, Matrix};
+use mnist_reader::{Dataset, Preprocessing};
+use crate::{Activation, ActivationFunction, InferenceBuffers, NeuralNetwork};
+
+//maps real values to int8 and back: real = scale * (quantized - zero_point).
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct QuantizationParameters {
+    pub scale: f32,
+    pub zero_point: i32,
+}
+
+impl QuantizationParameters {
+    //spreads the 256 values of an i8 over [min, max]. the range is widened to contain 0,
+    //so 0, the value of padding and of inactive relus, is represented exactly.
+    pub fn from_range(min: f32, max: f32) -> QuantizationParameters {
+        if min > max {
+            panic!("the range {} to {} is empty.", min, max);
+        }
+        let (min, max) = (min.min(0.0), max.max(0.0));
+        if max == min {
+            return QuantizationParameters { scale: 1.0, zero_point: 0 };
+        }
+        let scale = (max - min) / 255.0;
+        QuantizationParameters { scale, zero_point: (-128.0 - min / scale).round() as i32 }
+    }
+
+    //a zero point of 0 and [-max_magnitude, max_magnitude] mapped to [-127, 127].
+    //used for weights, it keeps the zero point out of the integer products.
+    pub fn symmetric(max_magnitude: f32) -> QuantizationParameters {
+        let scale = if max_magnitude > 0.0 { max_magnitude / 127.0 } else { 1.0 };
+        QuantizationParameters { scale, zero_point: 0 }
+    }
+
+    //values outside of the range are clamped to its ends.
+    pub fn quantize(&self, value: f32) -> i8 {
+        ((value / self.scale).round() as i32 + self.zero_point).clamp(-128, 127) as i8
+    }
+
+    pub fn dequantize(&self, quantized: i8) -> f32 {
+        self.scale * (quantized as i32 - self.zero_point) as f32
+    }
+}
+
+//a dense layer with int8 weights. the matrix vector product runs on integers with an i32
+//accumulator, only its result is scaled back to f32 for the activation function.
+#[derive(PartialEq, Debug, Clone)]
+pub struct QuantizedLayer {
+    pub weights: Vec<Vec<i8>>,
+    pub weight_quantization: QuantizationParameters,
+    //quantized with the scale input scale * weight scale and a zero point of 0,
+    //so they add straight onto the accumulator.
+    pub biases: Vec<i32>,
+    pub activation_function: ActivationFunction,
+}
+
+impl QuantizedLayer {
+    fn new(weights: &Matrix, biases: &[f32], activation_function: ActivationFunction, input_quantization: QuantizationParameters) -> QuantizedLayer {
+        let max_magnitude = weights.as_slice().iter().fold(0.0_f32, |max, weight| max.max(weight.abs()));
+        let weight_quantization = QuantizationParameters::symmetric(max_magnitude);
+        let bias_scale = input_quantization.scale * weight_quantization.scale;
+        QuantizedLayer {
+            weights: weights.rows().map(|row| row.iter().map(|&weight| weight_quantization.quantize(weight)).collect()).collect(),
+            weight_quantization,
+            biases: biases.iter().map(|bias| (bias / bias_scale).round() as i32).collect(),
+            activation_function,
+        }
+    }
+
+    //the activations of the layer in f32 for quantized inputs.
+    fn forward(&self, input: &[i8], input_quantization: QuantizationParameters) -> ColumnVector {
+        let scale = input_quantization.scale * self.weight_quantization.scale;
+        let z_values = zip(&self.weights, &self.biases).map(|(row, bias)| {
+            let accumulator: i32 = zip(row, input)
+                .map(|(&weight, &elem)| weight as i32 * (elem as i32 - input_quantization.zero_point))
+                .sum();
+            (accumulator + bias) as f32 * scale
+        });
+        self.activation_function.apply(&ColumnVector::from_vec(z_values.collect()))
+    }
+}
+
+//a network for int8 inference. the input and the activations between layers are quantized
+//with their own parameters, the output of the last layer is returned in f32.
+#[derive(PartialEq, Debug, Clone)]
+pub struct QuantizedNetwork {
+    pub layers: Vec<QuantizedLayer>,
+    //the parameters of the preprocessed input, then of the output of every hidden layer.
+    pub activation_quantization: Vec<QuantizationParameters>,
+    pub preprocessing: Option<Preprocessing>,
+}
+
+impl QuantizedNetwork {
+    //the output for input, like NeuralNetwork::infer.
+    pub fn infer(&self, input: &ColumnVector) -> ColumnVector {
+        let mut activations = ColumnVector::from_vec(input.data.iter().map(|&elem| match &self.preprocessing {
+            Some(preprocessing) => preprocessing.apply_to_value(elem),
+            None => elem,
+        }).collect());
+        for (layer, quantization) in zip(&self.layers, &self.activation_quantization) {
+            let quantized: Vec<i8> = activations.data.iter().map(|&elem| quantization.quantize(elem)).collect();
+            activations = layer.forward(&quantized, *quantization);
+        }
+        activations
+    }
+
+    //bytes taken up by the weights and biases.
+    pub fn parameter_bytes(&self) -> usize {
+        self.layers.iter()
+            .map(|layer| layer.weights.iter().map(Vec::len).sum::<usize>() * size_of::<i8>() + layer.biases.len() * size_of::<i32>())
+            .sum()
+    }
+}
+
+impl NeuralNetwork {
+    //the network with int8 weights. activation_quantization holds the parameters of the
+    //preprocessed input and then of the output of every hidden layer, from_range over the
+    //values they take on typical inputs is a good choice, see calibrate. dropout and the cost
+    //are left out.
+    pub fn quantize(&self, activation_quantization: Vec<QuantizationParameters>) -> Result<QuantizedNetwork, String> {
+        if self.normalization.is_some() {
+            return Err("networks with normalization can not be quantized.".to_string());
+        }
+        if activation_quantization.len() != self.weights.len() {
+            return Err(format!("expected quantization parameters for the input and {} hidden layers, got {}.", self.weights.len() - 1, activation_quantization.len()));
+        }
+        let layers = zip(zip(&self.weights, &self.biases), zip(&self.activation_functions, &activation_quantization))
+            .map(|((weights, biases), (activation_function, quantization))| {
+                QuantizedLayer::new(weights, &biases.data, activation_function.clone(), *quantization)
+            })
+            .collect();
+        Ok(QuantizedNetwork { layers, activation_quantization, preprocessing: self.preprocessing })
+    }
+
+    //runs the samples of a representative dataset, e.g. a few hundred training images, through
+    //the network and picks the parameters of the input and every hidden layer from the
+    //smallest and largest value seen there.
+    pub fn calibrate<D: Dataset + ?Sized>(&self, dataset: &D) -> Vec<QuantizationParameters> {
+        if dataset.is_empty() {
+            panic!("calibration needs at least one sample.");
+        }
+        let mut ranges = vec![(f32::INFINITY, f32::NEG_INFINITY); self.weights.len()];
+        let mut buffers = InferenceBuffers::default();
+        for index in 0..dataset.len() {
+            self.infer_with(&dataset.get(index).0, &mut buffers);
+            for ((min, max), activations) in zip(&mut ranges, &buffers.activation_values) {
+                for &elem in &activations.data {
+                    *min = min.min(elem);
+                    *max = max.max(elem);
+                }
+            }
+        }
+        ranges.into_iter().map(|(min, max)| QuantizationParameters::from_range(min, max)).collect()
+    }
+
+    //quantize with the parameters of calibrate.
+    pub fn quantize_calibrated<D: Dataset + ?Sized>(&self, dataset: &D) -> Result<QuantizedNetwork, String> {
+        if dataset.is_empty() {
+            return Err("calibration needs at least one sample.".to_string());
+        }
+        self.quantize(self.calibrate(dataset))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::iter::zip;
+    use matrix::ColumnVector;
+    use mnist_reader::Preprocessing;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::{ActivationFunction, InferenceBuffers, Initialization, LayerNorm, NeuralNetwork, Normalization, QuantizationParameters};
+
+    #[test]
+    fn quantization_parameters() {
+        let parameters = QuantizationParameters::from_range(-1.0, 3.0);
+        assert_eq!(parameters.quantize(-1.0), -128);
+        assert_eq!(parameters.quantize(3.0), 127);
+        assert_eq!(parameters.dequantize(parameters.quantize(0.0)), 0.0);
+        assert!((parameters.dequantize(parameters.quantize(1.3)) - 1.3).abs() <= parameters.scale / 2.0);
+        assert_eq!(parameters.quantize(10.0), 127);
+        //0 is always part of the range.
+        let positive = QuantizationParameters::from_range(2.0, 4.0);
+        assert_eq!((positive.quantize(0.0), positive.quantize(4.0)), (-128, 127));
+        let weights = QuantizationParameters::symmetric(0.5);
+        assert_eq!((weights.quantize(-0.5), weights.quantize(0.0), weights.quantize(0.5)), (-127, 0, 127));
+    }
+
+    #[test]
+    fn quantized_inference() {
+        let mut network = NeuralNetwork::new_with_rng(&[16, 12, 4], vec![ActivationFunction::Relu, ActivationFunction::Softmax], Initialization::HeNormal, &mut StdRng::seed_from_u64(6));
+        network.preprocessing = Some(Preprocessing::Scale { max: 255.0 });
+        let inputs: Vec<ColumnVector> = (0..8)
+            .map(|sample| ColumnVector::from_vec((0..16).map(|x| ((x * 37 + sample * 101) % 256) as f32).collect()))
+            .collect();
+        let mut buffers = InferenceBuffers::default();
+        let hidden_max = inputs.iter()
+            .map(|input| { network.infer_with(input, &mut buffers); buffers.activation_values[1].data.iter().cloned().fold(0.0, f32::max) })
+            .fold(0.0, f32::max);
+        assert!(hidden_max > 1.0 && hidden_max < 4.0);
+        let quantized = network.quantize(vec![QuantizationParameters::from_range(0.0, 1.0), QuantizationParameters::from_range(0.0, 4.0)]).unwrap();
+        assert_eq!(quantized.parameter_bytes(), 16 * 12 + 12 * 4 + (12 + 4) * 4);
+        for input in &inputs {
+            let (quantized_output, output) = (quantized.infer(input), network.infer(input));
+            for (quantized_elem, elem) in zip(&quantized_output.data, &output.data) {
+                assert!((quantized_elem - elem).abs() < 2e-2, "{:?} {:?}", quantized_output, output);
+            }
+        }
+    }
+
+    #[test]
+    fn calibration() {
+        let network = NeuralNetwork::new_with_rng(&[3, 5, 2], vec![ActivationFunction::Tanh, ActivationFunction::Sigmoid], Initialization::GlorotUniform, &mut StdRng::seed_from_u64(1));
+        let dataset: Vec<(ColumnVector, usize)> = (0..20)
+            .map(|sample| (ColumnVector::from_vec((0..3).map(|x| ((sample * 3 + x) as f32 * 0.9).sin() * 2.0).collect()), 0))
+            .collect();
+        let parameters = network.calibrate(&dataset);
+        assert_eq!(parameters.len(), 2);
+        //the extremes of the inputs and of the tanh outputs land on the ends of the i8 range.
+        let inputs = dataset.iter().flat_map(|(input, _)| input.data.iter().cloned());
+        let (min, max) = inputs.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), elem| (min.min(elem), max.max(elem)));
+        assert_eq!((parameters[0].quantize(min), parameters[0].quantize(max)), (-128, 127));
+        assert_eq!(parameters[1], {
+            let hidden = dataset.iter().flat_map(|(input, _)| {
+                let mut buffers = InferenceBuffers::default();
+                network.infer_with(input, &mut buffers);
+                buffers.activation_values[1].data.clone()
+            });
+            let (min, max) = hidden.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), elem| (min.min(elem), max.max(elem)));
+            QuantizationParameters::from_range(min, max)
+        });
+
+        let quantized = network.quantize_calibrated(&dataset).unwrap();
+        for (input, _) in &dataset {
+            for (quantized_elem, elem) in zip(quantized.infer(input).data, network.infer(input).data) {
+                assert!((quantized_elem - elem).abs() < 1e-2);
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_networks_are_errors() {
+        let mut network = NeuralNetwork::new_with_activations(&[2, 3, 2], vec![ActivationFunction::Relu; 2], Some(0.5));
+        let parameters = vec![QuantizationParameters::from_range(0.0, 1.0)];
+        assert_eq!(network.quantize(parameters.clone()).err(), Some("expected quantization parameters for the input and 1 hidden layers, got 1.".to_string()));
+        assert!(network.quantize_calibrated(&Vec::<(ColumnVector, usize)>::new()).is_err());
+        network.normalization = Some(Normalization::Layer(LayerNorm::for_network(&network)));
+        assert_eq!(network.quantize(parameters).err(), Some("networks with normalization can not be quantized.".to_string()));
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use crate::{OptimizerState, Trainable};
+
+const MAGIC: &[u8; 8] = b"nnstate\0";
+const VERSION: u32 = 2;
+
+//everything needed to continue an interrupted training run exactly where it stopped.
+//written by the trainer after every epoch when Trainer::checkpoint_path is set and read by Trainer::resume.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TrainingState {
+    //amount of finished epochs, training resumes with this one.
+    pub epoch: usize,
+    //amount of optimizer steps taken, which is where the scheduler continues.
+    pub step: usize,
+    //the shuffling and dropout randomness of the next epoch is derived from this seed.
+    pub seed: u64,
+    pub parameters: Vec<f32>,
+    //batch normalization running means followed by the running variances, empty without it.
+    pub running_statistics: Vec<f32>,
+    pub optimizer: OptimizerState,
+}
+
+fn write_values(values: &[f32], bytes: &mut Vec<u8>) {
+    bytes.extend((values.len() as u64).to_le_bytes());
+    values.iter().for_each(|value| bytes.extend(value.to_le_bytes()));
+}
+
+//reads little endian values from the front of a byte slice.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, amount: usize) -> std::io::Result<&'a [u8]> {
+        if self.bytes.len() < amount {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "the file is truncated"));
+        }
+        let (taken, rest) = self.bytes.split_at(amount);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u64(&mut self) -> std::io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> std::io::Result<String> {
+        let amount = self.u64()? as usize;
+        String::from_utf8(self.take(amount)?.to_vec()).map_err(|_| Error::new(ErrorKind::InvalidData, "the file is corrupt"))
+    }
+
+    fn values(&mut self) -> std::io::Result<Vec<f32>> {
+        let amount = self.u64()? as usize;
+        let bytes = self.take(amount.checked_mul(4).ok_or_else(|| Error::new(ErrorKind::InvalidData, "the file is corrupt"))?)?;
+        Ok(bytes.chunks_exact(4).map(|x| f32::from_le_bytes(x.try_into().unwrap())).collect())
+    }
+}
+
+impl TrainingState {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        for value in [self.epoch as u64, self.step as u64, self.seed, self.optimizer.steps] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend((self.optimizer.kind.len() as u64).to_le_bytes());
+        bytes.extend(self.optimizer.kind.as_bytes());
+        write_values(&self.parameters, &mut bytes);
+        write_values(&self.running_statistics, &mut bytes);
+        bytes.extend((self.optimizer.buffers.len() as u64).to_le_bytes());
+        self.optimizer.buffers.iter().for_each(|buffer| write_values(buffer, &mut bytes));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<TrainingState> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a training state"));
+        }
+        let version = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+        if version != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, format!("unsupported training state version {}", version)));
+        }
+        let (epoch, step, seed, optimizer_steps) = (reader.u64()?, reader.u64()?, reader.u64()?, reader.u64()?);
+        let optimizer_kind = reader.string()?;
+        let parameters = reader.values()?;
+        let running_statistics = reader.values()?;
+        let buffers = (0..reader.u64()?).map(|_| reader.values()).collect::<std::io::Result<_>>()?;
+        Ok(TrainingState {
+            epoch: epoch as usize,
+            step: step as usize,
+            seed,
+            parameters,
+            running_statistics,
+            optimizer: OptimizerState { kind: optimizer_kind, steps: optimizer_steps, buffers },
+        })
+    }
+
+    //written under a temporary name first so an interrupted write never replaces the last state with a partial one.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let mut partial_path = path.as_os_str().to_owned();
+        partial_path.push(".part");
+        fs::write(&partial_path, self.to_bytes())?;
+        fs::rename(&partial_path, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<TrainingState> {
+        TrainingState::from_bytes(&fs::read(path)?)
+    }
+}
+
+//whether the parameters and running statistics of a state fit a network of this shape.
+pub(crate) fn check_network<N: Trainable>(network: &mut N, state: &TrainingState) -> std::io::Result<()> {
+    if network.parameters_mut().count() != state.parameters.len() || network.running_statistics().len() != state.running_statistics.len() {
+        return Err(Error::new(ErrorKind::InvalidInput, "training state does not fit the network"));
+    }
+    Ok(())
+}
+
+//puts the parameters and running statistics of a state that passed check_network back into the network.
+pub(crate) fn restore_network<N: Trainable>(network: &mut N, state: &TrainingState) {
+    network.parameters_mut().zip(&state.parameters).for_each(|(parameter, value)| *parameter = *value);
+    network.set_running_statistics(&state.running_statistics);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use matrix::ColumnVector;
+    use mnist_reader::DataLoader;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::{ActivationFunction, Adam, Dropout, Momentum, NeuralNetwork, Trainer, TrainingState};
+
+    #[test]
+    fn resumed_training_matches_uninterrupted_training() {
+        let dataset: Vec<(ColumnVector, usize)> = (0..10)
+            .map(|x| (ColumnVector::from_vec(vec![(x % 2) as f32, (x % 3) as f32 / 2.0]), x % 2))
+            .collect();
+        let directory = std::env::temp_dir().join(format!("nn_resume_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let run = |epochs: usize, path: &str, resume_from: Option<&str>| -> NeuralNetwork {
+            let mut network = NeuralNetwork::new_with_seed(&[2, 4, 2], vec![ActivationFunction::Relu, ActivationFunction::Sigmoid], 5);
+            network.dropout = Some(Dropout::new_with_seed(0.2, 6));
+            let mut trainer = Trainer::new_with_optimizer(3, 0.05, epochs, Adam::default());
+            trainer.rng = StdRng::seed_from_u64(7);
+            trainer.checkpoint_path = Some(directory.join(path));
+            if let Some(resume_from) = resume_from {
+                trainer.resume(&mut network, directory.join(resume_from)).unwrap();
+            }
+            trainer.train_with_loader(&mut network, &DataLoader::new_shuffled(&dataset, 3));
+            network
+        };
+        let uninterrupted = run(4, "uninterrupted", None);
+        run(2, "interrupted", None);
+        let state = TrainingState::load(directory.join("interrupted")).unwrap();
+        assert_eq!((state.epoch, state.step), (2, 8));
+        assert_eq!(TrainingState::from_bytes(&state.to_bytes()).unwrap(), state);
+        let resumed = run(4, "resumed", Some("interrupted"));
+        assert_eq!(resumed, uninterrupted);
+
+        //missing, corrupt and mismatched states are errors, not panics.
+        assert_eq!(TrainingState::load(directory.join("missing")).unwrap_err().kind(), ErrorKind::NotFound);
+        std::fs::write(directory.join("corrupt"), b"nnstate\0").unwrap();
+        assert!(TrainingState::load(directory.join("corrupt")).is_err());
+        let mut network = NeuralNetwork::new_with_activations(&[2, 3, 2], vec![ActivationFunction::Relu; 2], Some(0.5));
+        let mut trainer = Trainer::new(3, 0.05, 1);
+        assert_eq!(trainer.resume(&mut network, directory.join("interrupted")).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+        //a network of the right shape still needs an optimizer of the kind and size of the state.
+        let mut network = NeuralNetwork::new_with_seed(&[2, 4, 2], vec![ActivationFunction::Relu, ActivationFunction::Sigmoid], 5);
+        let untouched = NeuralNetwork::new_with_seed(&[2, 4, 2], vec![ActivationFunction::Relu, ActivationFunction::Sigmoid], 5);
+        let mut trainer = Trainer::new_with_optimizer(3, 0.05, 1, Momentum::new(0.9, false));
+        assert_eq!(trainer.resume(&mut network, directory.join("interrupted")).unwrap_err().kind(), ErrorKind::InvalidInput);
+        let mut short_buffers = state.clone();
+        short_buffers.optimizer.buffers[1].pop();
+        short_buffers.save(directory.join("short_buffers")).unwrap();
+        let mut trainer = Trainer::new_with_optimizer(3, 0.05, 1, Adam::default());
+        assert_eq!(trainer.resume(&mut network, directory.join("short_buffers")).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!((network, trainer.initial_epoch), (untouched, 0));
+
+        //states are written through a temporary file and a failed write is an error.
+        assert!(!directory.join("interrupted.part").exists());
+        assert!(state.save(directory.join("missing").join("state")).is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use safetensors::{Dtype, SafeTensors};
+use safetensors::tensor::TensorView;
+use matrix::{ColumnVector, Matrix};
+use crate::{invalid_data, ActivationFunction, NeuralNetwork};
+
+//tensors are named like the layers of a pytorch nn.Sequential of nn.Linear:
+//layers.<index>.weight with shape [outputs, inputs] and layers.<index>.bias with shape [outputs],
+//all f32. The activation of every layer is kept in the metadata as activation.<index>.
+
+fn to_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn tensor_values(tensors: &SafeTensors, name: &str, shape: &[usize]) -> io::Result<Vec<f32>> {
+    let tensor = tensors.tensor(name).map_err(|error| invalid_data(format!("could not read tensor {}: {}.", name, error)))?;
+    if tensor.dtype() != Dtype::F32 {
+        return Err(invalid_data(format!("tensor {} is {:?}, only F32 is supported.", name, tensor.dtype())));
+    }
+    if tensor.shape() != shape {
+        return Err(invalid_data(format!("tensor {} has shape {:?}, expected {:?}.", name, tensor.shape(), shape)));
+    }
+    Ok(tensor.data().chunks_exact(4).map(|x| f32::from_le_bytes(x.try_into().unwrap())).collect())
+}
+
+impl NeuralNetwork {
+    pub fn to_safetensors(&self) -> io::Result<Vec<u8>> {
+        let data: Vec<(String, Vec<usize>, Vec<u8>)> = self.weights.iter().zip(&self.biases).enumerate()
+            .flat_map(|(index, (weights, biases))| [
+                (format!("layers.{}.weight", index), vec![weights.height(), weights.width()], to_bytes(weights.as_slice())),
+                (format!("layers.{}.bias", index), vec![biases.data.len()], to_bytes(&biases.data)),
+            ])
+            .collect();
+        //the shapes match the data by construction.
+        let views: Vec<(String, TensorView)> = data.iter()
+            .map(|(name, shape, bytes)| (name.clone(), TensorView::new(Dtype::F32, shape.clone(), bytes).unwrap()))
+            .collect();
+        let metadata = self.activation_functions.iter().enumerate()
+            .filter_map(|(index, activation)| Some((format!("activation.{}", index), activation.name()?)))
+            .collect::<HashMap<String, String>>();
+        safetensors::serialize(views, &Some(metadata)).map_err(|error| invalid_data(format!("could not write safetensors: {}.", error)))
+    }
+
+    //layers without an activation in the metadata, or an unknown one, use relu.
+    //dropout, normalization and preprocessing are not stored.
+    pub fn from_safetensors(bytes: &[u8]) -> io::Result<NeuralNetwork> {
+        let tensors = SafeTensors::deserialize(bytes).map_err(|error| invalid_data(format!("not a safetensors file: {}.", error)))?;
+        let mut weights = Vec::new();
+        let mut biases = Vec::new();
+        while let Ok(tensor) = tensors.tensor(&format!("layers.{}.weight", weights.len())) {
+            let index = weights.len();
+            let shape = tensor.shape().to_vec();
+            if shape.len() != 2 || shape.contains(&0) {
+                return Err(invalid_data(format!("tensor layers.{}.weight must be a non empty matrix, got shape {:?}.", index, shape)));
+            }
+            if let Some(previous) = weights.last().map(|x: &Matrix| x.height()) {
+                if previous != shape[1] {
+                    return Err(invalid_data(format!("layer {} has {} inputs but the previous one {} outputs.", index, shape[1], previous)));
+                }
+            }
+            let values = tensor_values(&tensors, &format!("layers.{}.weight", index), &shape)?;
+            weights.push(Matrix::from_vec(values.chunks(shape[1]).map(|x| x.to_vec()).collect()));
+            biases.push(ColumnVector::from_vec(tensor_values(&tensors, &format!("layers.{}.bias", index), &shape[..1])?));
+        }
+        if weights.is_empty() {
+            return Err(invalid_data("no tensor layers.0.weight in the file.".to_string()));
+        }
+        let metadata = SafeTensors::read_metadata(bytes).map_err(|error| invalid_data(format!("not a safetensors file: {}.", error)))?.1;
+        let activations = metadata.metadata().clone().unwrap_or_default();
+        let mut network = NeuralNetwork::new_from_vecs(weights, Some(biases), None, None);
+        for (index, activation) in network.activation_functions.iter_mut().enumerate() {
+            if let Some(named) = activations.get(&format!("activation.{}", index)).and_then(|name| ActivationFunction::from_name(name)) {
+                *activation = named;
+            }
+        }
+        Ok(network)
+    }
+
+    pub fn save_safetensors<P: AsRef<Path>>(&self, file_path: P) -> io::Result<()> {
+        fs::write(file_path, self.to_safetensors()?)
+    }
+
+    pub fn load_safetensors<P: AsRef<Path>>(file_path: P) -> io::Result<NeuralNetwork> {
+        NeuralNetwork::from_safetensors(&fs::read(file_path)?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use safetensors::{Dtype, SafeTensors};
+    use matrix::ColumnVector;
+    use crate::{ActivationFunction, NeuralNetwork};
+
+    #[test]
+    fn safetensors_round_trip() {
+        let mut network = NeuralNetwork::new_classifier(&[4, 3, 2], None);
+        network.activation_functions[0] = ActivationFunction::LeakyRelu(0.1);
+        let bytes = network.to_safetensors().unwrap();
+
+        let tensors = SafeTensors::deserialize(&bytes).unwrap();
+        let weight = tensors.tensor("layers.0.weight").unwrap();
+        assert_eq!((weight.dtype(), weight.shape()), (Dtype::F32, &[3, 4][..]));
+        assert_eq!(tensors.tensor("layers.1.bias").unwrap().shape(), &[2]);
+
+        let loaded = NeuralNetwork::from_safetensors(&bytes).unwrap();
+        assert_eq!(loaded.weights, network.weights);
+        assert_eq!(loaded.biases, network.biases);
+        assert_eq!(loaded.activation_functions, network.activation_functions);
+        let input = ColumnVector::from_vec(vec![0.5, -1.0, 2.0, 0.0]);
+        assert_eq!(loaded.infer(&input), network.infer(&input));
+        assert!(NeuralNetwork::from_safetensors(&bytes[..bytes.len() - 4]).is_err());
+    }
+}
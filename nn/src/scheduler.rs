@@ -0,0 +1,160 @@
+use std::f32::consts::PI;
+
+//decides the learning rate the trainer uses for every mini batch.
+//epoch counts from 0 and step is the amount of mini batches already trained across all epochs.
+pub trait LrScheduler {
+    fn learning_rate(&self, base_learning_rate: f32, epoch: usize, step: usize) -> f32;
+}
+
+//always uses the trainer's learning rate.
+pub struct ConstantLr;
+
+impl LrScheduler for ConstantLr {
+    fn learning_rate(&self, base_learning_rate: f32, _epoch: usize, _step: usize) -> f32 {
+        base_learning_rate
+    }
+}
+
+//multiplies the learning rate by gamma every step_size epochs.
+pub struct StepDecay {
+    pub step_size: usize,
+    pub gamma: f32,
+}
+
+impl StepDecay {
+    pub fn new(step_size: usize, gamma: f32) -> StepDecay {
+        if step_size == 0 {
+            panic!("step decay requires a step size of at least 1.");
+        }
+        StepDecay {
+            step_size,
+            gamma,
+        }
+    }
+}
+
+impl LrScheduler for StepDecay {
+    fn learning_rate(&self, base_learning_rate: f32, epoch: usize, _step: usize) -> f32 {
+        base_learning_rate * self.gamma.powi((epoch / self.step_size) as i32)
+    }
+}
+
+//multiplies the learning rate by gamma every epoch.
+pub struct ExponentialDecay {
+    pub gamma: f32,
+}
+
+impl ExponentialDecay {
+    pub fn new(gamma: f32) -> ExponentialDecay {
+        ExponentialDecay {
+            gamma,
+        }
+    }
+}
+
+impl LrScheduler for ExponentialDecay {
+    fn learning_rate(&self, base_learning_rate: f32, epoch: usize, _step: usize) -> f32 {
+        base_learning_rate * self.gamma.powi(epoch as i32)
+    }
+}
+
+//follows half a cosine from the base learning rate down to min_learning_rate over total_epochs,
+//then stays at min_learning_rate.
+pub struct CosineAnnealing {
+    pub total_epochs: usize,
+    pub min_learning_rate: f32,
+}
+
+impl CosineAnnealing {
+    pub fn new(total_epochs: usize, min_learning_rate: f32) -> CosineAnnealing {
+        if total_epochs == 0 {
+            panic!("cosine annealing requires at least 1 epoch.");
+        }
+        CosineAnnealing {
+            total_epochs,
+            min_learning_rate,
+        }
+    }
+}
+
+impl LrScheduler for CosineAnnealing {
+    fn learning_rate(&self, base_learning_rate: f32, epoch: usize, _step: usize) -> f32 {
+        let progress = epoch.min(self.total_epochs) as f32 / self.total_epochs as f32;
+        self.min_learning_rate + (base_learning_rate - self.min_learning_rate) * (1.0 + (PI * progress).cos()) * 0.5
+    }
+}
+
+//ramps the learning rate linearly up to the wrapped schedule over the first warmup_steps
+//mini batches, after which the wrapped schedule is used unchanged.
+pub struct Warmup<S: LrScheduler> {
+    pub warmup_steps: usize,
+    pub schedule: S,
+}
+
+impl<S: LrScheduler> Warmup<S> {
+    pub fn new(warmup_steps: usize, schedule: S) -> Warmup<S> {
+        Warmup {
+            warmup_steps,
+            schedule,
+        }
+    }
+}
+
+impl<S: LrScheduler> LrScheduler for Warmup<S> {
+    fn learning_rate(&self, base_learning_rate: f32, epoch: usize, step: usize) -> f32 {
+        let target = self.schedule.learning_rate(base_learning_rate, epoch, step);
+        if step < self.warmup_steps {
+            //step counts from 0, so the last warmup step reaches the target.
+            target * (step + 1) as f32 / self.warmup_steps as f32
+        } else {
+            target
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConstantLr, CosineAnnealing, ExponentialDecay, LrScheduler, StepDecay, Warmup};
+
+    fn assert_close(x: f32, y: f32) {
+        assert!((x - y).abs() < 1e-6, "{} != {}", x, y);
+    }
+
+    #[test]
+    fn step_decay() {
+        let scheduler = StepDecay::new(2, 0.5);
+        assert_close(scheduler.learning_rate(1.0, 0, 0), 1.0);
+        assert_close(scheduler.learning_rate(1.0, 1, 0), 1.0);
+        assert_close(scheduler.learning_rate(1.0, 2, 0), 0.5);
+        assert_close(scheduler.learning_rate(1.0, 5, 0), 0.25);
+    }
+
+    #[test]
+    fn exponential_decay() {
+        let scheduler = ExponentialDecay::new(0.5);
+        assert_close(scheduler.learning_rate(2.0, 0, 0), 2.0);
+        assert_close(scheduler.learning_rate(2.0, 3, 0), 0.25);
+    }
+
+    #[test]
+    fn cosine_annealing() {
+        let scheduler = CosineAnnealing::new(4, 0.1);
+        assert_close(scheduler.learning_rate(1.1, 0, 0), 1.1);
+        assert_close(scheduler.learning_rate(1.1, 2, 0), 0.6);
+        assert_close(scheduler.learning_rate(1.1, 4, 0), 0.1);
+        assert_close(scheduler.learning_rate(1.1, 10, 0), 0.1);
+    }
+
+    #[test]
+    fn warmup() {
+        let scheduler = Warmup::new(4, ConstantLr);
+        assert_close(scheduler.learning_rate(1.0, 0, 0), 0.25);
+        assert_close(scheduler.learning_rate(1.0, 0, 2), 0.75);
+        assert_close(scheduler.learning_rate(1.0, 1, 3), 1.0);
+        assert_close(scheduler.learning_rate(1.0, 1, 100), 1.0);
+        let decaying = Warmup::new(2, StepDecay::new(1, 0.5));
+        assert_close(decaying.learning_rate(1.0, 0, 0), 0.5);
+        assert_close(decaying.learning_rate(1.0, 2, 10), 0.25);
+    }
+}
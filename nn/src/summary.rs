@@ -0,0 +1,84 @@
+use std::fmt;
+use crate::{Model, Shape};
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct LayerSummary {
+    pub name: String,
+    pub output_shape: Shape,
+    pub parameters: usize,
+}
+
+//the layers of a model with their output shapes and parameter counts, printed like a table.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Summary {
+    pub input_shape: Shape,
+    pub layers: Vec<LayerSummary>,
+}
+
+impl Summary {
+    pub fn total_parameters(&self) -> usize {
+        self.layers.iter().map(|layer| layer.parameters).sum()
+    }
+}
+
+impl Model {
+    pub fn summary(&self) -> Summary {
+        let shapes = self.shapes();
+        Summary {
+            input_shape: self.input_shape,
+            layers: self.layers.iter().zip(&shapes[1..])
+                .map(|(layer, &output_shape)| LayerSummary {
+                    name: layer.name(),
+                    output_shape,
+                    parameters: layer.parameter_count(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<24} {:>16} {:>12}", "layer", "output shape", "parameters")?;
+        writeln!(f, "{:<24} {:>16} {:>12}", "input", self.input_shape.to_string(), 0)?;
+        for layer in &self.layers {
+            writeln!(f, "{:<24} {:>16} {:>12}", layer.name, layer.output_shape.to_string(), layer.parameters)?;
+        }
+        writeln!(f, "total parameters: {}", self.total_parameters())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::{Model, Shape};
+
+    #[test]
+    fn summary() {
+        let model = Model::sequential()
+            .input(Shape::image(1, 28, 28))
+            .conv2d(8, 3, 1, 1).relu()
+            .max_pool2d(2, 2)
+            .flatten()
+            .dense(8 * 14 * 14, 10)
+            .residual(|block| block.dense(10, 10).tanh())
+            .softmax()
+            .build().unwrap();
+        let summary = model.summary();
+        let parameters: Vec<usize> = summary.layers.iter().map(|layer| layer.parameters).collect();
+        assert_eq!(parameters, vec![8 * 9 + 8, 0, 0, 0, 1568 * 10 + 10, 110, 0]);
+        assert_eq!(summary.total_parameters(), 80 + 15690 + 110);
+        assert_eq!(summary.to_string(), "\
+layer                        output shape   parameters
+input                         (1, 28, 28)            0
+Conv2d                        (8, 28, 28)           80
+Activation(relu)              (8, 28, 28)            0
+MaxPool2d                     (8, 14, 14)            0
+Flatten                            (1568)            0
+Dense                                (10)        15690
+Residual                             (10)          110
+Activation(softmax)                  (10)            0
+total parameters: 15880
+");
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::protobuf::Message;
+use crate::{Callback, Control, EpochEnd};
+
+//crc32c (castagnoli), the checksum of the tfrecord framing, computed bit by bit.
+fn crc32c(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0x82f6_3b78 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn masked_crc32c(bytes: &[u8]) -> u32 {
+    let crc = crc32c(bytes);
+    crc.rotate_right(15).wrapping_add(0xa282_ead8)
+}
+
+//length, masked checksum of the length, data and masked checksum of the data.
+fn record(data: &[u8]) -> Vec<u8> {
+    let length = (data.len() as u64).to_le_bytes();
+    let mut bytes = Vec::with_capacity(data.len() + 16);
+    bytes.extend(length);
+    bytes.extend(masked_crc32c(&length).to_le_bytes());
+    bytes.extend(data);
+    bytes.extend(masked_crc32c(data).to_le_bytes());
+    bytes
+}
+
+//an Event with its wall time, step and either a file version or a summary of scalars.
+fn event(wall_time: f64, step: i64, file_version: Option<&str>, scalars: &[(String, f32)]) -> Vec<u8> {
+    let mut event = Message::new();
+    event.double(1, wall_time).int(2, step);
+    match file_version {
+        Some(file_version) => event.string(3, file_version),
+        None => {
+            let mut summary = Message::new();
+            for (tag, value) in scalars {
+                summary.message(1, Message::new().string(1, tag).float(2, *value));
+            }
+            event.message(5, &summary)
+        }
+    };
+    event.bytes
+}
+
+fn wall_time() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |duration| duration.as_secs_f64())
+}
+
+//writes the losses, accuracy, learning rate and metrics of every epoch as scalar summaries
+//to an event file in log_dir, which TensorBoard picks up with --logdir pointing at it.
+//the file is created when the first epoch ends and the epoch is used as the step.
+//like ProgressBar it is best effort: a failed write is reported once on stderr and training goes on.
+pub struct TensorBoard {
+    pub log_dir: PathBuf,
+    writer: Option<BufWriter<File>>,
+    reported_error: bool,
+}
+
+impl TensorBoard {
+    pub fn new<P: Into<PathBuf>>(log_dir: P) -> TensorBoard {
+        TensorBoard {
+            log_dir: log_dir.into(),
+            writer: None,
+            reported_error: false,
+        }
+    }
+
+    fn scalars(epoch: &EpochEnd) -> Vec<(String, f32)> {
+        let mut scalars = vec![
+            ("training/loss".to_string(), epoch.training_loss),
+            ("learning_rate".to_string(), epoch.learning_rate),
+        ];
+        if let Some(validation) = &epoch.validation {
+            scalars.push(("validation/loss".to_string(), validation.loss));
+            scalars.push(("validation/accuracy".to_string(), validation.accuracy));
+            scalars.extend(validation.metrics.iter().map(|(name, value)| (format!("validation/{}", name), *value)));
+        }
+        scalars
+    }
+
+    fn write(&mut self, epoch: &EpochEnd) -> std::io::Result<()> {
+        if self.writer.is_none() {
+            fs::create_dir_all(&self.log_dir)?;
+            let file_name = format!("events.out.tfevents.{}.mnist_rust.{}", wall_time() as u64, std::process::id());
+            let mut writer = BufWriter::new(File::create(self.log_dir.join(file_name))?);
+            writer.write_all(&record(&event(wall_time(), 0, Some("brain.Event:2"), &[])))?;
+            self.writer = Some(writer);
+        }
+        let writer = self.writer.as_mut().unwrap();
+        writer.write_all(&record(&event(wall_time(), epoch.epoch as i64, None, &TensorBoard::scalars(epoch))))?;
+        //flushed every epoch so TensorBoard can follow a running training.
+        writer.flush()
+    }
+}
+
+impl<N> Callback<N> for TensorBoard {
+    fn on_epoch_end(&mut self, _network: &mut N, epoch: &EpochEnd) -> Control {
+        if let Err(error) = self.write(epoch) {
+            if !self.reported_error {
+                eprintln!("could not write to {}: {}.", self.log_dir.display(), error);
+                self.reported_error = true;
+            }
+        }
+        Control::Continue
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::tensorboard::{crc32c, masked_crc32c};
+    use crate::{ActivationFunction, Callback, Control, EpochEnd, Evaluation, NeuralNetwork, TensorBoard};
+
+    #[test]
+    fn event_file_records() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+
+        let log_dir = std::env::temp_dir().join(format!("nn_tensorboard_{}", std::process::id()));
+        let mut tensorboard = TensorBoard::new(&log_dir);
+        let mut network = NeuralNetwork::new_with_activations(&[2, 2], vec![ActivationFunction::Relu], Some(0.5));
+        for epoch in 0..2 {
+            let validation = Some(Evaluation { loss: 0.25, accuracy: 0.75, metrics: Vec::new() });
+            tensorboard.on_epoch_end(&mut network, &EpochEnd { epoch, training_loss: 0.5, validation, learning_rate: 0.1 });
+        }
+        let files: Vec<_> = fs::read_dir(&log_dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].file_name().unwrap().to_str().unwrap().starts_with("events.out.tfevents."));
+        let bytes = fs::read(&files[0]).unwrap();
+        fs::remove_dir_all(&log_dir).unwrap();
+
+        //the file version event followed by one summary per epoch, each with intact checksums.
+        let mut records = Vec::new();
+        let mut position = 0;
+        while position < bytes.len() {
+            let length_bytes = &bytes[position..position + 8];
+            let length = u64::from_le_bytes(length_bytes.try_into().unwrap()) as usize;
+            assert_eq!(u32::from_le_bytes(bytes[position + 8..position + 12].try_into().unwrap()), masked_crc32c(length_bytes));
+            let data = &bytes[position + 12..position + 12 + length];
+            assert_eq!(u32::from_le_bytes(bytes[position + 12 + length..position + 16 + length].try_into().unwrap()), masked_crc32c(data));
+            records.push(data.to_vec());
+            position += length + 16;
+        }
+        assert_eq!(records.len(), 3);
+        assert!(records[0].ends_with(b"brain.Event:2"));
+        let contains = |data: &[u8], pattern: &[u8]| data.windows(pattern.len()).any(|window| window == pattern);
+        assert!(contains(&records[2], b"validation/accuracy"));
+        let mut accuracy_value = vec![0x15];
+        accuracy_value.extend(0.75f32.to_le_bytes());
+        assert!(contains(&records[2], &accuracy_value));
+        //step 1 of the second epoch.
+        assert_eq!(&records[2][9..11], &[0x10, 0x01]);
+    }
+
+    #[test]
+    fn write_errors_do_not_stop_training() {
+        //the log directory can not be created where a file is.
+        let path = std::env::temp_dir().join(format!("nn_tensorboard_file_{}", std::process::id()));
+        fs::write(&path, "not a directory").unwrap();
+        let mut tensorboard = TensorBoard::new(&path);
+        let mut network = NeuralNetwork::new_with_activations(&[2, 2], vec![ActivationFunction::Relu], Some(0.5));
+        let epoch = EpochEnd { epoch: 0, training_loss: 0.5, validation: None, learning_rate: 0.1 };
+        assert_eq!(tensorboard.on_epoch_end(&mut network, &epoch), Control::Continue);
+        assert_eq!(tensorboard.on_epoch_end(&mut network, &epoch), Control::Continue);
+        fs::remove_file(&path).unwrap();
+        assert!(tensorboard.reported_error);
+    }
+}
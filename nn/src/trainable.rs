@@ -0,0 +1,179 @@
+use std::iter::zip;
+use matrix::ColumnVector;
+use mnist_reader::Dataset;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::{Evaluation, Gradients, LossAccumulator, Metric, Mode, NeuralNetwork, Normalization};
+
+//one gradient per parameter of a Trainable, in the order of Trainable::parameters_mut.
+pub trait GradientValues {
+    fn values(&self) -> impl Iterator<Item=&f32>;
+
+    fn values_mut(&mut self) -> impl Iterator<Item=&mut f32>;
+
+    fn accumulate(&mut self, other: &Self) {
+        zip(self.values_mut(), other.values()).for_each(|(acc, gradient)| *acc += gradient);
+    }
+
+    fn scale(&mut self, factor: f32) {
+        self.values_mut().for_each(|elem| *elem *= factor);
+    }
+
+    //euclidean norm over every gradient together.
+    fn global_norm(&self) -> f32 {
+        self.values().map(|x| x * x).sum::<f32>().sqrt()
+    }
+
+    //rescales all gradients together so their global norm is at most max_norm.
+    fn clip_by_global_norm(&mut self, max_norm: f32) {
+        let norm = self.global_norm();
+        if norm > max_norm {
+            self.scale(max_norm / norm);
+        }
+    }
+
+    //clamps every gradient element into [-max_value, max_value].
+    fn clip_by_value(&mut self, max_value: f32) {
+        self.values_mut().for_each(|elem| *elem = elem.clamp(-max_value, max_value));
+    }
+}
+
+//the gradients of a Model, flattened like its parameters.
+impl GradientValues for Vec<f32> {
+    fn values(&self) -> impl Iterator<Item=&f32> {
+        self.iter()
+    }
+
+    fn values_mut(&mut self) -> impl Iterator<Item=&mut f32> {
+        self.iter_mut()
+    }
+}
+
+//what the trainer, the optimizers and the callbacks need of what they train. NeuralNetwork
+//and Model both implement it, so either is trained by a Trainer with any Optimizer.
+pub trait Trainable {
+    type Gradients: GradientValues;
+
+    //every parameter in a fixed order, which is also the order of the gradients and of the
+    //optimizer buffers.
+    fn parameters_mut(&mut self) -> impl Iterator<Item=&mut f32>;
+
+    //whether every parameter is trained, in the order of parameters_mut.
+    fn trainable_parameters(&self) -> Vec<bool>;
+
+    //a gradient of 0 for every parameter, to accumulate into.
+    fn zeroed_gradients(&self) -> Self::Gradients;
+
+    //summed, not averaged, gradients of every (input, desired output) pair in the batch, with the
+    //cost of the outputs they were computed from. the batch is split over up to threads threads.
+    fn parallel_batch_gradients_and_loss(&mut self, batch: &[(ColumnVector, ColumnVector)], threads: usize) -> (Self::Gradients, LossAccumulator);
+
+    //the l2 and l1 penalties of the parameters that are regularized, 0 lambdas disable them.
+    fn penalty(&mut self, l2_lambda: f32, l1_lambda: f32) -> f32;
+
+    //adds the gradient of penalty to gradients.
+    fn add_penalty_gradients(&mut self, gradients: &mut Self::Gradients, l2_lambda: f32, l1_lambda: f32);
+
+    //amount of outputs, the size of the one hot targets made from labels.
+    fn output_size(&self) -> usize;
+
+    //mean cost over the data, without regularization penalties.
+    fn mean_loss(&mut self, data: &[(ColumnVector, ColumnVector)]) -> f32;
+
+    //the loss, accuracy and metrics of the validation data after every epoch.
+    fn validate<D: Dataset + ?Sized>(&mut self, dataset: &D, metrics: &mut [Box<dyn Metric>]) -> Evaluation;
+
+    //switches to mode and returns the mode it was in. what behaves the same in training and
+    //in inference keeps the default.
+    fn set_mode(&mut self, mode: Mode) -> Mode {
+        mode
+    }
+
+    //draws the randomness used while training, like dropout masks, from rng.
+    fn reseed(&mut self, _rng: &mut StdRng) {}
+
+    //values that are learned next to the parameters but not by the optimizer, like batch
+    //normalization running statistics, flattened. empty for what has none.
+    fn running_statistics(&self) -> Vec<f32> {
+        Vec::new()
+    }
+
+    //puts back values of running_statistics, nothing changes for an empty slice.
+    fn set_running_statistics(&mut self, _values: &[f32]) {}
+}
+
+impl Trainable for NeuralNetwork {
+    type Gradients = Gradients;
+
+    fn parameters_mut(&mut self) -> impl Iterator<Item=&mut f32> {
+        NeuralNetwork::parameters_mut(self)
+    }
+
+    fn trainable_parameters(&self) -> Vec<bool> {
+        NeuralNetwork::trainable_parameters(self)
+    }
+
+    fn zeroed_gradients(&self) -> Gradients {
+        Gradients::zeros_like(self)
+    }
+
+    fn parallel_batch_gradients_and_loss(&mut self, batch: &[(ColumnVector, ColumnVector)], threads: usize) -> (Gradients, LossAccumulator) {
+        NeuralNetwork::parallel_batch_gradients_and_loss(self, batch, threads)
+    }
+
+    //only the weights are regularized, see l2_penalty.
+    fn penalty(&mut self, l2_lambda: f32, l1_lambda: f32) -> f32 {
+        self.l2_penalty(l2_lambda) + self.l1_penalty(l1_lambda)
+    }
+
+    fn add_penalty_gradients(&mut self, gradients: &mut Gradients, l2_lambda: f32, l1_lambda: f32) {
+        if l2_lambda != 0.0 {
+            gradients.add_l2_penalty(self, l2_lambda);
+        }
+        if l1_lambda != 0.0 {
+            gradients.add_l1_penalty(self, l1_lambda);
+        }
+    }
+
+    fn output_size(&self) -> usize {
+        self.biases.last().unwrap().data.len()
+    }
+
+    fn mean_loss(&mut self, data: &[(ColumnVector, ColumnVector)]) -> f32 {
+        NeuralNetwork::mean_loss(self, data.iter().map(|(input_vector, desired_vector)| (input_vector, desired_vector)))
+    }
+
+    fn validate<D: Dataset + ?Sized>(&mut self, dataset: &D, metrics: &mut [Box<dyn Metric>]) -> Evaluation {
+        self.evaluate_with_metrics(dataset, metrics)
+    }
+
+    fn set_mode(&mut self, mode: Mode) -> Mode {
+        std::mem::replace(&mut self.mode, mode)
+    }
+
+    fn reseed(&mut self, rng: &mut StdRng) {
+        if let Some(dropout) = &mut self.dropout {
+            dropout.rng = StdRng::seed_from_u64(rng.gen());
+        }
+    }
+
+    //the batch normalization running means followed by the running variances.
+    fn running_statistics(&self) -> Vec<f32> {
+        match &self.normalization {
+            Some(Normalization::Batch(batch_norm)) => batch_norm.running_means.iter()
+                .chain(&batch_norm.running_variances)
+                .flat_map(|x| x.data.iter().copied())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn set_running_statistics(&mut self, values: &[f32]) {
+        if let Some(Normalization::Batch(batch_norm)) = &mut self.normalization {
+            let statistics = batch_norm.running_means.iter_mut()
+                .chain(batch_norm.running_variances.iter_mut())
+                .flat_map(|x| x.data.iter_mut());
+            zip(statistics, values).for_each(|(elem, value)| *elem = *value);
+        }
+    }
+}
@@ -0,0 +1,675 @@
+use rand::seq::SliceRandom;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::io;
+use std::path::{Path, PathBuf};
+use matrix::ColumnVector;
+use mnist_reader::{one_hot, DataLoader, Dataset};
+use crate::resume::{check_network, restore_network};
+use crate::{BatchEnd, Callback, ConstantLr, Control, EpochEnd, Evaluation, GradientValues, LossAccumulator, LrScheduler, Metric, Mode, NeuralNetwork, Optimizer, Sgd, Trainable, TrainingState};
+
+//applied to the averaged gradients of a mini batch before the optimizer step.
+pub enum GradientClipping {
+    GlobalNorm(f32),
+    Value(f32),
+}
+
+//trains a network with mini-batch stochastic gradient descent.
+//every mini batch the gradients of each sample are averaged before the update is applied.
+//N is what is trained, a NeuralNetwork or a Model.
+pub struct Trainer<O: Optimizer<N> = Sgd, N: Trainable = NeuralNetwork> {
+    pub mini_batch_size: usize,
+    pub learning_rate: f32,
+    pub epochs: usize,
+    pub optimizer: O,
+    //consulted before every mini batch with the learning rate above as its base.
+    pub scheduler: Box<dyn LrScheduler>,
+    pub gradient_clipping: Option<GradientClipping>,
+    //amount of mini batches whose gradients are combined into a single optimizer step.
+    //the effective batch size is mini_batch_size * accumulation_steps.
+    pub accumulation_steps: usize,
+    //strength of the l2 weight decay, 0 disables it.
+    pub l2_lambda: f32,
+    //strength of the l1 sparsity penalty, 0 disables it.
+    pub l1_lambda: f32,
+    //shuffles the training data, seed it for reproducible runs.
+    pub rng: StdRng,
+    //reported on the validation data after every epoch of train_with_validation, next to the loss.
+    pub metrics: Vec<Box<dyn Metric>>,
+    //called by every training method, see Callback.
+    pub callbacks: Vec<Box<dyn Callback<N>>>,
+    //when set, the training state is written here after every epoch so the run can be resumed.
+    //the randomness of every epoch is then derived from a seed stored in that state. a state
+    //that can not be written is reported on stderr once and training goes on.
+    pub checkpoint_path: Option<PathBuf>,
+    //set by make_deterministic. the randomness of the data loader and of the dataset, like its
+    //augmentation, is then derived from the rng above every epoch. gradients are always summed
+    //in a fixed order, so runs with the same seed and threads are bit for bit identical.
+    pub deterministic: bool,
+    //every mini batch is split over this many threads, see
+    //Trainable::parallel_batch_gradients_and_loss. 1 trains on the calling thread.
+    pub threads: usize,
+    //where training starts, both are set by resume.
+    pub initial_epoch: usize,
+    pub initial_step: usize,
+    reported_save_error: bool,
+}
+
+impl<N: Trainable> Trainer<Sgd, N> {
+    pub fn new(mini_batch_size: usize, learning_rate: f32, epochs: usize) -> Trainer<Sgd, N> {
+        Trainer::new_with_optimizer(mini_batch_size, learning_rate, epochs, Sgd)
+    }
+}
+
+impl<O: Optimizer<N>, N: Trainable> Trainer<O, N> {
+    pub fn new_with_optimizer(mini_batch_size: usize, learning_rate: f32, epochs: usize, optimizer: O) -> Trainer<O, N> {
+        if mini_batch_size == 0 {
+            panic!("mini batch size must be at least 1.");
+        }
+        Trainer {
+            mini_batch_size,
+            learning_rate,
+            epochs,
+            optimizer,
+            scheduler: Box::new(ConstantLr),
+            gradient_clipping: None,
+            accumulation_steps: 1,
+            l2_lambda: 0.0,
+            l1_lambda: 0.0,
+            rng: StdRng::from_entropy(),
+            metrics: Vec::new(),
+            callbacks: Vec::new(),
+            checkpoint_path: None,
+            deterministic: false,
+            threads: 1,
+            initial_epoch: 0,
+            initial_step: 0,
+            reported_save_error: false,
+        }
+    }
+
+    //continues from a state written with checkpoint_path: the network (which must have the shape
+    //it was trained with) and optimizer get their state back and the next training call picks up
+    //at the epoch and step it stopped. With the same data and settings the result is the same
+    //as if training was never interrupted. a missing or corrupt state, or one of a network of
+    //another shape or another kind of optimizer, is an error and leaves the network and optimizer untouched.
+    pub fn resume<P: AsRef<Path>>(&mut self, network: &mut N, path: P) -> io::Result<()> {
+        let mut state = TrainingState::load(path)?;
+        check_network(network, &state)?;
+        self.optimizer.load_state(network, std::mem::take(&mut state.optimizer))?;
+        restore_network(network, &state);
+        self.initial_epoch = state.epoch;
+        self.initial_step = state.step;
+        self.reseed(network, state.seed);
+        Ok(())
+    }
+
+    //fixes all randomness of training from seed: shuffling, dropout masks, the order of loader
+    //batches and augmentation. the network itself should come from a seeded constructor
+    //like NeuralNetwork::new_with_seed. meant for debugging regressions.
+    pub fn make_deterministic(&mut self, network: &mut N, seed: u64) {
+        self.deterministic = true;
+        self.reseed(network, seed);
+    }
+
+    fn reseed(&mut self, network: &mut N, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        network.reseed(&mut self.rng);
+    }
+
+    fn save_state(&mut self, network: &mut N, path: &Path, epoch: usize, step: usize) {
+        let seed = self.rng.gen();
+        self.reseed(network, seed);
+        let saved = TrainingState {
+            epoch,
+            step,
+            seed,
+            parameters: network.parameters_mut().map(|x| *x).collect(),
+            running_statistics: network.running_statistics(),
+            optimizer: self.optimizer.state(),
+        }.save(path);
+        if let Err(error) = saved {
+            if !self.reported_save_error {
+                eprintln!("could not write the training state to {}: {}.", path.display(), error);
+                self.reported_save_error = true;
+            }
+        }
+    }
+
+    //training data is a list of (input, desired output) pairs. It is shuffled in place every epoch.
+    //the network is put in training mode for the duration of training.
+    pub fn train(&mut self, network: &mut N, training_data: &mut [(ColumnVector, ColumnVector)]) {
+        let batch_amount = training_data.len().div_ceil(self.mini_batch_size);
+        self.run_epochs(network, |trainer, network, epoch, step, callbacks| {
+            training_data.shuffle(&mut trainer.rng);
+            let batches = training_data.chunks(trainer.mini_batch_size);
+            trainer.train_epoch(network, epoch, step, batches, Some(batch_amount), callbacks)
+        }, |_, _| None);
+    }
+
+    //trains on the batches of a data loader instead of mini_batch_size chunks.
+    //labels are turned into one hot targets the size of the output layer.
+    pub fn train_with_loader<D: Dataset + ?Sized>(&mut self, network: &mut N, loader: &DataLoader<D>) {
+        self.run_epochs(network, |trainer, network, epoch, step, callbacks| {
+            trainer.train_loader_epoch(network, loader, epoch, step, callbacks)
+        }, |_, _| None);
+    }
+
+    //like train_with_loader, but evaluates the network on the validation dataset after every
+    //epoch and returns those evaluations.
+    pub fn train_with_validation<D: Dataset + ?Sized, V: Dataset + ?Sized>(&mut self, network: &mut N, loader: &DataLoader<D>, validation: &V) -> Vec<Evaluation> {
+        self.run_epochs(network, |trainer, network, epoch, step, callbacks| {
+            trainer.train_loader_epoch(network, loader, epoch, step, callbacks)
+        }, |network, metrics| Some(network.validate(validation, metrics)))
+    }
+
+    fn train_loader_epoch<D: Dataset + ?Sized>(&mut self, network: &mut N, loader: &DataLoader<D>, epoch: usize, step: &mut usize, callbacks: &mut [Box<dyn Callback<N>>]) -> (LossAccumulator, f32) {
+        let output_size = network.output_size();
+        if self.checkpoint_path.is_some() || self.deterministic {
+            loader.rng.replace(StdRng::seed_from_u64(self.rng.gen()));
+            loader.dataset.reseed(self.rng.gen());
+        }
+        let batches = loader.iter().map(|batch| -> Vec<(ColumnVector, ColumnVector)> {
+            batch.into_iter().map(|(input, label)| (input, one_hot(label, output_size))).collect()
+        });
+        self.train_epoch(network, epoch, step, batches, Some(loader.batch_amount()), callbacks)
+    }
+
+    //the loop shared by the training methods. train_one_epoch returns the training loss and the
+    //last learning rate of an epoch, evaluate the optional evaluation after it. callbacks and
+    //metrics are taken out of the trainer while it runs, so they can be handed out mutably.
+    fn run_epochs(
+        &mut self,
+        network: &mut N,
+        mut train_one_epoch: impl FnMut(&mut Trainer<O, N>, &mut N, usize, &mut usize, &mut [Box<dyn Callback<N>>]) -> (LossAccumulator, f32),
+        mut evaluate: impl FnMut(&mut N, &mut [Box<dyn Metric>]) -> Option<Evaluation>,
+    ) -> Vec<Evaluation> {
+        if self.accumulation_steps == 0 {
+            panic!("accumulation steps must be at least 1.");
+        }
+        let previous_mode = network.set_mode(Mode::Training);
+        let mut callbacks = std::mem::take(&mut self.callbacks);
+        let mut metrics = std::mem::take(&mut self.metrics);
+        let mut evaluations = Vec::new();
+        let mut step = self.initial_step;
+        for epoch in self.initial_epoch..self.epochs {
+            callbacks.iter_mut().for_each(|callback| callback.on_epoch_start(network, epoch));
+            let (training_loss, learning_rate) = train_one_epoch(self, network, epoch, &mut step, &mut callbacks);
+            let validation = evaluate(network, &mut metrics);
+            let epoch_end = EpochEnd {
+                epoch,
+                training_loss: training_loss.mean(),
+                validation: validation.clone(),
+                learning_rate,
+            };
+            evaluations.extend(validation);
+            //every callback sees the end of the epoch, even after another one asked to stop.
+            let controls: Vec<Control> = callbacks.iter_mut().map(|callback| callback.on_epoch_end(network, &epoch_end)).collect();
+            if let Some(path) = self.checkpoint_path.clone() {
+                self.save_state(network, &path, epoch + 1, step);
+            }
+            if controls.contains(&Control::Stop) {
+                break;
+            }
+        }
+        callbacks.iter_mut().for_each(|callback| callback.on_train_end(network));
+        self.callbacks = callbacks;
+        self.metrics = metrics;
+        network.set_mode(previous_mode);
+        evaluations
+    }
+
+    //step counts optimizer steps across epochs and is advanced by every step taken.
+    //returns the loss of the epoch and the learning rate of its last optimizer step.
+    fn train_epoch<B: AsRef<[(ColumnVector, ColumnVector)]>>(&mut self, network: &mut N, epoch: usize, step: &mut usize, batches: impl Iterator<Item=B>, batch_amount: Option<usize>, callbacks: &mut [Box<dyn Callback<N>>]) -> (LossAccumulator, f32) {
+        let mut batches = batches.enumerate().peekable();
+        let mut accumulated = network.zeroed_gradients();
+        let mut accumulated_samples = 0;
+        let mut epoch_loss = LossAccumulator::new();
+        let mut learning_rate = self.scheduler.learning_rate(self.learning_rate, epoch, *step);
+        while let Some((batch_index, batch)) = batches.next() {
+            let batch = batch.as_ref();
+            let (gradients, batch_loss) = network.parallel_batch_gradients_and_loss(batch, self.threads);
+            accumulated.accumulate(&gradients);
+            accumulated_samples += batch.len();
+            epoch_loss.merge(&batch_loss);
+            if (batch_index + 1) % self.accumulation_steps == 0 || batches.peek().is_none() {
+                learning_rate = self.scheduler.learning_rate(self.learning_rate, epoch, *step);
+                self.optimizer_step(network, accumulated, accumulated_samples, learning_rate);
+                accumulated = network.zeroed_gradients();
+                accumulated_samples = 0;
+                *step += 1;
+            }
+            let batch_end = BatchEnd {
+                epoch,
+                batch_index,
+                batch_amount,
+                samples: batch.len(),
+                loss: batch_loss.mean(),
+                learning_rate,
+            };
+            callbacks.iter_mut().for_each(|callback| callback.on_batch_end(network, &batch_end));
+        }
+        (epoch_loss, learning_rate)
+    }
+
+    //average cost over the data plus the regularization penalties the trainer applies.
+    //evaluated in inference mode.
+    pub fn loss(&self, network: &mut N, data: &[(ColumnVector, ColumnVector)]) -> f32 {
+        let previous_mode = network.set_mode(Mode::Inference);
+        let mean_loss = network.mean_loss(data);
+        network.set_mode(previous_mode);
+        mean_loss + network.penalty(self.l2_lambda, self.l1_lambda)
+    }
+
+    pub fn train_mini_batch(&mut self, network: &mut N, batch: &[(ColumnVector, ColumnVector)], learning_rate: f32) {
+        let (gradients, _) = network.parallel_batch_gradients_and_loss(batch, self.threads);
+        self.optimizer_step(network, gradients, batch.len(), learning_rate);
+    }
+
+    //averages summed gradients over sample_amount, clips them and hands them to the optimizer.
+    fn optimizer_step(&mut self, network: &mut N, mut gradients: N::Gradients, sample_amount: usize, learning_rate: f32) {
+        gradients.scale(1.0 / sample_amount as f32);
+        network.add_penalty_gradients(&mut gradients, self.l2_lambda, self.l1_lambda);
+        match self.gradient_clipping {
+            Some(GradientClipping::GlobalNorm(max_norm)) => gradients.clip_by_global_norm(max_norm),
+            Some(GradientClipping::Value(max_value)) => gradients.clip_by_value(max_value),
+            None => {}
+        }
+        self.optimizer.step(network, &gradients, learning_rate);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use matrix::{ColumnVector, Matrix};
+    use std::sync::Arc;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use mnist_reader::{one_hot, Augmentation, Augmented, DataLoader};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::{squared_error, ActivationFunction, Adam, BatchEnd, Callback, Control, Cost, Dropout, EpochEnd, ExponentialDecay, GradientClipping, GradientValues, Gradients, Loss, Momentum, NeuralNetwork, Optimizer, RmsProp, TopKAccuracy, Trainer};
+
+    fn total_error(network: &mut NeuralNetwork, data: &[(ColumnVector, ColumnVector)]) -> f32 {
+        data.iter().map(|(input, desired)| {
+            network.calculate_all_activation_values(input);
+            squared_error(network.activation_values.back().unwrap(), desired)
+        }).sum()
+    }
+
+    fn test_network() -> NeuralNetwork {
+        let weights = vec![
+            Matrix::from_vec(vec![vec![0.5, 0.4], vec![0.3, 0.6]]),
+            Matrix::from_vec(vec![vec![0.6, 0.2], vec![0.1, 0.7]]),
+        ];
+        NeuralNetwork::new_from_vecs(weights, None, None, None)
+    }
+
+    //squared error weighted by a constant, so the expected values are easy to derive.
+    #[derive(Debug)]
+    struct WeightedSquaredError(f32);
+
+    impl Loss for WeightedSquaredError {
+        fn value(&self, output_activations: &ColumnVector, desired_output: &ColumnVector) -> f32 {
+            self.0 * squared_error(output_activations, desired_output)
+        }
+
+        fn gradient(&self, output_activations: &ColumnVector, desired_output: &ColumnVector) -> ColumnVector {
+            ColumnVector::from_vec((output_activations - desired_output).data.iter().map(|x| self.0 * x).collect())
+        }
+    }
+
+    fn test_data() -> Vec<(ColumnVector, ColumnVector)> {
+        vec![
+            (ColumnVector::from_vec(vec![1.0, 0.0]), ColumnVector::from_vec(vec![0.0, 1.0])),
+            (ColumnVector::from_vec(vec![0.0, 1.0]), ColumnVector::from_vec(vec![1.0, 0.0])),
+            (ColumnVector::from_vec(vec![1.0, 1.0]), ColumnVector::from_vec(vec![1.0, 1.0])),
+        ]
+    }
+
+    #[test]
+    fn training_reduces_error() {
+        let mut network = test_network();
+        let mut data = test_data();
+        let error_before = total_error(&mut network, &data);
+        Trainer::new(2, 0.1, 200).train(&mut network, &mut data);
+        let error_after = total_error(&mut network, &data);
+        assert!(error_after < error_before);
+    }
+
+    #[test]
+    fn training_with_adam_reduces_error() {
+        let mut network = test_network();
+        let mut data = test_data();
+        let error_before = total_error(&mut network, &data);
+        Trainer::new_with_optimizer(2, 0.01, 200, Adam::default()).train(&mut network, &mut data);
+        let error_after = total_error(&mut network, &data);
+        assert!(error_after < error_before);
+    }
+
+    #[test]
+    fn frozen_layers_are_not_trained() {
+        let mut network = test_network();
+        network.freeze_all_but_output();
+        let frozen_weights = network.weights[0].clone();
+        let output_weights = network.weights[1].clone();
+        let mut data = test_data();
+        Trainer::new_with_optimizer(2, 0.01, 20, Adam::default()).train(&mut network, &mut data);
+        Trainer::new_with_optimizer(2, 0.01, 20, Momentum::new(0.9, true)).train(&mut network, &mut data);
+        Trainer::new_with_optimizer(2, 0.01, 20, RmsProp::default()).train(&mut network, &mut data);
+        Trainer::new(2, 0.1, 20).train(&mut network, &mut data);
+        assert_eq!(network.weights[0], frozen_weights);
+        assert_ne!(network.weights[1], output_weights);
+
+        network.unfreeze(0);
+        Trainer::new(2, 0.1, 20).train(&mut network, &mut data);
+        assert_ne!(network.weights[0], frozen_weights);
+    }
+
+    #[test]
+    fn training_with_rms_prop_reduces_error() {
+        let mut network = test_network();
+        let mut data = test_data();
+        let error_before = total_error(&mut network, &data);
+        Trainer::new_with_optimizer(2, 0.01, 200, RmsProp::default()).train(&mut network, &mut data);
+        let error_after = total_error(&mut network, &data);
+        assert!(error_after < error_before);
+    }
+
+    struct CountingOptimizer {
+        steps: usize,
+    }
+
+    impl Optimizer for CountingOptimizer {
+        fn step(&mut self, network: &mut NeuralNetwork, gradients: &Gradients, learning_rate: f32) {
+            self.steps += 1;
+            network.apply_gradients(gradients, learning_rate);
+        }
+    }
+
+    #[test]
+    fn training_with_custom_optimizer() {
+        let mut network = test_network();
+        let mut data = test_data();
+        let mut trainer = Trainer::new_with_optimizer(2, 0.1, 5, CountingOptimizer { steps: 0 });
+        trainer.train(&mut network, &mut data);
+        //three samples in batches of two is two steps per epoch.
+        assert_eq!(trainer.optimizer.steps, 10);
+    }
+
+    struct RecordingOptimizer {
+        learning_rates: Vec<f32>,
+        gradient_norms: Vec<f32>,
+    }
+
+    impl RecordingOptimizer {
+        fn new() -> RecordingOptimizer {
+            RecordingOptimizer {
+                learning_rates: vec![],
+                gradient_norms: vec![],
+            }
+        }
+    }
+
+    impl Optimizer for RecordingOptimizer {
+        fn step(&mut self, _network: &mut NeuralNetwork, gradients: &Gradients, learning_rate: f32) {
+            self.learning_rates.push(learning_rate);
+            self.gradient_norms.push(gradients.global_norm());
+        }
+    }
+
+    #[test]
+    fn trainer_consults_scheduler() {
+        let mut network = test_network();
+        let mut data = test_data();
+        let mut trainer = Trainer::new_with_optimizer(3, 1.0, 3, RecordingOptimizer::new());
+        trainer.scheduler = Box::new(ExponentialDecay::new(0.5));
+        trainer.train(&mut network, &mut data);
+        assert_eq!(trainer.optimizer.learning_rates, vec![1.0, 0.5, 0.25]);
+    }
+
+    #[test]
+    fn trainer_clips_gradients() {
+        let mut network = test_network();
+        let mut data = test_data();
+        let mut trainer = Trainer::new_with_optimizer(1, 1.0, 2, RecordingOptimizer::new());
+        trainer.gradient_clipping = Some(GradientClipping::GlobalNorm(0.01));
+        trainer.train(&mut network, &mut data);
+        assert_eq!(trainer.optimizer.gradient_norms.len(), 6);
+        assert!(trainer.optimizer.gradient_norms.iter().all(|&norm| norm <= 0.01 + 1e-6));
+    }
+
+    #[test]
+    fn trainer_accumulates_gradients() {
+        let mut network = test_network();
+        let mut data = test_data();
+        let mut trainer = Trainer::new_with_optimizer(1, 1.0, 2, RecordingOptimizer::new());
+        trainer.accumulation_steps = 2;
+        trainer.train(&mut network, &mut data);
+        //three micro batches per epoch: one full accumulation and the leftover one.
+        assert_eq!(trainer.optimizer.learning_rates.len(), 4);
+    }
+
+    #[test]
+    fn accumulated_gradients_match_large_batch() {
+        let mut data = test_data();
+        let mut large_batch = Trainer::new_with_optimizer(3, 1.0, 1, RecordingOptimizer::new());
+        large_batch.train(&mut test_network(), &mut data);
+        let mut accumulated = Trainer::new_with_optimizer(1, 1.0, 1, RecordingOptimizer::new());
+        accumulated.accumulation_steps = 3;
+        accumulated.train(&mut test_network(), &mut data);
+        assert_eq!(accumulated.optimizer.gradient_norms.len(), 1);
+        assert!((accumulated.optimizer.gradient_norms[0] - large_batch.optimizer.gradient_norms[0]).abs() < 1e-6);
+    }
+
+    #[test]
+    fn l2_weight_decay() {
+        let mut data = test_data();
+        let mut trainer = Trainer::new(3, 0.1, 1);
+        let mut network = test_network();
+        let unregularized_loss = trainer.loss(&mut network, &data);
+        trainer.l2_lambda = 0.5;
+        //squared weights of the test network sum to 1.76.
+        assert!((trainer.loss(&mut network, &data) - unregularized_loss - 0.44).abs() < 1e-5);
+
+        let mut decayed = test_network();
+        let mut plain = test_network();
+        trainer.train(&mut decayed, &mut data);
+        Trainer::new(3, 0.1, 1).train(&mut plain, &mut data);
+        assert!(decayed.l2_penalty(1.0) < plain.l2_penalty(1.0));
+    }
+
+    #[test]
+    fn l1_regularization() {
+        let mut data = test_data();
+        let mut trainer = Trainer::new(3, 0.1, 1);
+        let mut network = test_network();
+        let unregularized_loss = trainer.loss(&mut network, &data);
+        trainer.l1_lambda = 0.5;
+        //absolute weights of the test network sum to 3.4.
+        assert!((trainer.loss(&mut network, &data) - unregularized_loss - 1.7).abs() < 1e-5);
+
+        let mut sparse = test_network();
+        let mut plain = test_network();
+        trainer.train(&mut sparse, &mut data);
+        Trainer::new(3, 0.1, 1).train(&mut plain, &mut data);
+        assert!(sparse.l1_penalty(1.0) < plain.l1_penalty(1.0));
+    }
+
+    #[test]
+    fn training_with_custom_loss() {
+        let mut data = test_data();
+        let mut trainer = Trainer::new(3, 0.1, 20);
+        let mut network = test_network();
+        let squared_error_loss = trainer.loss(&mut network, &data);
+        network.cost = Cost::Custom(Arc::new(WeightedSquaredError(3.0)));
+        let initial_loss = trainer.loss(&mut network, &data);
+        assert!((initial_loss - 3.0 * squared_error_loss).abs() < 1e-5);
+
+        let mut plain = test_network();
+        let expected = plain.backpropagation(&data[0].0, &data[0].1);
+        let mut gradients = network.backpropagation(&data[0].0, &data[0].1);
+        gradients.scale(1.0 / 3.0);
+        for (a, b) in gradients.values().zip(expected.values()) {
+            assert!((a - b).abs() < 1e-6);
+        }
+
+        trainer.train(&mut network, &mut data);
+        assert!(trainer.loss(&mut network, &data) < initial_loss);
+    }
+
+    #[test]
+    fn training_with_data_loader() {
+        let dataset: Vec<(ColumnVector, usize)> = vec![
+            (ColumnVector::from_vec(vec![1.0, 0.0]), 1),
+            (ColumnVector::from_vec(vec![0.0, 1.0]), 0),
+            (ColumnVector::from_vec(vec![1.0, 0.2]), 1),
+            (ColumnVector::from_vec(vec![0.1, 1.0]), 0),
+        ];
+        let one_hot_data: Vec<(ColumnVector, ColumnVector)> = dataset.iter()
+            .map(|(input, label)| (input.clone(), one_hot(*label, 2)))
+            .collect();
+        let loader = DataLoader::new(&dataset, 3);
+        let mut network = test_network();
+        let mut recording = Trainer::new_with_optimizer(1, 0.1, 30, RecordingOptimizer::new());
+        recording.train_with_loader(&mut network, &loader);
+        //two batches of the loader per epoch, not mini_batch_size.
+        assert_eq!(recording.optimizer.learning_rates.len(), 60);
+
+        let initial_error = total_error(&mut network, &one_hot_data);
+        Trainer::new(1, 0.1, 30).train_with_loader(&mut network, &loader);
+        assert!(total_error(&mut network, &one_hot_data) < initial_error);
+    }
+
+    #[test]
+    fn evaluation_after_every_epoch() {
+        let dataset: Vec<(ColumnVector, usize)> = (0..8)
+            .map(|x| (ColumnVector::from_vec(vec![(x % 2) as f32, 1.0 - (x % 2) as f32]), x % 2))
+            .collect();
+        let mut network = NeuralNetwork::new_with_seed(&[2, 3, 2], vec![ActivationFunction::Tanh, ActivationFunction::Sigmoid], 4);
+        let initial = network.evaluate(&dataset);
+        let evaluations = Trainer::new(2, 0.5, 20).train_with_validation(&mut network, &DataLoader::new(&dataset, 2), &dataset[..4]);
+        assert_eq!(evaluations.len(), 20);
+        assert_eq!(evaluations[19], network.evaluate(&dataset[..4]));
+        assert!(evaluations[19].loss < initial.loss);
+        assert_eq!(evaluations[19].accuracy, 1.0);
+
+        let mut trainer = Trainer::new(2, 0.5, 3);
+        trainer.metrics.push(Box::new(TopKAccuracy::new(1)));
+        let evaluations = trainer.train_with_validation(&mut network, &DataLoader::new(&dataset, 2), &dataset);
+        assert!(evaluations.iter().all(|evaluation| evaluation.metrics == vec![("top 1 accuracy".to_string(), evaluation.accuracy)]));
+        assert_eq!(trainer.metrics.len(), 1);
+    }
+
+    #[test]
+    fn seeded_training_is_reproducible() {
+        let run = |seed: u64| -> NeuralNetwork {
+            let mut network = NeuralNetwork::new_with_seed(&[2, 4, 2], vec![ActivationFunction::Relu, ActivationFunction::Sigmoid], seed);
+            network.dropout = Some(Dropout::new_with_seed(0.3, seed));
+            let mut data = test_data();
+            let mut trainer = Trainer::new(1, 0.1, 5);
+            trainer.rng = StdRng::seed_from_u64(seed);
+            trainer.train(&mut network, &mut data);
+            network
+        };
+        assert_eq!(run(3), run(3));
+        assert_ne!(run(3).weights, run(4).weights);
+    }
+
+    #[test]
+    fn deterministic_training_is_bit_identical() {
+        let dataset: Vec<(ColumnVector, usize)> = (0..12)
+            .map(|x| (ColumnVector::from_vec((0..9).map(|pixel| ((x * 9 + pixel) % 5) as f32 / 4.0).collect()), x % 2))
+            .collect();
+        let run = |seed: u64, threads: usize| -> NeuralNetwork {
+            let mut network = NeuralNetwork::new_with_seed(&[9, 5, 2], vec![ActivationFunction::Relu, ActivationFunction::Sigmoid], seed);
+            network.dropout = Some(Dropout::new(0.3));
+            let augmented = Augmented { dataset: &dataset, augmentation: Augmentation::new(3, 3) };
+            //neither the dropout, the augmentation nor the loader are seeded themselves.
+            let loader = DataLoader::new_shuffled(&augmented, 4);
+            let mut trainer = Trainer::new(4, 0.5, 3);
+            trainer.threads = threads;
+            trainer.make_deterministic(&mut network, seed);
+            trainer.train_with_loader(&mut network, &loader);
+            network
+        };
+        let bits = |network: &mut NeuralNetwork| -> Vec<u32> { network.parameters_mut().map(|x| x.to_bits()).collect() };
+        assert_eq!(bits(&mut run(5, 1)), bits(&mut run(5, 1)));
+        assert_ne!(bits(&mut run(5, 1)), bits(&mut run(6, 1)));
+        //also when the batches are split over threads.
+        assert_eq!(bits(&mut run(5, 2)), bits(&mut run(5, 2)));
+    }
+
+    //logs every hook and stops once stop_after epochs are done.
+    struct RecordingCallback {
+        log: Rc<RefCell<Vec<String>>>,
+        stop_after: usize,
+    }
+
+    impl Callback for RecordingCallback {
+        fn on_epoch_start(&mut self, _network: &NeuralNetwork, epoch: usize) {
+            self.log.borrow_mut().push(format!("start {}", epoch));
+        }
+
+        fn on_batch_end(&mut self, _network: &NeuralNetwork, batch: &BatchEnd) {
+            self.log.borrow_mut().push(format!("batch {}/{:?} of {}", batch.batch_index, batch.batch_amount, batch.samples));
+        }
+
+        fn on_epoch_end(&mut self, _network: &mut NeuralNetwork, epoch: &EpochEnd) -> Control {
+            self.log.borrow_mut().push(format!("end {} {}", epoch.epoch, epoch.validation.is_some()));
+            if epoch.epoch + 1 == self.stop_after { Control::Stop } else { Control::Continue }
+        }
+
+        fn on_train_end(&mut self, _network: &mut NeuralNetwork) {
+            self.log.borrow_mut().push("train end".to_string());
+        }
+    }
+
+    #[test]
+    fn callbacks_are_called_and_can_stop_training() {
+        let dataset: Vec<(ColumnVector, usize)> = (0..3)
+            .map(|x| (ColumnVector::from_vec(vec![(x % 2) as f32, 1.0 - (x % 2) as f32]), x % 2))
+            .collect();
+        let mut network = NeuralNetwork::new_with_seed(&[2, 3, 2], vec![ActivationFunction::Tanh, ActivationFunction::Sigmoid], 4);
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut trainer = Trainer::new(2, 0.5, 5);
+        trainer.callbacks.push(Box::new(RecordingCallback { log: Rc::clone(&log), stop_after: 2 }));
+        let evaluations = trainer.train_with_validation(&mut network, &DataLoader::new(&dataset, 2), &dataset);
+        assert_eq!(evaluations.len(), 2);
+        assert_eq!(*log.borrow(), vec![
+            "start 0", "batch 0/Some(2) of 2", "batch 1/Some(2) of 1", "end 0 true",
+            "start 1", "batch 0/Some(2) of 2", "batch 1/Some(2) of 1", "end 1 true",
+            "train end",
+        ]);
+
+        log.borrow_mut().clear();
+        trainer.callbacks[0] = Box::new(RecordingCallback { log: Rc::clone(&log), stop_after: 0 });
+        trainer.epochs = 1;
+        let mut data: Vec<(ColumnVector, ColumnVector)> = dataset.iter().map(|(input, label)| (input.clone(), one_hot(*label, 2))).collect();
+        trainer.train(&mut network, &mut data);
+        assert_eq!(log.borrow().last().unwrap(), "train end");
+        assert_eq!(log.borrow()[3], "end 0 false");
+    }
+
+    #[test]
+    fn epoch_loss_is_the_mean_over_batches() {
+        struct Losses(Rc<RefCell<Vec<f32>>>);
+        impl Callback for Losses {
+            fn on_batch_end(&mut self, _network: &NeuralNetwork, batch: &BatchEnd) {
+                self.0.borrow_mut().push(batch.loss * batch.samples as f32);
+            }
+
+            fn on_epoch_end(&mut self, _network: &mut NeuralNetwork, epoch: &EpochEnd) -> Control {
+                let total: f32 = self.0.borrow_mut().drain(..).sum();
+                assert!((total / 3.0 - epoch.training_loss).abs() < 1e-6);
+                Control::Continue
+            }
+        }
+        let mut network = test_network();
+        let mut trainer = Trainer::new(2, 0.1, 3);
+        trainer.callbacks.push(Box::new(Losses(Rc::new(RefCell::new(Vec::new())))));
+        trainer.train(&mut network, &mut test_data());
+    }
+}
@@ -0,0 +1,455 @@
+use std::sync::mpsc;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use crate::device::activation_kind;
+use crate::{ActivationFunction, Backend, DeviceBuffer};
+
+//every shader gets its sizes and the parameter of the activation in this uniform.
+//the padding keeps it at 32 bytes, a multiple of the 16 bytes uniforms are aligned to.
+const PARAMETERS: &str = "
+struct Parameters {
+    m: u32,
+    k: u32,
+    n: u32,
+    kind: u32,
+    parameter: f32,
+    pad0: u32,
+    pad1: u32,
+    pad2: u32,
+}
+";
+
+//kind 1 reads lhs transposed and kind 2 rhs, see the transposed products of Backend.
+const MATMUL_SHADER: &str = "
+@group(0) @binding(0) var<storage, read> lhs: array<f32>;
+@group(0) @binding(1) var<storage, read> rhs: array<f32>;
+@group(0) @binding(2) var<storage, read_write> result: array<f32>;
+@group(0) @binding(3) var<uniform> parameters: Parameters;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let row = id.y;
+    let column = id.x;
+    if (row >= parameters.m || column >= parameters.n) {
+        return;
+    }
+    var sum = 0.0;
+    for (var i = 0u; i < parameters.k; i = i + 1u) {
+        let lhs_elem = select(lhs[row * parameters.k + i], lhs[i * parameters.m + row], parameters.kind == 1u);
+        let rhs_elem = select(rhs[i * parameters.n + column], rhs[column * parameters.k + i], parameters.kind == 2u);
+        sum = sum + lhs_elem * rhs_elem;
+    }
+    result[row * parameters.n + column] = sum;
+}
+";
+
+//one invocation per element. large matrices need more workgroups than fit into x, so the
+//dispatch is spread over x and y.
+const ELEMENT_INDEX: &str = "
+fn element_index(id: vec3<u32>, workgroups: vec3<u32>) -> u32 {
+    return id.y * workgroups.x * 64u + id.x;
+}
+";
+
+const BIAS_SHADER: &str = "
+@group(0) @binding(0) var<storage, read_write> matrix: array<f32>;
+@group(0) @binding(1) var<storage, read> bias: array<f32>;
+@group(0) @binding(2) var<uniform> parameters: Parameters;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) workgroups: vec3<u32>) {
+    let index = element_index(id, workgroups);
+    if (index >= parameters.m * parameters.n) {
+        return;
+    }
+    matrix[index] = matrix[index] + bias[index / parameters.n];
+}
+";
+
+//kind selects the function, see activation_kind in device.rs. tanh of large values overflows in some
+//implementations, its argument is clamped to where it is 1 in f32 anyway.
+const ACTIVATION_SHADER: &str = "
+@group(0) @binding(0) var<storage, read_write> matrix: array<f32>;
+@group(0) @binding(1) var<uniform> parameters: Parameters;
+
+fn sigmoid(x: f32) -> f32 {
+    return 1.0 / (1.0 + exp(-x));
+}
+
+fn safe_tanh(x: f32) -> f32 {
+    return tanh(clamp(x, -15.0, 15.0));
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) workgroups: vec3<u32>) {
+    let index = element_index(id, workgroups);
+    if (index >= parameters.m * parameters.n) {
+        return;
+    }
+    let x = matrix[index];
+    var y = x;
+    switch parameters.kind {
+        case 0u: { y = max(x, 0.0); }
+        case 1u: { y = sigmoid(x); }
+        case 3u: { y = select(x, parameters.parameter * x, x < 0.0); }
+        case 4u: { y = select(x, parameters.parameter * (exp(x) - 1.0), x < 0.0); }
+        case 5u: { y = 0.5 * x * (1.0 + safe_tanh(0.7978846 * (x + 0.044715 * x * x * x))); }
+        case 6u: { y = safe_tanh(x); }
+        case 7u: { y = x * sigmoid(x); }
+        default: {}
+    }
+    matrix[index] = y;
+}
+";
+
+//multiplies the gradient by the derivative of the activation at the z values.
+const ACTIVATION_BACKWARD_SHADER: &str = "
+@group(0) @binding(0) var<storage, read> z_values: array<f32>;
+@group(0) @binding(1) var<storage, read_write> gradient: array<f32>;
+@group(0) @binding(2) var<uniform> parameters: Parameters;
+
+fn sigmoid(x: f32) -> f32 {
+    return 1.0 / (1.0 + exp(-x));
+}
+
+fn safe_tanh(x: f32) -> f32 {
+    return tanh(clamp(x, -15.0, 15.0));
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) workgroups: vec3<u32>) {
+    let index = element_index(id, workgroups);
+    if (index >= parameters.m * parameters.n) {
+        return;
+    }
+    let x = z_values[index];
+    var derivative = 1.0;
+    switch parameters.kind {
+        case 0u: { derivative = select(1.0, 0.0, x < 0.0); }
+        case 1u: { derivative = sigmoid(x) * (1.0 - sigmoid(x)); }
+        case 3u: { derivative = select(1.0, parameters.parameter, x < 0.0); }
+        case 4u: { derivative = select(1.0, parameters.parameter * exp(x), x < 0.0); }
+        case 5u: {
+            let t = safe_tanh(0.7978846 * (x + 0.044715 * x * x * x));
+            derivative = 0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * 0.7978846 * (1.0 + 3.0 * 0.044715 * x * x);
+        }
+        case 6u: { derivative = 1.0 - safe_tanh(x) * safe_tanh(x); }
+        case 7u: { derivative = sigmoid(x) + x * sigmoid(x) * (1.0 - sigmoid(x)); }
+        default: {}
+    }
+    gradient[index] = gradient[index] * derivative;
+}
+";
+
+//one invocation per column, which holds a whole sample.
+const SOFTMAX_SHADER: &str = "
+@group(0) @binding(0) var<storage, read_write> matrix: array<f32>;
+@group(0) @binding(1) var<uniform> parameters: Parameters;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let column = id.x;
+    if (column >= parameters.n) {
+        return;
+    }
+    var max_value = matrix[column];
+    for (var row = 1u; row < parameters.m; row = row + 1u) {
+        max_value = max(max_value, matrix[row * parameters.n + column]);
+    }
+    var sum = 0.0;
+    for (var row = 0u; row < parameters.m; row = row + 1u) {
+        let exponential = exp(matrix[row * parameters.n + column] - max_value);
+        matrix[row * parameters.n + column] = exponential;
+        sum = sum + exponential;
+    }
+    for (var row = 0u; row < parameters.m; row = row + 1u) {
+        matrix[row * parameters.n + column] = matrix[row * parameters.n + column] / sum;
+    }
+}
+";
+
+//the full jacobian of softmax, p * (gradient - gradient . p) for the probabilities p of a column.
+const SOFTMAX_BACKWARD_SHADER: &str = "
+@group(0) @binding(0) var<storage, read> z_values: array<f32>;
+@group(0) @binding(1) var<storage, read_write> gradient: array<f32>;
+@group(0) @binding(2) var<uniform> parameters: Parameters;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let column = id.x;
+    if (column >= parameters.n) {
+        return;
+    }
+    var max_value = z_values[column];
+    for (var row = 1u; row < parameters.m; row = row + 1u) {
+        max_value = max(max_value, z_values[row * parameters.n + column]);
+    }
+    var sum = 0.0;
+    for (var row = 0u; row < parameters.m; row = row + 1u) {
+        sum = sum + exp(z_values[row * parameters.n + column] - max_value);
+    }
+    var projection = 0.0;
+    for (var row = 0u; row < parameters.m; row = row + 1u) {
+        let index = row * parameters.n + column;
+        projection = projection + gradient[index] * exp(z_values[index] - max_value) / sum;
+    }
+    for (var row = 0u; row < parameters.m; row = row + 1u) {
+        let index = row * parameters.n + column;
+        gradient[index] = exp(z_values[index] - max_value) / sum * (gradient[index] - projection);
+    }
+}
+";
+
+//one invocation per row.
+const SUM_ROWS_SHADER: &str = "
+@group(0) @binding(0) var<storage, read> matrix: array<f32>;
+@group(0) @binding(1) var<storage, read_write> result: array<f32>;
+@group(0) @binding(2) var<uniform> parameters: Parameters;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) workgroups: vec3<u32>) {
+    let row = element_index(id, workgroups);
+    if (row >= parameters.m) {
+        return;
+    }
+    var sum = 0.0;
+    for (var column = 0u; column < parameters.n; column = column + 1u) {
+        sum = sum + matrix[row * parameters.n + column];
+    }
+    result[row] = sum;
+}
+";
+
+//the factor is the parameter, the destination has m elements.
+const ADD_SCALED_SHADER: &str = "
+@group(0) @binding(0) var<storage, read_write> destination: array<f32>;
+@group(0) @binding(1) var<storage, read> source: array<f32>;
+@group(0) @binding(2) var<uniform> parameters: Parameters;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) workgroups: vec3<u32>) {
+    let index = element_index(id, workgroups);
+    if (index >= parameters.m) {
+        return;
+    }
+    destination[index] = destination[index] + parameters.parameter * source[index];
+}
+";
+
+const MAX_WORKGROUPS: u32 = 65535;
+const ELEMENT_WORKGROUP_SIZE: u32 = 64;
+const MATMUL_WORKGROUP_SIZE: u32 = 8;
+
+fn parameter_bytes(m: usize, k: usize, n: usize, kind: u32, parameter: f32) -> Vec<u8> {
+    [m as u32, k as u32, n as u32, kind, parameter.to_bits(), 0, 0, 0].iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+//runs inference and training on the first gpu wgpu finds, through vulkan, metal, dx12 or
+//opengl. every call submits its own work and download waits for it to finish.
+pub struct WgpuBackend {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    adapter_name: String,
+    matmul: wgpu::ComputePipeline,
+    add_bias: wgpu::ComputePipeline,
+    activate: wgpu::ComputePipeline,
+    softmax: wgpu::ComputePipeline,
+    activation_backward: wgpu::ComputePipeline,
+    softmax_backward: wgpu::ComputePipeline,
+    sum_rows: wgpu::ComputePipeline,
+    add_scaled: wgpu::ComputePipeline,
+}
+
+impl WgpuBackend {
+    pub fn new() -> Result<WgpuBackend, String> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        })).map_err(|error| format!("no gpu found: {}", error))?;
+        //the default limits cap storage buffers at 128 MiB, large batches need what the gpu has.
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("nn"),
+            required_limits: adapter.limits(),
+            ..Default::default()
+        })).map_err(|error| format!("the gpu could not be opened: {}", error))?;
+        let pipeline = |source: &str, label: &str| {
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(format!("{}{}{}", PARAMETERS, ELEMENT_INDEX, source).into()),
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: None,
+                module: &module,
+                entry_point: Some("main"),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        Ok(WgpuBackend {
+            matmul: pipeline(MATMUL_SHADER, "matmul"),
+            add_bias: pipeline(BIAS_SHADER, "add_bias"),
+            activate: pipeline(ACTIVATION_SHADER, "activate"),
+            softmax: pipeline(SOFTMAX_SHADER, "softmax"),
+            activation_backward: pipeline(ACTIVATION_BACKWARD_SHADER, "activation_backward"),
+            softmax_backward: pipeline(SOFTMAX_BACKWARD_SHADER, "softmax_backward"),
+            sum_rows: pipeline(SUM_ROWS_SHADER, "sum_rows"),
+            add_scaled: pipeline(ADD_SCALED_SHADER, "add_scaled"),
+            adapter_name: adapter.get_info().name,
+            device,
+            queue,
+        })
+    }
+
+    fn storage_buffer(&self, size: usize) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (size * size_of::<f32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    //binds buffers in order, then the parameters, and runs pipeline once.
+    fn dispatch(&self, pipeline: &wgpu::ComputePipeline, buffers: &[&wgpu::Buffer], parameters: Vec<u8>, workgroups: (u32, u32)) {
+        let parameters = self.device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: &parameters,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let mut entries: Vec<wgpu::BindGroupEntry> = buffers.iter().enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry { binding: binding as u32, resource: buffer.as_entire_binding() })
+            .collect();
+        entries.push(wgpu::BindGroupEntry { binding: buffers.len() as u32, resource: parameters.as_entire_binding() });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        });
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None, timestamp_writes: None });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(workgroups.0, workgroups.1, 1);
+        }
+        self.queue.submit([encoder.finish()]);
+    }
+
+    //the m x n product for kind 0, or with lhs (1) or rhs (2) read transposed.
+    fn product(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize, kind: u32) -> DeviceBuffer {
+        let result = self.storage_buffer(m * n);
+        let workgroups = ((n as u32).div_ceil(MATMUL_WORKGROUP_SIZE), (m as u32).div_ceil(MATMUL_WORKGROUP_SIZE));
+        self.dispatch(&self.matmul, &[lhs.inner(), rhs.inner(), &result], parameter_bytes(m, k, n, kind, 0.0), workgroups);
+        DeviceBuffer::new(m * n, result)
+    }
+
+    //enough workgroups of ELEMENT_WORKGROUP_SIZE for every element, see element_index.
+    fn element_workgroups(elements: usize) -> (u32, u32) {
+        let workgroups = elements.div_ceil(ELEMENT_WORKGROUP_SIZE as usize) as u32;
+        (workgroups.min(MAX_WORKGROUPS), workgroups.div_ceil(MAX_WORKGROUPS))
+    }
+}
+
+impl Backend for WgpuBackend {
+    fn name(&self) -> String {
+        format!("wgpu ({})", self.adapter_name)
+    }
+
+    fn upload(&self, data: &[f32]) -> DeviceBuffer {
+        let bytes: Vec<u8> = data.iter().flat_map(|elem| elem.to_le_bytes()).collect();
+        let buffer = self.device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: &bytes,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+        DeviceBuffer::new(data.len(), buffer)
+    }
+
+    //copies into a buffer the host can map, the storage buffers can not be read directly.
+    fn download(&self, buffer: &DeviceBuffer) -> Vec<f32> {
+        let size = (buffer.len * size_of::<f32>()) as u64;
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(buffer.inner::<wgpu::Buffer>(), 0, &staging, 0, size);
+        self.queue.submit([encoder.finish()]);
+        let (sender, receiver) = mpsc::channel();
+        staging.slice(..).map_async(wgpu::MapMode::Read, move |result| sender.send(result).unwrap());
+        self.device.poll(wgpu::PollType::Wait { submission_index: None, timeout: None }).expect("the gpu stopped responding.");
+        receiver.recv().unwrap().expect("the result could not be read from the gpu.");
+        let data = staging.slice(..).get_mapped_range().expect("the result could not be read from the gpu.")
+            .chunks_exact(size_of::<f32>())
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        staging.unmap();
+        data
+    }
+
+    fn copy(&self, buffer: &DeviceBuffer) -> DeviceBuffer {
+        let copy = self.storage_buffer(buffer.len);
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(buffer.inner::<wgpu::Buffer>(), 0, &copy, 0, (buffer.len * size_of::<f32>()) as u64);
+        self.queue.submit([encoder.finish()]);
+        DeviceBuffer::new(buffer.len, copy)
+    }
+
+    fn matmul(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize) -> DeviceBuffer {
+        self.product(lhs, rhs, m, k, n, 0)
+    }
+
+    fn matmul_transposed_lhs(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize) -> DeviceBuffer {
+        self.product(lhs, rhs, m, k, n, 1)
+    }
+
+    fn matmul_transposed_rhs(&self, lhs: &DeviceBuffer, rhs: &DeviceBuffer, m: usize, k: usize, n: usize) -> DeviceBuffer {
+        self.product(lhs, rhs, m, k, n, 2)
+    }
+
+    fn add_bias(&self, matrix: &mut DeviceBuffer, bias: &DeviceBuffer, m: usize, n: usize) {
+        self.dispatch(&self.add_bias, &[matrix.inner(), bias.inner()], parameter_bytes(m, 0, n, 0, 0.0), Self::element_workgroups(m * n));
+    }
+
+    fn sum_rows(&self, matrix: &DeviceBuffer, m: usize, n: usize) -> DeviceBuffer {
+        let result = self.storage_buffer(m);
+        self.dispatch(&self.sum_rows, &[matrix.inner(), &result], parameter_bytes(m, 0, n, 0, 0.0), Self::element_workgroups(m));
+        DeviceBuffer::new(m, result)
+    }
+
+    fn add_scaled(&self, target: &mut DeviceBuffer, factor: f32, source: &DeviceBuffer) {
+        self.dispatch(&self.add_scaled, &[target.inner(), source.inner()], parameter_bytes(target.len, 0, 1, 0, factor), Self::element_workgroups(target.len));
+    }
+
+    fn activate(&self, activation_function: &ActivationFunction, matrix: &mut DeviceBuffer, m: usize, n: usize) {
+        match activation_kind(activation_function) {
+            Some((kind, parameter)) => {
+                self.dispatch(&self.activate, &[matrix.inner()], parameter_bytes(m, 0, n, kind, parameter), Self::element_workgroups(m * n));
+            }
+            None => {
+                let workgroups = (n as u32).div_ceil(ELEMENT_WORKGROUP_SIZE);
+                self.dispatch(&self.softmax, &[matrix.inner()], parameter_bytes(m, 0, n, 0, 0.0), (workgroups, 1));
+            }
+        }
+    }
+
+    fn activation_backward(&self, activation_function: &ActivationFunction, z_values: &DeviceBuffer, gradient: &mut DeviceBuffer, m: usize, n: usize) {
+        match activation_kind(activation_function) {
+            Some((kind, parameter)) => {
+                let workgroups = Self::element_workgroups(m * n);
+                self.dispatch(&self.activation_backward, &[z_values.inner(), gradient.inner()], parameter_bytes(m, 0, n, kind, parameter), workgroups);
+            }
+            None => {
+                let workgroups = (n as u32).div_ceil(ELEMENT_WORKGROUP_SIZE);
+                self.dispatch(&self.softmax_backward, &[z_values.inner(), gradient.inner()], parameter_bytes(m, 0, n, 0, 0.0), (workgroups, 1));
+            }
+        }
+    }
+
+    fn supports(&self, activation_function: &ActivationFunction) -> bool {
+        !matches!(activation_function, ActivationFunction::Custom(_))
+    }
+}